@@ -1,8 +1,28 @@
-program : rule SEMI program |;
-rule : rule' SEMI;
-rule' : rule'' rule''';
-rule'' : TERMINAL rule'' | NONTERMINAL rule'' |;
-rule''' : BAR rule'|;
+// The syntax of rantlr grammars, over the tokens of rantlr's own tokenizer.
+// `rantlr bootstrap examples/rantlr.g4` generates the meta-parser in `src/internal/parser` from it:
+// each parser rule `r` becomes a tree type `ParseR`, whose variants are named by `%label`.
 
-TERMINAL: aa*;
-NONTERMINAL: bb*;
+s: r1 SEMICOLON s %label Concat | %label Epsilon;
+r1: PARSER_IDENT COLON r2 %label NonTerminal
+  | LEXER_IDENT COLON LEXER_PATTERN %label Terminal
+  | DIRECTIVE r3 %label Directive;
+r2: r3 r4 %label Rule;
+r3: PARSER_IDENT r3 %label NonTerminal
+  | LEXER_IDENT r3 %label Terminal
+  | DIRECTIVE r3 %label Directive
+  | AND &ident r3 %label And
+  | NOT &ident r3 %label Not
+  | %label Epsilon;
+r4: BAR r2 %label Concat | %label Epsilon;
+// A lookahead applies to exactly one ident
+ident: PARSER_IDENT | LEXER_IDENT;
+
+PARSER_IDENT: [a-z_][a-zA-Z0-9_']*;
+LEXER_IDENT: [A-Z][a-zA-Z0-9_']*;
+LEXER_PATTERN: [^\x3B\s]+;
+DIRECTIVE: %[a-zA-Z0-9_]+;
+BAR: \|;
+SEMICOLON: \x3B;
+COLON: :;
+AND: &;
+NOT: !;
@@ -0,0 +1,411 @@
+//! Parsers generated by rantlr at compile time, for grammars small enough not to need a build
+//! script (see `rantlr::build` for those that do).
+//!
+//! [`grammar!`] takes the grammar inline, [`macro@rantlr`] reads it from a file into a module.
+//!
+//! The macros live in this crate, as `rantlr_macros::grammar!` and `#[rantlr_macros::rantlr]`,
+//! not in `rantlr` itself: this crate depends on `rantlr`, which therefore cannot re-export them.
+//! A crate using them depends on `rantlr-macros`, and only on `rantlr` to call it directly.
+//! Both lint the grammar and generate its parser as `rantlr generate` does, and report syntax
+//! errors, denied lints and conflicts as compile errors. Warnings are not reported, `rantlr lint`
+//! shows them.
+//!
+//! Settings are given as `name = "value"`:
+//!
+//! - `backend`: `ll1`, `slr`, `lalr` (the default), `glr`, `recursive-descent` or `peg`,
+//! - `conflicts`: `error` (the default), `warn` or `prefer-shift`,
+//! - `messages`: the syntax error messages of the LR backends, relative to the crate root.
+//!
+//! The generated parser tokenizes with the `regex` crate, which the crate depends on as well.
+
+use std::{env, path::PathBuf};
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use rantlr::{
+    analysis::{lint, Severity},
+    codegen::{self, Backend},
+    diagnostic::{of_grammar_error, Diagnostic},
+    span::{FileId, SourceMap},
+    table::ConflictPolicy,
+    Grammar,
+};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+    Ident, ItemMod, LitStr, Token,
+};
+
+/// What diagnostics call an inline grammar
+const INLINE: &str = "<grammar>";
+
+/// A setting, `name = "value"`
+struct Setting {
+    name: Ident,
+    value: LitStr,
+}
+
+impl Parse for Setting {
+    fn parse(input: ParseStream) -> syn::Result<Setting> {
+        let name = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let value = input.parse()?;
+        Ok(Setting { name, value })
+    }
+}
+
+/// The input of [`grammar!`]: settings, then the grammar
+struct Inline {
+    settings: Vec<Setting>,
+    source: LitStr,
+}
+
+impl Parse for Inline {
+    fn parse(input: ParseStream) -> syn::Result<Inline> {
+        let mut settings = vec![];
+        while input.peek(Ident) {
+            settings.push(input.parse()?);
+            input.parse::<Token![,]>()?;
+        }
+        let source = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(Inline { settings, source })
+    }
+}
+
+/// The settings of a grammar. The grammar file is only set by [`macro@rantlr`].
+#[derive(Default)]
+struct Settings {
+    grammar: Option<LitStr>,
+    options: codegen::Options,
+}
+
+impl Settings {
+    fn new(settings: impl IntoIterator<Item = Setting>, file: bool) -> syn::Result<Settings> {
+        let mut result = Settings::default();
+        for Setting { name, value } in settings {
+            match name.to_string().as_str() {
+                "grammar" if file => result.grammar = Some(value),
+                "backend" => result.options.backend = backend(&value)?,
+                "conflicts" => result.options.conflicts = conflicts(&value)?,
+                "messages" => result.options.messages = Some(crate_root().join(value.value())),
+                _ => {
+                    let expected = if file {
+                        "`grammar`, `backend`, `conflicts` or `messages`"
+                    } else {
+                        "`backend`, `conflicts` or `messages`"
+                    };
+                    return Err(syn::Error::new(
+                        name.span(),
+                        format!("unknown setting `{name}`, expected {expected}"),
+                    ));
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+fn backend(value: &LitStr) -> syn::Result<Backend> {
+    match value.value().as_str() {
+        "ll1" => Ok(Backend::Ll1),
+        "slr" => Ok(Backend::Slr),
+        "lalr" => Ok(Backend::Lalr),
+        "glr" => Ok(Backend::Glr),
+        "recursive-descent" => Ok(Backend::RecursiveDescent),
+        "peg" => Ok(Backend::Peg),
+        other => Err(syn::Error::new(
+            value.span(),
+            format!(
+                "unknown backend `{other}`, expected `ll1`, `slr`, `lalr`, `glr`, \
+                 `recursive-descent` or `peg`"
+            ),
+        )),
+    }
+}
+
+fn conflicts(value: &LitStr) -> syn::Result<ConflictPolicy> {
+    match value.value().as_str() {
+        "error" => Ok(ConflictPolicy::Error),
+        "warn" => Ok(ConflictPolicy::Warn),
+        "prefer-shift" => Ok(ConflictPolicy::PreferShift),
+        other => Err(syn::Error::new(
+            value.span(),
+            format!(
+                "unknown conflict policy `{other}`, expected `error`, `warn` or `prefer-shift`"
+            ),
+        )),
+    }
+}
+
+/// The root of the crate being compiled, which paths are relative to
+fn crate_root() -> PathBuf {
+    env::var_os("CARGO_MANIFEST_DIR").map_or_else(PathBuf::new, PathBuf::from)
+}
+
+/// One compile error for every error, or else nothing
+fn combine(errors: impl IntoIterator<Item = syn::Error>) -> syn::Result<()> {
+    let mut errors = errors.into_iter();
+    match errors.next() {
+        Some(mut first) => {
+            first.extend(errors);
+            Err(first)
+        }
+        None => Ok(()),
+    }
+}
+
+/// The span of a diagnostic of an inline grammar. The bytes of a raw string are those of the
+/// grammar, and compilers which locate spans inside literals then point at the error itself;
+/// otherwise it is the whole literal.
+fn span(literal: &LitStr, diagnostic: &Diagnostic) -> Span {
+    let token = literal.token();
+    let text = token.to_string();
+    match (&diagnostic.primary, text.find('"')) {
+        (Some(label), Some(quote)) if text.starts_with('r') => token
+            .subspan(quote + 1 + label.span.start..quote + 1 + label.span.end)
+            .unwrap_or_else(|| literal.span()),
+        _ => literal.span(),
+    }
+}
+
+/// The lints of a grammar which deny it, rendered by `render`
+fn denied(grammar: &Grammar, render: impl Fn(&Diagnostic) -> syn::Error) -> syn::Result<()> {
+    combine(
+        lint(grammar)
+            .iter()
+            .filter(|lint| lint.severity == Severity::Deny)
+            .map(|lint| render(&Diagnostic::from(lint))),
+    )
+}
+
+/// The parser of a grammar, as items. Errors located in the grammar are rendered by `render`.
+fn generate(
+    grammar: &Grammar,
+    name: &str,
+    options: &codegen::Options,
+    at: Span,
+    render: impl Fn(&Diagnostic) -> syn::Error,
+) -> syn::Result<TokenStream> {
+    let generated =
+        codegen::generate(grammar, name, options).map_err(|err| grammar_error(err, at, render))?;
+    syn::parse_str(&generated.code)
+}
+
+/// An error of a grammar, as one compile error for each of its diagnostics rendered by `render`,
+/// or as its message at `at` when it has none
+fn grammar_error(
+    err: impl Into<rantlr::Error>,
+    at: Span,
+    render: impl Fn(&Diagnostic) -> syn::Error,
+) -> syn::Error {
+    let err = err.into();
+    combine(of_grammar_error(&err).iter().map(render))
+        .err()
+        .unwrap_or_else(|| syn::Error::new(at, err))
+}
+
+fn expand_inline(input: Inline) -> syn::Result<TokenStream> {
+    let Inline { settings, source } = input;
+    let settings = Settings::new(settings, false)?;
+    let text = source.value();
+    let render = |diagnostic: &Diagnostic| {
+        syn::Error::new(
+            span(&source, diagnostic),
+            diagnostic.render(INLINE, FileId::default(), &text),
+        )
+    };
+    let grammar = Grammar::parse(&text)
+        .map_err(|err| combine(err.diagnostics.iter().map(render)).unwrap_err())?;
+    denied(&grammar, render)?;
+    generate(&grammar, INLINE, &settings.options, source.span(), render)
+}
+
+fn expand_module(settings: Settings, module: ItemMod) -> syn::Result<TokenStream> {
+    let ItemMod {
+        attrs,
+        vis,
+        ident,
+        content,
+        ..
+    } = module;
+    let Some((_, items)) = content else {
+        return Err(syn::Error::new(
+            ident.span(),
+            "the parser is generated into a module with a body, as in `mod parser {}`",
+        ));
+    };
+    let generated = settings
+        .grammar
+        .ok_or_else(|| syn::Error::new(Span::call_site(), "expected `grammar = \"<path>\"`"))
+        .and_then(|grammar| expand_file(&grammar, &settings.options))
+        .unwrap_or_else(syn::Error::into_compile_error);
+    Ok(quote! {
+        #(#attrs)*
+        #vis mod #ident {
+            #(#items)*
+            #generated
+        }
+    })
+}
+
+/// The parser of a grammar file, and constants which make the crate depend on the files it read
+fn expand_file(grammar: &LitStr, options: &codegen::Options) -> syn::Result<TokenStream> {
+    let mut files = SourceMap::default();
+    let loaded = Grammar::load_into(crate_root().join(grammar.value()), &mut files);
+    let read: Vec<String> = files
+        .paths()
+        .chain(options.messages.as_deref())
+        .map(|path| path.display().to_string())
+        .collect();
+    let tracked = quote! { #(const _: &str = include_str!(#read);)* };
+    let at = grammar.span();
+    let render = |diagnostic: &Diagnostic| syn::Error::new(at, diagnostic.render_in(&files));
+    let parsed = loaded.map_err(|err| grammar_error(err, at, render))?;
+    denied(&parsed, render)?;
+    let generated = generate(&parsed, &grammar.value(), options, at, render)?;
+    Ok(quote! { #tracked #generated })
+}
+
+/// Generate the parser of an inline grammar, as items. The grammar is a string literal, raw to
+/// not escape its backslashes, and comes after the settings:
+///
+/// ```
+/// mod sum {
+///     rantlr_macros::grammar!(backend = "lalr", r#"
+///         sum: sum PLUS NUM | NUM;
+///         PLUS: \+;
+///         NUM: [0-9]+;
+///     "#);
+/// }
+///
+/// assert!(sum::parse("1+2+3").is_ok());
+/// assert!(sum::parse("1+").is_err());
+/// ```
+///
+/// Imports are not read, [`macro@rantlr`] reads them from grammar files.
+#[proc_macro]
+pub fn grammar(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as Inline);
+    expand_inline(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Generate the parser of a grammar file, and its imports, into a module. The path is relative
+/// to the crate root, and the crate is rebuilt when one of the files changes.
+///
+/// ```ignore
+/// #[rantlr_macros::rantlr(grammar = "src/sql.g4", backend = "lalr")]
+/// mod sql {}
+/// ```
+///
+/// Items of the module are kept, next to the parser.
+#[proc_macro_attribute]
+pub fn rantlr(
+    args: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let args = parse_macro_input!(args with Punctuated::<Setting, Token![,]>::parse_terminated);
+    let module = parse_macro_input!(item as ItemMod);
+    Settings::new(args, true)
+        .and_then(|settings| expand_module(settings, module))
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inline(tokens: TokenStream) -> String {
+        syn::parse2(tokens)
+            .and_then(expand_inline)
+            .unwrap_or_else(syn::Error::into_compile_error)
+            .to_string()
+    }
+
+    fn module(args: TokenStream, item: TokenStream) -> String {
+        let args =
+            syn::parse::Parser::parse2(Punctuated::<Setting, Token![,]>::parse_terminated, args)
+                .unwrap();
+        Settings::new(args, true)
+            .and_then(|settings| expand_module(settings, syn::parse2(item).unwrap()))
+            .unwrap_or_else(syn::Error::into_compile_error)
+            .to_string()
+    }
+
+    macro_rules! test_expand {
+        ($name:ident, $expanded:expr, $expected:expr) => {
+            #[test]
+            fn $name() {
+                let expanded = $expanded;
+                assert!(expanded.contains($expected), "{expanded}");
+            }
+        };
+    }
+
+    test_expand!(
+        inline_parser,
+        inline(quote!(r"sum: sum PLUS NUM | NUM; PLUS: \+; NUM: [0-9]+;")),
+        "pub fn parse"
+    );
+    test_expand!(
+        inline_syntax_error,
+        inline(quote!(r"sum: sum PLUS NUM | NUM; PLUS NUM;")),
+        "compile_error ! { \"error[E002]: unexpected lexer rule name `NUM`"
+    );
+    test_expand!(
+        inline_denied_lint,
+        inline(quote!(r"s: A t; A: a;")),
+        "unproductive_rule"
+    );
+    test_expand!(
+        inline_conflict,
+        inline(quote!(r"e: e PLUS e | NUM; PLUS: \+; NUM: [0-9]+;")),
+        "compile_error ! { \"error[E007]: shift/reduce conflict"
+    );
+    test_expand!(
+        unknown_backend,
+        inline(quote!(backend = "cyk", r"s: A; A: a;")),
+        "unknown backend `cyk`"
+    );
+    test_expand!(
+        grammar_setting_inline,
+        inline(quote!(grammar = "x.g4", r"s: A; A: a;")),
+        "unknown setting `grammar`, expected `backend`, `conflicts` or `messages`"
+    );
+    test_expand!(
+        file_parser,
+        module(
+            quote!(grammar = "../examples/rantlr.g4", backend = "ll1"),
+            quote!(
+                mod meta {
+                    pub struct Kept;
+                }
+            )
+        ),
+        "pub struct Kept ; const _ : & str = include_str ! ("
+    );
+    test_expand!(
+        file_missing,
+        module(
+            quote!(grammar = "missing.g4"),
+            quote!(
+                mod meta {}
+            )
+        ),
+        "compile_error ! { \"cannot read `"
+    );
+    test_expand!(
+        file_no_body,
+        module(
+            quote!(grammar = "x.g4"),
+            quote!(
+                mod meta;
+            )
+        ),
+        "into a module with a body"
+    );
+}
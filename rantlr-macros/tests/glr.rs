@@ -0,0 +1,42 @@
+//! The derivations of generated GLR parsers, whose forests split long rules into intermediate nodes
+
+mod ternary {
+    rantlr_macros::grammar!(
+        backend = "glr",
+        r#"
+        s: s s s | A;
+        A: a;
+    "#
+    );
+}
+
+macro_rules! test_glr {
+    ($name:ident, $module:ident, $input:expr => $should_be:expr) => {
+        #[test]
+        fn $name() {
+            let parse = $module::parse($input).unwrap();
+            assert_eq!(parse.trees().count(), $should_be);
+        }
+    };
+}
+
+test_glr!(glr_unambiguous, ternary, "aaa" => 1);
+test_glr!(glr_ternary_trees, ternary, "aaaaaaa" => 12);
+
+#[test]
+fn glr_tree() {
+    let parse = ternary::parse("aaa").unwrap();
+    let ternary::Tree::Node { children, .. } = &parse.tree().unwrap() else {
+        panic!("the root is a node");
+    };
+    assert_eq!(children.len(), 3);
+}
+
+#[test]
+fn glr_ambiguous() {
+    let parse = ternary::parse("aaaaa").unwrap();
+    assert_eq!(
+        parse.tree().unwrap_err().derivations,
+        ternary::sppf::Count::Finite(3)
+    );
+}
@@ -0,0 +1,45 @@
+//! The trees of generated packrat parsers, which are built from their memoized matches
+
+rantlr_macros::grammar!(
+    backend = "peg",
+    r#"
+    s: o o !B A | o B;
+    o: C | ;
+    A: a;
+    B: b;
+    C: c;
+"#
+);
+
+/// A tree as the rules and token kinds it is made of
+fn render(tree: &Tree) -> String {
+    let list = |trees: &[Tree]| trees.iter().map(render).collect::<Vec<_>>().join(" ");
+    match tree {
+        Tree::Node { rule, children, .. } => format!("{rule:?}({})", list(children)),
+        Tree::Leaf(token) => format!("{:?}", token.kind),
+        Tree::Error(children) => format!("Error({})", list(children)),
+        Tree::Missing { kind, .. } => format!("Missing({kind:?})"),
+    }
+}
+
+macro_rules! test_peg {
+    ($name:ident, $input:expr => $should_be:expr) => {
+        #[test]
+        fn $name() {
+            assert_eq!(render(&parse($input).unwrap()), $should_be);
+        }
+    };
+}
+
+test_peg!(peg_first_alternative, "ca" => "s(o(C) o() A)");
+test_peg!(peg_shared_empty_match, "a" => "s(o() o() A)");
+test_peg!(peg_second_alternative, "cb" => "s(o(C) B)");
+
+#[test]
+fn peg_unexpected_token() {
+    let Err(Error::UnexpectedToken { found, expected }) = parse("cc") else {
+        panic!("cc parsed");
+    };
+    assert_eq!(found.kind, TokenKind::Eof);
+    assert_eq!(expected, [TokenKind::A]);
+}
@@ -0,0 +1,123 @@
+//! Recovery of the generated parsers from truncated input, which they complete with missing tokens
+
+/// `render`, a tree as the rules and token kinds it is made of
+macro_rules! render {
+    () => {
+        pub fn render(tree: &Tree) -> String {
+            let list = |trees: &[Tree]| trees.iter().map(render).collect::<Vec<_>>().join(" ");
+            match tree {
+                Tree::Node { rule, children, .. } => format!("{rule:?}({})", list(children)),
+                Tree::Leaf(token) => format!("{:?}", token.kind),
+                Tree::Error(children) => format!("Error({})", list(children)),
+                Tree::Missing { kind, .. } => format!("Missing({kind:?})"),
+            }
+        }
+
+        /// The tree of the input, and the token kinds expected by its errors, sorted
+        pub fn recover(input: &str) -> (String, Vec<Vec<String>>) {
+            let recovered = parse_recovering(input);
+            let errors = recovered
+                .errors
+                .iter()
+                .map(|err| {
+                    let Error::UnexpectedToken { expected, .. } = err else {
+                        panic!("{err}");
+                    };
+                    let mut expected: Vec<String> =
+                        expected.iter().map(|kind| format!("{kind:?}")).collect();
+                    expected.sort();
+                    expected
+                })
+                .collect();
+            (render(&recovered.tree), errors)
+        }
+    };
+}
+
+mod ll1 {
+    rantlr_macros::grammar!(
+        backend = "ll1",
+        r#"
+        e: t e2;
+        e2: PLUS t e2 | ;
+        t: NUM | LP e RP;
+        PLUS: \+;
+        NUM: [0-9]+;
+        LP: \(;
+        RP: \);
+    "#
+    );
+    render!();
+}
+
+mod rd {
+    rantlr_macros::grammar!(
+        backend = "recursive-descent",
+        r#"
+        e: t e2;
+        e2: PLUS t e2 | ;
+        t: NUM | LP e RP;
+        PLUS: \+;
+        NUM: [0-9]+;
+        LP: \(;
+        RP: \);
+    "#
+    );
+    render!();
+}
+
+mod lalr {
+    rantlr_macros::grammar!(
+        backend = "lalr",
+        r#"
+        e: e PLUS t | t;
+        t: NUM | LP e RP;
+        PLUS: \+;
+        NUM: [0-9]+;
+        LP: \(;
+        RP: \);
+    "#
+    );
+    render!();
+}
+
+macro_rules! test_truncated {
+    ($name:ident, $input:expr, top_down: $top_down:expr, lalr: $lalr:expr, expected: [$($kind:expr),*]) => {
+        #[test]
+        fn $name() {
+            let expected = vec![vec![$($kind.to_string()),*]];
+            assert_eq!(ll1::recover($input), ($top_down.to_string(), expected.clone()), "ll1");
+            assert_eq!(rd::recover($input), ($top_down.to_string(), expected.clone()), "rd");
+            assert_eq!(lalr::recover($input), ($lalr.to_string(), expected), "lalr");
+        }
+    };
+}
+
+test_truncated!(
+    missing_operand,
+    "1+",
+    top_down: "e(t(NUM) e2(PLUS t(Missing(NUM)) e2()))",
+    lalr: "e(e(t(NUM)) PLUS t(Missing(NUM)))",
+    expected: ["LP", "NUM"]
+);
+test_truncated!(
+    missing_parenthesis,
+    "(1",
+    top_down: "e(t(LP e(t(NUM) e2()) Missing(RP)) e2())",
+    lalr: "e(t(LP e(t(NUM)) Missing(RP)))",
+    expected: ["PLUS", "RP"]
+);
+test_truncated!(
+    missing_operand_and_parenthesis,
+    "(1+",
+    top_down: "e(t(LP e(t(NUM) e2(PLUS t(Missing(NUM)) e2())) Missing(RP)) e2())",
+    lalr: "e(t(LP e(e(t(NUM)) PLUS t(Missing(NUM))) Missing(RP)))",
+    expected: ["LP", "NUM"]
+);
+test_truncated!(
+    nested,
+    "((",
+    top_down: "e(t(LP e(t(LP e(t(Missing(NUM)) e2()) Missing(RP)) e2()) Missing(RP)) e2())",
+    lalr: "e(t(LP e(t(LP e(t(Missing(NUM))) Missing(RP))) Missing(RP)))",
+    expected: ["LP", "NUM"]
+);
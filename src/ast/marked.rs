@@ -1,17 +1,31 @@
 use std::{
     any::{Any, TypeId},
     collections::HashMap,
+    fmt,
     marker::PhantomData,
 };
 
 pub trait Marker {}
 
-#[derive(Debug)]
 pub struct Node<TNode, TMarker: Marker> {
     pub node: TNode,
     _marker: PhantomData<TMarker>,
     // This is kinda cursed, but only accessed from typesafe functions
-    annotations: HashMap<TypeId, Box<dyn Any>>,
+    annotations: HashMap<TypeId, Box<dyn Annotation>>,
+}
+
+/// Annotations are listed in the order of their `Debug` output, to keep snapshots stable
+impl<TNode: fmt::Debug, TMarker: Marker> fmt::Debug for Node<TNode, TMarker> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut annotations: Vec<&dyn Annotation> =
+            self.annotations.values().map(Box::as_ref).collect();
+        annotations.sort_by_cached_key(|annotation| format!("{annotation:?}"));
+        f.debug_struct("Node")
+            .field("node", &self.node)
+            .field("_marker", &self._marker)
+            .field("annotations", &DebugSet(annotations))
+            .finish()
+    }
 }
 
 impl<TNode, TMarker: Marker> From<TNode> for Node<TNode, TMarker> {
@@ -43,11 +57,19 @@ impl<TNode, TMarker: Marker> Node<TNode, TMarker> {
     pub fn get_annotation<U: Annotation>(&self) -> Option<&U> {
         self.annotations
             .get(&TypeId::of::<U>())
-            .and_then(|boxed| boxed.downcast_ref::<U>())
+            .and_then(|boxed| (boxed.as_ref() as &dyn Any).downcast_ref::<U>())
+    }
+}
+
+struct DebugSet<'a>(Vec<&'a dyn Annotation>);
+
+impl fmt::Debug for DebugSet<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(&self.0).finish()
     }
 }
 
-pub trait Annotation: 'static {}
+pub trait Annotation: Any + fmt::Debug {}
 
 #[derive(Debug)]
 pub struct DefaultMarker;
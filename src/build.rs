@@ -0,0 +1,290 @@
+//! Generating parsers from a build script.
+//!
+//! ```no_run
+//! // build.rs
+//! use rantlr::{build::Config, codegen::Backend};
+//!
+//! fn main() {
+//!     Config::new()
+//!         .grammar("src/sql.g4")
+//!         .backend(Backend::Lalr)
+//!         .compile()
+//!         .unwrap();
+//! }
+//! ```
+//!
+//! The parser is then included where it is used. It tokenizes with the `regex` crate, which the
+//! crate depends on as well:
+//!
+//! ```ignore
+//! mod sql {
+//!     include!(concat!(env!("OUT_DIR"), "/sql.rs"));
+//! }
+//! ```
+//!
+//! Cargo reruns the build script when the grammar, one of its imports or its messages file
+//! changes. Lints and resolved conflicts are reported as cargo warnings, denied lints and errors
+//! as cargo errors.
+
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    analysis::{lint, Severity},
+    codegen::{self, Backend},
+    diagnostic::{of_grammar_error, Diagnostic},
+    error::Error,
+    span::SourceMap,
+    table::ConflictPolicy,
+    Grammar,
+};
+
+/// How to generate the parser of a grammar. Each grammar of a crate has its own `Config`.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    grammar: Option<PathBuf>,
+    include: Vec<PathBuf>,
+    options: codegen::Options,
+    out_dir: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn new() -> Config {
+        Config::default()
+    }
+
+    /// The grammar file, relative to the crate root. Its parser is written to `<name>.rs`, where
+    /// `<name>` is the file name without its extension.
+    pub fn grammar(&mut self, path: impl Into<PathBuf>) -> &mut Config {
+        self.grammar = Some(path.into());
+        self
+    }
+
+    /// A directory to look for imported grammars in, after the directory of the importing file
+    pub fn include(&mut self, dir: impl Into<PathBuf>) -> &mut Config {
+        self.include.push(dir.into());
+        self
+    }
+
+    /// The kind of parser to generate, LALR by default
+    pub fn backend(&mut self, backend: Backend) -> &mut Config {
+        self.options.backend = backend;
+        self
+    }
+
+    /// What to do with conflicts not declared by `%expect` and `%expect_rr`
+    pub fn conflicts(&mut self, policy: ConflictPolicy) -> &mut Config {
+        self.options.conflicts = policy;
+        self
+    }
+
+    /// Syntax error messages for the states of the LR backends, see [`crate::table::messages`]
+    pub fn messages(&mut self, path: impl Into<PathBuf>) -> &mut Config {
+        self.options.messages = Some(path.into());
+        self
+    }
+
+    /// Where to write the parser, `OUT_DIR` by default
+    pub fn out_dir(&mut self, path: impl Into<PathBuf>) -> &mut Config {
+        self.out_dir = Some(path.into());
+        self
+    }
+
+    /// Generate the parser, printing the instructions for cargo on stdout
+    ///
+    /// # Panics
+    ///
+    /// If no grammar was given, or no output directory and `OUT_DIR` is not set, as outside of
+    /// build scripts.
+    pub fn compile(&self) -> Result<(), Error> {
+        self.compile_to(&mut io::stdout().lock())
+    }
+
+    fn compile_to(&self, out: &mut impl Write) -> Result<(), Error> {
+        let result = self.generate(out);
+        if let Err(err) = &result {
+            cargo(out, "error", &err.to_string());
+        }
+        result
+    }
+
+    fn generate(&self, out: &mut impl Write) -> Result<(), Error> {
+        let path = self.grammar.as_deref().expect("no grammar to compile");
+        let out_dir = match &self.out_dir {
+            Some(out_dir) => out_dir.clone(),
+            None => env::var_os("OUT_DIR")
+                .expect("`OUT_DIR` is set for build scripts")
+                .into(),
+        };
+        let mut files = SourceMap::default();
+        let loaded = Grammar::load_with(path, &self.include, &mut files);
+        rerun_if_changed(out, files.paths().chain(self.options.messages.as_deref()));
+        // Errors in the grammar are printed with its source, before their summary
+        let located = |out: &mut _, err: Error| {
+            for diagnostic in of_grammar_error(&err) {
+                cargo(out, "error", &diagnostic.render_in(&files));
+            }
+            err
+        };
+        let grammar = loaded.map_err(|err| located(out, err))?;
+        let lints = lint(&grammar);
+        for lint in &lints {
+            let level = match lint.severity {
+                Severity::Deny => "error",
+                _ => "warning",
+            };
+            cargo(out, level, &Diagnostic::from(lint).render_in(&files));
+        }
+        let count = lints
+            .iter()
+            .filter(|lint| lint.severity == Severity::Deny)
+            .count();
+        if count > 0 {
+            return Err(Error::Lints {
+                path: path.into(),
+                count,
+            });
+        }
+        let generated = codegen::generate(&grammar, &path.display().to_string(), &self.options)
+            .map_err(|err| located(out, err))?;
+        for warning in &generated.warnings {
+            cargo(out, "warning", &warning.render_in(&files));
+        }
+        let stem = path.file_stem().unwrap_or(path.as_os_str());
+        let target = out_dir.join(Path::new(stem).with_extension("rs"));
+        fs::write(&target, generated.code).map_err(|source| Error::Io {
+            path: target,
+            source,
+        })
+    }
+}
+
+fn rerun_if_changed<'a>(out: &mut impl Write, paths: impl Iterator<Item = &'a Path>) {
+    for path in paths {
+        writeln!(out, "cargo::rerun-if-changed={}", path.display()).unwrap();
+    }
+}
+
+/// Print a message for cargo, a line at a time since cargo reads instructions by line
+fn cargo(out: &mut impl Write, level: &str, message: &str) {
+    for line in message.lines() {
+        writeln!(out, "cargo::{level}={line}").unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_snapshot;
+
+    use super::*;
+
+    /// A fresh directory holding `files`
+    fn dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("rantlr-build-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (file, content) in files {
+            fs::write(dir.join(file), content).unwrap();
+        }
+        dir
+    }
+
+    fn compile(dir: &Path, config: &mut Config) -> (Result<(), Error>, String) {
+        let mut out = vec![];
+        let result = config
+            .grammar(dir.join("sum.g4"))
+            .out_dir(dir)
+            .compile_to(&mut out);
+        (result, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn imports() {
+        let dir = dir(
+            "imports",
+            &[
+                ("sum.g4", "%import tokens;\nsum: sum PLUS NUM | NUM;\n"),
+                (
+                    "tokens.g4",
+                    "%import tokens;\nsum: NUM;\nPLUS: \\+;\nNUM: [0-9]+;\n",
+                ),
+            ],
+        );
+        let grammar = Grammar::load(dir.join("sum.g4")).unwrap();
+        let rules: Vec<&str> = grammar.rules.iter().map(|rule| rule.name()).collect();
+        assert_eq!(rules, ["sum", "PLUS", "NUM"]);
+    }
+
+    #[test]
+    fn imports_from_include() {
+        let dir = dir(
+            "imports_from_include",
+            &[("sum.g4", "%import tokens;\nsum: sum PLUS NUM | NUM;\n")],
+        );
+        let include = dir.join("include");
+        fs::create_dir(&include).unwrap();
+        fs::write(include.join("tokens.g4"), "PLUS: \\+;\nNUM: [0-9]+;\n").unwrap();
+        let (result, out) = compile(&dir, Config::new().include(&include));
+        result.unwrap();
+        assert!(out.ends_with(&format!(
+            "cargo::rerun-if-changed={}\n",
+            include.join("tokens.g4").display()
+        )));
+    }
+
+    #[test]
+    fn writes_module() {
+        let dir = dir(
+            "writes_module",
+            &[
+                ("sum.g4", "%import tokens;\nsum: sum PLUS NUM | NUM;\n"),
+                ("tokens.g4", "PLUS: \\+;\nNUM: [0-9]+;\n"),
+            ],
+        );
+        let (result, out) = compile(&dir, &mut Config::new());
+        result.unwrap();
+        assert_eq!(
+            out,
+            format!(
+                "cargo::rerun-if-changed={}\ncargo::rerun-if-changed={}\n",
+                dir.join("sum.g4").display(),
+                dir.join("tokens.g4").display()
+            )
+        );
+        assert!(fs::read_to_string(dir.join("sum.rs"))
+            .unwrap()
+            .contains("pub fn parse("));
+    }
+
+    #[test]
+    fn syntax_error_in_import() {
+        let dir = dir(
+            "syntax_error_in_import",
+            &[
+                ("sum.g4", "%import tokens;\nsum: sum PLUS NUM | NUM;\n"),
+                ("tokens.g4", "PLUS: \\+;\nNUM NUM;\n"),
+            ],
+        );
+        let (result, out) = compile(&dir, &mut Config::new());
+        assert!(matches!(result, Err(Error::Syntax(_))));
+        assert_snapshot!(out.replace(&dir.display().to_string(), "DIR"));
+        assert!(!dir.join("sum.rs").exists());
+    }
+
+    #[test]
+    fn warnings() {
+        let dir = dir(
+            "warnings",
+            &[(
+                "sum.g4",
+                "sum: sum PLUS NUM | NUM;\nPLUS: \\+;\nNUM: [0-9]+;\nMINUS: -;\n",
+            )],
+        );
+        let (result, out) = compile(&dir, &mut Config::new());
+        result.unwrap();
+        assert_snapshot!(out.replace(&dir.display().to_string(), "DIR"));
+    }
+}
@@ -0,0 +1,569 @@
+//! Compiler-style diagnostics, rendered with the source lines they point at.
+
+use std::fmt::{self, Write};
+
+use crate::{
+    error::{ConflictError, Error, GrammarError, InputError, LexerError, ParserError},
+    internal::{
+        analysis::lint::{self, Lint},
+        lexer::token::TokenKind,
+    },
+    span::{FileId, LineIndex, SourceMap, Span, Unit},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+/// A span of source with a message, underlined with `^` when primary and `-` otherwise
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+/// A fix for a diagnostic, which puts `replacement` in place of the source at `span`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub message: String,
+    pub span: Span,
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<String>,
+    pub message: String,
+    pub primary: Option<Label>,
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity,
+            code: None,
+            message: message.into(),
+            primary: None,
+            secondary: vec![],
+            notes: vec![],
+            help: None,
+            suggestions: vec![],
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Diagnostic::new(Severity::Error, message)
+    }
+
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+
+    pub fn with_primary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.primary = Some(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.secondary.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    pub fn with_suggestion(
+        mut self,
+        span: Span,
+        replacement: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        self.suggestions.push(Suggestion {
+            message: message.into(),
+            span,
+            replacement: replacement.into(),
+        });
+        self
+    }
+
+    /// The file of the primary label
+    fn file(&self) -> FileId {
+        self.primary
+            .as_ref()
+            .map_or(FileId::default(), |label| label.span.file)
+    }
+
+    /// The diagnostic rendered against the file of its primary label, see [`Diagnostic::render`].
+    /// One without labels is rendered without a location.
+    pub fn render_in(&self, files: &SourceMap) -> String {
+        if self.primary.is_none() {
+            return self.render_unlocated();
+        }
+        let file = self.file();
+        self.render(
+            &files.path(file).display().to_string(),
+            file,
+            files.source(file),
+        )
+    }
+
+    fn header(&self) -> String {
+        match &self.code {
+            Some(code) => format!("{}[{code}]: {}\n", self.severity, self.message),
+            None => format!("{}: {}\n", self.severity, self.message),
+        }
+    }
+
+    /// Notes, help and suggestions, each on a line after `prefix`. Notes of several lines
+    /// continue under their first one.
+    fn footer(&self, out: &mut String, prefix: &str) {
+        let indent = format!("\n{}", " ".repeat(prefix.len() + "note: ".len()));
+        for note in &self.notes {
+            writeln!(out, "{prefix}note: {}", note.replace('\n', &indent)).unwrap();
+        }
+        if let Some(help) = &self.help {
+            writeln!(out, "{prefix}help: {help}").unwrap();
+        }
+        for suggestion in &self.suggestions {
+            writeln!(out, "{prefix}help: {}", suggestion.message).unwrap();
+        }
+    }
+
+    fn render_unlocated(&self) -> String {
+        let mut out = self.header();
+        self.footer(&mut out, "  = ");
+        out
+    }
+
+    /// The diagnostic the way rustc prints one, with every labelled line of `source` and carets
+    /// under the labels. Only labels in `file` are shown.
+    pub fn render(&self, path: &str, file: FileId, source: &str) -> String {
+        let mut out = self.header();
+
+        let index = LineIndex::new(source);
+        let mut labels: Vec<(&Label, char)> = self
+            .primary
+            .iter()
+            .map(|label| (label, '^'))
+            .chain(self.secondary.iter().map(|label| (label, '-')))
+            .filter(|(label, _)| label.span.file == file)
+            .collect();
+        let lines: Vec<&str> = source.split('\n').collect();
+        let gutter = labels
+            .iter()
+            .map(|(label, _)| {
+                (index.line_col(label.span.start, Unit::Char).line + 1)
+                    .to_string()
+                    .len()
+            })
+            .max()
+            .unwrap_or(1);
+        let pad = " ".repeat(gutter);
+
+        match labels.first() {
+            Some((label, _)) => writeln!(
+                out,
+                "{pad}--> {path}:{}",
+                index.line_col(label.span.start, Unit::Char)
+            ),
+            None => writeln!(out, "{pad}--> {path}"),
+        }
+        .unwrap();
+        if !labels.is_empty() {
+            writeln!(out, "{pad} |").unwrap();
+        }
+        labels.sort_by_key(|(label, _)| label.span.start);
+        let mut previous_line = None;
+        for (label, marker) in labels {
+            let start = index.line_col(label.span.start, Unit::Char);
+            let line = lines[start.line].trim_end_matches('\r');
+            if previous_line != Some(start.line) {
+                writeln!(out, "{:>gutter$} | {line}", start.line + 1).unwrap();
+                previous_line = Some(start.line);
+            }
+            // Spans running past the line are underlined to its end, and empty ones get a single mark
+            let end = index.line_col(label.span.end, Unit::Char);
+            let end_col = if end.line == start.line {
+                end.col
+            } else {
+                line.chars().count()
+            };
+            let width = end_col.saturating_sub(start.col).max(1);
+            // Keep tabs, so the marks line up however wide they are shown
+            let indent: String = line
+                .chars()
+                .take(start.col)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let marks: String = std::iter::repeat_n(marker, width).collect();
+            let underline = format!("{indent}{marks} {}", label.message);
+            writeln!(out, "{pad} | {}", underline.trim_end()).unwrap();
+        }
+        self.footer(&mut out, &format!("{pad} = "));
+        out
+    }
+}
+
+/// `a`, `a or b`, `a, b or c`
+fn one_of(items: &[String]) -> String {
+    match items {
+        [] => "nothing".into(),
+        [only] => only.clone(),
+        [init @ .., last] => format!("{} or {last}", init.join(", ")),
+    }
+}
+
+impl From<&LexerError> for Diagnostic {
+    fn from(err: &LexerError) -> Self {
+        match err {
+            LexerError::UnexpectedChar(c, span) if c.is_whitespace() => {
+                Diagnostic::error("unexpected whitespace in a lexer pattern")
+                    .with_code("E001")
+                    .with_primary(*span, "the pattern ends here")
+                    .with_help("end the rule with `;`, and match whitespace with `\\s`")
+            }
+            LexerError::UnexpectedChar(c, span) => {
+                Diagnostic::error(format!("unexpected character {c:?}"))
+                    .with_code("E001")
+                    .with_primary(*span, "not part of any token")
+            }
+            LexerError::UnexpectedEof => Diagnostic::error("the grammar is empty")
+                .with_code("E003")
+                .with_help("a grammar needs at least one rule, such as `s: A; A: a;`"),
+        }
+    }
+}
+
+impl From<&ParserError> for Diagnostic {
+    fn from(err: &ParserError) -> Self {
+        match err {
+            ParserError::UnexpectedToken { found, expected } => {
+                let names: Vec<String> = expected.iter().map(TokenKind::to_string).collect();
+                let mut diagnostic = Diagnostic::error(format!("unexpected {}", found.kind))
+                    .with_code("E002")
+                    .with_primary(found.span, format!("expected {}", one_of(&names)));
+                let missing = match expected.as_slice() {
+                    [TokenKind::Semicolon] => Some(";"),
+                    [TokenKind::Colon] => Some(":"),
+                    _ => None,
+                };
+                if let Some(missing) = missing {
+                    let at = Span::new(found.span.file, found.span.start..found.span.start);
+                    diagnostic =
+                        diagnostic.with_suggestion(at, missing, format!("insert `{missing}`"));
+                }
+                match found.kind {
+                    TokenKind::EOF => {
+                        diagnostic.with_help("every rule and directive ends with `;`")
+                    }
+                    _ => diagnostic,
+                }
+            }
+            ParserError::UnexpectedEof => {
+                Diagnostic::error("unexpected end of input").with_code("E003")
+            }
+        }
+    }
+}
+
+impl From<&InputError> for Diagnostic {
+    fn from(err: &InputError) -> Self {
+        match err {
+            InputError::UnexpectedChar(c, offset) => {
+                Diagnostic::error(format!("unexpected character {c:?}"))
+                    .with_code("E001")
+                    .with_primary(
+                        Span::new(FileId::default(), *offset..offset + c.len_utf8()),
+                        "no lexer rule matches here",
+                    )
+            }
+            InputError::UnexpectedToken {
+                found,
+                span,
+                expected,
+            } => Diagnostic::error(format!("unexpected {found}"))
+                .with_code("E002")
+                .with_primary(*span, format!("expected one of: {expected}")),
+            _ => Diagnostic::error(err.to_string()),
+        }
+    }
+}
+
+impl From<&Lint> for Diagnostic {
+    fn from(lint: &Lint) -> Self {
+        let severity = match lint.severity {
+            lint::Severity::Deny => Severity::Error,
+            lint::Severity::Warn | lint::Severity::Allow => Severity::Warning,
+        };
+        let mut diagnostic = Diagnostic::new(severity, lint.message.clone())
+            .with_code(format!("{}/{}", lint.id.code(), lint.id.name()))
+            .with_primary(lint.span, "");
+        for (span, message) in &lint.related {
+            diagnostic = diagnostic.with_secondary(*span, message.clone());
+        }
+        for note in &lint.notes {
+            diagnostic = diagnostic.with_note(note.clone());
+        }
+        diagnostic.help.clone_from(&lint.help);
+        diagnostic.suggestions.clone_from(&lint.suggestions);
+        diagnostic
+    }
+}
+
+impl From<&ConflictError> for Vec<Diagnostic> {
+    fn from(err: &ConflictError) -> Self {
+        let count = |span: Span, expected: usize, found: usize, kind: &str| {
+            let conflicts = if expected == 1 {
+                "conflict"
+            } else {
+                "conflicts"
+            };
+            Diagnostic::error(format!(
+                "expected {expected} {kind} {conflicts}, found {found}"
+            ))
+            .with_code("E008")
+            .with_primary(span, "declared here")
+            .with_help("run `rantlr analyze` to see the conflicts of the table")
+        };
+        match err {
+            ConflictError::InvalidExpect(directive, arg, span) => {
+                vec![Diagnostic::error(format!("invalid conflict count `{arg}`"))
+                    .with_code("E008")
+                    .with_primary(*span, format!("`%{directive}` takes a number of conflicts"))]
+            }
+            ConflictError::ShiftReduceCount {
+                expected,
+                found,
+                span,
+            } => vec![count(*span, *expected, *found, "shift/reduce")],
+            ConflictError::ReduceReduceCount {
+                expected,
+                found,
+                span,
+            } => vec![count(*span, *expected, *found, "reduce/reduce")],
+            ConflictError::Unresolved(diagnostics) => diagnostics.clone(),
+        }
+    }
+}
+
+impl From<&GrammarError> for Vec<Diagnostic> {
+    fn from(err: &GrammarError) -> Self {
+        match err {
+            GrammarError::LeftRecursive(rules) => {
+                let mut diagnostic =
+                    Diagnostic::error("left recursive rules need a bottom-up backend")
+                        .with_code("E009")
+                        .with_help("use an LR backend, or make the recursion right recursive");
+                for (index, (name, span)) in rules.iter().enumerate() {
+                    let message = format!("`{name}` derives itself first");
+                    diagnostic = if index == 0 {
+                        diagnostic.with_primary(*span, message)
+                    } else {
+                        diagnostic.with_secondary(*span, message)
+                    };
+                }
+                vec![diagnostic]
+            }
+            GrammarError::Ll1Conflicts(diagnostics) => diagnostics.clone(),
+            GrammarError::UnknownToken(_) => vec![],
+        }
+    }
+}
+
+/// The diagnostics of an error in a grammar, whose spans point into the files it was read from.
+/// Errors found anywhere else have none.
+pub fn of_grammar_error(err: &Error) -> Vec<Diagnostic> {
+    match err {
+        Error::Names(err) | Error::Input(InputError::Names(err)) => err.diagnostics.clone(),
+        Error::Conflict(err) => err.into(),
+        Error::Grammar(err) | Error::Input(InputError::Grammar(err)) => err.into(),
+        _ => vec![],
+    }
+}
+
+/// Every diagnostic of `source` rendered one after the other, as rustc reports a failed build
+pub fn render_all(diagnostics: &[Diagnostic], path: &str, source: &str) -> String {
+    let mut out: String = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.render(path, diagnostic.file(), source))
+        .collect();
+    if diagnostics.len() > 1 {
+        writeln!(
+            out,
+            "error: aborting due to {} previous errors",
+            diagnostics.len()
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// The diagnostic of an error found while reading a grammar or an input, if it has one
+pub fn of_error(err: &(dyn std::error::Error + 'static)) -> Option<Diagnostic> {
+    if let Some(err) = err.downcast_ref::<LexerError>() {
+        Some(err.into())
+    } else if let Some(err) = err.downcast_ref::<ParserError>() {
+        Some(err.into())
+    } else {
+        err.downcast_ref::<InputError>().map(Diagnostic::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::{lexer::lexer::tokenize, parser::functional::parse, testing::grammar};
+
+    fn span(range: std::ops::Range<usize>) -> Span {
+        Span::new(FileId::default(), range)
+    }
+
+    macro_rules! test_render {
+        ($name:ident, $source:expr, $diagnostic:expr => $expected:expr) => {
+            #[test]
+            fn $name() {
+                let diagnostic: Diagnostic = $diagnostic;
+                assert_eq!(
+                    diagnostic.render("g.g4", FileId::default(), $source),
+                    $expected
+                );
+            }
+        };
+    }
+
+    test_render!(
+        render_primary,
+        "s: A | ;\nA: a;",
+        Diagnostic::error("unexpected `;`").with_code("E002").with_primary(span(7..8), "expected an ident")
+            => "error[E002]: unexpected `;`\n --> g.g4:1:8\n  |\n1 | s: A | ;\n  |        ^ expected an ident\n"
+    );
+
+    test_render!(
+        render_secondary_note_and_help,
+        "s: t;\nt: s;",
+        Diagnostic::new(Severity::Warning, "cycle")
+            .with_primary(span(6..7), "here")
+            .with_secondary(span(0..1), "through")
+            .with_note("a note")
+            .with_help("some help")
+            => "warning: cycle\n --> g.g4:2:1\n  |\n1 | s: t;\n  | - through\n2 | t: s;\n  | ^ here\n  = note: a note\n  = help: some help\n"
+    );
+
+    test_render!(
+        render_multiline_span_and_tab,
+        "\tab\ncd",
+        Diagnostic::error("e").with_primary(span(1..5), "")
+            => "error: e\n --> g.g4:1:2\n  |\n1 | \tab\n  | \t^^\n"
+    );
+
+    test_render!(
+        render_empty_span_at_end,
+        "s: A",
+        Diagnostic::error("unexpected end of input").with_primary(span(4..4), "expected `;`")
+            => "error: unexpected end of input\n --> g.g4:1:5\n  |\n1 | s: A\n  |     ^ expected `;`\n"
+    );
+
+    test_render!(
+        render_without_labels,
+        "s: A;",
+        Diagnostic::error("no location")
+            => "error: no location\n --> g.g4\n"
+    );
+
+    test_render!(
+        render_wide_gutter,
+        "\n\n\n\n\n\n\n\n\ns: A;",
+        Diagnostic::error("e").with_primary(span(9..10), "")
+            => "error: e\n  --> g.g4:10:1\n   |\n10 | s: A;\n   | ^\n"
+    );
+
+    macro_rules! test_grammar_error {
+        ($name:ident, $source:expr => $expected:expr) => {
+            #[test]
+            fn $name() {
+                let err = tokenize($source).and_then(parse).unwrap_err();
+                let diagnostic = of_error(err.as_ref()).expect("the error has a diagnostic");
+                assert_eq!(
+                    diagnostic.render("g.g4", FileId::default(), $source),
+                    $expected
+                );
+            }
+        };
+    }
+
+    test_grammar_error!(
+        grammar_error_lists_expected,
+        "s: &: B;"
+            => "error[E002]: unexpected `:`\n --> g.g4:1:5\n  |\n1 | s: &: B;\n  |     ^ expected parser rule name or lexer rule name\n"
+    );
+
+    test_grammar_error!(
+        grammar_error_at_end,
+        "s: A"
+            => "error[E002]: unexpected end of input\n --> g.g4:1:5\n  |\n1 | s: A\n  |     ^ expected parser rule name, lexer rule name, directive, `&`, `!`, `|` or `;`\n  = help: every rule and directive ends with `;`\n"
+    );
+
+    test_grammar_error!(
+        grammar_error_unexpected_char,
+        "s: A $;\nA: a;"
+            => "error[E001]: unexpected character '$'\n --> g.g4:1:6\n  |\n1 | s: A $;\n  |      ^ not part of any token\n"
+    );
+
+    test_grammar_error!(
+        grammar_error_whitespace_in_pattern,
+        "s: A;\nA: a\n"
+            => "error[E001]: unexpected whitespace in a lexer pattern\n --> g.g4:2:5\n  |\n2 | A: a\n  |     ^ the pattern ends here\n  = help: end the rule with `;`, and match whitespace with `\\s`\n"
+    );
+
+    #[test]
+    fn lint_with_related_rules() {
+        let grammar = grammar("s: t;\nt: s | ;");
+        let lints = lint::lint(&grammar);
+        let cyclic = lints
+            .iter()
+            .find(|lint| lint.id == lint::LintId::CyclicRule)
+            .unwrap();
+        assert_eq!(
+            Diagnostic::from(cyclic).render("g.g4", FileId::default(), "s: t;\nt: s | ;"),
+            "error[L004/cyclic_rule]: rules form a cycle that consumes no input: `s` -> `t` -> `s`\n --> g.g4:1:1\n  |\n1 | s: t;\n  | ^\n2 | t: s | ;\n  | - `t` is part of the cycle\n  = note: a parser for it could loop forever\n"
+        );
+    }
+
+    #[test]
+    fn one_of_lists() {
+        let items = ["a", "b", "c"].map(String::from);
+        assert_eq!(one_of(&items[..1]), "a");
+        assert_eq!(one_of(&items[..2]), "a or b");
+        assert_eq!(one_of(&items), "a, b or c");
+    }
+}
@@ -1,12 +1,20 @@
+//! The errors of every stage, and [`Error`], which any of them converts into.
+
+use std::{fmt, io, path::PathBuf};
+
 use thiserror::Error;
 
-use crate::internal::lexer::token::{Token, TokenKind};
+use crate::{
+    diagnostic::{render_all, Diagnostic},
+    internal::lexer::token::{Token, TokenKind},
+    span::Span,
+};
 
 #[allow(clippy::module_name_repetitions)]
 #[derive(Error, Debug)]
 pub enum LexerError {
     #[error("Unexpected character: {0}")]
-    UnexpectedChar(char),
+    UnexpectedChar(char, Span),
     #[error("Unexpected end of input")]
     UnexpectedEof,
 }
@@ -14,22 +22,194 @@ pub enum LexerError {
 #[allow(clippy::module_name_repetitions)]
 #[derive(Error, Debug)]
 pub enum ParserError {
-    #[error("Unexpected token: {0:?}. Expected token of kind: {1:?}")]
-    UnexpectedToken(Token, TokenKind),
+    #[error("Unexpected {}, expected one of: {}", found.kind, expected.iter().map(TokenKind::to_string).collect::<Vec<_>>().join(", "))]
+    UnexpectedToken {
+        found: Token,
+        /// Every token the parser could have continued with
+        expected: Vec<TokenKind>,
+    },
     #[error("Unexpected end of input")]
     UnexpectedEof,
 }
 
+/// `conflict` or `conflicts`, after `count`
+fn conflicts(count: usize) -> &'static str {
+    if count == 1 {
+        "conflict"
+    } else {
+        "conflicts"
+    }
+}
+
+/// Conflicts of an LR table which its grammar does not account for
+#[derive(Error, Debug)]
+pub enum ConflictError {
+    /// The directive, its argument, and the span of the directive
+    #[error("Invalid conflict count for %{0}: {1}")]
+    InvalidExpect(String, String, Span),
+    #[error("Expected {expected} shift/reduce {}, found {found}", conflicts(*expected))]
+    ShiftReduceCount {
+        expected: usize,
+        found: usize,
+        /// The `%expect` directive
+        span: Span,
+    },
+    #[error("Expected {expected} reduce/reduce {}, found {found}", conflicts(*expected))]
+    ReduceReduceCount {
+        expected: usize,
+        found: usize,
+        /// The `%expect_rr` directive
+        span: Span,
+    },
+    /// An error for each conflict
+    #[error("{} unresolved {}", .0.len(), conflicts(.0.len()))]
+    Unresolved(Vec<Diagnostic>),
+}
+
+/// Grammars a backend cannot handle
+#[derive(Error, Debug)]
+pub enum GrammarError {
+    /// The left recursive rules, with the spans of their names
+    #[error("Left recursive rules need a bottom-up backend: {}", .0.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(", "))]
+    LeftRecursive(Vec<(String, Span)>),
+    /// An error for each conflict
+    #[error("The LL(1) table has {} {}", .0.len(), conflicts(.0.len()))]
+    Ll1Conflicts(Vec<Diagnostic>),
+    #[error("The meta-parser tokenizer has no token {0}")]
+    UnknownToken(String),
+}
+
+/// Errors found while interpreting a grammar on some input
+#[derive(Error, Debug)]
+pub enum InputError {
+    #[error("Invalid pattern for lexer rule {0}: {1}")]
+    InvalidPattern(String, String),
+    #[error("Unexpected character {0:?} at offset {1}")]
+    UnexpectedChar(char, usize),
+    #[error("Unexpected {found} at offset {}, expected one of: {expected}", span.start)]
+    UnexpectedToken {
+        found: String,
+        span: Span,
+        expected: String,
+    },
+    #[error("Every derivation of the input was removed by disambiguation filters")]
+    Filtered,
+    #[error(transparent)]
+    Grammar(#[from] GrammarError),
+    #[error(transparent)]
+    Names(#[from] NameErrors),
+}
+
+/// A messages file which does not fit the LR automaton of its grammar
+#[derive(Error, Debug)]
+pub enum MessagesError {
+    #[error("line {line}: expected a sentence such as `{start}: A B`, found `{found}`")]
+    Sentence {
+        line: usize,
+        start: String,
+        found: String,
+    },
+    #[error("line {0}: the entry has no message")]
+    NoMessage(usize),
+    #[error("line {0}: the entry still has the placeholder message")]
+    Placeholder(usize),
+    #[error("line {line}: unknown terminal `{terminal}`")]
+    UnknownTerminal { line: usize, terminal: String },
+    #[error("line {line}: the parser fails on token {position} of the sentence, `{terminal}`")]
+    Fails {
+        line: usize,
+        position: usize,
+        terminal: String,
+    },
+    #[error("line {line}: the sentence leads to state {state}, where no syntax error can happen")]
+    NoError { line: usize, state: usize },
+    #[error("line {line}: the sentence leads to state {state}, like the one on line {other}")]
+    Duplicate {
+        line: usize,
+        state: usize,
+        other: usize,
+    },
+    #[error("error states without a message: {}", .0.iter().map(usize::to_string).collect::<Vec<_>>().join(", "))]
+    Missing(Vec<usize>),
+}
+
+/// Every syntax error of a grammar, displayed as diagnostics against its source
+#[derive(Debug)]
+pub struct SyntaxErrors {
+    /// The name of the grammar in diagnostics, usually its path
+    pub name: String,
+    pub source: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Display for SyntaxErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&render_all(&self.diagnostics, &self.name, &self.source))
+    }
+}
+
+impl std::error::Error for SyntaxErrors {}
+
+/// Rules a grammar defines more than once or refers to without defining, as diagnostics against
+/// the files it was read from
+#[derive(Debug)]
+pub struct NameErrors {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Display for NameErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let messages: Vec<&str> = self
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        f.write_str(&messages.join("\n"))
+    }
+}
+
+impl std::error::Error for NameErrors {}
+
+/// Any error of the library, for callers which need not tell the stages apart
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("cannot read `{}`: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+    #[error(transparent)]
+    Syntax(#[from] SyntaxErrors),
+    #[error(transparent)]
+    Names(#[from] NameErrors),
+    #[error(transparent)]
+    Conflict(#[from] ConflictError),
+    #[error(transparent)]
+    Grammar(#[from] GrammarError),
+    #[error(transparent)]
+    Input(#[from] InputError),
+    #[error("{}", errors.iter().map(|err| format!("`{}`: {err}", path.display())).collect::<Vec<_>>().join("\n"))]
+    Messages {
+        path: PathBuf,
+        errors: Vec<MessagesError>,
+    },
+    #[error("syntax error messages need an LR backend, `slr` or `lalr`")]
+    MessagesBackend,
+    #[error("`{}` has {count} denied lints", path.display())]
+    Lints { path: PathBuf, count: usize },
+}
+
 #[macro_export]
 macro_rules! unexpected_char {
-    ($c:expr) => {
-        Err(LexerError::UnexpectedChar($c).into())
+    ($c:expr, $span:expr) => {
+        Err(LexerError::UnexpectedChar($c, $span).into())
     };
 }
 
 #[macro_export]
 macro_rules! unexpected_token {
-    ($got:expr, $expected:expr) => {
-        Err(ParserError::UnexpectedToken($got.clone(), $expected).into())
+    ($got:expr, [$($expected:expr),* $(,)?]) => {
+        Err(ParserError::UnexpectedToken {
+            found: $got.clone(),
+            expected: vec![$($expected),*],
+        }
+        .into())
     };
 }
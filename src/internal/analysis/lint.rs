@@ -0,0 +1,487 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+};
+
+use crate::{
+    diagnostic::Suggestion,
+    internal::{
+        ast::{Grammar, Ident, Production},
+        codegen::terminals,
+    },
+    span::Span,
+};
+
+use super::sets::{nullable, productive, reachable};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Allow,
+    Warn,
+    Deny,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintId {
+    UnknownLint,
+    UnreachableRule,
+    UnproductiveRule,
+    UnusedToken,
+    CyclicRule,
+    EmptyAlternative,
+    InvalidFilter,
+}
+
+impl LintId {
+    pub const ALL: [LintId; 7] = [
+        LintId::UnknownLint,
+        LintId::UnreachableRule,
+        LintId::UnproductiveRule,
+        LintId::UnusedToken,
+        LintId::CyclicRule,
+        LintId::EmptyAlternative,
+        LintId::InvalidFilter,
+    ];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            LintId::UnknownLint => "L000",
+            LintId::UnreachableRule => "L001",
+            LintId::UnproductiveRule => "L002",
+            LintId::UnusedToken => "L003",
+            LintId::CyclicRule => "L004",
+            LintId::EmptyAlternative => "L005",
+            LintId::InvalidFilter => "L006",
+        }
+    }
+
+    /// The name used to configure the lint with `%allow`, `%warn` and `%deny`
+    pub fn name(self) -> &'static str {
+        match self {
+            LintId::UnknownLint => "unknown_lint",
+            LintId::UnreachableRule => "unreachable_rule",
+            LintId::UnproductiveRule => "unproductive_rule",
+            LintId::UnusedToken => "unused_token",
+            LintId::CyclicRule => "cyclic_rule",
+            LintId::EmptyAlternative => "empty_alternative",
+            LintId::InvalidFilter => "invalid_filter",
+        }
+    }
+
+    pub fn default_severity(self) -> Severity {
+        match self {
+            LintId::UnproductiveRule | LintId::CyclicRule => Severity::Deny,
+            LintId::UnknownLint
+            | LintId::UnreachableRule
+            | LintId::UnusedToken
+            | LintId::EmptyAlternative
+            | LintId::InvalidFilter => Severity::Warn,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|id| id.name() == name)
+    }
+}
+
+#[derive(Debug)]
+pub struct Lint {
+    pub id: LintId,
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+    /// Other places the lint is about, with what they have to do with it
+    pub related: Vec<(Span, String)>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.severity {
+            Severity::Allow => "allowed",
+            Severity::Warn => "warning",
+            Severity::Deny => "error",
+        };
+        write!(
+            f,
+            "{level}[{}/{}]: {}",
+            self.id.code(),
+            self.id.name(),
+            self.message
+        )
+    }
+}
+
+/// Lint severities as configured by the `%allow`, `%warn` and `%deny` directives of a grammar.
+/// Later directives override earlier ones.
+struct LintConfig {
+    severities: HashMap<LintId, Severity>,
+    unknown: Vec<(String, Span)>,
+}
+
+impl From<&Grammar> for LintConfig {
+    fn from(grammar: &Grammar) -> Self {
+        let mut config = LintConfig {
+            severities: HashMap::new(),
+            unknown: vec![],
+        };
+        for directive in &grammar.directives {
+            let severity = match directive.name.as_str() {
+                "allow" => Severity::Allow,
+                "warn" => Severity::Warn,
+                "deny" => Severity::Deny,
+                _ => continue,
+            };
+            for arg in &directive.args {
+                match LintId::from_name(arg) {
+                    Some(id) => {
+                        config.severities.insert(id, severity);
+                    }
+                    None => config.unknown.push((arg.clone(), directive.span)),
+                }
+            }
+        }
+        config
+    }
+}
+
+impl LintConfig {
+    fn severity(&self, id: LintId) -> Severity {
+        self.severities
+            .get(&id)
+            .copied()
+            .unwrap_or(id.default_severity())
+    }
+}
+
+struct Linter<'a> {
+    grammar: &'a Grammar,
+    config: LintConfig,
+    lints: Vec<Lint>,
+}
+
+impl<'a> Linter<'a> {
+    /// The lint reported, to attach more to it, unless it is allowed
+    fn report(&mut self, id: LintId, span: Span, message: String) -> Option<&mut Lint> {
+        let severity = self.config.severity(id);
+        if severity == Severity::Allow {
+            return None;
+        }
+        self.lints.push(Lint {
+            id,
+            severity,
+            message,
+            span,
+            related: vec![],
+            notes: vec![],
+            help: None,
+            suggestions: vec![],
+        });
+        self.lints.last_mut()
+    }
+
+    /// A suggestion to remove a lexer rule, with the `;` right after its pattern. Parser rules
+    /// may have whitespace before their `;`, which their span does not tell.
+    fn remove_lexer_rule(&self, name: &str) -> Vec<Suggestion> {
+        let Some(rule) = self.grammar.rules.iter().find(|rule| rule.name() == name) else {
+            return vec![];
+        };
+        let span = rule.span();
+        vec![Suggestion {
+            message: format!("remove `{name}`"),
+            span: Span::new(span.file, span.start..span.end + 1),
+            replacement: String::new(),
+        }]
+    }
+
+    /// Where a rule is defined, or the start of the grammar if it is not
+    fn rule_span(&self, name: &str) -> Span {
+        self.grammar
+            .rules
+            .iter()
+            .find(|rule| rule.name() == name)
+            .map(Production::name_span)
+            .unwrap_or_default()
+    }
+
+    fn unknown_lints(&mut self) {
+        for (name, span) in std::mem::take(&mut self.config.unknown) {
+            let known: Vec<&str> = LintId::ALL.iter().map(|id| id.name()).collect();
+            if let Some(lint) =
+                self.report(LintId::UnknownLint, span, format!("unknown lint `{name}`"))
+            {
+                lint.help = Some(format!("the known lints are {}", known.join(", ")));
+            }
+        }
+    }
+
+    fn unreachable_rules(&mut self) {
+        let Some(start) = self.grammar.start_rule() else {
+            return;
+        };
+        let reachable = reachable(self.grammar);
+        for (name, _) in self.grammar.parser_rules() {
+            if !reachable.contains(name) {
+                self.report(
+                    LintId::UnreachableRule,
+                    self.rule_span(name),
+                    format!("rule `{name}` is unreachable from start rule `{start}`"),
+                );
+            }
+        }
+    }
+
+    fn unproductive_rules(&mut self) {
+        let productive = productive(self.grammar);
+        for (name, _) in self.grammar.parser_rules() {
+            if !productive.contains(name) {
+                self.report(
+                    LintId::UnproductiveRule,
+                    self.rule_span(name),
+                    format!("rule `{name}` can never derive a string of terminals"),
+                );
+            }
+        }
+    }
+
+    fn unused_tokens(&mut self) {
+        let used: HashSet<&str> = self
+            .grammar
+            .parser_rules()
+            .flat_map(|(_, alternatives)| {
+                alternatives
+                    .iter()
+                    .flat_map(|alternative| &alternative.idents)
+            })
+            .filter_map(|ident| match ident {
+                Ident::Terminal(name, _) => Some(name.as_str()),
+                Ident::NonTerminal(_, _) => None,
+            })
+            .collect();
+        for (name, _) in self.grammar.lexer_rules() {
+            if !used.contains(name) {
+                let suggestions = self.remove_lexer_rule(name);
+                if let Some(lint) = self.report(
+                    LintId::UnusedToken,
+                    self.rule_span(name),
+                    format!("lexer rule `{name}` is never used by a parser rule"),
+                ) {
+                    lint.suggestions = suggestions;
+                }
+            }
+        }
+    }
+
+    /// Finds rules which can derive themselves without consuming input, `A =>+ A`.
+    /// `A` steps to `B` if some alternative of `A` is `α B β` where both `α` and `β` are nullable.
+    fn cyclic_rules(&mut self) {
+        let nullable = nullable(self.grammar);
+        let mut steps: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (name, alternatives) in self.grammar.parser_rules() {
+            for alternative in alternatives {
+                for (i, ident) in alternative.idents.iter().enumerate() {
+                    let Ident::NonTerminal(target, _) = ident else {
+                        continue;
+                    };
+                    let others_nullable = alternative
+                        .idents
+                        .iter()
+                        .enumerate()
+                        .filter(|(j, _)| *j != i)
+                        .all(|(_, other)| match other {
+                            Ident::NonTerminal(other, _) => nullable.contains(other.as_str()),
+                            Ident::Terminal(_, _) => false,
+                        });
+                    if others_nullable {
+                        steps.entry(name).or_default().push(target);
+                    }
+                }
+            }
+        }
+
+        let mut reported = HashSet::new();
+        for (name, _) in self.grammar.parser_rules() {
+            if reported.contains(name) {
+                continue;
+            }
+            if let Some(cycle) = shortest_cycle(&steps, name) {
+                reported.extend(cycle.iter().copied());
+                let related: Vec<(Span, String)> = cycle
+                    .iter()
+                    .filter(|&&rule| rule != name)
+                    .map(|rule| {
+                        (
+                            self.rule_span(rule),
+                            format!("`{rule}` is part of the cycle"),
+                        )
+                    })
+                    .collect();
+                if let Some(lint) = self.report(
+                    LintId::CyclicRule,
+                    self.rule_span(name),
+                    format!(
+                        "rules form a cycle that consumes no input: {}",
+                        cycle
+                            .iter()
+                            .chain(std::iter::once(&name))
+                            .map(|rule| format!("`{rule}`"))
+                            .collect::<Vec<_>>()
+                            .join(" -> ")
+                    ),
+                ) {
+                    lint.related = related;
+                    lint.notes.push("a parser for it could loop forever".into());
+                }
+            }
+        }
+    }
+
+    fn empty_alternatives(&mut self) {
+        for (name, alternatives) in self.grammar.parser_rules() {
+            let empty = alternatives.iter().find(|alternative| {
+                alternative.idents.is_empty() && alternative.directives.is_empty()
+            });
+            if let (true, Some(empty)) = (alternatives.len() > 1, empty) {
+                if let Some(lint) = self.report(
+                    LintId::EmptyAlternative,
+                    empty.span,
+                    format!("rule `{name}` has an empty alternative, which may be accidental"),
+                ) {
+                    lint.help = Some("label it, as in `| %label Epsilon`, if it is meant".into());
+                }
+            }
+        }
+    }
+
+    /// `%reject` and `%follow_restrict` take a parser rule followed by terminals.
+    /// Filters naming anything else are ignored when parsing.
+    fn invalid_filters(&mut self) {
+        let terminals = terminals(self.grammar);
+        for directive in &self.grammar.directives {
+            if !matches!(directive.name.as_str(), "reject" | "follow_restrict") {
+                continue;
+            }
+            let name = &directive.name;
+            let Some((rule, rest)) = directive.args.split_first() else {
+                self.report(
+                    LintId::InvalidFilter,
+                    directive.span,
+                    format!("`%{name}` needs a parser rule"),
+                );
+                continue;
+            };
+            if !self.grammar.parser_rules().any(|(other, _)| other == rule) {
+                self.report(
+                    LintId::InvalidFilter,
+                    directive.span,
+                    format!("`%{name}` names `{rule}`, which is not a parser rule"),
+                );
+            }
+            for terminal in rest {
+                if !terminals.contains(&terminal.as_str()) {
+                    self.report(
+                        LintId::InvalidFilter,
+                        directive.span,
+                        format!("`%{name}` names `{terminal}`, which is not a terminal"),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Breadth first search for the shortest path from `start` back to itself
+fn shortest_cycle<'a>(
+    steps: &HashMap<&'a str, Vec<&'a str>>,
+    start: &'a str,
+) -> Option<Vec<&'a str>> {
+    let mut previous: HashMap<&str, &str> = HashMap::new();
+    let mut queue = VecDeque::from([start]);
+    while let Some(current) = queue.pop_front() {
+        for &next in steps.get(current).into_iter().flatten() {
+            if next == start {
+                let mut path = vec![current];
+                while let Some(&before) = previous.get(path.last().unwrap()) {
+                    path.push(before);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if !previous.contains_key(next) {
+                previous.insert(next, current);
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+/// Run every lint over the grammar, at the severities configured by its directives.
+/// Allowed lints are not returned.
+pub fn lint(grammar: &Grammar) -> Vec<Lint> {
+    let mut linter = Linter {
+        grammar,
+        config: LintConfig::from(grammar),
+        lints: vec![],
+    };
+    linter.unknown_lints();
+    linter.unreachable_rules();
+    linter.unproductive_rules();
+    linter.unused_tokens();
+    linter.cyclic_rules();
+    linter.empty_alternatives();
+    linter.invalid_filters();
+    linter.lints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::testing::grammar;
+
+    fn lint_src(src: &str) -> Vec<(LintId, Severity)> {
+        let grammar = grammar(src);
+        lint(&grammar)
+            .into_iter()
+            .map(|lint| (lint.id, lint.severity))
+            .collect()
+    }
+
+    macro_rules! test_lint {
+        ($name:ident, $src:expr => [$($id:ident: $severity:ident),*]) => {
+            #[test]
+            fn $name() {
+                assert_eq!(lint_src($src), vec![$((LintId::$id, Severity::$severity)),*]);
+            }
+        };
+    }
+
+    test_lint!(lint_clean_grammar, "s: A s | B; A: a; B: b;" => []);
+    test_lint!(lint_unreachable_rule, "s: A; t: A; A: a;" => [UnreachableRule: Warn]);
+    test_lint!(lint_unproductive_rule, "s: A t; t: A t; A: a;" => [UnproductiveRule: Deny, UnproductiveRule: Deny]);
+    test_lint!(lint_undefined_rule_is_unproductive, "s: A t; A: a;" => [UnproductiveRule: Deny]);
+    test_lint!(lint_unused_token, "s: A; A: a; B: b;" => [UnusedToken: Warn]);
+    test_lint!(lint_cycle, "s: t | A; t: u; u: s; A: a;" => [CyclicRule: Deny]);
+    test_lint!(lint_cycle_through_nullable, "s: n s n | A; n: |A; A: a;" => [CyclicRule: Deny, EmptyAlternative: Warn]);
+    test_lint!(lint_empty_alternative, "s: A s |; A: a;" => [EmptyAlternative: Warn]);
+    test_lint!(lint_only_empty_alternative_is_fine, "s: A e; e: ; A: a;" => []);
+    test_lint!(lint_labeled_empty_alternative_is_fine, "s: A s | %label Epsilon; A: a;" => []);
+    test_lint!(lint_allow, "%allow empty_alternative; s: A s |; A: a;" => []);
+    test_lint!(lint_deny, "%deny unused_token; s: A; A: a; B: b;" => [UnusedToken: Deny]);
+    test_lint!(lint_unknown, "%deny not_a_lint; s: A; A: a;" => [UnknownLint: Warn]);
+    test_lint!(lint_filters, "%reject s A; %follow_restrict s A; s: A; A: a;" => []);
+    test_lint!(lint_invalid_filters, "%reject t B; %follow_restrict; s: A; A: a;" =>
+        [InvalidFilter: Warn, InvalidFilter: Warn, InvalidFilter: Warn]);
+
+    #[test]
+    fn lint_cycle_message() {
+        let grammar = grammar("s: t | A; t: s; A: a;");
+        let lints = lint(&grammar);
+        assert_eq!(
+            lints[0].to_string(),
+            "error[L004/cyclic_rule]: rules form a cycle that consumes no input: `s` -> `t` -> `s`"
+        );
+    }
+}
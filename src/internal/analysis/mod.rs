@@ -0,0 +1,3 @@
+pub mod lint;
+pub mod names;
+pub mod sets;
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use crate::{
+    diagnostic::Diagnostic,
+    internal::{
+        ast::{Grammar, Ident, Production},
+        codegen::terminals,
+    },
+};
+
+/// Rules defined more than once, and references to parser rules the grammar does not define.
+///
+/// Tokens need no lexer rule, as they may come from another lexer, but a lookahead on one which
+/// appears nowhere else could never match.
+pub fn check_names(grammar: &Grammar) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let mut defined: HashMap<&str, &Production> = HashMap::new();
+    for rule in &grammar.rules {
+        match defined.get(rule.name()) {
+            Some(first) => diagnostics.push(
+                Diagnostic::error(format!(
+                    "the rule `{}` is defined more than once",
+                    rule.name()
+                ))
+                .with_code("E005")
+                .with_primary(rule.name_span(), "defined again here")
+                .with_secondary(first.name_span(), "first defined here")
+                .with_help("merge the alternatives into one rule, or rename one of them"),
+            ),
+            None => {
+                defined.insert(rule.name(), rule);
+            }
+        }
+    }
+    let terminals = terminals(grammar);
+    for (_, alternatives) in grammar.parser_rules() {
+        for alternative in alternatives {
+            let predicates = alternative
+                .predicates
+                .iter()
+                .map(|predicate| &predicate.ident);
+            for ident in alternative.idents.iter().chain(predicates) {
+                let (kind, span) = match ident {
+                    Ident::NonTerminal(name, span)
+                        if !matches!(defined.get(name.as_str()), Some(Production::Parser(..))) =>
+                    {
+                        ("rule", span)
+                    }
+                    Ident::Terminal(name, span) if !terminals.contains(&name.as_str()) => {
+                        ("token", span)
+                    }
+                    _ => continue,
+                };
+                diagnostics.push(
+                    Diagnostic::error(format!("undefined {kind} `{}`", ident.name()))
+                        .with_code("E004")
+                        .with_primary(*span, format!("no {kind} of this name")),
+                );
+            }
+        }
+    }
+    diagnostics.sort_by_key(|diagnostic| {
+        diagnostic
+            .primary
+            .as_ref()
+            .map(|label| (label.span.file, label.span.start))
+    });
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_src(src: &str) -> Vec<String> {
+        check_names(&Grammar::parse(src).unwrap())
+            .iter()
+            .map(|diagnostic| {
+                format!(
+                    "{}: {}",
+                    diagnostic.code.as_deref().unwrap_or(""),
+                    diagnostic.message
+                )
+            })
+            .collect()
+    }
+
+    macro_rules! test_names {
+        ($name:ident, $src:expr => [$($message:expr),*]) => {
+            #[test]
+            fn $name() {
+                assert_eq!(check_src($src), vec![$($message),*] as Vec<&str>);
+            }
+        };
+    }
+
+    test_names!(names_defined, "s: A t | !B A; t: B; A: a; B: b;" => []);
+    test_names!(names_token_without_lexer_rule, "s: A ERROR; A: a;" => []);
+    test_names!(names_undefined_rule, "e: a | N; N: n;" => ["E004: undefined rule `a`"]);
+    test_names!(names_undefined_in_lookahead, "s: &t A | !B A; A: a;" =>
+        ["E004: undefined rule `t`", "E004: undefined token `B`"]);
+    test_names!(names_duplicate_rule, "s: A; s: B; A: a; B: b;" =>
+        ["E005: the rule `s` is defined more than once"]);
+    test_names!(names_duplicate_lexer_rule, "s: A; A: a; A: b;" =>
+        ["E005: the rule `A` is defined more than once"]);
+}
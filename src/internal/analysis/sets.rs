@@ -0,0 +1,261 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::{
+    error::GrammarError,
+    internal::ast::{Alternative, Grammar, Ident, Production, Symbol},
+};
+
+/// The terminal marking the end of input, which follows the start rule
+pub const END: &str = "$end";
+
+/// Iterate `step` over every parser rule until it stops adding rules to the set.
+fn fixpoint<'a>(
+    grammar: &'a Grammar,
+    mut set: HashSet<&'a str>,
+    step: impl Fn(&HashSet<&'a str>, &'a [Alternative]) -> bool,
+) -> HashSet<&'a str> {
+    loop {
+        let mut changed = false;
+        for (name, alternatives) in grammar.parser_rules() {
+            if !set.contains(name) && step(&set, alternatives) {
+                set.insert(name);
+                changed = true;
+            }
+        }
+        if !changed {
+            return set;
+        }
+    }
+}
+
+/// Parser rules which can derive the empty string
+pub fn nullable(grammar: &Grammar) -> HashSet<&str> {
+    fixpoint(grammar, HashSet::new(), |nullable, alternatives| {
+        alternatives.iter().any(|alternative| {
+            alternative.idents.iter().all(|ident| match ident {
+                Ident::NonTerminal(name, _) => nullable.contains(name.as_str()),
+                Ident::Terminal(_, _) => false,
+            })
+        })
+    })
+}
+
+/// Parser rules which can derive at least one string of terminals
+pub fn productive(grammar: &Grammar) -> HashSet<&str> {
+    fixpoint(grammar, HashSet::new(), |productive, alternatives| {
+        alternatives.iter().any(|alternative| {
+            alternative.idents.iter().all(|ident| match ident {
+                Ident::NonTerminal(name, _) => productive.contains(name.as_str()),
+                Ident::Terminal(_, _) => true,
+            })
+        })
+    })
+}
+
+/// Parser rules and terminals which appear in some derivation from the start rule,
+/// or in a lookahead checked along one
+pub fn reachable(grammar: &Grammar) -> HashSet<&str> {
+    let mut reachable = HashSet::new();
+    let mut stack: Vec<&str> = grammar.start_rule().into_iter().collect();
+    while let Some(name) = stack.pop() {
+        if !reachable.insert(name) {
+            continue;
+        }
+        let alternatives = grammar
+            .parser_rules()
+            .filter(|(rule, _)| *rule == name)
+            .flat_map(|(_, alternatives)| alternatives);
+        let idents = alternatives.flat_map(|alternative| {
+            let lookaheads = alternative
+                .predicates
+                .iter()
+                .map(|predicate| &predicate.ident);
+            alternative.idents.iter().chain(lookaheads)
+        });
+        for ident in idents {
+            if !reachable.contains(ident.name()) {
+                stack.push(ident.name());
+            }
+        }
+    }
+    reachable
+}
+
+/// Parser rules which derive a string starting with themselves, on which top-down parsers loop
+pub fn left_recursive(grammar: &Grammar) -> Vec<&str> {
+    let nullable = nullable(grammar);
+    let mut steps: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for (name, alternatives) in grammar.parser_rules() {
+        let targets = steps.entry(name).or_default();
+        for alternative in alternatives {
+            for ident in &alternative.idents {
+                let Ident::NonTerminal(target, _) = ident else {
+                    break;
+                };
+                targets.insert(target);
+                if !nullable.contains(target.as_str()) {
+                    break;
+                }
+            }
+        }
+    }
+    grammar
+        .parser_rules()
+        .map(|(name, _)| name)
+        .filter(|&name| {
+            let mut seen = HashSet::new();
+            let mut stack: Vec<&str> = steps[name].iter().copied().collect();
+            while let Some(rule) = stack.pop() {
+                if rule == name {
+                    return true;
+                }
+                if seen.insert(rule) {
+                    stack.extend(steps.get(rule).into_iter().flatten().copied());
+                }
+            }
+            false
+        })
+        .collect()
+}
+
+/// Fail on a left recursive grammar, which the top-down backends cannot handle
+pub fn reject_left_recursion(grammar: &Grammar) -> Result<(), GrammarError> {
+    let recursive = left_recursive(grammar);
+    if recursive.is_empty() {
+        return Ok(());
+    }
+    let rules = grammar
+        .rules
+        .iter()
+        .filter(|rule| matches!(rule, Production::Parser(..)) && recursive.contains(&rule.name()))
+        .map(|rule| (rule.name().to_string(), rule.name_span()))
+        .collect();
+    Err(GrammarError::LeftRecursive(rules))
+}
+
+/// Nullability, FIRST and FOLLOW sets of every parser rule
+#[derive(Debug)]
+pub struct FirstFollow<'a> {
+    pub nullable: HashSet<&'a str>,
+    pub first: HashMap<&'a str, BTreeSet<&'a str>>,
+    pub follow: HashMap<&'a str, BTreeSet<&'a str>>,
+}
+
+impl<'a> FirstFollow<'a> {
+    pub fn new(grammar: &'a Grammar) -> Self {
+        let mut sets = FirstFollow {
+            nullable: nullable(grammar),
+            first: HashMap::new(),
+            follow: HashMap::new(),
+        };
+        for (name, _) in grammar.parser_rules() {
+            sets.first.entry(name).or_default();
+            sets.follow.entry(name).or_default();
+        }
+        if let Some(start) = grammar.start_rule() {
+            sets.follow.entry(start).or_default().insert(END);
+        }
+
+        let rules: Vec<(&str, Vec<Symbol>)> = grammar
+            .parser_rules()
+            .flat_map(|(name, alternatives)| {
+                alternatives.iter().map(move |alternative| {
+                    (name, alternative.idents.iter().map(Ident::symbol).collect())
+                })
+            })
+            .collect();
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (name, symbols) in &rules {
+                let (first, _) = sets.first_of(symbols);
+                let entry = sets.first.entry(name).or_default();
+                let before = entry.len();
+                entry.extend(first);
+                changed |= entry.len() != before;
+            }
+        }
+
+        changed = true;
+        while changed {
+            changed = false;
+            for (name, symbols) in &rules {
+                for (i, symbol) in symbols.iter().enumerate() {
+                    let Symbol::NonTerminal(target) = symbol else {
+                        continue;
+                    };
+                    let (mut follow, nullable) = sets.first_of(&symbols[i + 1..]);
+                    if nullable {
+                        follow.extend(sets.follow[name].iter().copied());
+                    }
+                    let entry = sets.follow.entry(target).or_default();
+                    let before = entry.len();
+                    entry.extend(follow);
+                    changed |= entry.len() != before;
+                }
+            }
+        }
+        sets
+    }
+
+    /// The FIRST set of a sequence of symbols, and whether the whole sequence is nullable
+    pub fn first_of(&self, symbols: &[Symbol<'a>]) -> (BTreeSet<&'a str>, bool) {
+        let mut first = BTreeSet::new();
+        for symbol in symbols {
+            match symbol {
+                Symbol::Terminal(name) => {
+                    first.insert(*name);
+                    return (first, false);
+                }
+                Symbol::NonTerminal(name) => {
+                    first.extend(self.first.get(name).into_iter().flatten().copied());
+                    if !self.nullable.contains(name) {
+                        return (first, false);
+                    }
+                }
+            }
+        }
+        (first, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::testing::grammar;
+
+    fn set<'a>(items: &[&'a str]) -> BTreeSet<&'a str> {
+        items.iter().copied().collect()
+    }
+
+    /// The grammar from the README of the internal parser
+    const SRC: &str = "s: r SEMI s |; r: N COLON rp | T COLON P; rp: rpp rppp; rpp: T rpp | N rpp |; rppp: B rp |;";
+
+    #[test]
+    fn first_follow_of_readme_grammar() {
+        let grammar = grammar(SRC);
+        let sets = FirstFollow::new(&grammar);
+
+        assert_eq!(
+            sets.nullable,
+            ["s", "rp", "rpp", "rppp"].into_iter().collect()
+        );
+        assert_eq!(sets.first["s"], set(&["N", "T"]));
+        assert_eq!(sets.first["r"], set(&["N", "T"]));
+        assert_eq!(sets.first["rp"], set(&["B", "N", "T"]));
+        assert_eq!(sets.first["rppp"], set(&["B"]));
+        assert_eq!(sets.follow["s"], set(&[END]));
+        assert_eq!(sets.follow["r"], set(&["SEMI"]));
+        assert_eq!(sets.follow["rp"], set(&["SEMI"]));
+        assert_eq!(sets.follow["rpp"], set(&["B", "SEMI"]));
+        assert_eq!(sets.follow["rppp"], set(&["SEMI"]));
+    }
+
+    #[test]
+    fn left_recursive_through_nullable_prefix() {
+        let src = "s: n e | A; e: s B | C; n: |D; u: u; A: a; B: b; C: c; D: d;";
+        let grammar = grammar(src);
+        assert_eq!(left_recursive(&grammar), ["s", "e", "u"]);
+    }
+}
@@ -1,34 +1,208 @@
-use crate::ast::Annotation;
+use std::collections::{BTreeMap, HashMap};
 
-#[derive(Debug)]
+use crate::{ast::Annotation, span::Span};
+
+#[derive(Debug, Default, PartialEq, Eq)]
 pub struct Grammar {
     pub rules: Vec<Production>,
+    pub directives: Vec<Directive>,
+    /// The text of the `/** ... */` comment before each rule which has one, by rule name
+    pub docs: HashMap<String, String>,
 }
 
-#[derive(Debug)]
+/// A parser or lexer rule, spanning from its name up to the `;` ending it
+#[derive(Debug, PartialEq, Eq)]
 pub enum Production {
-    Parser(String, Vec<Vec<Ident>>),
-    Lexer(String, String),
+    Parser(String, Vec<Alternative>, Span),
+    Lexer(String, String, Span),
+}
+
+/// One `|`-separated alternative of a parser rule, with any directives written inside it such as `%prec PLUS`
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Alternative {
+    pub idents: Vec<Ident>,
+    pub directives: Vec<Directive>,
+    /// PEG lookaheads, which only the PEG backends check and every other analysis ignores
+    pub predicates: Vec<Predicate>,
+    /// Covers the idents and directives of the alternative, or is empty at the `|` or `;` after an empty one
+    pub span: Span,
 }
 
-#[derive(Debug)]
+/// A PEG lookahead `&ident` or `!ident`, checked before the ident at `position` in the alternative,
+/// or at its end when `position` is the number of idents
+#[derive(Debug, PartialEq, Eq)]
+pub struct Predicate {
+    pub position: usize,
+    pub negated: bool,
+    pub ident: Ident,
+}
+
+#[derive(Debug, PartialEq, Eq)]
 pub enum Ident {
-    Terminal(String),
-    NonTerminal(String),
+    Terminal(String, Span),
+    NonTerminal(String, Span),
+}
+
+/// A borrowed view of an [`Ident`], cheap to copy and hash for use in analyses and tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Symbol<'a> {
+    Terminal(&'a str),
+    NonTerminal(&'a str),
+}
+
+/// A grammar-level `%name arg ...;` statement, such as `%deny unreachable_rule;`.
+///
+/// Directives are kept uninterpreted here, and each analysis picks out the ones it cares about.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Directive {
+    pub name: String,
+    pub args: Vec<String>,
+    pub span: Span,
+}
+
+impl Grammar {
+    pub fn parser_rules(&self) -> impl Iterator<Item = (&str, &[Alternative])> {
+        self.rules.iter().filter_map(|rule| match rule {
+            Production::Parser(name, alternatives, _) => {
+                Some((name.as_str(), alternatives.as_slice()))
+            }
+            Production::Lexer(..) => None,
+        })
+    }
+
+    pub fn lexer_rules(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.rules.iter().filter_map(|rule| match rule {
+            Production::Lexer(name, pattern, _) => Some((name.as_str(), pattern.as_str())),
+            Production::Parser(..) => None,
+        })
+    }
+
+    /// The doc comment of a rule, if it has one
+    pub fn doc(&self, rule: &str) -> Option<&str> {
+        self.docs.get(rule).map(String::as_str)
+    }
+
+    /// The rules using each rule, lookaheads included, in the order they come in the grammar
+    pub fn used_by(&self) -> BTreeMap<&str, Vec<&str>> {
+        let mut used_by: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (name, alternatives) in self.parser_rules() {
+            for alternative in alternatives {
+                let lookaheads = alternative
+                    .predicates
+                    .iter()
+                    .map(|predicate| &predicate.ident);
+                for ident in alternative.idents.iter().chain(lookaheads) {
+                    let users = used_by.entry(ident.name()).or_default();
+                    if !users.contains(&name) {
+                        users.push(name);
+                    }
+                }
+            }
+        }
+        used_by
+    }
+
+    /// The start rule is the first parser rule in the grammar file
+    pub fn start_rule(&self) -> Option<&str> {
+        self.parser_rules().next().map(|(name, _)| name)
+    }
+}
+
+impl Alternative {
+    /// The terminal named by `%prec`, overriding the precedence of the alternative
+    pub fn prec(&self) -> Option<&str> {
+        self.directives
+            .iter()
+            .find(|directive| directive.name == "prec")
+            .and_then(|directive| directive.args.first())
+            .map(String::as_str)
+    }
+
+    /// The name given to the alternative with `%label`, naming its variant in generated tree types
+    pub fn label(&self) -> Option<&str> {
+        self.directives
+            .iter()
+            .find(|directive| directive.name == "label")
+            .and_then(|directive| directive.args.first())
+            .map(String::as_str)
+    }
+
+    /// Whether the alternative is marked with a directive, such as `%prefer`
+    pub fn has_directive(&self, name: &str) -> bool {
+        self.directives
+            .iter()
+            .any(|directive| directive.name == name)
+    }
+}
+
+impl Production {
+    pub fn name(&self) -> &str {
+        match self {
+            Production::Parser(name, ..) | Production::Lexer(name, ..) => name,
+        }
+    }
+
+    /// The span of the whole rule, from its name to the `;` ending it
+    pub fn span(&self) -> Span {
+        match self {
+            Production::Parser(_, _, span) | Production::Lexer(_, _, span) => *span,
+        }
+    }
+
+    /// The span of the name of the rule, which starts it
+    pub fn name_span(&self) -> Span {
+        let (name, span) = match self {
+            Production::Parser(name, _, span) | Production::Lexer(name, _, span) => (name, span),
+        };
+        Span::new(span.file, span.start..span.start + name.len())
+    }
+}
+
+impl Ident {
+    pub fn name(&self) -> &str {
+        match self {
+            Ident::Terminal(name, _) | Ident::NonTerminal(name, _) => name,
+        }
+    }
+
+    pub fn symbol(&self) -> Symbol<'_> {
+        match self {
+            Ident::Terminal(name, _) => Symbol::Terminal(name),
+            Ident::NonTerminal(name, _) => Symbol::NonTerminal(name),
+        }
+    }
+}
+
+impl<'a> Symbol<'a> {
+    pub fn name(self) -> &'a str {
+        match self {
+            Symbol::Terminal(name) | Symbol::NonTerminal(name) => name,
+        }
+    }
+}
+
+impl std::fmt::Display for Symbol<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
 }
 
 impl From<Vec<Production>> for Grammar {
     fn from(rules: Vec<Production>) -> Self {
-        Grammar { rules }
+        Grammar {
+            rules,
+            directives: vec![],
+            docs: HashMap::new(),
+        }
     }
 }
 
 impl From<&str> for Ident {
     fn from(s: &str) -> Self {
         if s.chars().next().unwrap().is_uppercase() {
-            Ident::NonTerminal(s.to_string())
+            Ident::NonTerminal(s.to_string(), Span::default())
         } else {
-            Ident::Terminal(s.to_string())
+            Ident::Terminal(s.to_string(), Span::default())
         }
     }
 }
@@ -0,0 +1,525 @@
+//! Generation of rantlr's own meta-parser, from `examples/rantlr.g4`.
+//!
+//! Unlike the other backends, the generated parser reads the tokens of the hand-written tokenizer
+//! and builds the tree types which `parser::transform` turns into a [`Grammar`]. Each parser rule `r`
+//! becomes a type `ParseR`, and each of its alternatives the variant named by `%label`, holding the
+//! text of every token with a payload and the tree of every nonterminal.
+
+use std::{collections::BTreeSet, fmt::Write};
+
+use crate::{
+    error::GrammarError,
+    internal::{
+        analysis::sets::{FirstFollow, END},
+        ast::{Alternative, Grammar, Ident, Symbol},
+        table::ll1::Ll1Table,
+    },
+};
+
+use super::{parse_fn, rust_ident};
+
+/// Tokens of the hand-written tokenizer by the name the meta grammar gives them,
+/// with their `TokenKind` variant and whether they carry the text they matched
+const TOKENS: [(&str, &str, bool); 10] = [
+    ("PARSER_IDENT", "ParserIdent", true),
+    ("LEXER_IDENT", "LexerIdent", true),
+    ("LEXER_PATTERN", "LexerPattern", true),
+    ("DIRECTIVE", "Directive", true),
+    ("BAR", "Bar", false),
+    ("AND", "And", false),
+    ("NOT", "Not", false),
+    ("SEMICOLON", "Semicolon", false),
+    ("COLON", "Colon", false),
+    (END, "EOF", false),
+];
+
+const PARSER: &str = r#"
+struct GeneratedParser<I: Iterator<Item = Token>> {
+    remaining_input: I,
+    next: Token,
+    previous_end: usize,
+}
+
+/// Recursive descent parser for the grammar, choosing alternatives with its LL(1) table
+impl<I: Iterator<Item = Token>> GeneratedParser<I> {
+    fn advance(&mut self) -> Result<TokenKind> {
+        match self.remaining_input.next() {
+            Some(next) => {
+                self.previous_end = self.next.span.end;
+                Ok(std::mem::replace(&mut self.next, next).kind)
+            }
+            None => Err(ParserError::UnexpectedEof.into()),
+        }
+    }
+
+    fn node<T>(&self, start: usize, value: T) -> Node<T> {
+        let mut node = Node::new(value);
+        let end = self.previous_end.max(start);
+        node.add_annotation(Span::new(self.next.span.file, start..end));
+        node
+    }
+
+    fn expect(&mut self, expected: TokenKind) -> Result<TokenKind> {
+        if std::mem::discriminant(&self.next.kind) == std::mem::discriminant(&expected) {
+            self.advance()
+        } else {
+            unexpected_token!(self.next, [expected])
+        }
+    }
+"#;
+
+const TEXT: &str = r#"
+    fn text(&mut self, expected: TokenKind) -> Result<String> {
+        match self.expect(expected)? {
+            TokenKind::ParserIdent(text)
+            | TokenKind::LexerIdent(text)
+            | TokenKind::LexerPattern(text)
+            | TokenKind::Directive(text) => Ok(text),
+            _ => unreachable!("only tokens with a payload are expected"),
+        }
+    }
+"#;
+
+/// The meta-parser generated from a grammar: tree types for `tree.rs`, and the parser for `generated.rs`
+#[derive(Debug)]
+pub struct Bootstrap {
+    pub tree: String,
+    pub parser: String,
+}
+
+fn token(name: &str) -> Result<(&'static str, bool), GrammarError> {
+    TOKENS
+        .iter()
+        .find(|(token, ..)| *token == name)
+        .map(|&(_, variant, payload)| (variant, payload))
+        .ok_or_else(|| GrammarError::UnknownToken(name.into()))
+}
+
+/// A pattern matching a token of any text
+fn pattern(name: &str) -> Result<String, GrammarError> {
+    Ok(match token(name)? {
+        (variant, true) => format!("TokenKind::{variant}(_)"),
+        (variant, false) => format!("TokenKind::{variant}"),
+    })
+}
+
+/// A value of a token, to report it as expected
+fn expected(name: &str) -> Result<String, GrammarError> {
+    Ok(match token(name)? {
+        (variant, true) => format!("TokenKind::{variant}(String::new())"),
+        (variant, false) => format!("TokenKind::{variant}"),
+    })
+}
+
+/// The tree type of a rule, so that `r1` becomes `ParseR1`
+fn tree_type(rule: &str) -> String {
+    let ident = rust_ident(rule);
+    let camel: String = ident
+        .trim_start_matches("r#")
+        .split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            chars.next().map_or_else(String::new, |first| {
+                first.to_ascii_uppercase().to_string() + chars.as_str()
+            })
+        })
+        .collect();
+    format!("Parse{camel}")
+}
+
+fn variant(alternative: &Alternative, index: usize) -> String {
+    alternative
+        .label()
+        .map_or_else(|| format!("Alternative{index}"), str::to_string)
+}
+
+/// The type of the field an ident adds to its variant, if any
+fn field(ident: &Ident) -> Result<Option<String>, GrammarError> {
+    Ok(match ident {
+        Ident::Terminal(name, _) => token(name)?.1.then(|| "String".to_string()),
+        Ident::NonTerminal(name, _) => Some(format!("Box<Node<{}>>", tree_type(name))),
+    })
+}
+
+/// Rules which build a tree: the start rule and every rule used by an alternative.
+/// Rules only used in lookaheads are checked against their FIRST set instead.
+fn tree_rules(grammar: &Grammar) -> Vec<&str> {
+    let used: BTreeSet<&str> = grammar
+        .parser_rules()
+        .flat_map(|(_, alternatives)| alternatives)
+        .flat_map(|alternative| &alternative.idents)
+        .filter_map(|ident| match ident {
+            Ident::NonTerminal(name, _) => Some(name.as_str()),
+            Ident::Terminal(_, _) => None,
+        })
+        .collect();
+    grammar
+        .parser_rules()
+        .map(|(name, _)| name)
+        .filter(|name| used.contains(name) || grammar.start_rule() == Some(name))
+        .collect()
+}
+
+/// Order terminals as the tokenizer declares them
+fn ordered<'a>(terminals: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut terminals: Vec<&str> = terminals.into_iter().collect();
+    terminals.sort_by_key(|terminal| TOKENS.iter().position(|(token, ..)| token == terminal));
+    terminals
+}
+
+/// A call reporting that one of `terminals` was expected, laid out as rustfmt would: on one
+/// line when it fits, else one argument per line, and the array broken up when it is long
+fn unexpected(
+    indent: &str,
+    prefix: &str,
+    suffix: &str,
+    terminals: &[&str],
+) -> Result<String, GrammarError> {
+    let expected = terminals
+        .iter()
+        .map(|terminal| expected(terminal))
+        .collect::<Result<Vec<_>, _>>()?;
+    let array = format!("[{}]", expected.join(", "));
+    let line = format!("{indent}{prefix}unexpected_token!(self.next, {array}){suffix}");
+    if array.len() + 11 <= 60 && line.len() <= 100 {
+        return Ok(line);
+    }
+    let array = if array.len() <= 60 && indent.len() + 4 + array.len() < 100 {
+        format!("{indent}    {array}")
+    } else {
+        let items: String = expected
+            .iter()
+            .map(|item| format!("{indent}        {item},\n"))
+            .collect();
+        format!("{indent}    [\n{items}{indent}    ]")
+    };
+    Ok(format!(
+        "{indent}{prefix}unexpected_token!(\n{indent}    self.next,\n{array}\n{indent}){suffix}"
+    ))
+}
+
+fn patterns(terminals: &[&str]) -> Result<String, GrammarError> {
+    Ok(terminals
+        .iter()
+        .map(|terminal| pattern(terminal))
+        .collect::<Result<Vec<_>, _>>()?
+        .join(" | "))
+}
+
+struct Generator<'a> {
+    sets: FirstFollow<'a>,
+    table: Ll1Table<'a>,
+}
+
+impl<'a> Generator<'a> {
+    /// Terminals predicting an alternative, one of which is expected when none of them is found
+    fn lookaheads(&self, rule: &str, index: usize) -> Vec<&'a str> {
+        let Some(predicted) = self
+            .table
+            .rules
+            .iter()
+            .position(|entry| entry.lhs == rule && entry.alternative == index)
+        else {
+            return vec![];
+        };
+        ordered(
+            self.table
+                .entries
+                .iter()
+                .filter(|(&(lhs, _), rules)| lhs == rule && **rules == [predicted])
+                .map(|(&(_, terminal), _)| terminal),
+        )
+    }
+
+    /// Terminals which may start the rest of an alternative
+    fn continuations(&self, rule: &'a str, symbols: &[Symbol<'a>]) -> BTreeSet<&'a str> {
+        let (mut first, nullable) = self.sets.first_of(symbols);
+        if nullable {
+            first.extend(self.sets.follow[rule].iter().copied());
+        }
+        first
+    }
+
+    fn write_alternative(
+        &self,
+        out: &mut String,
+        rule: &'a str,
+        alternative: &'a Alternative,
+        index: usize,
+        indent: &str,
+    ) -> Result<(), GrammarError> {
+        let symbols: Vec<Symbol> = alternative.idents.iter().map(Ident::symbol).collect();
+        let mut vars: Vec<String> = vec![];
+        let mut args: Vec<String> = vec![];
+        for position in 0..=symbols.len() {
+            for predicate in alternative
+                .predicates
+                .iter()
+                .filter(|predicate| predicate.position == position)
+            {
+                let checked = ordered(self.sets.first_of(&[predicate.ident.symbol()]).0);
+                let (condition, reported) = if predicate.negated {
+                    let continuations = self.continuations(rule, &symbols[position..]);
+                    let reported: Vec<&str> = ordered(continuations)
+                        .into_iter()
+                        .filter(|terminal| !checked.contains(terminal))
+                        .collect();
+                    ("matches!", reported)
+                } else {
+                    ("!matches!", checked.clone())
+                };
+                let patterns = patterns(&checked)?;
+                let line = format!("{indent}if {condition}(self.next.kind, {patterns}) {{");
+                if line.len() < 100 {
+                    writeln!(out, "{line}").unwrap();
+                } else {
+                    // Broken up as rustfmt would
+                    writeln!(
+                        out,
+                        "{indent}if {condition}(\n{indent}    self.next.kind,\n{indent}    {patterns}\n{indent}) {{"
+                    )
+                    .unwrap();
+                }
+                let indented = format!("{indent}    ");
+                writeln!(out, "{}", unexpected(&indented, "return ", ";", &reported)?).unwrap();
+                writeln!(out, "{indent}}}").unwrap();
+            }
+            let Some(ident) = alternative.idents.get(position) else {
+                continue;
+            };
+            let mut var = match ident {
+                Ident::Terminal(name, _) => name.to_lowercase(),
+                Ident::NonTerminal(name, _) => rust_ident(name),
+            };
+            if vars.contains(&var) {
+                var = format!("{var}_{position}");
+            }
+            match ident {
+                Ident::Terminal(name, _) if token(name)?.1 => {
+                    writeln!(out, "{indent}let {var} = self.text({})?;", expected(name)?).unwrap();
+                    args.push(var.clone());
+                }
+                Ident::Terminal(name, _) => {
+                    writeln!(out, "{indent}self.expect({})?;", expected(name)?).unwrap();
+                }
+                Ident::NonTerminal(name, _) => {
+                    writeln!(out, "{indent}let {var} = self.{}()?;", parse_fn(name)).unwrap();
+                    args.push(format!("{var}.into()"));
+                }
+            }
+            vars.push(var);
+        }
+        let fields = if args.is_empty() {
+            String::new()
+        } else {
+            format!("({})", args.join(", "))
+        };
+        writeln!(
+            out,
+            "{indent}Ok(self.node(start, {}::{}{fields}))",
+            tree_type(rule),
+            variant(alternative, index)
+        )
+        .unwrap();
+        Ok(())
+    }
+
+    fn write_rule(
+        &self,
+        out: &mut String,
+        rule: &'a str,
+        alternatives: &'a [Alternative],
+    ) -> Result<(), GrammarError> {
+        writeln!(
+            out,
+            "\n    fn {}(&mut self) -> Result<Node<{}>> {{\n        let start = self.next.span.start;",
+            parse_fn(rule),
+            tree_type(rule)
+        )
+        .unwrap();
+        if let [alternative] = alternatives {
+            if alternative.predicates.is_empty() {
+                self.write_alternative(out, rule, alternative, 0, "        ")?;
+                out.push_str("    }\n");
+                return Ok(());
+            }
+        }
+        out.push_str("        match self.next.kind {\n");
+        let mut covered = BTreeSet::new();
+        for (index, alternative) in alternatives.iter().enumerate() {
+            let lookaheads = self.lookaheads(rule, index);
+            if lookaheads.is_empty() {
+                continue;
+            }
+            covered.extend(lookaheads.iter().copied());
+            let mut body = String::new();
+            self.write_alternative(&mut body, rule, alternative, index, "                ")?;
+            let pattern = patterns(&lookaheads)?;
+            if body.lines().count() == 1 {
+                writeln!(out, "            {pattern} => {},", body.trim()).unwrap();
+            } else {
+                writeln!(out, "            {pattern} => {{\n{body}            }}").unwrap();
+            }
+        }
+        if covered.len() < TOKENS.len() {
+            let reported = ordered(covered);
+            writeln!(
+                out,
+                "{}",
+                unexpected("            ", "_ => ", ",", &reported)?
+            )
+            .unwrap();
+        }
+        out.push_str("        }\n    }\n");
+        Ok(())
+    }
+}
+
+/// Generate the meta-parser of `grammar`, which must be LL(1) over the tokens of the hand-written tokenizer
+pub fn generate(grammar: &Grammar, source: &str) -> Result<Bootstrap, GrammarError> {
+    let table = Ll1Table::new(grammar);
+    table.reject_conflicts()?;
+    let generator = Generator {
+        sets: FirstFollow::new(grammar),
+        table,
+    };
+    let rules = tree_rules(grammar);
+    let header =
+        format!("// @generated by `rantlr bootstrap` from `{source}`. Do not edit by hand.\n");
+
+    let mut tree = header.clone();
+    tree.push_str("use crate::ast::Node;\n");
+    for (name, alternatives) in grammar.parser_rules() {
+        if !rules.contains(&name) {
+            continue;
+        }
+        writeln!(tree, "\n#[derive(Debug)]\npub enum {} {{", tree_type(name)).unwrap();
+        for (index, alternative) in alternatives.iter().enumerate() {
+            let fields = alternative
+                .idents
+                .iter()
+                .map(field)
+                .collect::<Result<Vec<_>, _>>()?;
+            let fields: Vec<String> = fields.into_iter().flatten().collect();
+            if fields.is_empty() {
+                writeln!(tree, "    {},", variant(alternative, index)).unwrap();
+            } else {
+                writeln!(
+                    tree,
+                    "    {}({}),",
+                    variant(alternative, index),
+                    fields.join(", ")
+                )
+                .unwrap();
+            }
+        }
+        tree.push_str("}\n");
+    }
+
+    let mut types: Vec<String> = rules.iter().map(|rule| tree_type(rule)).collect();
+    types.sort();
+    let mut parser = header;
+    writeln!(
+        parser,
+        "use crate::{{\n    ast::Node,\n    error::ParserError,\n    internal::lexer::token::{{Token, TokenKind}},\n    span::Span,\n    unexpected_token,\n}};\nuse anyhow::Result;\n\nuse super::tree::{{{}}};",
+        types.join(", ")
+    )
+    .unwrap();
+    parser.push_str(PARSER);
+    let payloads = grammar
+        .parser_rules()
+        .filter(|(name, _)| rules.contains(name))
+        .flat_map(|(_, alternatives)| alternatives)
+        .flat_map(|alternative| &alternative.idents)
+        .any(|ident| matches!(ident, Ident::Terminal(name, _) if token(name).is_ok_and(|(_, payload)| payload)));
+    if payloads {
+        parser.push_str(TEXT);
+    }
+    for (name, alternatives) in grammar.parser_rules() {
+        if rules.contains(&name) {
+            generator.write_rule(&mut parser, name, alternatives)?;
+        }
+    }
+    parser.push_str("}\n");
+    if let Some(start) = grammar.start_rule() {
+        write!(
+            parser,
+            r#"
+pub fn parse(input: Vec<Token>) -> Result<Node<{}>> {{
+    let mut iter = input.into_iter();
+    let first = iter.next().ok_or(ParserError::UnexpectedEof)?;
+    GeneratedParser {{
+        remaining_input: iter,
+        previous_end: first.span.start,
+        next: first,
+    }}
+    .{}()
+}}
+"#,
+            tree_type(start),
+            parse_fn(start)
+        )
+        .unwrap();
+    }
+    Ok(Bootstrap { tree, parser })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::{
+        lexer::lexer::tokenize,
+        parser::{functional, generated},
+    };
+
+    const META: &str = include_str!("../../../examples/rantlr.g4");
+
+    fn meta_grammar() -> Grammar {
+        Grammar::from(functional::parse(tokenize(META).unwrap()).unwrap())
+    }
+
+    #[test]
+    fn bootstrap_is_up_to_date() {
+        let bootstrap = generate(&meta_grammar(), "examples/rantlr.g4").unwrap();
+        assert_eq!(bootstrap.tree, include_str!("../parser/tree.rs"));
+        assert_eq!(bootstrap.parser, include_str!("../parser/generated.rs"));
+    }
+
+    #[test]
+    fn bootstrap_rejects_unknown_tokens() {
+        let tokens = tokenize("s: PLUS;").unwrap();
+        let grammar = Grammar::from(functional::parse(tokens).unwrap());
+        assert!(matches!(
+            generate(&grammar, "s.g4"),
+            Err(GrammarError::UnknownToken(token)) if token == "PLUS"
+        ));
+    }
+
+    /// Both meta-parsers must turn every grammar into the same [`Grammar`]
+    macro_rules! test_bootstrap_corpus {
+        ($name:ident, $src:expr) => {
+            #[test]
+            fn $name() {
+                let tokens = tokenize($src).unwrap();
+                let hand_written = Grammar::from(functional::parse(tokens.clone()).unwrap());
+                let generated = Grammar::from(generated::parse(tokens).unwrap());
+                assert_eq!(hand_written, generated);
+            }
+        };
+    }
+
+    test_bootstrap_corpus!(bootstrap_corpus_meta, META);
+    test_bootstrap_corpus!(
+        bootstrap_corpus_precedence,
+        "%left PLUS; %right POW; e: e PLUS e | e POW e %prec POW | NUM; PLUS: \\+; POW: \\^; NUM: [0-9]+;"
+    );
+    test_bootstrap_corpus!(
+        bootstrap_corpus_lookahead,
+        "s: &a a B | !B x | ; a: A; x: B B; A: a; B: b;"
+    );
+    test_bootstrap_corpus!(
+        bootstrap_corpus_directives,
+        "%allow empty_alternative; %expect 1; s: A s %prefer | ; A: a;"
+    );
+    test_bootstrap_corpus!(bootstrap_corpus_lexer_only, "A: a; B: [b-z]+;");
+}
@@ -0,0 +1,172 @@
+use std::fmt::Write;
+
+use crate::{
+    error::InputError,
+    internal::{
+        ast::{Grammar, Symbol},
+        runtime::{sppf::Filters, Symbols},
+        table::lr::{LrAction, LrTable},
+    },
+};
+
+use super::{preamble, token_kind, write_gotos, write_rules, write_token_kinds};
+
+/// The forest and GLR driver of the runtime, which only depend on `std`
+const SPPF: &str = include_str!("../runtime/sppf.rs");
+const GLR: &str = include_str!("../runtime/glr.rs");
+
+const DRIVER: &str = r#"
+struct Tables;
+
+impl glr::Table for Tables {
+    fn actions(&self, state: usize, terminal: usize) -> &[glr::Action] {
+        ACTIONS[state]
+            .iter()
+            .find(|(kind, _)| *kind as usize == terminal)
+            .map_or(&[], |&(_, actions)| actions)
+    }
+
+    fn goto(&self, state: usize, nonterminal: usize) -> Option<usize> {
+        GOTOS[state]
+            .iter()
+            .find(|(rule, _)| *rule as usize == nonterminal)
+            .map(|&(_, state)| state)
+    }
+
+    fn rule(&self, rule: usize) -> (usize, usize) {
+        let (lhs, _, length) = RULES[rule];
+        (lhs as usize, length)
+    }
+
+    fn expected(&self, state: usize) -> Vec<usize> {
+        ACTIONS[state].iter().map(|&(kind, _)| kind as usize).collect()
+    }
+}
+
+/// The tokens of the input, and every derivation of them. In the forest, terminals are labelled
+/// by `TokenKind as usize`, nonterminals by `Rule as usize`, and packed nodes index `RULES`.
+#[derive(Debug)]
+pub struct Parse<'input> {
+    pub tokens: Vec<Token<'input>>,
+    pub forest: sppf::Forest,
+}
+
+impl<'input> Parse<'input> {
+    /// The parse tree, when the disambiguation filters left a single derivation
+    pub fn tree(&self) -> Result<Tree<'input>, sppf::Ambiguity> {
+        Ok(self.convert(self.forest.tree()?))
+    }
+
+    /// Every derivation as a parse tree, skipping those through cycles of the forest
+    pub fn trees(&self) -> impl Iterator<Item = Tree<'input>> + '_ {
+        self.forest.derivations().map(|tree| self.convert(tree))
+    }
+
+    fn convert(&self, mut tree: sppf::Tree) -> Tree<'input> {
+        match &mut tree {
+            sppf::Tree::Leaf { position, .. } => Tree::Leaf(self.tokens[*position]),
+            sppf::Tree::Node { rule, children } => {
+                let (rule, alternative, _) = RULES[*rule];
+                let children = std::mem::take(children)
+                    .into_iter()
+                    .map(|child| self.convert(child))
+                    .collect();
+                Tree::Node { rule, alternative, children }
+            }
+        }
+    }
+}
+
+/// Parse the input with the generated GLR table, keeping every derivation the disambiguation
+/// filters allow. When they allow none, the forest has no root.
+pub fn parse(input: &str) -> Result<Parse<'_>, Error<'_>> {
+    let tokens = tokenize(input)?;
+    let kinds: Vec<usize> = tokens.iter().map(|token| token.kind as usize).collect();
+    match glr::parse(&Tables, &kinds) {
+        Ok(mut forest) => {
+            forest.filter(&filters(), &kinds);
+            Ok(Parse { tokens, forest })
+        }
+        Err(err) => Err(Error::UnexpectedToken {
+            found: tokens[err.position.min(tokens.len() - 1)],
+            expected: err.expected.iter().map(|&kind| TOKEN_KINDS[kind]).collect(),
+        }),
+    }
+}
+"#;
+
+/// Generate a GLR parser, which keeps every action of a table cell and so accepts tables with conflicts
+pub fn generate(grammar: &Grammar, table: &LrTable, source: &str) -> Result<String, InputError> {
+    let mut out = preamble(grammar, source);
+
+    for (name, module) in [("sppf", SPPF), ("glr", GLR)] {
+        write!(
+            out,
+            "\n#[allow(dead_code, clippy::all)]\npub mod {name} {{\n{module}}}\n"
+        )
+        .unwrap();
+    }
+    write_rules(&mut out, &table.automaton.rules);
+
+    write_token_kinds(&mut out, grammar);
+
+    out.push_str("\nconst ACTIONS: &[&[(TokenKind, &[glr::Action])]] = &[\n");
+    for row in &table.actions {
+        out.push_str("    &[");
+        for (symbol, cell) in row {
+            let Symbol::Terminal(terminal) = symbol else {
+                continue;
+            };
+            write!(out, "({}, &[", token_kind(terminal)).unwrap();
+            for action in cell {
+                match action {
+                    LrAction::Shift(state) => write!(out, "glr::Action::Shift({state}), "),
+                    LrAction::Reduce(rule) => write!(out, "glr::Action::Reduce({}), ", rule - 1),
+                    LrAction::Accept => write!(out, "glr::Action::Accept, "),
+                    LrAction::Go(_) => Ok(()),
+                }
+                .unwrap();
+            }
+            out.push_str("]), ");
+        }
+        out.push_str("],\n");
+    }
+    out.push_str("];\n");
+    write_gotos(&mut out, table);
+    write_filters(&mut out, &Symbols::new(grammar)?.filters);
+    out.push_str(DRIVER);
+    Ok(out)
+}
+
+fn write_filters(out: &mut String, filters: &Filters) {
+    let pairs = |pairs: &[(usize, usize)]| format!("vec!{pairs:?}");
+    let lists = |lists: &[(usize, Vec<usize>)]| {
+        let lists: Vec<String> = lists
+            .iter()
+            .map(|(nonterminal, terminals)| format!("({nonterminal}, vec!{terminals:?})"))
+            .collect();
+        format!("vec![{}]", lists.join(", "))
+    };
+    writeln!(
+        out,
+        "
+/// The disambiguation filters of the grammar, by rule, `Rule as usize` and `TokenKind as usize`
+fn filters() -> sppf::Filters {{
+    sppf::Filters {{
+        not_first: {},
+        not_last: {},
+        rejects: {},
+        preferred: vec!{:?},
+        avoided: vec!{:?},
+        follow: {},
+    }}
+}}",
+        pairs(&filters.not_first),
+        pairs(&filters.not_last),
+        lists(&filters.rejects),
+        filters.preferred,
+        filters.avoided,
+        lists(&filters.follow),
+    )
+    .unwrap();
+}
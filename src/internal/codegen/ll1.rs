@@ -0,0 +1,272 @@
+use std::fmt::Write;
+
+use crate::{
+    error::GrammarError,
+    internal::{ast::Grammar, table::ll1::Ll1Table},
+};
+
+use super::{
+    preamble, rust_ident, token_kind, write_error_kind, write_missing, write_symbols, RECOVERED,
+};
+
+const DRIVER: &str = r#"
+fn lookup(rule: Rule, kind: TokenKind) -> Option<usize> {
+    TABLE[rule as usize].iter().find(|(other, _)| *other == kind).map(|&(_, index)| index)
+}
+
+/// Parse the input with the generated LL(1) table. Symbols still to match are kept on a stack
+/// rather than in host recursion, so nesting is only limited by memory.
+///
+/// On a syntax error the parser deletes the unexpected token when the next one fits, or makes up
+/// a missing token when the unexpected one fits after it. A rule which cannot start takes its
+/// error production, one whose alternative starts with `ERROR`, if it has one. Otherwise tokens
+/// are skipped up to one which the rule can start with or which can follow it, as ANTLR
+/// resynchronizes on FOLLOW sets, and the rule is made up of missing tokens if it still cannot
+/// start.
+pub fn parse_recovering(input: &str) -> Recovered<'_> {
+    enum Entry {
+        Expect(Symbol),
+        /// The symbols of the innermost expanded rule were all matched
+        Finish,
+    }
+
+    /// Whether the symbols still expected can go on with a token of `kind`. The row of a rule
+    /// which can match nothing has the tokens which can follow it, so the innermost symbol decides.
+    fn fits(stack: &[Entry], kind: TokenKind) -> bool {
+        for entry in stack.iter().rev() {
+            match *entry {
+                Entry::Expect(Symbol::Token(expected)) => {
+                    return expected == kind || Some(expected) == ERROR
+                }
+                Entry::Expect(Symbol::Rule(rule)) => return lookup(rule, kind).is_some(),
+                Entry::Finish => {}
+            }
+        }
+        kind == TokenKind::Eof
+    }
+
+    /// `expected` after the tokens which would have taken another alternative of the rules
+    /// expanded at `position`, as those matched nothing before the error
+    fn expecting(
+        others: &(usize, Vec<TokenKind>),
+        position: usize,
+        expected: impl IntoIterator<Item = TokenKind>,
+    ) -> Vec<TokenKind> {
+        let others = if others.0 == position { others.1.as_slice() } else { &[] };
+        let mut kinds: Vec<TokenKind> = vec![];
+        for kind in others.iter().copied().chain(expected) {
+            if !kinds.contains(&kind) && Some(kind) != ERROR {
+                kinds.push(kind);
+            }
+        }
+        kinds
+    }
+
+    /// The children of the innermost node being built, or else the trees outside of any node
+    fn innermost<'a, 'input>(
+        nodes: &'a mut [(Rule, usize, Vec<Tree<'input>>)],
+        outside: &'a mut Vec<Tree<'input>>,
+    ) -> &'a mut Vec<Tree<'input>> {
+        match nodes.last_mut() {
+            Some((_, _, children)) => children,
+            None => outside,
+        }
+    }
+
+    let (tokens, mut errors) = tokenize_recovering(input);
+    let last = tokens.len() - 1;
+    let mut stack = vec![Entry::Expect(START)];
+    // The rule, alternative and children of each node being built
+    let mut nodes: Vec<(Rule, usize, Vec<Tree>)> = vec![];
+    // The tree of the start rule, and any input skipped around it
+    let mut outside: Vec<Tree> = vec![];
+    // Where an error production was last taken, as it is taken at most once per token, and
+    // whether rules are being expanded towards its `ERROR`
+    let mut error_at = None;
+    let mut to_error = false;
+    // Errors are reported again only once a token was matched, as in ANTLR
+    let mut recovering = false;
+    // Where rules were last expanded, and the tokens which would have taken their other
+    // alternatives
+    let mut others: (usize, Vec<TokenKind>) = (0, vec![]);
+    let mut position = 0;
+    while let Some(entry) = stack.pop() {
+        let token = tokens[position];
+        match entry {
+            Entry::Expect(Symbol::Token(kind)) if kind == token.kind => {
+                innermost(&mut nodes, &mut outside).push(Tree::Leaf(token));
+                position += 1;
+                recovering = false;
+            }
+            // The error token matches the input up to what can follow it
+            Entry::Expect(Symbol::Token(kind)) if Some(kind) == ERROR => {
+                let end = (position..last).find(|&end| fits(&stack, tokens[end].kind)).unwrap_or(last);
+                innermost(&mut nodes, &mut outside).push(Tree::Error(leaves(&tokens[position..end])));
+                position = end;
+                to_error = false;
+            }
+            Entry::Expect(Symbol::Token(kind)) => {
+                if !recovering {
+                    let expected = expecting(&others, position, [kind]);
+                    errors.push(Error::UnexpectedToken { found: token, expected });
+                }
+                recovering = true;
+                let children = innermost(&mut nodes, &mut outside);
+                if position < last && tokens[position + 1].kind == kind {
+                    children.push(Tree::Error(vec![Tree::Leaf(token)]));
+                    children.push(Tree::Leaf(tokens[position + 1]));
+                    position += 2;
+                } else if fits(&stack, token.kind) {
+                    children.push(Tree::Missing { kind, offset: token.start });
+                } else {
+                    let end = (position..last)
+                        .find(|&end| tokens[end].kind == kind || fits(&stack, tokens[end].kind))
+                        .unwrap_or(last);
+                    if end > position {
+                        children.push(Tree::Error(leaves(&tokens[position..end])));
+                    }
+                    position = end;
+                    if tokens[end].kind == kind {
+                        children.push(Tree::Leaf(tokens[end]));
+                        position += 1;
+                    } else {
+                        children.push(Tree::Missing { kind, offset: tokens[end].start });
+                    }
+                }
+            }
+            Entry::Expect(Symbol::Rule(rule)) => {
+                let lookahead = match ERROR {
+                    Some(error) if to_error => error,
+                    _ => token.kind,
+                };
+                let mut index = lookup(rule, lookahead);
+                if index.is_none() {
+                    if !recovering {
+                        let row = TABLE[rule as usize].iter().map(|&(kind, _)| kind);
+                        let expected = expecting(&others, position, row);
+                        errors.push(Error::UnexpectedToken { found: token, expected });
+                    }
+                    recovering = true;
+                    let children = innermost(&mut nodes, &mut outside);
+                    index = ERROR.and_then(|error| lookup(rule, error)).filter(|_| error_at != Some(position));
+                    if index.is_some() {
+                        error_at = Some(position);
+                        to_error = true;
+                    } else if position < last && lookup(rule, tokens[position + 1].kind).is_some() {
+                        children.push(Tree::Error(vec![Tree::Leaf(token)]));
+                        position += 1;
+                        index = lookup(rule, tokens[position].kind);
+                    } else {
+                        let end = (position..last)
+                            .find(|&end| {
+                                lookup(rule, tokens[end].kind).is_some() || fits(&stack, tokens[end].kind)
+                            })
+                            .unwrap_or(last);
+                        index = lookup(rule, tokens[end].kind);
+                        if end > position {
+                            children.push(Tree::Error(leaves(&tokens[position..end])));
+                        }
+                        position = end;
+                        // A rule which still cannot start is missing, or else left out as an
+                        // error node
+                        if index.is_none() {
+                            let made_up = missing(rule, tokens[end].start);
+                            children.push(made_up.unwrap_or(Tree::Error(vec![])));
+                        }
+                    }
+                }
+                if let Some(index) = index {
+                    if others.0 != position {
+                        others = (position, vec![]);
+                    }
+                    let row = TABLE[rule as usize].iter().filter(|&&(_, other)| other != index);
+                    others.1.extend(row.map(|&(kind, _)| kind));
+                    let (rule, alternative, symbols) = RULES[index];
+                    nodes.push((rule, alternative, Vec::with_capacity(symbols.len())));
+                    stack.push(Entry::Finish);
+                    stack.extend(symbols.iter().rev().map(|&symbol| Entry::Expect(symbol)));
+                }
+            }
+            Entry::Finish => {
+                let (rule, alternative, children) = nodes.pop().expect("every finish has a rule");
+                let tree = Tree::Node { rule, alternative, children };
+                innermost(&mut nodes, &mut outside).push(tree);
+            }
+        }
+    }
+    if position < last && !recovering {
+        let expected = expecting(&others, position, [TokenKind::Eof]);
+        errors.push(Error::UnexpectedToken { found: tokens[position], expected });
+    }
+    if position < last {
+        outside.push(Tree::Error(leaves(&tokens[position..last])));
+    }
+    // Input skipped around the tree of the start rule goes into it
+    let tree = match outside.iter().position(|tree| matches!(tree, Tree::Node { .. })) {
+        Some(index) => {
+            let after = outside.split_off(index + 1);
+            let mut tree = outside.pop().unwrap();
+            if let Tree::Node { children, .. } = &mut tree {
+                children.splice(0..0, outside);
+                children.extend(after);
+            }
+            tree
+        }
+        None if outside.len() == 1 => outside.pop().unwrap(),
+        None => Tree::Error(outside),
+    };
+    Recovered { tree, errors }
+}
+"#;
+
+/// Generate a table-driven LL(1) parser. The table must have no conflicts.
+pub fn generate(grammar: &Grammar, table: &Ll1Table, source: &str) -> Result<String, GrammarError> {
+    table.reject_conflicts()?;
+    let mut out = preamble(grammar, source);
+    write_missing(&mut out, grammar);
+    if let Some(start) = grammar.start_rule() {
+        writeln!(
+            out,
+            "\nconst START: Symbol = Symbol::Rule(Rule::{});",
+            rust_ident(start)
+        )
+        .unwrap();
+    }
+
+    out.push_str(
+        "\n/// The rule expanded to, its alternative, and its symbols\n\
+         const RULES: &[(Rule, usize, &[Symbol])] = &[\n",
+    );
+    for rule in &table.rules[1..] {
+        write!(
+            out,
+            "    (Rule::{}, {}, ",
+            rust_ident(rule.lhs),
+            rule.alternative
+        )
+        .unwrap();
+        write_symbols(&mut out, &rule.rhs);
+        out.push_str("),\n");
+    }
+    out.push_str("];\n");
+
+    out.push_str(
+        "\n/// The index in `RULES` to expand each rule to, by lookahead token\n\
+         const TABLE: &[&[(TokenKind, usize)]] = &[\n",
+    );
+    for (name, _) in grammar.parser_rules() {
+        out.push_str("    &[");
+        for (&(nonterminal, terminal), rules) in &table.entries {
+            if nonterminal == name {
+                // `RULES` leaves out the augmented start rule
+                write!(out, "({}, {}), ", token_kind(terminal), rules[0] - 1).unwrap();
+            }
+        }
+        out.push_str("],\n");
+    }
+    out.push_str("];\n");
+    write_error_kind(&mut out, grammar);
+    out.push_str(RECOVERED);
+    out.push_str(DRIVER);
+    Ok(out)
+}
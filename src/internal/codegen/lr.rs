@@ -0,0 +1,257 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::internal::{
+    ast::{Grammar, Symbol},
+    table::lr::{LrAction, LrTable},
+};
+
+use super::{preamble, token_kind, write_error_kind, write_gotos, write_rules, RECOVERED};
+
+const DRIVER: &str = r#"
+fn action(state: usize, kind: TokenKind) -> Option<Action> {
+    ACTIONS[state].iter().find(|(other, _)| *other == kind).map(|&(_, action)| action)
+}
+
+fn goto(state: usize, rule: Rule) -> usize {
+    let (_, next) = GOTOS[state]
+        .iter()
+        .find(|(target, _)| *target == rule)
+        .expect("the table has a goto for every reduction");
+    *next
+}
+
+/// The states after reducing as the table says and then shifting `kind`, or `None` if the
+/// parser in `states` would fail on `kind`
+fn simulate(states: &[usize], kind: TokenKind) -> Option<Vec<usize>> {
+    let mut states = states.to_vec();
+    loop {
+        match action(*states.last().unwrap(), kind)? {
+            Action::Shift(next) => {
+                states.push(next);
+                return Some(states);
+            }
+            Action::Reduce(rule) => {
+                let (rule, _, length) = RULES[rule];
+                states.truncate(states.len() - length);
+                states.push(goto(*states.last().unwrap(), rule));
+            }
+            Action::Accept => return Some(states),
+        }
+    }
+}
+
+/// The fewest tokens, up to `longest`, after which the parser in `states` goes on with `kind`.
+/// The search gives up after trying as many stacks as `SEARCH_LIMIT`.
+fn insertion(states: &[usize], kind: TokenKind, longest: usize) -> Option<Vec<TokenKind>> {
+    const SEARCH_LIMIT: usize = 10_000;
+    let mut queue = std::collections::VecDeque::from([(states.to_vec(), vec![])]);
+    let mut tried = 0;
+    while let Some((states, inserted)) = queue.pop_front() {
+        if !inserted.is_empty() && simulate(&states, kind).is_some() {
+            return Some(inserted);
+        }
+        if inserted.len() == longest {
+            continue;
+        }
+        for &(candidate, _) in ACTIONS[*states.last().unwrap()] {
+            if candidate == TokenKind::Eof || Some(candidate) == ERROR {
+                continue;
+            }
+            tried += 1;
+            if tried > SEARCH_LIMIT {
+                return None;
+            }
+            if let Some(after) = simulate(&states, candidate) {
+                queue.push_back((after, [inserted.as_slice(), &[candidate]].concat()));
+            }
+        }
+    }
+    None
+}
+
+/// Skipped input as a `Tree::Error`, merged into the error just before it if there is one
+fn skip<'input>(trees: &mut Vec<Tree<'input>>, mut skipped: Vec<Tree<'input>>) {
+    match trees.last_mut() {
+        Some(Tree::Error(previous)) => previous.append(&mut skipped),
+        _ => trees.push(Tree::Error(skipped)),
+    }
+}
+
+/// Parse the input with the generated LR table. On a syntax error the parser tries, in order:
+/// deleting the unexpected token, inserting a missing one (or at the end of input the fewest
+/// which complete it), resuming after the `ERROR` of an error production, and last popping states
+/// and skipping tokens until some state can go on.
+/// Like yacc, errors are only reported again after three tokens were shifted.
+pub fn parse_recovering(input: &str) -> Recovered<'_> {
+    let (tokens, mut errors) = tokenize_recovering(input);
+    let mut states = vec![0];
+    // The trees of every state but the first, after any input skipped before them
+    let mut trees: Vec<Vec<Tree>> = vec![];
+    // Input skipped since the last shift
+    let mut skipped: Vec<Tree> = vec![];
+    // Tokens made up by the recovery, shifted from the last before the input goes on
+    let mut inserted: Vec<TokenKind> = vec![];
+    // Tokens to shift before errors are reported again
+    let mut quiet: usize = 0;
+    // The state entered by the last shift, which messages are written for
+    let mut shifted = 0;
+    let mut position = 0;
+    loop {
+        let token = tokens[position];
+        let state = *states.last().unwrap();
+        match action(state, inserted.last().copied().unwrap_or(token.kind)) {
+            Some(Action::Shift(next)) => {
+                states.push(next);
+                shifted = next;
+                let mut slot = std::mem::take(&mut skipped);
+                match inserted.pop() {
+                    // The skipped input is the tree of the error token
+                    Some(kind) if Some(kind) == ERROR => {}
+                    Some(kind) => slot.push(Tree::Missing { kind, offset: token.start }),
+                    None => {
+                        slot.push(Tree::Leaf(token));
+                        position += 1;
+                        quiet = quiet.saturating_sub(1);
+                    }
+                }
+                trees.push(slot);
+            }
+            Some(Action::Reduce(rule)) => {
+                let (rule, alternative, length) = RULES[rule];
+                let children = trees.split_off(trees.len() - length).into_iter().flatten().collect();
+                states.truncate(states.len() - length);
+                states.push(goto(*states.last().unwrap(), rule));
+                trees.push(vec![Tree::Node { rule, alternative, children }]);
+            }
+            Some(Action::Accept) => {
+                let mut tree = trees.pop().and_then(|mut slot| slot.pop()).expect("the start rule was reduced");
+                if let Tree::Node { children, .. } = &mut tree {
+                    children.append(&mut skipped);
+                }
+                return Recovered { tree, errors };
+            }
+            None => {
+                if quiet == 0 {
+                    let expected = ACTIONS[state]
+                        .iter()
+                        .map(|&(kind, _)| kind)
+                        .filter(|&kind| Some(kind) != ERROR)
+                        .collect();
+                    errors.push(match MESSAGES.binary_search_by_key(&shifted, |&(state, _)| state) {
+                        Ok(index) => Error::Message { found: token, expected, message: MESSAGES[index].1 },
+                        Err(_) => Error::UnexpectedToken { found: token, expected },
+                    });
+                }
+                quiet = 3;
+
+                // Delete the unexpected token when the next one fits
+                if token.kind != TokenKind::Eof && simulate(&states, tokens[position + 1].kind).is_some() {
+                    skip(&mut skipped, vec![Tree::Leaf(token)]);
+                    position += 1;
+                    continue;
+                }
+                // Insert a token after which the unexpected one fits, or complete the input
+                let longest = if token.kind == TokenKind::Eof { usize::MAX } else { 1 };
+                if let Some(mut tokens) = insertion(&states, token.kind, longest) {
+                    tokens.reverse();
+                    inserted = tokens;
+                    continue;
+                }
+                // Pop to the innermost state with an error production, and skip tokens up to one
+                // which fits after its `ERROR`
+                let resume = ERROR.and_then(|error| {
+                    (0..states.len()).rev().find_map(|depth| {
+                        let after = simulate(&states[..=depth], error)?;
+                        let end = (position..tokens.len())
+                            .find(|&end| simulate(&after, tokens[end].kind).is_some())?;
+                        Some((depth, end))
+                    })
+                });
+                if let Some((depth, end)) = resume {
+                    skip(&mut skipped, trees.split_off(depth).into_iter().flatten().collect());
+                    skip(&mut skipped, leaves(&tokens[position..end]));
+                    states.truncate(depth + 1);
+                    shifted = states[depth];
+                    position = end;
+                    inserted.extend(ERROR);
+                    continue;
+                }
+                // Panic mode: skip the fewest tokens up to one which some state on the stack can
+                // go on with, popping the states above it
+                let resume = (position..tokens.len()).find_map(|end| {
+                    (0..states.len())
+                        .rev()
+                        .find(|&depth| simulate(&states[..=depth], tokens[end].kind).is_some())
+                        .map(|depth| (depth, end))
+                });
+                let (depth, end) = resume.unwrap_or((0, tokens.len() - 1));
+                skip(&mut skipped, trees.split_off(depth).into_iter().flatten().collect());
+                skip(&mut skipped, leaves(&tokens[position..end]));
+                states.truncate(depth + 1);
+                shifted = states[depth];
+                position = end;
+                if resume.is_none() {
+                    // Nothing can go on at the end of input, so all of it is an error
+                    let tree = match skipped.pop() {
+                        Some(tree) if skipped.is_empty() => tree,
+                        tree => Tree::Error(skipped.into_iter().chain(tree).collect()),
+                    };
+                    return Recovered { tree, errors };
+                }
+            }
+        }
+    }
+}
+"#;
+
+/// Generate a table-driven LR parser. Every cell of the table must hold at most one action,
+/// see [`crate::internal::table::policy::apply`]. Syntax errors in the states of `messages`
+/// are reported with their message, see [`crate::internal::table::messages`].
+pub fn generate(
+    grammar: &Grammar,
+    table: &LrTable,
+    messages: &BTreeMap<usize, String>,
+    source: &str,
+) -> String {
+    let mut out = preamble(grammar, source);
+
+    out.push_str(
+        r#"
+#[derive(Debug, Clone, Copy)]
+enum Action {
+    Shift(usize),
+    Reduce(usize),
+    Accept,
+}
+"#,
+    );
+    write_rules(&mut out, &table.automaton.rules);
+    out.push_str("\nconst ACTIONS: &[&[(TokenKind, Action)]] = &[\n");
+    for row in &table.actions {
+        out.push_str("    &[");
+        for (symbol, cell) in row {
+            let (Symbol::Terminal(terminal), Some(action)) = (symbol, cell.first()) else {
+                continue;
+            };
+            let action = match action {
+                LrAction::Shift(state) => format!("Action::Shift({state})"),
+                LrAction::Reduce(rule) => format!("Action::Reduce({})", rule - 1),
+                LrAction::Accept => "Action::Accept".into(),
+                LrAction::Go(_) => continue,
+            };
+            write!(out, "({}, {action}), ", token_kind(terminal)).unwrap();
+        }
+        out.push_str("],\n");
+    }
+    out.push_str("];\n");
+    write_gotos(&mut out, table);
+    out.push_str("\nconst MESSAGES: &[(usize, &str)] = &[\n");
+    for (state, message) in messages {
+        writeln!(out, "    ({state}, {message:?}),").unwrap();
+    }
+    out.push_str("];\n");
+    write_error_kind(&mut out, grammar);
+    out.push_str(RECOVERED);
+    out.push_str(DRIVER);
+    out
+}
@@ -0,0 +1,655 @@
+//! Generation of standalone Rust parsers.
+//!
+//! Generated modules only depend on the `regex` crate, which drives the generated lexer.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    path::PathBuf,
+};
+
+use crate::{
+    diagnostic::Diagnostic,
+    error::{Error, NameErrors},
+    internal::{
+        analysis::{names::check_names, sets::END},
+        ast::{Grammar, Ident, Production, Symbol},
+        table::{
+            counterexample::smallest,
+            ll1::Ll1Table,
+            lr::{LrAction, LrKind, LrTable},
+            messages,
+            policy::{resolved_table, ConflictPolicy},
+            rules, Rule,
+        },
+    },
+    span::Span,
+};
+
+pub mod bootstrap;
+pub mod glr;
+pub mod ll1;
+pub mod lr;
+pub mod peg;
+pub mod rd;
+
+/// The kind of parser to generate
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// LL(1) table driven on an explicit stack, for grammars without LL(1) conflicts
+    Ll1,
+    Slr,
+    #[default]
+    Lalr,
+    /// LALR table whose conflicts are kept, driven by a GLR parser producing a parse forest
+    Glr,
+    /// Recursive descent, with adaptive LL(*) prediction where one token of lookahead is not enough
+    RecursiveDescent,
+    /// Packrat parser reading `|` as ordered choice, with `&` and `!` lookaheads
+    Peg,
+}
+
+/// How to generate a parser module
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub backend: Backend,
+    /// What to do with conflicts not declared by `%expect` and `%expect_rr`.
+    /// The GLR backend keeps every conflict.
+    pub conflicts: ConflictPolicy,
+    /// Syntax error messages for the states of the LR backends, see [`messages`]
+    pub messages: Option<PathBuf>,
+}
+
+/// A generated parser module
+#[derive(Debug)]
+pub struct Generated {
+    pub code: String,
+    /// Conflicts resolved by default rather than declared
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Generate a parser module for the grammar. `source` names the grammar in the header of the
+/// module. Grammars defining a rule twice or referring to an undefined one are rejected first,
+/// as are names which would collide once turned into Rust identifiers.
+pub fn generate(grammar: &Grammar, source: &str, options: &Options) -> Result<Generated, Error> {
+    let mut diagnostics = check_names(grammar);
+    if diagnostics.is_empty() {
+        diagnostics = collisions(grammar);
+    }
+    if !diagnostics.is_empty() {
+        return Err(NameErrors { diagnostics }.into());
+    }
+    if options.messages.is_some() && !matches!(options.backend, Backend::Slr | Backend::Lalr) {
+        return Err(Error::MessagesBackend);
+    }
+    let mut warnings = vec![];
+    let code = match options.backend {
+        Backend::Ll1 => ll1::generate(grammar, &Ll1Table::new(grammar), source)?,
+        Backend::Glr => glr::generate(grammar, &LrTable::new(grammar, LrKind::Lalr), source)?,
+        Backend::RecursiveDescent => rd::generate(grammar, source)?,
+        Backend::Peg => peg::generate(grammar, source)?,
+        Backend::Slr | Backend::Lalr => {
+            let kind = match options.backend {
+                Backend::Slr => LrKind::Slr,
+                _ => LrKind::Lalr,
+            };
+            let (table, resolved) = resolved_table(grammar, kind, options.conflicts)?;
+            warnings = resolved;
+            let messages = match &options.messages {
+                Some(path) => messages::load(path, &table)?,
+                None => BTreeMap::new(),
+            };
+            lr::generate(grammar, &table, &messages, source)
+        }
+    };
+    Ok(Generated { code, warnings })
+}
+
+/// Rust keywords which cannot be used as raw identifiers either
+const RESERVED: [&str; 5] = ["crate", "self", "Self", "super", "_"];
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where", "while",
+    "abstract", "become", "box", "do", "final", "gen", "macro", "override", "priv", "try",
+    "typeof", "unsized", "virtual", "yield",
+];
+
+/// Turn a grammar name into a Rust identifier. Ticks, as in `rule''`, become a `_pp` suffix.
+pub fn rust_ident(name: &str) -> String {
+    let ticks = name.chars().filter(|&c| c == '\'').count();
+    let base: String = name.chars().filter(|&c| c != '\'').collect();
+    let ident = if ticks > 0 {
+        format!("{base}_{}", "p".repeat(ticks))
+    } else {
+        base
+    };
+    if RESERVED.contains(&ident.as_str()) {
+        format!("{ident}_")
+    } else if KEYWORDS.contains(&ident.as_str()) {
+        format!("r#{ident}")
+    } else {
+        ident
+    }
+}
+
+/// The name of the generated function parsing a rule, for top-down backends
+fn parse_fn(name: &str) -> String {
+    format!("parse_{}", rust_ident(name).trim_start_matches("r#"))
+}
+
+/// Terminals of the grammar, in order of first appearance: lexer rules first, then any
+/// terminal used by a parser rule without a lexer rule of its own
+pub fn terminals(grammar: &Grammar) -> Vec<&str> {
+    let mut terminals: Vec<&str> = grammar.lexer_rules().map(|(name, _)| name).collect();
+    for (_, alternatives) in grammar.parser_rules() {
+        for ident in alternatives
+            .iter()
+            .flat_map(|alternative| &alternative.idents)
+        {
+            if let Ident::Terminal(name, _) = ident {
+                if !terminals.contains(&name.as_str()) {
+                    terminals.push(name);
+                }
+            }
+        }
+    }
+    terminals
+}
+
+/// The Rust expression for a terminal of the generated `TokenKind`
+pub fn token_kind(terminal: &str) -> String {
+    if terminal == END {
+        "TokenKind::Eof".into()
+    } else {
+        format!("TokenKind::{}", rust_ident(terminal))
+    }
+}
+
+/// Names which generate the same Rust identifier, in the `TokenKind` enum, where `Eof` is taken
+/// by the end of input, or in the `Rule` enum. Ticks and keywords are mangled by [`rust_ident`],
+/// so `rule''` and `rule_pp` collide.
+fn collisions(grammar: &Grammar) -> Vec<Diagnostic> {
+    let mut tokens: Vec<(&str, Span)> = grammar
+        .rules
+        .iter()
+        .filter(|rule| matches!(rule, Production::Lexer(..)))
+        .map(|rule| (rule.name(), rule.name_span()))
+        .collect();
+    let mut rules = vec![];
+    for rule in &grammar.rules {
+        let Production::Parser(_, alternatives, _) = rule else {
+            continue;
+        };
+        rules.push((rule.name(), rule.name_span()));
+        for ident in alternatives
+            .iter()
+            .flat_map(|alternative| &alternative.idents)
+        {
+            if let Ident::Terminal(name, span) = ident {
+                if !tokens.iter().any(|(token, _)| token == name) {
+                    tokens.push((name, *span));
+                }
+            }
+        }
+    }
+    let mut diagnostics = vec![];
+    for (kind, names) in [("TokenKind", tokens), ("Rule", rules)] {
+        // The grammar name each identifier was generated from, or none for `TokenKind::Eof`
+        let mut generated: HashMap<String, Option<(&str, Span)>> = HashMap::new();
+        if kind == "TokenKind" {
+            generated.insert("Eof".into(), None);
+        }
+        for (name, span) in names {
+            let ident = rust_ident(name);
+            let diagnostic = match generated.get(ident.as_str()) {
+                Some(None) => Diagnostic::error(format!(
+                    "`{name}` is generated as `{kind}::Eof`, which is the end of input"
+                )),
+                Some(Some((other, first))) => Diagnostic::error(format!(
+                    "`{name}` and `{other}` are both generated as `{kind}::{ident}`"
+                ))
+                .with_secondary(*first, format!("`{other}` is here")),
+                None => {
+                    generated.insert(ident, Some((name, span)));
+                    continue;
+                }
+            };
+            diagnostics.push(
+                diagnostic
+                    .with_code("E006")
+                    .with_primary(span, "this name collides in the generated parser")
+                    .with_help(format!("rename `{name}`")),
+            );
+        }
+    }
+    diagnostics
+}
+
+/// The parts of a generated module shared by every backend: token and tree types, errors and the lexer
+pub fn preamble(grammar: &Grammar, source: &str) -> String {
+    let mut out = String::new();
+    let terminals = terminals(grammar);
+    let rules: Vec<&str> = grammar.parser_rules().map(|(name, _)| name).collect();
+
+    writeln!(
+        out,
+        "// @generated by rantlr from `{source}`. Do not edit by hand."
+    )
+    .unwrap();
+    out.push_str(
+        r#"
+use std::{fmt, sync::OnceLock};
+
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+"#,
+    );
+    for terminal in &terminals {
+        writeln!(out, "    {},", rust_ident(terminal)).unwrap();
+    }
+    out.push_str(
+        r#"    Eof,
+}
+
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+"#,
+    );
+    for rule in &rules {
+        writeln!(out, "    {},", rust_ident(rule)).unwrap();
+    }
+    out.push_str(
+        r#"}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'input> {
+    pub kind: TokenKind,
+    pub text: &'input str,
+    /// Byte offsets of the token in the input
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum Tree<'input> {
+    Node {
+        rule: Rule,
+        /// Index of the alternative of the rule, in grammar order
+        alternative: usize,
+        children: Vec<Tree<'input>>,
+    },
+    Leaf(Token<'input>),
+    /// Input skipped to recover from a syntax error, with any subtrees already built from it
+    Error(Vec<Tree<'input>>),
+    /// A token which was expected but not found, made up at the offset where it is missing
+    Missing { kind: TokenKind, offset: usize },
+}
+
+/// Trees are dropped with a stack of their children rather than recursively, as deeply nested
+/// input would otherwise overflow the stack when its tree is freed
+impl Drop for Tree<'_> {
+    fn drop(&mut self) {
+        let (Tree::Node { children, .. } | Tree::Error(children)) = self else {
+            return;
+        };
+        let mut stack = std::mem::take(children);
+        while let Some(mut tree) = stack.pop() {
+            if let Tree::Node { children, .. } | Tree::Error(children) = &mut tree {
+                stack.append(children);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error<'input> {
+    UnexpectedChar(usize),
+    UnexpectedToken {
+        found: Token<'input>,
+        expected: Vec<TokenKind>,
+    },
+    /// An unexpected token, with the message written for the state the parser failed in
+    Message {
+        found: Token<'input>,
+        expected: Vec<TokenKind>,
+        message: &'static str,
+    },
+}
+
+impl fmt::Display for Error<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnexpectedChar(offset) => write!(f, "unexpected character at offset {offset}"),
+            Error::UnexpectedToken { found, expected } => write!(
+                f,
+                "unexpected {:?} `{}` at offset {}, expected one of {expected:?}",
+                found.kind, found.text, found.start
+            ),
+            Error::Message { found, message, .. } => {
+                write!(f, "syntax error at offset {}: {message}", found.start)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error<'_> {}
+
+const LEXER_RULES: &[(TokenKind, &str)] = &[
+"#,
+    );
+    for (name, pattern) in grammar.lexer_rules() {
+        writeln!(
+            out,
+            "    ({}, {:?}),",
+            token_kind(name),
+            format!("^(?:{pattern})")
+        )
+        .unwrap();
+    }
+    out.push_str(
+        r#"];
+
+/// Split the input into tokens, skipping whitespace. The longest match wins, and ties go to the
+/// lexer rule declared first. The last token is always `TokenKind::Eof`.
+pub fn tokenize(input: &str) -> Result<Vec<Token<'_>>, Error<'_>> {
+    let (tokens, mut errors) = tokenize_recovering(input);
+    if errors.is_empty() {
+        Ok(tokens)
+    } else {
+        Err(errors.swap_remove(0))
+    }
+}
+
+/// Split the input into tokens as `tokenize` does, skipping characters which start no token
+pub fn tokenize_recovering(input: &str) -> (Vec<Token<'_>>, Vec<Error<'_>>) {
+    static PATTERNS: OnceLock<Vec<(TokenKind, regex::Regex)>> = OnceLock::new();
+    let patterns = PATTERNS.get_or_init(|| {
+        LEXER_RULES
+            .iter()
+            .map(|&(kind, pattern)| (kind, regex::Regex::new(pattern).expect(pattern)))
+            .collect()
+    });
+
+    let mut tokens = vec![];
+    let mut errors = vec![];
+    let mut offset = 0;
+    loop {
+        offset += input[offset..].len() - input[offset..].trim_start().len();
+        if offset == input.len() {
+            tokens.push(Token { kind: TokenKind::Eof, text: "", start: offset, end: offset });
+            return (tokens, errors);
+        }
+        let rest = &input[offset..];
+        let mut best: Option<(TokenKind, usize)> = None;
+        for (kind, pattern) in patterns {
+            let length = pattern.find(rest).map_or(0, |found| found.end());
+            if length > best.map_or(0, |(_, longest)| longest) {
+                best = Some((*kind, length));
+            }
+        }
+        let Some((kind, length)) = best else {
+            errors.push(Error::UnexpectedChar(offset));
+            offset += rest.chars().next().map_or(1, char::len_utf8);
+            continue;
+        };
+        tokens.push(Token { kind, text: &rest[..length], start: offset, end: offset + length });
+        offset += length;
+    }
+}
+"#,
+    );
+    out
+}
+
+/// The parts of a generated module shared by the backends which recover from syntax errors,
+/// whose `parse_recovering` always returns a tree
+const RECOVERED: &str = r#"
+/// A best-effort tree of the input, with `Tree::Error` and `Tree::Missing` nodes where the parser
+/// recovered from syntax errors, and every error it recovered from
+#[derive(Debug)]
+pub struct Recovered<'input> {
+    pub tree: Tree<'input>,
+    pub errors: Vec<Error<'input>>,
+}
+
+/// Leaves of skipped tokens
+fn leaves<'input>(tokens: &[Token<'input>]) -> Vec<Tree<'input>> {
+    tokens.iter().map(|&token| Tree::Leaf(token)).collect()
+}
+
+/// Parse the input, failing on its first syntax error
+pub fn parse(input: &str) -> Result<Tree<'_>, Error<'_>> {
+    let Recovered { tree, mut errors } = parse_recovering(input);
+    if errors.is_empty() {
+        Ok(tree)
+    } else {
+        Err(errors.swap_remove(0))
+    }
+}
+"#;
+
+/// The terminal standing for input skipped by recovery, as `error` does in yacc. A rule with an
+/// alternative such as `stmt: ERROR SEMI;` resumes after the next `SEMI` following an error.
+/// `ERROR` is an ordinary terminal when the grammar has a lexer rule for it.
+pub const ERROR: &str = "ERROR";
+
+/// Whether the grammar uses the `ERROR` terminal for error productions
+fn has_error_productions(grammar: &Grammar) -> bool {
+    terminals(grammar).contains(&ERROR) && grammar.lexer_rules().all(|(name, _)| name != ERROR)
+}
+
+/// Emit `ERROR`, the token kind of error productions if the grammar has any
+fn write_error_kind(out: &mut String, grammar: &Grammar) {
+    let kind = if has_error_productions(grammar) {
+        format!("Some({})", token_kind(ERROR))
+    } else {
+        "None".into()
+    };
+    writeln!(
+        out,
+        "\n/// The terminal of error productions, which stands for the input skipped by recovery\n\
+         const ERROR: Option<TokenKind> = {kind};"
+    )
+    .unwrap();
+}
+
+/// The made up tree of a rule missing in the input, of the smallest alternative of the rule with
+/// every token missing where the rule was expected
+const MISSING: &str = r#"
+fn missing<'input>(rule: Rule, offset: usize) -> Option<Tree<'input>> {
+    let (alternative, symbols) = SMALLEST[rule as usize]?;
+    let children = symbols
+        .iter()
+        .map(|&symbol| match symbol {
+            Symbol::Token(kind) => Some(Tree::Missing { kind, offset }),
+            Symbol::Rule(rule) => missing(rule, offset),
+        })
+        .collect::<Option<_>>()?;
+    Some(Tree::Node { rule, alternative, children })
+}
+"#;
+
+/// The symbols of an alternative, as the generated `Symbol`
+fn write_symbols(out: &mut String, rhs: &[Symbol]) {
+    let symbols: Vec<String> = rhs
+        .iter()
+        .map(|symbol| match symbol {
+            Symbol::Terminal(name) => format!("Symbol::Token({})", token_kind(name)),
+            Symbol::NonTerminal(name) => format!("Symbol::Rule(Rule::{})", rust_ident(name)),
+        })
+        .collect();
+    write!(out, "&[{}]", symbols.join(", ")).unwrap();
+}
+
+/// Emit `missing`, which top-down parsers make up a rule missing in the input with, and the
+/// `Symbol` it is made up of
+fn write_missing(out: &mut String, grammar: &Grammar) {
+    out.push_str(
+        "
+#[derive(Debug, Clone, Copy)]
+enum Symbol {
+    Token(TokenKind),
+    Rule(Rule),
+}
+
+/// The alternative at the root of the smallest tree of each rule, and its symbols. A rule which
+/// derives no input has none.
+const SMALLEST: &[Option<(usize, &[Symbol])>] = &[
+",
+    );
+    let rules = rules(grammar);
+    let smallest = smallest(&rules);
+    for (name, _) in grammar.parser_rules() {
+        match smallest.get(name) {
+            Some(&index) => {
+                write!(out, "    Some(({}, ", rules[index].alternative).unwrap();
+                write_symbols(out, &rules[index].rhs);
+                out.push_str(")),\n");
+            }
+            None => out.push_str("    None,\n"),
+        }
+    }
+    out.push_str("];\n");
+    out.push_str(MISSING);
+}
+
+/// Emit `RULES`, which LR drivers index by the rule number of the table minus one,
+/// as rule 0 is the augmented start rule which is accepted rather than reduced
+fn write_rules(out: &mut String, rules: &[Rule]) {
+    out.push_str(
+        "\n/// The rule reduced to, its alternative, and the number of symbols it pops\n\
+         const RULES: &[(Rule, usize, usize)] = &[\n",
+    );
+    for rule in &rules[1..] {
+        writeln!(
+            out,
+            "    (Rule::{}, {}, {}),",
+            rust_ident(rule.lhs),
+            rule.alternative,
+            rule.rhs.len()
+        )
+        .unwrap();
+    }
+    out.push_str("];\n");
+}
+
+/// Emit `GOTOS`, the state to go to after reducing to a rule, for every state
+fn write_gotos(out: &mut String, table: &LrTable) {
+    out.push_str("\nconst GOTOS: &[&[(Rule, usize)]] = &[\n");
+    for row in &table.actions {
+        out.push_str("    &[");
+        for (symbol, cell) in row {
+            if let (Symbol::NonTerminal(name), Some(LrAction::Go(state))) = (symbol, cell.first()) {
+                write!(out, "(Rule::{}, {state}), ", rust_ident(name)).unwrap();
+            }
+        }
+        out.push_str("],\n");
+    }
+    out.push_str("];\n");
+}
+
+/// Emit `TOKEN_KINDS`, mapping the terminals of a runtime back to `TokenKind`
+fn write_token_kinds(out: &mut String, grammar: &Grammar) {
+    out.push_str("\nconst TOKEN_KINDS: &[TokenKind] = &[\n");
+    for terminal in terminals(grammar) {
+        writeln!(out, "    {},", token_kind(terminal)).unwrap();
+    }
+    out.push_str("    TokenKind::Eof,\n];\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::testing::grammar;
+
+    macro_rules! test_rust_ident {
+        ($name:ident, $src:expr => $should_be:expr) => {
+            #[test]
+            fn $name() {
+                assert_eq!(rust_ident($src), $should_be);
+            }
+        };
+    }
+
+    test_rust_ident!(rust_ident_plain, "expr" => "expr");
+    test_rust_ident!(rust_ident_ticks, "rule''" => "rule_pp");
+    test_rust_ident!(rust_ident_keyword, "type" => "r#type");
+    test_rust_ident!(rust_ident_reserved, "self" => "self_");
+
+    macro_rules! test_has_error_productions {
+        ($name:ident, $src:expr => $should_be:expr) => {
+            #[test]
+            fn $name() {
+                assert_eq!(has_error_productions(&grammar($src)), $should_be);
+            }
+        };
+    }
+
+    test_has_error_productions!(error_production, "s: A SEMI | ERROR SEMI; A: a; SEMI: \\x3B;" => true);
+    test_has_error_productions!(no_error_production, "s: A SEMI; A: a; SEMI: \\x3B;" => false);
+    test_has_error_productions!(error_lexer_rule, "s: A | ERROR; A: a; ERROR: e;" => false);
+
+    macro_rules! test_generate_helper {
+        ($name:ident, $backend:ident, $src:expr, $helper:expr => $should_be:expr) => {
+            #[test]
+            fn $name() {
+                let options = Options {
+                    backend: Backend::$backend,
+                    ..Options::default()
+                };
+                let generated = generate(&grammar($src), "g.g4", &options).unwrap();
+                assert_eq!(generated.code.contains($helper), $should_be);
+            }
+        };
+    }
+
+    // Helpers no rule calls would be dead code in the crate of the parser
+    test_generate_helper!(peg_lookahead, Peg, "s: !A B | A; A: a; B: b;", "fn check(" => true);
+    test_generate_helper!(peg_no_lookahead, Peg, "s: A B | A; A: a; B: b;", "fn check(" => false);
+    test_generate_helper!(rd_decision, RecursiveDescent, "s: A | B; A: a; B: b;", "fn lookahead(" => true);
+    test_generate_helper!(rd_no_decision, RecursiveDescent, "s: A B; A: a; B: b;", "fn lookahead(" => false);
+    test_generate_helper!(rd_nullable, RecursiveDescent, "s: A | ; A: a;", "fn matching_nothing(" => true);
+    test_generate_helper!(rd_not_nullable, RecursiveDescent, "s: A | B; A: a; B: b;", "fn matching_nothing(" => false);
+    test_generate_helper!(rd_unreachable, RecursiveDescent, "s: A; t: A; A: a;", "fn parse_t(" => false);
+
+    macro_rules! test_generate_names {
+        ($name:ident, $src:expr => $should_be:expr) => {
+            #[test]
+            fn $name() {
+                let grammar = grammar($src);
+                for backend in [
+                    Backend::Ll1,
+                    Backend::Slr,
+                    Backend::Lalr,
+                    Backend::Glr,
+                    Backend::RecursiveDescent,
+                    Backend::Peg,
+                ] {
+                    let options = Options {
+                        backend,
+                        ..Options::default()
+                    };
+                    let err = generate(&grammar, "g.g4", &options).unwrap_err();
+                    assert!(matches!(err, Error::Names(_)), "{backend:?}: {err}");
+                    assert_eq!(err.to_string(), $should_be);
+                }
+            }
+        };
+    }
+
+    test_generate_names!(generate_undefined_rule, "e: a | N; N: n;" => "undefined rule `a`");
+    test_generate_names!(generate_duplicate_rule, "s: A; s: B; A: a; B: b;" =>
+        "the rule `s` is defined more than once");
+    test_generate_names!(generate_eof_token, "s: Eof; Eof: eof;" =>
+        "`Eof` is generated as `TokenKind::Eof`, which is the end of input");
+    test_generate_names!(generate_eof_token_without_lexer_rule, "s: A Eof; A: a;" =>
+        "`Eof` is generated as `TokenKind::Eof`, which is the end of input");
+    test_generate_names!(generate_ticked_rule, "s: rule_pp rule''; rule_pp: A; rule'': A; A: a;" =>
+        "`rule''` and `rule_pp` are both generated as `Rule::rule_pp`");
+    test_generate_names!(generate_reserved_rule, "s: self self_; self: A; self_: A; A: a;" =>
+        "`self_` and `self` are both generated as `Rule::self_`");
+}
@@ -0,0 +1,224 @@
+use std::fmt::Write;
+
+use crate::{
+    error::GrammarError,
+    internal::{
+        analysis::sets::reject_left_recursion,
+        ast::{Grammar, Ident},
+    },
+};
+
+use super::{parse_fn, preamble, rust_ident, token_kind};
+
+const PARSER: &str = r#"
+/// A matched token by its position, or a rule by its index in `Parser::nodes`
+#[derive(Clone, Copy)]
+enum Child {
+    Leaf(usize),
+    Node(usize),
+}
+
+type Match = Option<(usize, Child)>;
+
+struct Node {
+    rule: Rule,
+    alternative: usize,
+    children: Vec<Child>,
+}
+
+struct Parser<'input> {
+    tokens: Vec<Token<'input>>,
+    /// Every rule matched, which `memo` and the nodes of other rules refer to by index
+    nodes: Vec<Node>,
+    /// The end and node of each rule by start position, or `None` when it failed
+    memo: HashMap<(Rule, usize), Match>,
+    /// The farthest position a token failed to match at, and the kinds expected there
+    farthest: (usize, Vec<TokenKind>),
+    /// How many lookaheads are running, whose failures are not reported
+    lookahead: usize,
+}
+
+#[allow(clippy::all)]
+impl<'input> Parser<'input> {
+    fn token(&mut self, kind: TokenKind, position: usize) -> Match {
+        if self.tokens[position].kind == kind {
+            return Some((position + 1, Child::Leaf(position)));
+        }
+        self.fail(kind, position);
+        None
+    }
+
+    fn fail(&mut self, kind: TokenKind, position: usize) {
+        if self.lookahead > 0 || position < self.farthest.0 {
+            return;
+        }
+        if position > self.farthest.0 {
+            self.farthest = (position, vec![]);
+        }
+        if !self.farthest.1.contains(&kind) {
+            self.farthest.1.push(kind);
+        }
+    }
+
+    /// Run a rule at most once per position. A rule counts as failed while it runs, so it fails
+    /// instead of looping if a lookahead reaches it again at the same position.
+    fn rule(&mut self, rule: Rule, position: usize, parse: fn(&mut Self, usize) -> Match) -> Match {
+        if let Some(&result) = self.memo.get(&(rule, position)) {
+            return result;
+        }
+        self.memo.insert((rule, position), None);
+        let result = parse(self, position);
+        self.memo.insert((rule, position), result);
+        result
+    }
+
+    fn node(&mut self, rule: Rule, alternative: usize, children: Vec<Child>) -> Child {
+        self.nodes.push(Node { rule, alternative, children });
+        Child::Node(self.nodes.len() - 1)
+    }
+
+    /// The tree of a match, built once the parse succeeded
+    fn tree(&self, child: Child) -> Tree<'input> {
+        match child {
+            Child::Leaf(position) => Tree::Leaf(self.tokens[position]),
+            Child::Node(index) => {
+                let node = &self.nodes[index];
+                Tree::Node {
+                    rule: node.rule,
+                    alternative: node.alternative,
+                    children: node.children.iter().map(|&child| self.tree(child)).collect(),
+                }
+            }
+        }
+    }
+"#;
+
+/// Only grammars with `&` or `!` lookaheads need it
+const CHECK: &str = r#"
+    /// Whether `&` or `!` lookahead matches, without consuming input
+    fn check(&mut self, matched: impl FnOnce(&mut Self) -> bool, negated: bool) -> bool {
+        self.lookahead += 1;
+        let matched = matched(self);
+        self.lookahead -= 1;
+        matched != negated
+    }
+"#;
+
+/// The Rust expression matching an ident at `position`
+fn matcher(ident: &Ident) -> String {
+    match ident {
+        Ident::Terminal(name, _) => format!("self.token({}, position)", token_kind(name)),
+        Ident::NonTerminal(name, _) => format!(
+            "self.rule(Rule::{}, position, Self::{})",
+            rust_ident(name),
+            parse_fn(name)
+        ),
+    }
+}
+
+/// Generate a packrat parser, reading `|` as ordered choice and checking `&` and `!` lookaheads
+pub fn generate(grammar: &Grammar, source: &str) -> Result<String, GrammarError> {
+    reject_left_recursion(grammar)?;
+    let mut out = preamble(grammar, source);
+    out.push_str("\nuse std::collections::HashMap;\n");
+    out.push_str(PARSER);
+    let predicates = grammar.parser_rules().any(|(_, alternatives)| {
+        alternatives
+            .iter()
+            .any(|alternative| !alternative.predicates.is_empty())
+    });
+    if predicates {
+        out.push_str(CHECK);
+    }
+
+    for (name, alternatives) in grammar.parser_rules() {
+        write!(
+            out,
+            "\n    fn {}(&mut self, start: usize) -> Match {{\n",
+            parse_fn(name)
+        )
+        .unwrap();
+        let mut fallible = true;
+        for (index, alternative) in alternatives.iter().enumerate() {
+            let node = format!("self.node(Rule::{}, {index}", rust_ident(name));
+            // An empty alternative always matches, and ends the ordered choice
+            if alternative.idents.is_empty() && alternative.predicates.is_empty() {
+                writeln!(out, "        Some((start, {node}, vec![])))").unwrap();
+                fallible = false;
+                break;
+            }
+            writeln!(out, "        'alternative{index}: {{").unwrap();
+            let consumes = !alternative.idents.is_empty();
+            if consumes {
+                out.push_str("            let mut position = start;\n");
+                out.push_str("            let mut children = vec![];\n");
+            } else {
+                out.push_str("            let position = start;\n");
+            }
+            for position in 0..=alternative.idents.len() {
+                for predicate in &alternative.predicates {
+                    if predicate.position == position {
+                        writeln!(
+                            out,
+                            "            if !self.check(|parser| parser.{}.is_some(), {}) {{\n                break 'alternative{index};\n            }}",
+                            matcher(&predicate.ident).trim_start_matches("self."),
+                            predicate.negated
+                        )
+                        .unwrap();
+                    }
+                }
+                if let Some(ident) = alternative.idents.get(position) {
+                    writeln!(
+                        out,
+                        "            let Some((end, child)) = {} else {{\n                break 'alternative{index};\n            }};\n            \
+                         children.push(child);\n            position = end;",
+                        matcher(ident)
+                    )
+                    .unwrap();
+                }
+            }
+            writeln!(
+                out,
+                "            return Some((position, {node}, {})));\n        }}",
+                if consumes { "children" } else { "vec![]" }
+            )
+            .unwrap();
+        }
+        if fallible {
+            out.push_str("        None\n");
+        }
+        out.push_str("    }\n");
+    }
+    out.push_str("}\n");
+
+    let start = grammar.start_rule().map_or(String::new(), parse_fn);
+    write!(
+        out,
+        r#"
+/// Parse the input with ordered choice, reporting the farthest token which failed to match
+pub fn parse(input: &str) -> Result<Tree<'_>, Error<'_>> {{
+    let tokens = tokenize(input)?;
+    let mut parser = Parser {{
+        tokens,
+        nodes: vec![],
+        memo: HashMap::new(),
+        farthest: (0, vec![]),
+        lookahead: 0,
+    }};
+    let end = parser.tokens.len() - 1;
+    match parser.{start}(0) {{
+        Some((position, child)) if position == end => return Ok(parser.tree(child)),
+        Some((position, _)) => parser.fail(TokenKind::Eof, position),
+        None => {{}}
+    }}
+    let (position, expected) = parser.farthest;
+    Err(Error::UnexpectedToken {{
+        found: parser.tokens[position],
+        expected,
+    }})
+}}
+"#
+    )
+    .unwrap();
+    Ok(out)
+}
@@ -0,0 +1,533 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{
+    error::InputError,
+    internal::{
+        analysis::sets::{reject_left_recursion, FirstFollow},
+        ast::{Grammar, Symbol},
+        runtime::{
+            adaptive::atn,
+            allstar::{Atn, Transition},
+            Symbols,
+        },
+        table::ll1::Ll1Table,
+    },
+};
+
+use super::{
+    parse_fn, preamble, rust_ident, token_kind, write_error_kind, write_missing, write_token_kinds,
+    ERROR, RECOVERED,
+};
+
+/// The adaptive LL(*) prediction of the runtime, which only depends on `std`
+const ALLSTAR: &str = include_str!("../runtime/allstar.rs");
+
+const PARSER: &str = r#"
+struct Parser<'input> {
+    tokens: Vec<Token<'input>>,
+    position: usize,
+    errors: Vec<Error<'input>>,
+    /// Errors are reported again only once a token was matched, as in ANTLR
+    recovering: bool,
+    /// Where an error production was last taken, as it is taken at most once per token
+    error_at: Option<usize>,
+    /// Whether rules are being parsed towards the `ERROR` of an error production
+    to_error: bool,
+    /// Where rules last took an alternative matching nothing, and the tokens which would have
+    /// taken their other alternatives
+    others: (usize, Vec<TokenKind>),
+"#;
+
+const HELPERS: &str = r#"
+    fn report(&mut self, error: Error<'input>) {
+        if !self.recovering {
+            self.errors.push(error);
+        }
+        self.recovering = true;
+    }
+
+    /// An error on the current token, where `expected` and the tokens which would have taken
+    /// another alternative of the rules which matched nothing before it were expected
+    fn unexpected(&self, expected: &[TokenKind]) -> Error<'input> {
+        let others = if self.others.0 == self.position { self.others.1.as_slice() } else { &[] };
+        let mut kinds: Vec<TokenKind> = vec![];
+        for &kind in others.iter().chain(expected) {
+            if !kinds.contains(&kind) && Some(kind) != ERROR {
+                kinds.push(kind);
+            }
+        }
+        Error::UnexpectedToken { found: self.tokens[self.position], expected: kinds }
+    }
+
+    /// Skip tokens up to one of `kinds` or the end of input
+    fn skip_to(&mut self, kinds: &[TokenKind]) -> Vec<Tree<'input>> {
+        let start = self.position;
+        while !matches!(self.tokens[self.position].kind, kind if kind == TokenKind::Eof || kinds.contains(&kind)) {
+            self.position += 1;
+        }
+        leaves(&self.tokens[start..self.position])
+    }
+
+    /// Match a token of `kind`, followed by one of `follow`. When the input has another token,
+    /// it is deleted if the next one matches, a token of `kind` is made up if it is one of
+    /// `follow`, or else tokens are skipped up to one of either. `ERROR` matches the input up to
+    /// one of `follow`.
+    fn expect(&mut self, children: &mut Vec<Tree<'input>>, kind: TokenKind, follow: &[TokenKind]) {
+        let token = self.tokens[self.position];
+        if Some(kind) == ERROR {
+            children.push(Tree::Error(self.skip_to(follow)));
+            self.to_error = false;
+            return;
+        }
+        if token.kind == kind {
+            children.push(Tree::Leaf(token));
+            self.position += 1;
+            self.recovering = false;
+            return;
+        }
+        self.report(self.unexpected(&[kind]));
+        if token.kind != TokenKind::Eof && self.tokens[self.position + 1].kind == kind {
+            children.push(Tree::Error(vec![Tree::Leaf(token)]));
+            children.push(Tree::Leaf(self.tokens[self.position + 1]));
+            self.position += 2;
+            return;
+        }
+        if !follow.contains(&token.kind) {
+            let skipped = self.skip_to(&[&[kind], follow].concat());
+            children.push(Tree::Error(skipped));
+            let token = self.tokens[self.position];
+            if token.kind == kind {
+                children.push(Tree::Leaf(token));
+                self.position += 1;
+                return;
+            }
+        }
+        children.push(Tree::Missing { kind, offset: self.tokens[self.position].start });
+    }
+
+    /// Recover from a rule which cannot start, parsed by `rule`. It takes its error production
+    /// if it has one. Otherwise tokens are skipped up to one the rule can start with, to parse it
+    /// after all, or one of `follow` which can come after it, making the rule up of missing
+    /// tokens. When it is an adaptive prediction which failed, at least one token is skipped.
+    fn recover(
+        &mut self,
+        error: Error<'input>,
+        rule: fn(&mut Self) -> Tree<'input>,
+        kind: Rule,
+        expected: &[TokenKind],
+        follow: &[TokenKind],
+    ) -> Tree<'input> {
+        self.report(error);
+        if ERROR.is_some_and(|error| expected.contains(&error)) && self.error_at != Some(self.position) {
+            self.error_at = Some(self.position);
+            self.to_error = true;
+            return rule(self);
+        }
+        let start = self.position;
+        if expected.contains(&self.tokens[start].kind) && self.tokens[start].kind != TokenKind::Eof {
+            self.position += 1;
+        }
+        self.skip_to(&[expected, follow].concat());
+        let skipped = Tree::Error(leaves(&self.tokens[start..self.position]));
+        let mut tree = if expected.contains(&self.tokens[self.position].kind) {
+            rule(self)
+        } else {
+            match missing(kind, self.tokens[self.position].start) {
+                Some(tree) => tree,
+                None => return skipped,
+            }
+        };
+        if let (Tree::Node { children, .. }, true) = (&mut tree, self.position > start) {
+            children.insert(0, skipped);
+        }
+        tree
+    }
+"#;
+
+/// Only rules deciding on one token of lookahead need it
+const LOOKAHEAD: &str = r#"
+    /// The kind of the current token, or `ERROR` while parsing towards an error production
+    fn lookahead(&self) -> TokenKind {
+        match ERROR {
+            Some(error) if self.to_error => error,
+            _ => self.tokens[self.position].kind,
+        }
+    }
+"#;
+
+/// Only decisions between an alternative matching nothing and others need it
+const MATCHING_NOTHING: &str = r#"
+    /// Take an alternative which matches nothing here, when `others` would have taken another
+    fn matching_nothing(&mut self, alternative: usize, others: &[TokenKind]) -> usize {
+        if self.others.0 != self.position {
+            self.others = (self.position, vec![]);
+        }
+        self.others.1.extend_from_slice(others);
+        alternative
+    }
+"#;
+
+const PREDICTION: &str = r#"
+    /// Parse a rule, returning to a state of the ATN afterwards
+    fn call(&mut self, follow: usize, rule: fn(&mut Self) -> Tree<'input>) -> Tree<'input> {
+        self.context.push(follow);
+        let tree = rule(self);
+        self.context.pop();
+        tree
+    }
+
+    /// Choose an alternative of a rule which one token of lookahead cannot tell
+    fn predict(&self, rule: Rule) -> Result<usize, Error<'input>> {
+        let mut predictor = predictor()
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        predictor
+            .predict(rule as usize, &self.kinds, self.position, &self.context)
+            .map_err(|err| Error::UnexpectedToken {
+                found: self.tokens[err.position.min(self.tokens.len() - 1)],
+                expected: err.expected.iter().map(|&kind| TOKEN_KINDS[kind]).collect(),
+            })
+    }
+"#;
+
+/// Generate a recursive descent parser. Decisions which one token of lookahead can tell apart
+/// become a `match`, while the others call the embedded adaptive LL(*) prediction.
+pub fn generate(grammar: &Grammar, source: &str) -> Result<String, InputError> {
+    reject_left_recursion(grammar)?;
+    let symbols = Symbols::new(grammar)?;
+    let table = Ll1Table::new(grammar);
+    // The lookahead terminals of each alternative of the LL(1) decisions
+    let mut decisions: Vec<Option<BTreeMap<usize, Vec<&str>>>> =
+        vec![Some(BTreeMap::new()); symbols.nonterminals.len()];
+    for (&(lhs, terminal), rules) in &table.entries {
+        let decision = &mut decisions[symbols.nonterminal(lhs)];
+        match (rules.as_slice(), decision.as_mut()) {
+            (&[rule], Some(alternatives)) => alternatives
+                .entry(table.rules[rule].alternative)
+                .or_default()
+                .push(terminal),
+            _ => *decision = None,
+        }
+    }
+    let atn = atn(&symbols);
+    let predicting = decisions.iter().any(Option::is_none);
+    let sets = FirstFollow::new(grammar);
+    let kinds = |terminals: &mut dyn Iterator<Item = &str>| {
+        let kinds: Vec<String> = terminals.map(token_kind).collect();
+        format!("&[{}]", kinds.join(", "))
+    };
+
+    let mut out = preamble(grammar, source);
+    if predicting {
+        write!(
+            out,
+            "\n#[allow(dead_code, clippy::all)]\npub mod allstar {{\n{ALLSTAR}}}\n"
+        )
+        .unwrap();
+        write_token_kinds(&mut out, grammar);
+        write_atn(&mut out, &atn);
+    }
+    write_error_kind(&mut out, grammar);
+    write_missing(&mut out, grammar);
+    out.push_str(RECOVERED);
+
+    out.push_str(PARSER);
+    if predicting {
+        out.push_str(
+            "    kinds: Vec<usize>,\n    \
+             /// The ATN states following the rules being parsed, innermost last\n    \
+             context: Vec<usize>,\n",
+        );
+    }
+    // The code of each rule follows the shape of the grammar, which clippy may find fault with
+    out.push_str("}\n\n#[allow(clippy::all)]\nimpl<'input> Parser<'input> {");
+    out.push_str(HELPERS);
+    if predicting {
+        out.push_str(PREDICTION);
+    }
+    // Helpers only some rules call are inserted here once the rules are generated
+    let helpers = out.len();
+    let mut lookahead = false;
+    let mut matching_nothing = false;
+
+    // Rules the start rule never reaches would never be called. Lookaheads are not checked, so
+    // rules only they reach are not called either.
+    let mut reachable: Vec<&str> = symbols.nonterminals.first().copied().into_iter().collect();
+    let mut index = 0;
+    while let Some(&lhs) = reachable.get(index) {
+        for rule in symbols.rules.iter().filter(|rule| rule.lhs == lhs) {
+            for symbol in &rule.rhs {
+                if let Symbol::NonTerminal(name) = symbol {
+                    if !reachable.contains(name) {
+                        reachable.push(name);
+                    }
+                }
+            }
+        }
+        index += 1;
+    }
+    for (nonterminal, name) in symbols.nonterminals.iter().enumerate() {
+        if !reachable.contains(name) {
+            continue;
+        }
+        let alternatives: Vec<_> = symbols
+            .rules
+            .iter()
+            .filter(|rule| rule.lhs == *name)
+            .collect();
+        write!(
+            out,
+            "\n    fn {}(&mut self) -> Tree<'input> {{\n",
+            parse_fn(name)
+        )
+        .unwrap();
+        let follow = kinds(&mut sets.follow[name].iter().copied());
+        let start = atn.rules[nonterminal].0;
+        let children = |alternative: usize, indent: &str| {
+            let Transition::Epsilon(mut state) = atn.transitions[start][alternative] else {
+                unreachable!("start states only have epsilon transitions")
+            };
+            let rhs = &alternatives[alternative].rhs;
+            let mut children = String::new();
+            for (index, symbol) in rhs.iter().enumerate() {
+                match (symbol, atn.transitions[state][0]) {
+                    (Symbol::Terminal(terminal), Transition::Terminal(_, next)) => {
+                        // What may come after the terminal, to tell a missing one from junk
+                        let (mut after, nullable) = sets.first_of(&rhs[index + 1..]);
+                        if nullable {
+                            after.extend(sets.follow[name].iter().copied());
+                        }
+                        writeln!(
+                            children,
+                            "{indent}self.expect(&mut children, {}, {});",
+                            token_kind(terminal),
+                            kinds(&mut after.into_iter())
+                        )
+                        .unwrap();
+                        state = next;
+                    }
+                    (Symbol::NonTerminal(name), Transition::Rule(_, follow)) => {
+                        if predicting {
+                            writeln!(
+                                children,
+                                "{indent}children.push(self.call({follow}, Self::{}));",
+                                parse_fn(name)
+                            )
+                        } else {
+                            writeln!(
+                                children,
+                                "{indent}children.push(self.{}());",
+                                parse_fn(name)
+                            )
+                        }
+                        .unwrap();
+                        state = follow;
+                    }
+                    _ => unreachable!("the ATN follows the symbols of the rule"),
+                }
+            }
+            children
+        };
+        // Tokens on which the rule can start, or end when it may match nothing
+        let expected: Vec<&str> = symbols
+            .terminals
+            .iter()
+            .copied()
+            .filter(|&terminal| table.entries.contains_key(&(*name, terminal)))
+            .collect();
+        let recover = format!(
+            "self.recover({{}}, Self::{}, Rule::{}, {}, {follow})",
+            parse_fn(name),
+            rust_ident(name),
+            kinds(&mut expected.iter().copied())
+        );
+        if alternatives.len() == 1 {
+            out.push_str("        let mut children = vec![];\n");
+            out.push_str(&children(0, "        "));
+            writeln!(
+                out,
+                "        Tree::Node {{ rule: Rule::{}, alternative: 0, children }}\n    }}",
+                rust_ident(name)
+            )
+            .unwrap();
+            continue;
+        }
+        match &decisions[nonterminal] {
+            Some(lookaheads) => {
+                lookahead = true;
+                out.push_str("        let alternative = match self.lookahead() {\n");
+                for (alternative, terminals) in lookaheads {
+                    let pattern: Vec<String> = terminals.iter().map(|t| token_kind(t)).collect();
+                    let pattern = pattern.join(" | ");
+                    if sets.first_of(&alternatives[*alternative].rhs).1 {
+                        matching_nothing = true;
+                        let mut others = expected
+                            .iter()
+                            .copied()
+                            .filter(|terminal| !terminals.contains(terminal));
+                        writeln!(
+                            out,
+                            "            {pattern} => self.matching_nothing({alternative}, {}),",
+                            kinds(&mut others)
+                        )
+                    } else {
+                        writeln!(out, "            {pattern} => {alternative},")
+                    }
+                    .unwrap();
+                }
+                // A match over every token kind needs no fallback arm
+                if expected.len() < symbols.terminals.len() {
+                    let error =
+                        format!("self.unexpected({})", kinds(&mut expected.iter().copied()));
+                    writeln!(
+                        out,
+                        "            _ => return {},",
+                        recover.replace("{}", &error)
+                    )
+                    .unwrap();
+                }
+                out.push_str("        };\n");
+            }
+            None => {
+                // Adaptive prediction does not know of error productions
+                let error_alternative = table
+                    .entries
+                    .get(&(*name, ERROR))
+                    .and_then(|rules| rules.first())
+                    .map(|&rule| table.rules[rule].alternative);
+                out.push_str("        let alternative = ");
+                if let Some(alternative) = error_alternative {
+                    write!(
+                        out,
+                        "if self.to_error {{\n            {alternative}\n        }} else {{\n    "
+                    )
+                    .unwrap();
+                }
+                writeln!(
+                    out,
+                    "        match self.predict(Rule::{}) {{\n            Ok(alternative) => alternative,\n            Err(err) => return {},\n        }}",
+                    rust_ident(name),
+                    recover.replace("{}", "err")
+                )
+                .unwrap();
+                if error_alternative.is_some() {
+                    out.push_str("        }");
+                }
+                out.push_str(";\n");
+            }
+        }
+        out.push_str("        let mut children = vec![];\n        match alternative {\n");
+        for alternative in 0..alternatives.len() {
+            let pattern = if alternative + 1 == alternatives.len() {
+                "_".to_string()
+            } else {
+                alternative.to_string()
+            };
+            writeln!(
+                out,
+                "            {pattern} => {{\n{}            }}",
+                children(alternative, "                ")
+            )
+            .unwrap();
+        }
+        writeln!(
+            out,
+            "        }}\n        Tree::Node {{ rule: Rule::{}, alternative, children }}\n    }}",
+            rust_ident(name)
+        )
+        .unwrap();
+    }
+    if matching_nothing {
+        out.insert_str(helpers, MATCHING_NOTHING);
+    }
+    if lookahead {
+        out.insert_str(helpers, LOOKAHEAD);
+    }
+    out.push_str("}\n");
+
+    let start = symbols
+        .nonterminals
+        .first()
+        .map_or(String::new(), |start| parse_fn(start));
+    write!(
+        out,
+        r#"
+/// Parse the input by recursive descent, recovering from syntax errors as ANTLR does: by deleting
+/// or making up single tokens, taking error productions, and skipping input up to what a rule can
+/// start with or what can follow it
+pub fn parse_recovering(input: &str) -> Recovered<'_> {{
+    let (tokens, errors) = tokenize_recovering(input);
+    let mut parser = Parser {{
+{}        tokens,
+        position: 0,
+        errors,
+        recovering: false,
+        error_at: None,
+        to_error: false,
+        others: (0, vec![]),
+    }};
+    let mut tree = parser.{start}();
+    // Input after the start rule
+    let rest = parser.skip_to(&[]);
+    if !rest.is_empty() {{
+        parser.report(Error::UnexpectedToken {{
+            found: parser.tokens[parser.position - rest.len()],
+            expected: vec![TokenKind::Eof],
+        }});
+        match &mut tree {{
+            Tree::Node {{ children, .. }} | Tree::Error(children) => children.push(Tree::Error(rest)),
+            _ => tree = Tree::Error(vec![tree, Tree::Error(rest)]),
+        }}
+    }}
+    Recovered {{ tree, errors: parser.errors }}
+}}
+"#,
+        if predicting {
+            "        kinds: tokens.iter().map(|token| token.kind as usize).collect(),\n        \
+             context: vec![],\n"
+        } else {
+            ""
+        }
+    )
+    .unwrap();
+    Ok(out)
+}
+
+/// Emit `predictor`, which shares the DFA cache of the ATN between parses
+fn write_atn(out: &mut String, atn: &Atn) {
+    out.push_str(
+        "
+/// Adaptive prediction over the ATN of the grammar, where terminals are numbered by
+/// `TokenKind as usize` and nonterminals by `Rule as usize`
+fn predictor() -> &'static std::sync::Mutex<allstar::Predictor> {
+    use allstar::Transition::{Epsilon as E, Rule as R, Terminal as T};
+
+    static PREDICTOR: OnceLock<std::sync::Mutex<allstar::Predictor>> = OnceLock::new();
+    PREDICTOR.get_or_init(|| {
+        let transitions = vec![\n",
+    );
+    for transitions in &atn.transitions {
+        let transitions: Vec<String> = transitions
+            .iter()
+            .map(|transition| match *transition {
+                Transition::Epsilon(state) => format!("E({state})"),
+                Transition::Terminal(terminal, state) => format!("T({terminal}, {state})"),
+                Transition::Rule(nonterminal, state) => format!("R({nonterminal}, {state})"),
+            })
+            .collect();
+        writeln!(out, "            vec![{}],", transitions.join(", ")).unwrap();
+    }
+    writeln!(
+        out,
+        "        ];
+        std::sync::Mutex::new(allstar::Predictor::new(allstar::Atn {{
+            transitions,
+            rules: vec!{:?},
+            end: {},
+        }}))
+    }})
+}}",
+        atn.rules, atn.end
+    )
+    .unwrap();
+}
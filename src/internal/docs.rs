@@ -0,0 +1,260 @@
+use std::fmt::Write;
+
+use crate::{
+    error::NameErrors,
+    internal::{
+        analysis::{names::check_names, sets::FirstFollow},
+        ast::{Grammar, Production},
+    },
+    span::SourceMap,
+};
+
+/// What the reference of a rule shows
+struct Entry<'a> {
+    name: &'a str,
+    doc: Option<&'a str>,
+    /// The rule as written, up to its `;`
+    definition: &'a str,
+    used_by: Vec<&'a str>,
+    /// Whether a parser rule is nullable, and its FIRST set
+    sets: Option<(bool, Vec<&'a str>)>,
+}
+
+/// The entries of the rules, in grammar order. Every rule must be defined once, so that each
+/// has one anchor and every reference a rule to link to.
+fn entries<'a>(grammar: &'a Grammar, files: &'a SourceMap) -> Result<Vec<Entry<'a>>, NameErrors> {
+    let diagnostics = check_names(grammar);
+    if !diagnostics.is_empty() {
+        return Err(NameErrors { diagnostics });
+    }
+    let sets = FirstFollow::new(grammar);
+    let mut used_by = grammar.used_by();
+    let entries = grammar
+        .rules
+        .iter()
+        .map(|rule| {
+            let name = rule.name();
+            let span = rule.span();
+            // The span of a rule ends at its last element, before its `;`
+            let source = files.source(span.file);
+            let end = source[span.end..]
+                .find(';')
+                .map_or(source.len(), |semicolon| span.end + semicolon + 1);
+            let sets = match rule {
+                Production::Parser(..) => {
+                    let first = sets.first.get(name).into_iter().flatten().copied();
+                    Some((sets.nullable.contains(name), first.collect()))
+                }
+                Production::Lexer(..) => None,
+            };
+            Entry {
+                name,
+                doc: grammar.doc(name),
+                definition: &source[span.start..end],
+                used_by: used_by.remove(name).unwrap_or_default(),
+                sets,
+            }
+        })
+        .collect();
+    Ok(entries)
+}
+
+/// Whether a rule of the grammar has this name, and so an entry to link to
+fn defined(grammar: &Grammar, name: &str) -> bool {
+    grammar.rules.iter().any(|rule| rule.name() == name)
+}
+
+fn yes_no(yes: bool) -> &'static str {
+    if yes {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+/// The reference of a grammar in Markdown: each rule under a heading anchored at its name, with
+/// its doc comment as Markdown, its definition, the rules using it, and for a parser rule
+/// whether it is nullable and its FIRST set. Rules link to one another by name, and `files`
+/// has the sources the spans of the grammar point into. Grammars defining a rule twice or
+/// referring to an undefined one are rejected.
+pub fn markdown(grammar: &Grammar, files: &SourceMap, title: &str) -> Result<String, NameErrors> {
+    let link = |name: &str| {
+        if defined(grammar, name) {
+            format!("[`{name}`](#{name})")
+        } else {
+            format!("`{name}`")
+        }
+    };
+    let list = |names: &[&str]| match names {
+        [] => "none".to_string(),
+        names => names
+            .iter()
+            .map(|name| link(name))
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+    let entries = entries(grammar, files)?;
+    let mut out = format!("# {title}\n\n");
+    let parser: Vec<&str> = grammar.parser_rules().map(|(name, _)| name).collect();
+    let lexer: Vec<&str> = grammar.lexer_rules().map(|(name, _)| name).collect();
+    writeln!(out, "Parser rules: {}\n", list(&parser)).unwrap();
+    writeln!(out, "Lexer rules: {}", list(&lexer)).unwrap();
+    for entry in &entries {
+        let name = entry.name;
+        write!(out, "\n## <a id=\"{name}\"></a>`{name}`\n\n").unwrap();
+        if let Some(doc) = entry.doc {
+            write!(out, "{doc}\n\n").unwrap();
+        }
+        write!(out, "```antlr\n{}\n```\n\n", entry.definition).unwrap();
+        if let Some((nullable, first)) = &entry.sets {
+            writeln!(out, "- Nullable: {}", yes_no(*nullable)).unwrap();
+            writeln!(out, "- FIRST: {}", list(first)).unwrap();
+        }
+        writeln!(out, "- Used by: {}", list(&entry.used_by)).unwrap();
+    }
+    Ok(out)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The reference of a grammar on a self-contained HTML page, as [`markdown`] has it. Doc
+/// comments are taken as plain text, in paragraphs separated by blank lines.
+pub fn html(grammar: &Grammar, files: &SourceMap, title: &str) -> Result<String, NameErrors> {
+    let link = |name: &str| {
+        if defined(grammar, name) {
+            format!(r##"<a href="#{name}"><code>{name}</code></a>"##)
+        } else {
+            format!("<code>{}</code>", escape(name))
+        }
+    };
+    let list = |names: &[&str]| match names {
+        [] => "none".to_string(),
+        names => names
+            .iter()
+            .map(|name| link(name))
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+    let entries = entries(grammar, files)?;
+    let title = escape(title);
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>\nbody {{ font-family: sans-serif; margin: 2em; max-width: 60em; }}\n\
+         pre {{ background: #f6f6f6; padding: 0.5em; }}\n</style>\n</head>\n<body>\n<h1>{title}</h1>\n"
+    );
+    let parser: Vec<&str> = grammar.parser_rules().map(|(name, _)| name).collect();
+    let lexer: Vec<&str> = grammar.lexer_rules().map(|(name, _)| name).collect();
+    writeln!(out, "<p>Parser rules: {}</p>", list(&parser)).unwrap();
+    writeln!(out, "<p>Lexer rules: {}</p>", list(&lexer)).unwrap();
+    for entry in &entries {
+        let name = entry.name;
+        writeln!(out, r#"<section id="{name}">"#).unwrap();
+        writeln!(out, "<h2><code>{name}</code></h2>").unwrap();
+        for paragraph in entry.doc.into_iter().flat_map(|doc| doc.split("\n\n")) {
+            writeln!(out, "<p>{}</p>", escape(paragraph)).unwrap();
+        }
+        writeln!(out, "<pre><code>{}</code></pre>", escape(entry.definition)).unwrap();
+        out.push_str("<ul>\n");
+        if let Some((nullable, first)) = &entry.sets {
+            writeln!(out, "<li>Nullable: {}</li>", yes_no(*nullable)).unwrap();
+            writeln!(out, "<li>FIRST: {}</li>", list(first)).unwrap();
+        }
+        writeln!(out, "<li>Used by: {}</li>", list(&entry.used_by)).unwrap();
+        out.push_str("</ul>\n</section>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_snapshot;
+
+    use super::*;
+
+    const GRAMMAR: &str = "\
+/** A sum of terms, as `1 + 2`. */
+sum: sum PLUS term | term;
+/**
+ * A number, or a sum in parentheses.
+ *
+ * Terms may be empty.
+ */
+term: NUM | LPAREN sum RPAREN | ;
+PLUS: \\+;
+NUM: [0-9]+;
+";
+
+    fn files() -> SourceMap {
+        let mut files = SourceMap::default();
+        files.add("sum.g4".into(), GRAMMAR.into());
+        files
+    }
+
+    #[test]
+    fn markdown_reference() {
+        let grammar = Grammar::parse(GRAMMAR).unwrap();
+        assert_snapshot!(markdown(&grammar, &files(), "Sums").unwrap());
+    }
+
+    #[test]
+    fn html_reference() {
+        let grammar = Grammar::parse(GRAMMAR).unwrap();
+        assert_snapshot!(html(&grammar, &files(), "Sums & more").unwrap());
+    }
+
+    /// The reference of `src` in both formats, one after the other
+    fn references(src: &str) -> Result<String, NameErrors> {
+        let grammar = Grammar::parse(src).unwrap();
+        let mut files = SourceMap::default();
+        files.add("g.g4".into(), src.into());
+        Ok(markdown(&grammar, &files, "g")? + &html(&grammar, &files, "g")?)
+    }
+
+    macro_rules! test_docs {
+        ($name:ident, $src:expr => [$($contains:expr),* $(,)?]) => {
+            #[test]
+            fn $name() {
+                let reference = references($src).unwrap();
+                $(assert!(reference.contains($contains), "no {:?} in\n{reference}", $contains);)*
+            }
+        };
+    }
+
+    test_docs!(docs_cross_links, "s: t A; t: A; A: a;" => [
+        "- FIRST: [`A`](#A)",
+        "```antlr\ns: t A;\n```",
+        r##"<li>FIRST: <a href="#A"><code>A</code></a></li>"##,
+    ]);
+    test_docs!(docs_token_without_lexer_rule, "s: A ERROR; A: a;" => [
+        "Lexer rules: [`A`](#A)",
+        "- Used by: none",
+        "<p>Lexer rules: <a href=\"#A\"><code>A</code></a></p>",
+    ]);
+    test_docs!(docs_used_by, "s: t u; t: u A; u: A; A: a;" => [
+        "## <a id=\"u\"></a>`u`\n\n```antlr\nu: A;\n```\n\n- Nullable: no\n- FIRST: [`A`](#A)\n- Used by: [`s`](#s), [`t`](#t)\n",
+        "<li>Used by: <a href=\"#s\"><code>s</code></a>, <a href=\"#t\"><code>t</code></a></li>",
+    ]);
+    test_docs!(docs_nullable, "s: o A; o: A |; A: a;" => [
+        "## <a id=\"s\"></a>`s`\n\n```antlr\ns: o A;\n```\n\n- Nullable: no\n",
+        "## <a id=\"o\"></a>`o`\n\n```antlr\no: A |;\n```\n\n- Nullable: yes\n",
+        "<li>Nullable: yes</li>",
+    ]);
+    test_docs!(docs_html_escapes, "/** Less than: `<`. */ s: LT; LT: <;" => [
+        "<p>Less than: `&lt;`.</p>",
+        "<pre><code>LT: &lt;;</code></pre>",
+    ]);
+
+    #[test]
+    fn docs_reject_undefined_and_duplicate_rules() {
+        let err = references("e: a | N; N: n;").unwrap_err();
+        assert_eq!(err.to_string(), "undefined rule `a`");
+        let err = references("s: A; s: B; A: a; B: b;").unwrap_err();
+        assert_eq!(err.to_string(), "the rule `s` is defined more than once");
+    }
+}
@@ -0,0 +1,455 @@
+//! The canonical layout of grammar files, which keeps their comments.
+//!
+//! Each statement goes on a line of its own, with a blank line between statements where the source
+//! had any. A parser rule stays on one line when it fits in [`MAX_WIDTH`], and otherwise puts each
+//! alternative on a line of its own, its `|` under the `:`. Alternatives which still do not fit
+//! wrap, indented past the `|`.
+//!
+//! Comments stay by the tokens they were written by: one on a line of its own before the next
+//! token, one at the end of a line at the end of the line, and one between tokens between them.
+//! Comments ending a rule before its `;` move after it.
+
+use crate::{
+    error::SyntaxErrors,
+    internal::{
+        ast::Grammar,
+        lexer::{
+            lexer::tokenize_lossless,
+            token::{Token, TokenKind, Trivia, TriviaKind},
+        },
+    },
+    span::Span,
+};
+
+/// The width lines are kept to where they can be, in characters
+pub const MAX_WIDTH: usize = 100;
+
+/// Where a comment is, relative to the tokens around it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placement {
+    /// Between tokens on a line
+    Inline,
+    /// At the end of a line with tokens
+    Trailing,
+    /// On lines of its own
+    OwnLine,
+}
+
+/// The source as tokens and comments, with where blank lines were
+#[derive(Debug)]
+enum Piece<'a> {
+    Token(&'a TokenKind, &'a str),
+    Comment(&'a str, Placement),
+    Blank,
+}
+
+fn pieces<'a>(source: &'a str, tokens: &'a [(Vec<Trivia>, Token)]) -> Vec<Piece<'a>> {
+    let text = |span: Span| &source[span.start..span.end];
+    let mut pieces = vec![];
+    // Newlines since the last token or comment, where the start of the source counts as one
+    let mut newlines = 1;
+    let mut own_line = false;
+    for (trivia, token) in tokens {
+        for (index, trivium) in trivia.iter().enumerate() {
+            if trivium.kind == TriviaKind::Whitespace {
+                newlines += text(trivium.span).matches('\n').count();
+                continue;
+            }
+            let ends_line = match trivia.get(index + 1) {
+                Some(next) => next.kind == TriviaKind::Whitespace && text(next.span).contains('\n'),
+                None => token.kind == TokenKind::EOF,
+            };
+            // A comment after one on a line of its own is on a line of its own as well
+            own_line = newlines > 0 || own_line;
+            let placement = if own_line {
+                Placement::OwnLine
+            } else if ends_line {
+                Placement::Trailing
+            } else {
+                Placement::Inline
+            };
+            if newlines > 1 {
+                pieces.push(Piece::Blank);
+            }
+            pieces.push(Piece::Comment(text(trivium.span).trim_end(), placement));
+            newlines = 0;
+        }
+        if token.kind == TokenKind::EOF {
+            break;
+        }
+        if newlines > 1 {
+            pieces.push(Piece::Blank);
+        }
+        pieces.push(Piece::Token(&token.kind, text(token.span)));
+        newlines = 0;
+        own_line = false;
+    }
+    pieces
+}
+
+#[derive(Debug)]
+enum Element<'a> {
+    Word(String),
+    Comment(&'a str, Placement),
+}
+
+#[derive(Debug, Default)]
+struct Alternative<'a> {
+    /// Comments on lines of their own before the `|`
+    leading: Vec<&'a str>,
+    elements: Vec<Element<'a>>,
+}
+
+impl<'a> Alternative<'a> {
+    /// Split off the comments after the last word, from the first on a line of its own
+    fn split_own_lines(&mut self) -> Vec<&'a str> {
+        let last = self.elements.iter().rposition(|element| {
+            matches!(
+                element,
+                Element::Word(_) | Element::Comment(_, Placement::Inline)
+            )
+        });
+        let first = last.map_or(0, |last| last + 1);
+        let split = self.elements[first..]
+            .iter()
+            .position(|element| matches!(element, Element::Comment(_, Placement::OwnLine)))
+            .map_or(self.elements.len(), |position| first + position);
+        self.elements
+            .split_off(split)
+            .into_iter()
+            .map(|element| match element {
+                Element::Comment(comment, _) => comment,
+                Element::Word(_) => unreachable!("only comments follow the last word"),
+            })
+            .collect()
+    }
+}
+
+/// The width of the last line of `text`
+fn width(text: &str) -> usize {
+    text.rsplit('\n').next().unwrap_or_default().chars().count()
+}
+
+/// Lines being laid out, the last of them still open
+struct Layout<'a> {
+    out: &'a mut String,
+    line: String,
+    /// Whether the line holds a word of the current alternative, after which it may wrap
+    filled: bool,
+    /// Whether a comment ends the line
+    ended: bool,
+}
+
+impl Layout<'_> {
+    fn break_line(&mut self) {
+        self.out.push_str(self.line.trim_end());
+        self.out.push('\n');
+        self.line.clear();
+        self.ended = false;
+    }
+
+    /// Add a word, followed by `end` on the line
+    fn word(&mut self, word: &str, end: &str, indent: usize) {
+        let first_line = word.split('\n').next().unwrap_or_default();
+        let fits = width(&self.line) + 1 + width(first_line) + end.len() <= MAX_WIDTH;
+        if self.ended || (self.filled && !fits) {
+            self.break_line();
+        }
+        if self.line.is_empty() {
+            self.line = " ".repeat(indent);
+        } else {
+            self.line.push(' ');
+        }
+        self.line.push_str(word);
+        self.filled = true;
+    }
+
+    fn comment(&mut self, comment: &str, placement: Placement, indent: usize) {
+        match placement {
+            // Never at the start of a line, where it would be on a line of its own the next time
+            Placement::Inline if !self.ended => {
+                self.line.push(' ');
+                self.line.push_str(comment);
+            }
+            Placement::Trailing if !self.ended => {
+                self.line.push(' ');
+                self.line.push_str(comment);
+                self.ended = true;
+            }
+            _ => {
+                if !self.line.trim().is_empty() {
+                    self.break_line();
+                }
+                self.line = " ".repeat(indent) + comment;
+                self.ended = true;
+            }
+        }
+    }
+}
+
+/// A statement on one line up to its `;`, if it has no comments which end lines and fits with
+/// the `end` it has
+fn one_line(head: &str, rule: bool, alternatives: &[Alternative], end: &str) -> Option<String> {
+    let mut line = head.to_string();
+    if rule {
+        line.push(':');
+    }
+    for (index, alternative) in alternatives.iter().enumerate() {
+        if !alternative.leading.is_empty() {
+            return None;
+        }
+        if index > 0 {
+            line.push_str(" |");
+        }
+        for element in &alternative.elements {
+            let text = match element {
+                Element::Word(word) => word,
+                Element::Comment(comment, Placement::Inline) => *comment,
+                Element::Comment(..) => return None,
+            };
+            line.push(' ');
+            line.push_str(text);
+        }
+    }
+    (width(&line) + end.len() <= MAX_WIDTH).then_some(line)
+}
+
+/// Lay out a statement: a directive, or a rule with its name and `:` first. `pieces` run from its
+/// first token to its `;`, and any comments after that on the same line.
+fn statement(out: &mut String, pieces: &[Piece]) {
+    let Some((Piece::Token(kind, head), pieces)) = pieces.split_first() else {
+        unreachable!("statements start with a token");
+    };
+    let rule = !matches!(kind, TokenKind::Directive(_));
+    let mut alternatives = vec![Alternative::default()];
+    let mut after = vec![];
+    let mut ended = false;
+    let mut glue = false;
+    for piece in pieces {
+        let alternative = alternatives.last_mut().unwrap();
+        match piece {
+            Piece::Blank | Piece::Token(TokenKind::Colon, _) => {}
+            Piece::Comment(comment, _) if ended => after.push(*comment),
+            Piece::Token(TokenKind::Semicolon, _) => ended = true,
+            Piece::Token(TokenKind::Bar, _) => {
+                let leading = alternative.split_own_lines();
+                alternatives.push(Alternative {
+                    leading,
+                    elements: vec![],
+                });
+            }
+            Piece::Token(kind, text) => {
+                match alternative.elements.last_mut() {
+                    Some(Element::Word(word)) if glue => word.push_str(text),
+                    _ => alternative.elements.push(Element::Word(text.to_string())),
+                }
+                glue = matches!(kind, TokenKind::And | TokenKind::Not);
+            }
+            Piece::Comment(comment, placement) => {
+                // The `:` between comments is not laid out between them, so a comment after one
+                // ending its line goes on a line of its own
+                let placement = match alternative.elements.last() {
+                    Some(Element::Comment(_, Placement::Trailing | Placement::OwnLine)) => {
+                        Placement::OwnLine
+                    }
+                    _ => *placement,
+                };
+                alternative
+                    .elements
+                    .push(Element::Comment(comment, placement));
+                glue = false;
+            }
+        }
+    }
+    // Comments ending the rule go after its `;`
+    let last = alternatives.last_mut().unwrap();
+    let mut own_lines = last.split_own_lines();
+    // At most one comment ends the line before the `;`, and then the ones after it have to go on
+    // lines of their own
+    let mut trailing = vec![];
+    if let Some(Element::Comment(comment, Placement::Trailing)) = last.elements.last() {
+        trailing.push(*comment);
+        last.elements.pop();
+        own_lines.append(&mut after);
+    } else {
+        trailing = after;
+    }
+    let end = if rule && last.elements.is_empty() {
+        " ;"
+    } else {
+        ";"
+    };
+
+    let mut layout = Layout {
+        out,
+        line: String::new(),
+        filled: false,
+        ended: false,
+    };
+    match one_line(head, rule, &alternatives, end) {
+        Some(line) => layout.line = line,
+        None => {
+            let indent = if rule { width(head) } else { 0 };
+            let continued = indent + 4;
+            layout.line = head.to_string();
+            if rule {
+                layout.line.push(':');
+            }
+            let last = alternatives.len() - 1;
+            for (index, alternative) in alternatives.iter().enumerate() {
+                if index > 0 {
+                    for comment in &alternative.leading {
+                        layout.comment(comment, Placement::OwnLine, indent);
+                    }
+                    layout.break_line();
+                    layout.line = " ".repeat(indent) + "|";
+                }
+                layout.filled = false;
+                for (position, element) in alternative.elements.iter().enumerate() {
+                    // The `;` goes right after the last element
+                    let end = if index == last && position == alternative.elements.len() - 1 {
+                        end
+                    } else {
+                        ""
+                    };
+                    match element {
+                        Element::Word(word) => layout.word(word, end, continued),
+                        Element::Comment(comment, placement) => {
+                            layout.comment(comment, *placement, continued);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    layout.line.push_str(end);
+    for comment in trailing {
+        layout.line.push(' ');
+        layout.line.push_str(comment);
+    }
+    for comment in own_lines {
+        layout.comment(comment, Placement::OwnLine, 0);
+    }
+    layout.break_line();
+}
+
+/// `source` laid out canonically, or its syntax errors if it has any
+pub fn format(source: &str) -> Result<String, SyntaxErrors> {
+    Grammar::parse(source)?;
+    let tokens = tokenize_lossless(source).expect("a grammar without syntax errors tokenizes");
+    let pieces = pieces(source, &tokens);
+    let mut out = String::new();
+    let mut index = 0;
+    while index < pieces.len() {
+        match pieces[index] {
+            Piece::Blank if !out.is_empty() => out.push('\n'),
+            Piece::Blank => {}
+            Piece::Comment(comment, _) => {
+                out.push_str(comment);
+                out.push('\n');
+            }
+            Piece::Token(..) => {
+                let mut end = index
+                    + pieces[index..]
+                        .iter()
+                        .position(|piece| matches!(piece, Piece::Token(TokenKind::Semicolon, _)))
+                        .expect("statements end with `;`");
+                while let Some(Piece::Comment(_, Placement::Inline | Placement::Trailing)) =
+                    pieces.get(end + 1)
+                {
+                    end += 1;
+                }
+                statement(&mut out, &pieces[index..=end]);
+                index = end;
+            }
+        }
+        index += 1;
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::lexer::lexer::tokenize;
+
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        tokenize(source)
+            .unwrap()
+            .into_iter()
+            .map(|token| token.kind)
+            .collect()
+    }
+
+    /// Formatting gives `$expected`, which is formatted already and has the same tokens
+    macro_rules! test_format {
+        ($name:ident, $source:expr => $expected:expr) => {
+            #[test]
+            fn $name() {
+                let formatted = format($source).unwrap();
+                assert_eq!(formatted, $expected);
+                assert_eq!(format(&formatted).unwrap(), formatted);
+                assert_eq!(kinds(&formatted), kinds($source));
+            }
+        };
+    }
+
+    test_format!(
+        format_spacing,
+        "\n\ns:A  B|c ;\nA :a;%left  PLUS;\n\n\n\nc: &A !B ; B: b;" =>
+            "s: A B | c;\nA: a;\n%left PLUS;\n\nc: &A !B;\nB: b;\n"
+    );
+
+    test_format!(
+        format_empty_alternatives,
+        "s: | A |;\nt:;\nA: a;" => "s: | A | ;\nt: ;\nA: a;\n"
+    );
+
+    test_format!(
+        format_aligns_bars,
+        &format!("expr: expr PLUS term {0} | term {0};\n", "%label Long ".repeat(6)) =>
+            format!(
+                "expr: expr PLUS term {0}\n    | term {0};\n",
+                "%label Long ".repeat(6).trim_end()
+            )
+    );
+
+    test_format!(
+        format_wraps_alternatives,
+        &format!("s: {};\n", "A ".repeat(60)) =>
+            format!("s: {}\n     {};\n", ["A"; 49].join(" "), ["A"; 11].join(" "))
+    );
+
+    test_format!(
+        format_wraps_before_semicolon,
+        &format!("s: {}AA;\n", "A ".repeat(48)) =>
+            format!("s: {}\n     AA;\n", ["A"; 48].join(" "))
+    );
+
+    test_format!(
+        format_keeps_comments,
+        "// grammar\n\n/* start */ s: A /* a */ B // b\n  // c\n  | C;  // s\n// end\n" =>
+            "// grammar\n\n/* start */\ns: A /* a */ B // b\n // c\n | C; // s\n// end\n"
+    );
+
+    test_format!(
+        format_moves_comments_before_semicolon,
+        "s: A // a\n  // b\n;\nA: a;" => "s: A; // a\n// b\nA: a;\n"
+    );
+
+    test_format!(
+        format_directive,
+        "%allow\n  // why\n  unused_token;" => "%allow\n    // why\n    unused_token;\n"
+    );
+
+    #[test]
+    fn format_syntax_error() {
+        assert!(format("s: A;\nA a;").is_err());
+    }
+
+    #[test]
+    fn format_examples() {
+        let source = include_str!("../../examples/rantlr.g4");
+        assert_eq!(format(source).unwrap(), source);
+    }
+}
@@ -0,0 +1,98 @@
+use std::fmt::Write;
+
+use crate::internal::{
+    ast::{Grammar, Ident},
+    table::lr::Automaton,
+};
+
+/// A string in the DOT language
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The rules of a grammar as a Graphviz graph, with an edge from each parser rule to every rule
+/// and token it uses. Parser rules are ellipses and tokens boxes; lookaheads are dashed edges.
+pub fn rules(grammar: &Grammar) -> String {
+    let mut out = String::from("digraph rules {\n");
+    let mut tokens: Vec<&str> = vec![];
+    let mut edges: Vec<(&str, &str, bool)> = vec![];
+    for (name, alternatives) in grammar.parser_rules() {
+        writeln!(out, "  {};", quote(name)).unwrap();
+        for alternative in alternatives {
+            let used = alternative.idents.iter().map(|ident| (ident, false));
+            let lookaheads = alternative
+                .predicates
+                .iter()
+                .map(|predicate| (&predicate.ident, true));
+            for (ident, lookahead) in used.chain(lookaheads) {
+                if matches!(ident, Ident::Terminal(..)) && !tokens.contains(&ident.name()) {
+                    tokens.push(ident.name());
+                }
+                if !edges.contains(&(name, ident.name(), lookahead)) {
+                    edges.push((name, ident.name(), lookahead));
+                }
+            }
+        }
+    }
+    for token in tokens {
+        writeln!(out, "  {} [shape=box];", quote(token)).unwrap();
+    }
+    for (from, to, lookahead) in edges {
+        let style = if lookahead { " [style=dashed]" } else { "" };
+        writeln!(out, "  {} -> {}{style};", quote(from), quote(to)).unwrap();
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// The LR(0) automaton of a grammar as a Graphviz graph: its states with their items, and its
+/// transitions labelled with the symbol they read
+pub fn automaton(grammar: &Grammar) -> String {
+    let automaton = Automaton::new(grammar);
+    let mut out = String::from("digraph automaton {\n  node [shape=box, fontname=monospace];\n");
+    for (index, state) in automaton.states.iter().enumerate() {
+        let items: String = state
+            .items
+            .iter()
+            .map(|&item| format!("{}\\l", automaton.display_item(item).replace('"', "\\\"")))
+            .collect();
+        writeln!(out, "  {index} [label=\"{index}\\n{items}\"];").unwrap();
+    }
+    for (index, state) in automaton.states.iter().enumerate() {
+        for (symbol, target) in &state.transitions {
+            writeln!(
+                out,
+                "  {index} -> {target} [label={}];",
+                quote(symbol.name())
+            )
+            .unwrap();
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_graph() {
+        let grammar = Grammar::parse("s: t A | !A t; t: t B | ; A: a; B: b;").unwrap();
+        assert_eq!(
+            rules(&grammar),
+            "digraph rules {\n  \"s\";\n  \"t\";\n  \"A\" [shape=box];\n  \"B\" [shape=box];\n  \
+             \"s\" -> \"t\";\n  \"s\" -> \"A\";\n  \"s\" -> \"A\" [style=dashed];\n  \
+             \"t\" -> \"t\";\n  \"t\" -> \"B\";\n}\n"
+        );
+    }
+
+    #[test]
+    fn automaton_graph() {
+        let grammar = Grammar::parse("s: A s | B; A: a; B: b;").unwrap();
+        let graph = automaton(&grammar);
+        assert!(graph.starts_with("digraph automaton {\n"));
+        assert!(graph.contains("  0 [label=\"0\\n$accept -> • s\\ls -> • A s\\ls -> • B\\l\"];\n"));
+        assert!(graph.contains("  0 -> 1 [label=\"A\"];\n"));
+    }
+}
@@ -1,13 +1,12 @@
-use crate::{error::LexerError, unexpected_char};
+use std::collections::HashMap;
 
-use super::token::{Token, TokenKind};
-use anyhow::Result;
+use crate::{
+    error::LexerError,
+    span::{FileId, Span},
+    unexpected_char,
+};
 
-impl Token {
-    fn new(kind: TokenKind, row: usize, col: usize) -> Self {
-        Token { kind, row, col }
-    }
-}
+use super::token::{Token, TokenKind, Trivia, TriviaKind};
 
 fn read_identifier(data: &str) -> (&str, usize) {
     let mut has_seen_tick = false;
@@ -27,15 +26,19 @@ fn read_identifier(data: &str) -> (&str, usize) {
     (matched, bytes_read)
 }
 
-#[allow(clippy::unnecessary_wraps)]
-fn read_lexer_pattern(data: &str) -> Result<(&str, usize)> {
+/// `span` is the empty span where the pattern starts, to locate errors
+fn read_lexer_pattern(data: &str, span: Span) -> Result<(&str, usize), LexerError> {
     let mut err = None;
     let bytes_read = data
-        .chars()
+        .char_indices()
         // Ensure the pattern is valid. If it's not, return an error
-        .scan(&mut err, |err, c| {
+        .scan(&mut err, |err, (offset, c)| {
             if c.is_whitespace() {
-                **err = Some(unexpected_char!(c));
+                let start = span.start + offset;
+                **err = Some(unexpected_char!(
+                    c,
+                    Span::new(span.file, start..start + c.len_utf8())
+                ));
                 None
             } else {
                 Some(c)
@@ -52,19 +55,15 @@ fn read_lexer_pattern(data: &str) -> Result<(&str, usize)> {
 
 struct Tokenizer<'a> {
     state: TokenizerState,
-    row: usize,
-    col: usize,
+    file: FileId,
+    /// Byte offset of the remaining text in the source
+    offset: usize,
     remaining_text: &'a str,
 }
 
 impl<'a> From<&'a str> for Tokenizer<'a> {
     fn from(value: &'a str) -> Self {
-        Tokenizer {
-            state: TokenizerState::Initial,
-            row: 0,
-            col: 0,
-            remaining_text: value,
-        }
+        Tokenizer::new(value, FileId::default())
     }
 }
 
@@ -83,61 +82,88 @@ enum TokenizerState {
 }
 
 impl<'a> Tokenizer<'a> {
-    fn skip(&mut self) {
-        loop {
-            let initial_length = self.remaining_text.len();
-            // Skip whitespace
-            self.remaining_text
-                .chars()
-                .take_while(|c| c.is_whitespace())
-                .for_each(|c| self.advance_char(c));
-            // Skip comments
-            let pairs = [("//", "\n"), ("/*", "*/")];
-            for (start, end) in pairs {
-                let mut offset: usize = 0;
-                if self.remaining_text.starts_with(start) {
-                    self.remaining_text
-                        .chars()
-                        .take_while(|&c| {
-                            if c == end.chars().nth(offset).unwrap() {
-                                offset += 1;
-                                if offset == end.len() {
-                                    return false;
-                                }
-                            } else {
-                                offset = 0;
-                            };
-                            true
-                        })
-                        .for_each(|c| self.advance_char(c));
-                }
-            }
+    fn new(text: &'a str, file: FileId) -> Self {
+        Tokenizer {
+            state: TokenizerState::Initial,
+            file,
+            offset: 0,
+            remaining_text: text,
+        }
+    }
 
-            if initial_length == self.remaining_text.len() {
-                break;
-            }
+    /// The kind and length of the trivia the remaining text starts with, if any
+    fn trivia(&self) -> Option<(TriviaKind, usize)> {
+        let text = self.remaining_text;
+        let whitespace = text.len() - text.trim_start().len();
+        if whitespace > 0 {
+            Some((TriviaKind::Whitespace, whitespace))
+        } else if text.starts_with("//") {
+            Some((
+                TriviaKind::LineComment,
+                text.find('\n').unwrap_or(text.len()),
+            ))
+        } else if let Some(comment) = text.strip_prefix("/*") {
+            let length = comment.find("*/").map_or(text.len(), |end| end + 4);
+            // `/**/` is empty and `/***` a banner, as in Rust
+            let doc = comment.starts_with('*') && !comment.starts_with("*/");
+            let kind = if doc && !comment.starts_with("**") {
+                TriviaKind::DocComment
+            } else {
+                TriviaKind::BlockComment
+            };
+            Some((kind, length))
+        } else {
+            None
+        }
+    }
+
+    /// Skip whitespace and comments, returning them
+    fn skip(&mut self) -> Vec<Trivia> {
+        let mut skipped = vec![];
+        while let Some((kind, length)) = self.trivia() {
+            skipped.push(Trivia {
+                kind,
+                span: Span::new(self.file, self.offset..self.offset + length),
+            });
+            self.advance(length);
         }
+        skipped
     }
 
-    fn next(&mut self) -> Result<Option<Token>> {
+    fn next(&mut self) -> Result<Option<Token>, LexerError> {
         self.skip();
+        self.token()
+    }
 
+    /// The token the remaining text starts with, which must not start with trivia
+    fn token(&mut self) -> Result<Option<Token>, LexerError> {
         if self.remaining_text.is_empty() {
             return Ok(None);
         };
 
         let next = self.remaining_text.chars().next().unwrap();
+        let next_span = Span::new(self.file, self.offset..self.offset + next.len_utf8());
 
         let (kind, size) = match self.state {
             TokenizerState::LexerPattern => {
-                let (pattern, length) = read_lexer_pattern(self.remaining_text)?;
+                let here = Span::new(self.file, self.offset..self.offset);
+                let (pattern, length) = read_lexer_pattern(self.remaining_text, here)?;
                 (TokenKind::LexerPattern(pattern.into()), length)
             }
             _ => match next {
                 // Symbols
                 '|' => (TokenKind::Bar, 1),
+                '&' => (TokenKind::And, 1),
+                '!' => (TokenKind::Not, 1),
                 ';' => (TokenKind::Semicolon, 1),
                 ':' => (TokenKind::Colon, 1),
+                '%' => {
+                    let (name, length) = read_identifier(&self.remaining_text[1..]);
+                    if name.is_empty() {
+                        return unexpected_char!(next, next_span);
+                    }
+                    (TokenKind::Directive(name.into()), length + 1)
+                }
 
                 // Other
                 c @ '_' | c if c.is_alphanumeric() => {
@@ -148,7 +174,7 @@ impl<'a> Tokenizer<'a> {
                         (TokenKind::ParserIdent(id.into()), length)
                     }
                 }
-                _ => return unexpected_char!(next),
+                _ => return unexpected_char!(next, next_span),
             },
         };
 
@@ -168,30 +194,63 @@ impl<'a> Tokenizer<'a> {
             }
         }
 
-        let token = Token::new(kind, self.row, self.col);
+        let span = Span::new(self.file, self.offset..self.offset + size);
         self.advance(size);
-        Ok(Some(token))
+        Ok(Some(Token { kind, span }))
     }
 
-    fn advance(&mut self, amount: usize) {
-        self.remaining_text = &self.remaining_text[amount..];
-        self.col += amount;
+    /// Skip past what an error is about, returning any tokens read before it.
+    /// A lexer pattern broken off by whitespace ends there, and so does its rule: the rest of
+    /// the line up to a `;` is skipped, and a `;` is made up when the line ends without one.
+    fn recover(&mut self, err: &LexerError) -> Vec<Token> {
+        let LexerError::UnexpectedChar(_, span) = err else {
+            return vec![];
+        };
+        if self.state != TokenizerState::LexerPattern {
+            self.advance(span.end - self.offset);
+            return vec![];
+        }
+        let mut tokens = vec![];
+        let read = span.start - self.offset;
+        if read > 0 {
+            tokens.push(Token {
+                kind: TokenKind::LexerPattern(self.remaining_text[..read].into()),
+                span: Span::new(self.file, self.offset..span.start),
+            });
+        }
+        self.advance(read);
+        let skipped = self
+            .remaining_text
+            .find([';', '\n'])
+            .unwrap_or(self.remaining_text.len());
+        self.advance(skipped);
+        if self.remaining_text.starts_with(';') {
+            self.state = TokenizerState::Other;
+        } else {
+            tokens.push(Token {
+                kind: TokenKind::Semicolon,
+                span: Span::new(self.file, self.offset..self.offset),
+            });
+            self.state = TokenizerState::Initial;
+        }
+        tokens
     }
 
-    fn advance_char(&mut self, c: char) {
-        match c {
-            '\n' => {
-                self.row += 1;
-                self.col = 0;
-            }
-            '\t' => self.col += 4,
-            _ => self.col += 1,
+    /// The end of input, an empty span after any trailing whitespace and comments
+    fn end(&self) -> Token {
+        Token {
+            kind: TokenKind::EOF,
+            span: Span::new(self.file, self.offset..self.offset),
         }
-        self.remaining_text = &self.remaining_text[c.len_utf8()..];
+    }
+
+    fn advance(&mut self, amount: usize) {
+        self.remaining_text = &self.remaining_text[amount..];
+        self.offset += amount;
     }
 }
 
-pub fn tokenize(src: &str) -> Result<Vec<Token>> {
+pub fn tokenize(src: &str) -> anyhow::Result<Vec<Token>> {
     let mut tokenizer = Tokenizer::from(src);
     let mut tokens = Vec::new();
 
@@ -199,14 +258,80 @@ pub fn tokenize(src: &str) -> Result<Vec<Token>> {
         tokens.push(token);
     }
 
-    if let Some(last) = tokens.last() {
-        let mut eof = last.clone();
-        eof.kind = TokenKind::EOF;
-        tokens.push(eof);
-        Ok(tokens)
-    } else {
-        Err(LexerError::UnexpectedEof.into())
+    if tokens.is_empty() {
+        return Err(LexerError::UnexpectedEof.into());
+    }
+    tokens.push(tokenizer.end());
+    Ok(tokens)
+}
+
+/// Every token of `src` with the trivia before it, the last being the end of input with any
+/// trailing trivia. Their text makes up `src` exactly, which formatting relies on.
+pub fn tokenize_lossless(src: &str) -> Result<Vec<(Vec<Trivia>, Token)>, LexerError> {
+    let mut tokenizer = Tokenizer::from(src);
+    let mut tokens = Vec::new();
+
+    loop {
+        let trivia = tokenizer.skip();
+        match tokenizer.token()? {
+            Some(token) => tokens.push((trivia, token)),
+            None => {
+                tokens.push((trivia, tokenizer.end()));
+                return Ok(tokens);
+            }
+        }
+    }
+}
+
+/// The doc comment before each token of `src`, the source of `file`, by the offset of the token.
+/// Of several doc comments before a token, the last one documents it.
+pub fn doc_comments(src: &str, file: FileId) -> HashMap<usize, Span> {
+    let mut tokenizer = Tokenizer::new(src, file);
+    let mut docs = HashMap::new();
+
+    loop {
+        let doc = tokenizer
+            .skip()
+            .into_iter()
+            .rev()
+            .find(|trivia| trivia.kind == TriviaKind::DocComment);
+        match tokenizer.token() {
+            Ok(Some(token)) => {
+                if let Some(doc) = doc {
+                    docs.insert(token.span.start, doc.span);
+                }
+            }
+            Ok(None) => return docs,
+            Err(err) => {
+                tokenizer.recover(&err);
+            }
+        }
+    }
+}
+
+/// Tokenize all of `src`, the source of `file`, skipping characters which start no token,
+/// and return every error found along the way with the tokens around them
+pub fn tokenize_recovering(src: &str, file: FileId) -> (Vec<Token>, Vec<LexerError>) {
+    let mut tokenizer = Tokenizer::new(src, file);
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+
+    loop {
+        match tokenizer.next() {
+            Ok(Some(token)) => tokens.push(token),
+            Ok(None) => break,
+            Err(err) => {
+                tokens.extend(tokenizer.recover(&err));
+                errors.push(err);
+            }
+        }
+    }
+
+    if tokens.is_empty() && errors.is_empty() {
+        errors.push(LexerError::UnexpectedEof);
     }
+    tokens.push(tokenizer.end());
+    (tokens, errors)
 }
 
 #[cfg(test)]
@@ -243,11 +368,10 @@ mod tests {
     }
 
     macro_rules! token {
-        ($kind:expr, $row:expr, $col:expr) => {
+        ($kind:expr, $range:expr) => {
             Token {
                 kind: $kind,
-                row: $row,
-                col: $col,
+                span: Span::new(FileId(0), $range),
             }
         };
     }
@@ -258,67 +382,224 @@ mod tests {
     test_next_token!(tokenize_single_parser_ident, "abc" => TokenKind::ParserIdent("abc".into()));
     test_next_token!(tokenize_single_lexer_ident, "ABC" => TokenKind::LexerIdent("ABC".into()));
     test_next_token!(tokenize_single_open_brace_with_trail, "|fdsfs" => TokenKind::Bar);
+    test_next_token!(tokenize_single_directive, "%deny" => TokenKind::Directive("deny".into()));
+    test_next_token!(tokenize_single_and, "&a" => TokenKind::And);
+    test_next_token!(tokenize_single_not, "!a" => TokenKind::Not);
+
+    #[test]
+    fn tokenize_lone_percent_fails() {
+        assert!(Tokenizer::from("% deny").next().is_err());
+    }
 
     test_tokenize!(
         tokenize_parser_rule,
         "program: rule SEMI | rule SEMI program;" => vec![
-            token!(TokenKind::ParserIdent("program".into()), 0, 0),
-            token!(TokenKind::Colon, 0, 7),
-            token!(TokenKind::ParserIdent("rule".into()), 0, 9),
-            token!(TokenKind::LexerIdent("SEMI".into()), 0, 14),
-            token!(TokenKind::Bar, 0, 19),
-            token!(TokenKind::ParserIdent("rule".into()), 0, 21),
-            token!(TokenKind::LexerIdent("SEMI".into()), 0, 26),
-            token!(TokenKind::ParserIdent("program".into()), 0, 31),
-            token!(TokenKind::Semicolon, 0, 38),
-            token!(TokenKind::EOF, 0, 38),
+            token!(TokenKind::ParserIdent("program".into()), 0..7),
+            token!(TokenKind::Colon, 7..8),
+            token!(TokenKind::ParserIdent("rule".into()), 9..13),
+            token!(TokenKind::LexerIdent("SEMI".into()), 14..18),
+            token!(TokenKind::Bar, 19..20),
+            token!(TokenKind::ParserIdent("rule".into()), 21..25),
+            token!(TokenKind::LexerIdent("SEMI".into()), 26..30),
+            token!(TokenKind::ParserIdent("program".into()), 31..38),
+            token!(TokenKind::Semicolon, 38..39),
+            token!(TokenKind::EOF, 39..39),
         ]
     );
 
     test_tokenize!(
         tokenize_parser_rule_with_comments,
         "program: rule SEMI // comment\n| rule SEMI program;" => vec![
-            token!(TokenKind::ParserIdent("program".into()), 0, 0),
-            token!(TokenKind::Colon, 0, 7),
-            token!(TokenKind::ParserIdent("rule".into()), 0, 9),
-            token!(TokenKind::LexerIdent("SEMI".into()), 0, 14),
-            token!(TokenKind::Bar, 1, 0),
-            token!(TokenKind::ParserIdent("rule".into()), 1, 2),
-            token!(TokenKind::LexerIdent("SEMI".into()), 1, 7),
-            token!(TokenKind::ParserIdent("program".into()), 1, 12),
-            token!(TokenKind::Semicolon, 1, 19),
-            token!(TokenKind::EOF, 1, 19),
+            token!(TokenKind::ParserIdent("program".into()), 0..7),
+            token!(TokenKind::Colon, 7..8),
+            token!(TokenKind::ParserIdent("rule".into()), 9..13),
+            token!(TokenKind::LexerIdent("SEMI".into()), 14..18),
+            token!(TokenKind::Bar, 30..31),
+            token!(TokenKind::ParserIdent("rule".into()), 32..36),
+            token!(TokenKind::LexerIdent("SEMI".into()), 37..41),
+            token!(TokenKind::ParserIdent("program".into()), 42..49),
+            token!(TokenKind::Semicolon, 49..50),
+            token!(TokenKind::EOF, 50..50),
+        ]
+    );
+
+    test_tokenize!(
+        tokenize_block_comments,
+        "/* a\n * b */ s: A /**/;" => vec![
+            token!(TokenKind::ParserIdent("s".into()), 13..14),
+            token!(TokenKind::Colon, 14..15),
+            token!(TokenKind::LexerIdent("A".into()), 16..17),
+            token!(TokenKind::Semicolon, 22..23),
+            token!(TokenKind::EOF, 23..23),
         ]
     );
 
     test_tokenize!(
         tokenize_lexer_rule,
         "LexerRule: [A-Z][a-zA-Z0-9_]*'*;" => vec![
-            token!(TokenKind::LexerIdent("LexerRule".into()), 0, 0),
-            token!(TokenKind::Colon, 0, 9),
-            token!(TokenKind::LexerPattern("[A-Z][a-zA-Z0-9_]*'*".into()), 0, 11),
-            token!(TokenKind::Semicolon, 0, 31),
-            token!(TokenKind::EOF, 0, 31),
+            token!(TokenKind::LexerIdent("LexerRule".into()), 0..9),
+            token!(TokenKind::Colon, 9..10),
+            token!(TokenKind::LexerPattern("[A-Z][a-zA-Z0-9_]*'*".into()), 11..31),
+            token!(TokenKind::Semicolon, 31..32),
+            token!(TokenKind::EOF, 32..32),
+        ]
+    );
+
+    test_tokenize!(
+        tokenize_directive,
+        "%allow empty_alternative;\nA: a;" => vec![
+            token!(TokenKind::Directive("allow".into()), 0..6),
+            token!(TokenKind::ParserIdent("empty_alternative".into()), 7..24),
+            token!(TokenKind::Semicolon, 24..25),
+            token!(TokenKind::LexerIdent("A".into()), 26..27),
+            token!(TokenKind::Colon, 27..28),
+            token!(TokenKind::LexerPattern("a".into()), 29..30),
+            token!(TokenKind::Semicolon, 30..31),
+            token!(TokenKind::EOF, 31..31),
         ]
     );
 
     test_tokenize!(
         tokenize_parser_and_lexer_rule,
         "program: rule SEMI | rule SEMI program;\nLexerRule: [A-Z][a-zA-Z0-9_]*'*;" => vec![
-            token!(TokenKind::ParserIdent("program".into()), 0, 0),
-            token!(TokenKind::Colon, 0, 7),
-            token!(TokenKind::ParserIdent("rule".into()), 0, 9),
-            token!(TokenKind::LexerIdent("SEMI".into()), 0, 14),
-            token!(TokenKind::Bar, 0, 19),
-            token!(TokenKind::ParserIdent("rule".into()), 0, 21),
-            token!(TokenKind::LexerIdent("SEMI".into()), 0, 26),
-            token!(TokenKind::ParserIdent("program".into()), 0, 31),
-            token!(TokenKind::Semicolon, 0, 38),
-            token!(TokenKind::LexerIdent("LexerRule".into()), 1, 0),
-            token!(TokenKind::Colon, 1, 9),
-            token!(TokenKind::LexerPattern("[A-Z][a-zA-Z0-9_]*'*".into()), 1, 11),
-            token!(TokenKind::Semicolon, 1, 31),
-            token!(TokenKind::EOF, 1, 31),
+            token!(TokenKind::ParserIdent("program".into()), 0..7),
+            token!(TokenKind::Colon, 7..8),
+            token!(TokenKind::ParserIdent("rule".into()), 9..13),
+            token!(TokenKind::LexerIdent("SEMI".into()), 14..18),
+            token!(TokenKind::Bar, 19..20),
+            token!(TokenKind::ParserIdent("rule".into()), 21..25),
+            token!(TokenKind::LexerIdent("SEMI".into()), 26..30),
+            token!(TokenKind::ParserIdent("program".into()), 31..38),
+            token!(TokenKind::Semicolon, 38..39),
+            token!(TokenKind::LexerIdent("LexerRule".into()), 40..49),
+            token!(TokenKind::Colon, 49..50),
+            token!(TokenKind::LexerPattern("[A-Z][a-zA-Z0-9_]*'*".into()), 51..71),
+            token!(TokenKind::Semicolon, 71..72),
+            token!(TokenKind::EOF, 72..72),
         ]
     );
+
+    test_tokenize!(
+        tokenize_spans_count_bytes,
+        "é: B;\t\n" => vec![
+            token!(TokenKind::ParserIdent("é".into()), 0..2),
+            token!(TokenKind::Colon, 2..3),
+            token!(TokenKind::LexerIdent("B".into()), 4..5),
+            token!(TokenKind::Semicolon, 5..6),
+            token!(TokenKind::EOF, 8..8),
+        ]
+    );
+
+    #[test]
+    fn tokenize_lossless_keeps_trivia() {
+        let src = "// s\ns: A /* a */ | ; // end\nA: a;\n/* open";
+        let tokens = tokenize_lossless(src).unwrap();
+        let text: String = tokens
+            .iter()
+            .flat_map(|(trivia, token)| trivia.iter().map(|trivia| trivia.span).chain([token.span]))
+            .map(|span| &src[span.start..span.end])
+            .collect();
+        assert_eq!(text, src);
+        let comments: Vec<TriviaKind> = tokens
+            .iter()
+            .flat_map(|(trivia, _)| trivia)
+            .map(|trivia| trivia.kind)
+            .filter(|kind| *kind != TriviaKind::Whitespace)
+            .collect();
+        assert_eq!(
+            comments,
+            [
+                TriviaKind::LineComment,
+                TriviaKind::BlockComment,
+                TriviaKind::LineComment,
+                TriviaKind::BlockComment
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenize_doc_comments() {
+        let src = "/** doc */ /**/ /*** banner */ /* plain */ /** open";
+        let tokens = tokenize_lossless(src).unwrap();
+        let comments: Vec<TriviaKind> = tokens[0]
+            .0
+            .iter()
+            .map(|trivia| trivia.kind)
+            .filter(|kind| *kind != TriviaKind::Whitespace)
+            .collect();
+        assert_eq!(
+            comments,
+            [
+                TriviaKind::DocComment,
+                TriviaKind::BlockComment,
+                TriviaKind::BlockComment,
+                TriviaKind::BlockComment,
+                TriviaKind::DocComment
+            ]
+        );
+    }
+
+    macro_rules! test_tokenize_recovering {
+        ($name:ident, $src:expr => $tokens:expr, errors: $errors:expr) => {
+            #[test]
+            fn $name() {
+                let (tokens, errors) = tokenize_recovering($src, FileId(0));
+                assert_eq!(tokens, $tokens);
+                let errors: Vec<(char, usize)> = errors
+                    .iter()
+                    .map(|err| match err {
+                        LexerError::UnexpectedChar(c, span) => (*c, span.start),
+                        LexerError::UnexpectedEof => ('\0', usize::MAX),
+                    })
+                    .collect();
+                assert_eq!(errors, $errors);
+            }
+        };
+    }
+
+    test_tokenize_recovering!(
+        tokenize_recovering_skips_char,
+        "s: $A;" => vec![
+            token!(TokenKind::ParserIdent("s".into()), 0..1),
+            token!(TokenKind::Colon, 1..2),
+            token!(TokenKind::LexerIdent("A".into()), 4..5),
+            token!(TokenKind::Semicolon, 5..6),
+            token!(TokenKind::EOF, 6..6),
+        ],
+        errors: [('$', 3)]
+    );
+
+    test_tokenize_recovering!(
+        tokenize_recovering_ends_pattern_at_newline,
+        "A: a\nB: b;" => vec![
+            token!(TokenKind::LexerIdent("A".into()), 0..1),
+            token!(TokenKind::Colon, 1..2),
+            token!(TokenKind::LexerPattern("a".into()), 3..4),
+            token!(TokenKind::Semicolon, 4..4),
+            token!(TokenKind::LexerIdent("B".into()), 5..6),
+            token!(TokenKind::Colon, 6..7),
+            token!(TokenKind::LexerPattern("b".into()), 8..9),
+            token!(TokenKind::Semicolon, 9..10),
+            token!(TokenKind::EOF, 10..10),
+        ],
+        errors: [('\n', 4)]
+    );
+
+    test_tokenize_recovering!(
+        tokenize_recovering_skips_rest_of_pattern,
+        "A: a b;" => vec![
+            token!(TokenKind::LexerIdent("A".into()), 0..1),
+            token!(TokenKind::Colon, 1..2),
+            token!(TokenKind::LexerPattern("a".into()), 3..4),
+            token!(TokenKind::Semicolon, 6..7),
+            token!(TokenKind::EOF, 7..7),
+        ],
+        errors: [(' ', 4)]
+    );
+
+    test_tokenize_recovering!(
+        tokenize_recovering_empty,
+        " // nothing" => vec![token!(TokenKind::EOF, 11..11)],
+        errors: [('\0', usize::MAX)]
+    );
 }
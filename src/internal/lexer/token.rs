@@ -1,17 +1,70 @@
+use std::fmt;
+
+use crate::span::Span;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum TokenKind {
     ParserIdent(String),
     LexerIdent(String),
     LexerPattern(String),
+    Directive(String),
     Bar,
+    /// `&`, a PEG lookahead which must match
+    And,
+    /// `!`, a PEG lookahead which must not match
+    Not,
     Semicolon,
     Colon,
+    #[allow(clippy::upper_case_acronyms)]
     EOF,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Token {
     pub kind: TokenKind,
-    pub row: usize,
-    pub col: usize,
+    pub span: Span,
+}
+
+/// Whitespace or a comment between tokens, which the parser never sees
+#[derive(Debug, PartialEq, Clone)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TriviaKind {
+    Whitespace,
+    /// `// ...` up to the end of the line, without it
+    LineComment,
+    /// `/* ... */`, or up to the end of input when it is not closed
+    BlockComment,
+    /// `/** ... */`, documenting the rule after it
+    DocComment,
+}
+
+/// Kinds with text show it when it is known, as the expected kinds are listed without
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (kind, text) = match self {
+            TokenKind::ParserIdent(text) => ("parser rule name", text),
+            TokenKind::LexerIdent(text) => ("lexer rule name", text),
+            TokenKind::LexerPattern(text) => ("lexer pattern", text),
+            TokenKind::Directive(name) if !name.is_empty() => {
+                return write!(f, "directive `%{name}`")
+            }
+            TokenKind::Directive(_) => return f.write_str("directive"),
+            TokenKind::Bar => return f.write_str("`|`"),
+            TokenKind::And => return f.write_str("`&`"),
+            TokenKind::Not => return f.write_str("`!`"),
+            TokenKind::Semicolon => return f.write_str("`;`"),
+            TokenKind::Colon => return f.write_str("`:`"),
+            TokenKind::EOF => return f.write_str("end of input"),
+        };
+        if text.is_empty() {
+            f.write_str(kind)
+        } else {
+            write!(f, "{kind} `{text}`")
+        }
+    }
 }
@@ -1,3 +1,13 @@
+pub mod analysis;
 pub mod ast;
+pub mod codegen;
+pub mod docs;
+pub mod format;
+pub mod graph;
 pub mod lexer;
 pub mod parser;
+pub mod railroad;
+pub mod runtime;
+pub mod table;
+#[cfg(test)]
+pub mod testing;
@@ -2,6 +2,7 @@ use crate::{
     ast::Node,
     error::ParserError,
     internal::lexer::token::{Token, TokenKind},
+    span::Span,
     unexpected_token,
 };
 use anyhow::Result;
@@ -11,24 +12,30 @@ use super::tree::{ParseR1, ParseR2, ParseR3, ParseR4, ParseS};
 struct FunctionalParser<I: Iterator<Item = Token>> {
     remaining_input: I,
     next: Token,
+    /// End of the last token matched, where the node being parsed ends
+    previous_end: usize,
+    /// Syntax errors recovered from, in the order they were found
+    errors: Vec<ParserError>,
 }
 
 /// Functional parser for the grammar.
 ///
 /// Each parse rule only checks the relevant tokens, and only considers the nullability
 /// of a nonterminal if it is explicitly nullable, to create a parse tree resembling
-/// the grammar.
+/// the grammar. An unexpected token is reported with every token which could
+/// have been there instead, from the FIRST and FOLLOW sets of the rule.
 impl<I: Iterator<Item = Token>> FunctionalParser<I> {
     fn match_(&mut self, expected: &TokenKind) -> Result<()> {
         if self.next.kind == *expected {
             self.continue_()
         } else {
-            unexpected_token!(self.next, expected.clone())
+            unexpected_token!(self.next, [expected.clone()])
         }
     }
     fn continue_(&mut self) -> Result<()> {
         match self.remaining_input.next() {
             Some(next) => {
+                self.previous_end = self.next.span.end;
                 self.next = next;
                 Ok(())
             }
@@ -36,20 +43,63 @@ impl<I: Iterator<Item = Token>> FunctionalParser<I> {
         }
     }
 
-    fn parse_s(&mut self) -> Result<ParseS> {
-        match self.next.kind {
-            TokenKind::LexerIdent(_) | TokenKind::ParserIdent(_) => {
-                let rule = self.parse_r1()?;
-                self.match_(&TokenKind::Semicolon)?;
+    /// Annotate a node with its span, from `start` up to the last token matched.
+    /// Nodes which matched nothing get an empty span at `start`.
+    fn node<T>(&self, start: usize, value: T) -> Node<T> {
+        let mut node = Node::new(value);
+        let end = self.previous_end.max(start);
+        node.add_annotation(Span::new(self.next.span.file, start..end));
+        node
+    }
+
+    /// Panic mode: keep the error, and skip past the next `;` to parse the rules after it.
+    /// Running out of tokens cannot be recovered from.
+    fn recover(&mut self, err: anyhow::Error) -> Result<()> {
+        match err.downcast::<ParserError>()? {
+            ParserError::UnexpectedEof => return Err(ParserError::UnexpectedEof.into()),
+            err => self.errors.push(err),
+        }
+        while !matches!(self.next.kind, TokenKind::Semicolon | TokenKind::EOF) {
+            self.continue_()?;
+        }
+        if self.next.kind == TokenKind::Semicolon {
+            self.continue_()?;
+        }
+        Ok(())
+    }
+
+    fn parse_s(&mut self) -> Result<Node<ParseS>> {
+        let start = self.next.span.start;
+        let rule = match self.next.kind {
+            TokenKind::LexerIdent(_) | TokenKind::ParserIdent(_) | TokenKind::Directive(_) => self
+                .parse_r1()
+                .and_then(|rule| self.match_(&TokenKind::Semicolon).map(|()| rule)),
+            TokenKind::EOF => return Ok(self.node(start, ParseS::Epsilon)),
+            _ => unexpected_token!(
+                self.next,
+                [
+                    TokenKind::ParserIdent(String::new()),
+                    TokenKind::LexerIdent(String::new()),
+                    TokenKind::Directive(String::new()),
+                    TokenKind::EOF,
+                ]
+            ),
+        };
+        match rule {
+            Ok(rule) => {
                 let rest = self.parse_s()?;
-                Ok(ParseS::Concat(rule.into(), rest.into()))
+                Ok(self.node(start, ParseS::Concat(rule.into(), rest.into())))
+            }
+            // The rule is left out of the tree
+            Err(err) => {
+                self.recover(err)?;
+                self.parse_s()
             }
-            TokenKind::EOF => Ok(ParseS::Epsilon),
-            _ => unexpected_token!(self.next, TokenKind::LexerIdent(String::new())),
         }
     }
 
-    fn parse_r1(&mut self) -> Result<ParseR1> {
+    fn parse_r1(&mut self) -> Result<Node<ParseR1>> {
+        let start = self.next.span.start;
         match &self.next.kind {
             // Parser rule
             TokenKind::ParserIdent(ident) => {
@@ -57,7 +107,7 @@ impl<I: Iterator<Item = Token>> FunctionalParser<I> {
                 self.continue_()?;
                 self.match_(&TokenKind::Colon)?;
                 let rule = self.parse_r2()?;
-                Ok(ParseR1::NonTerminal(ident_clone, rule.into()))
+                Ok(self.node(start, ParseR1::NonTerminal(ident_clone, rule.into())))
             }
             // Lexer rule
             TokenKind::LexerIdent(ident) => {
@@ -68,68 +118,155 @@ impl<I: Iterator<Item = Token>> FunctionalParser<I> {
                     TokenKind::LexerPattern(pattern) => {
                         let pattern_clone = pattern.clone();
                         self.continue_()?;
-                        Ok(ParseR1::Terminal(ident_clone, pattern_clone))
+                        Ok(self.node(start, ParseR1::Terminal(ident_clone, pattern_clone)))
                     }
-                    _ => unexpected_token!(self.next, TokenKind::LexerPattern(String::new())),
+                    _ => unexpected_token!(self.next, [TokenKind::LexerPattern(String::new())]),
                 }
             }
-            _ => unexpected_token!(self.next, TokenKind::ParserIdent(String::new())),
+            // Grammar directive
+            TokenKind::Directive(name) => {
+                let name_clone = name.clone();
+                self.continue_()?;
+                let args = self.parse_r3()?;
+                Ok(self.node(start, ParseR1::Directive(name_clone, args.into())))
+            }
+            _ => unexpected_token!(
+                self.next,
+                [
+                    TokenKind::ParserIdent(String::new()),
+                    TokenKind::LexerIdent(String::new()),
+                    TokenKind::Directive(String::new()),
+                ]
+            ),
         }
     }
 
     /// This fx does not consider nullability, even though it is nullable,
     /// as it is not explicitly nullable, and thus is handled deeper in the tree.
-    fn parse_r2(&mut self) -> Result<ParseR2> {
+    fn parse_r2(&mut self) -> Result<Node<ParseR2>> {
+        let start = self.next.span.start;
         let rule = self.parse_r3()?;
         let rest = self.parse_r4()?;
-        Ok(ParseR2::Rule(rule.into(), rest.into()))
+        Ok(self.node(start, ParseR2::Rule(rule.into(), rest.into())))
     }
 
-    fn parse_r3(&mut self) -> Result<ParseR3> {
+    fn parse_r3(&mut self) -> Result<Node<ParseR3>> {
+        let start = self.next.span.start;
         match &self.next.kind {
             TokenKind::ParserIdent(ident) => {
                 let ident_clone = ident.clone();
                 self.continue_()?;
                 let rest = self.parse_r3()?;
-                Ok(ParseR3::NonTerminal(ident_clone, rest.into()))
+                Ok(self.node(start, ParseR3::NonTerminal(ident_clone, rest.into())))
             }
             TokenKind::LexerIdent(ident) => {
                 let ident_clone = ident.clone();
                 self.continue_()?;
                 let rest = self.parse_r3();
-                Ok(ParseR3::Terminal(ident_clone, rest?.into()))
+                Ok(self.node(start, ParseR3::Terminal(ident_clone, rest?.into())))
+            }
+            TokenKind::Directive(name) => {
+                let name_clone = name.clone();
+                self.continue_()?;
+                // `%prec` and `%label` apply to the ident right after them
+                if matches!(name_clone.as_str(), "prec" | "label")
+                    && !matches!(
+                        self.next.kind,
+                        TokenKind::ParserIdent(_) | TokenKind::LexerIdent(_)
+                    )
+                {
+                    return unexpected_token!(
+                        self.next,
+                        [
+                            TokenKind::ParserIdent(String::new()),
+                            TokenKind::LexerIdent(String::new()),
+                        ]
+                    );
+                }
+                let rest = self.parse_r3()?;
+                Ok(self.node(start, ParseR3::Directive(name_clone, rest.into())))
+            }
+            TokenKind::And | TokenKind::Not => {
+                let negated = self.next.kind == TokenKind::Not;
+                self.continue_()?;
+                // A lookahead applies to exactly one ident
+                if !matches!(
+                    self.next.kind,
+                    TokenKind::ParserIdent(_) | TokenKind::LexerIdent(_)
+                ) {
+                    return unexpected_token!(
+                        self.next,
+                        [
+                            TokenKind::ParserIdent(String::new()),
+                            TokenKind::LexerIdent(String::new()),
+                        ]
+                    );
+                }
+                let rest = self.parse_r3()?.into();
+                let lookahead = if negated {
+                    ParseR3::Not(rest)
+                } else {
+                    ParseR3::And(rest)
+                };
+                Ok(self.node(start, lookahead))
             }
-            TokenKind::Semicolon | TokenKind::Bar => Ok(ParseR3::Epsilon),
-            _ => unexpected_token!(self.next, TokenKind::Semicolon),
+            TokenKind::Semicolon | TokenKind::Bar => Ok(self.node(start, ParseR3::Epsilon)),
+            _ => unexpected_token!(
+                self.next,
+                [
+                    TokenKind::ParserIdent(String::new()),
+                    TokenKind::LexerIdent(String::new()),
+                    TokenKind::Directive(String::new()),
+                    TokenKind::And,
+                    TokenKind::Not,
+                    TokenKind::Bar,
+                    TokenKind::Semicolon,
+                ]
+            ),
         }
     }
 
-    fn parse_r4(&mut self) -> Result<ParseR4> {
+    fn parse_r4(&mut self) -> Result<Node<ParseR4>> {
+        let start = self.next.span.start;
         match self.next.kind {
             TokenKind::Bar => {
                 self.match_(&TokenKind::Bar)?;
                 let rule = self.parse_r2()?;
-                Ok(ParseR4::Concat(rule.into()))
+                Ok(self.node(start, ParseR4::Concat(rule.into())))
             }
-            TokenKind::Semicolon => Ok(ParseR4::Epsilon),
-            _ => unexpected_token!(self.next, TokenKind::Semicolon),
+            TokenKind::Semicolon => Ok(self.node(start, ParseR4::Epsilon)),
+            _ => unexpected_token!(self.next, [TokenKind::Bar, TokenKind::Semicolon]),
         }
     }
 }
 
+/// Parse the input, failing on its first syntax error
+#[cfg(test)]
 pub fn parse(input: Vec<Token>) -> Result<Node<ParseS>> {
+    let (tree, mut errors) = parse_recovering(input)?;
+    if errors.is_empty() {
+        Ok(tree)
+    } else {
+        Err(errors.swap_remove(0).into())
+    }
+}
+
+/// Parse every rule which can be, leaving out those with syntax errors, and return the errors.
+/// Fails only when the input ends before its `EOF` token.
+pub fn parse_recovering(input: Vec<Token>) -> Result<(Node<ParseS>, Vec<ParserError>)> {
     let mut iter = input.into_iter();
     let first = iter.next();
     match first {
-        None => Ok(ParseS::Epsilon.into()),
+        None => Ok((ParseS::Epsilon.into(), vec![])),
         Some(token) => {
-            let parsed = FunctionalParser {
+            let mut parser = FunctionalParser {
                 remaining_input: iter,
+                previous_end: token.span.start,
                 next: token,
-            }
-            .parse_s()?;
-
-            Ok(parsed.into())
+                errors: vec![],
+            };
+            let tree = parser.parse_s()?;
+            Ok((tree, parser.errors))
         }
     }
 }
@@ -143,8 +280,7 @@ mod tests {
         fn from(value: TokenKind) -> Self {
             Token {
                 kind: value,
-                col: 0,
-                row: 0,
+                span: Span::default(),
             }
         }
     }
@@ -180,4 +316,115 @@ mod tests {
             TokenKind::EOF
         ]
     );
+
+    // "e: e PLUS e %prec PLUS;"
+    test_parse_tokens_snapshot!(
+        parse_alternative_directive,
+        vec![
+            TokenKind::ParserIdent("e".into()),
+            TokenKind::Colon,
+            TokenKind::ParserIdent("e".into()),
+            TokenKind::LexerIdent("PLUS".into()),
+            TokenKind::ParserIdent("e".into()),
+            TokenKind::Directive("prec".into()),
+            TokenKind::LexerIdent("PLUS".into()),
+            TokenKind::Semicolon,
+            TokenKind::EOF
+        ]
+    );
+
+    // "s: !A a B | &b a;"
+    test_parse_tokens_snapshot!(
+        parse_lookahead,
+        vec![
+            TokenKind::ParserIdent("s".into()),
+            TokenKind::Colon,
+            TokenKind::Not,
+            TokenKind::LexerIdent("A".into()),
+            TokenKind::ParserIdent("a".into()),
+            TokenKind::LexerIdent("B".into()),
+            TokenKind::Bar,
+            TokenKind::And,
+            TokenKind::ParserIdent("b".into()),
+            TokenKind::ParserIdent("a".into()),
+            TokenKind::Semicolon,
+            TokenKind::EOF
+        ]
+    );
+
+    #[test]
+    fn parse_lookahead_needs_ident() {
+        let tokens = [
+            TokenKind::ParserIdent("s".into()),
+            TokenKind::Colon,
+            TokenKind::Not,
+            TokenKind::Semicolon,
+            TokenKind::EOF,
+        ];
+        assert!(parse(tokens.into_iter().map(Token::from).collect()).is_err());
+    }
+
+    // "%deny unused_token cyclic_rule;"
+    test_parse_tokens_snapshot!(
+        parse_directive,
+        vec![
+            TokenKind::Directive("deny".into()),
+            TokenKind::ParserIdent("unused_token".into()),
+            TokenKind::ParserIdent("cyclic_rule".into()),
+            TokenKind::Semicolon,
+            TokenKind::EOF
+        ]
+    );
+
+    macro_rules! test_parse_recovering {
+        ($name:ident, $src:expr => rules: $rules:expr, errors: $errors:expr) => {
+            #[test]
+            fn $name() {
+                let tokens = crate::internal::lexer::lexer::tokenize($src).unwrap();
+                let (tree, errors) = parse_recovering(tokens).unwrap();
+                let grammar = crate::internal::ast::Grammar::from(tree);
+                let rules: Vec<&str> = grammar.rules.iter().map(|rule| rule.name()).collect();
+                assert_eq!(rules, $rules);
+                let errors: Vec<usize> = errors
+                    .iter()
+                    .map(|err| match err {
+                        ParserError::UnexpectedToken { found, .. } => found.span.start,
+                        ParserError::UnexpectedEof => usize::MAX,
+                    })
+                    .collect();
+                assert_eq!(errors, $errors, "offsets of the errors");
+            }
+        };
+    }
+
+    test_parse_recovering!(
+        parse_recovers_after_semicolon,
+        "s: A : B;\nt: B;\nA: a;" => rules: ["t", "A"], errors: [5]
+    );
+    test_parse_recovering!(
+        parse_recovers_at_rule_start,
+        "| s;\nt: B;\nu: : ;" => rules: ["t"], errors: [0, 14]
+    );
+    test_parse_recovering!(
+        parse_prec_without_terminal,
+        "e: e PLUS e %prec;\nf: e %prec | e;\nPLUS: \\+;" => rules: ["PLUS"], errors: [17, 30]
+    );
+    test_parse_recovering!(
+        parse_label_before_lookahead,
+        "s: %label !A A;\nA: a;" => rules: ["A"], errors: [10]
+    );
+    test_parse_recovering!(
+        parse_recovers_at_end,
+        "s: A;\nt: &" => rules: ["s"], errors: [10]
+    );
+
+    #[test]
+    fn parse_reports_first_error() {
+        let tokens = crate::internal::lexer::lexer::tokenize("s: : ; t: |: ;").unwrap();
+        let err = parse(tokens).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ParserError>(),
+            Some(ParserError::UnexpectedToken { found, .. }) if found.span.start == 3
+        ));
+    }
 }
@@ -0,0 +1,207 @@
+// @generated by `rantlr bootstrap` from `examples/rantlr.g4`. Do not edit by hand.
+use crate::{
+    ast::Node,
+    error::ParserError,
+    internal::lexer::token::{Token, TokenKind},
+    span::Span,
+    unexpected_token,
+};
+use anyhow::Result;
+
+use super::tree::{ParseR1, ParseR2, ParseR3, ParseR4, ParseS};
+
+struct GeneratedParser<I: Iterator<Item = Token>> {
+    remaining_input: I,
+    next: Token,
+    previous_end: usize,
+}
+
+/// Recursive descent parser for the grammar, choosing alternatives with its LL(1) table
+impl<I: Iterator<Item = Token>> GeneratedParser<I> {
+    fn advance(&mut self) -> Result<TokenKind> {
+        match self.remaining_input.next() {
+            Some(next) => {
+                self.previous_end = self.next.span.end;
+                Ok(std::mem::replace(&mut self.next, next).kind)
+            }
+            None => Err(ParserError::UnexpectedEof.into()),
+        }
+    }
+
+    fn node<T>(&self, start: usize, value: T) -> Node<T> {
+        let mut node = Node::new(value);
+        let end = self.previous_end.max(start);
+        node.add_annotation(Span::new(self.next.span.file, start..end));
+        node
+    }
+
+    fn expect(&mut self, expected: TokenKind) -> Result<TokenKind> {
+        if std::mem::discriminant(&self.next.kind) == std::mem::discriminant(&expected) {
+            self.advance()
+        } else {
+            unexpected_token!(self.next, [expected])
+        }
+    }
+
+    fn text(&mut self, expected: TokenKind) -> Result<String> {
+        match self.expect(expected)? {
+            TokenKind::ParserIdent(text)
+            | TokenKind::LexerIdent(text)
+            | TokenKind::LexerPattern(text)
+            | TokenKind::Directive(text) => Ok(text),
+            _ => unreachable!("only tokens with a payload are expected"),
+        }
+    }
+
+    fn parse_s(&mut self) -> Result<Node<ParseS>> {
+        let start = self.next.span.start;
+        match self.next.kind {
+            TokenKind::ParserIdent(_) | TokenKind::LexerIdent(_) | TokenKind::Directive(_) => {
+                let r1 = self.parse_r1()?;
+                self.expect(TokenKind::Semicolon)?;
+                let s = self.parse_s()?;
+                Ok(self.node(start, ParseS::Concat(r1.into(), s.into())))
+            }
+            TokenKind::EOF => Ok(self.node(start, ParseS::Epsilon)),
+            _ => unexpected_token!(
+                self.next,
+                [
+                    TokenKind::ParserIdent(String::new()),
+                    TokenKind::LexerIdent(String::new()),
+                    TokenKind::Directive(String::new()),
+                    TokenKind::EOF,
+                ]
+            ),
+        }
+    }
+
+    fn parse_r1(&mut self) -> Result<Node<ParseR1>> {
+        let start = self.next.span.start;
+        match self.next.kind {
+            TokenKind::ParserIdent(_) => {
+                let parser_ident = self.text(TokenKind::ParserIdent(String::new()))?;
+                self.expect(TokenKind::Colon)?;
+                let r2 = self.parse_r2()?;
+                Ok(self.node(start, ParseR1::NonTerminal(parser_ident, r2.into())))
+            }
+            TokenKind::LexerIdent(_) => {
+                let lexer_ident = self.text(TokenKind::LexerIdent(String::new()))?;
+                self.expect(TokenKind::Colon)?;
+                let lexer_pattern = self.text(TokenKind::LexerPattern(String::new()))?;
+                Ok(self.node(start, ParseR1::Terminal(lexer_ident, lexer_pattern)))
+            }
+            TokenKind::Directive(_) => {
+                let directive = self.text(TokenKind::Directive(String::new()))?;
+                let r3 = self.parse_r3()?;
+                Ok(self.node(start, ParseR1::Directive(directive, r3.into())))
+            }
+            _ => unexpected_token!(
+                self.next,
+                [
+                    TokenKind::ParserIdent(String::new()),
+                    TokenKind::LexerIdent(String::new()),
+                    TokenKind::Directive(String::new()),
+                ]
+            ),
+        }
+    }
+
+    fn parse_r2(&mut self) -> Result<Node<ParseR2>> {
+        let start = self.next.span.start;
+        let r3 = self.parse_r3()?;
+        let r4 = self.parse_r4()?;
+        Ok(self.node(start, ParseR2::Rule(r3.into(), r4.into())))
+    }
+
+    fn parse_r3(&mut self) -> Result<Node<ParseR3>> {
+        let start = self.next.span.start;
+        match self.next.kind {
+            TokenKind::ParserIdent(_) => {
+                let parser_ident = self.text(TokenKind::ParserIdent(String::new()))?;
+                let r3 = self.parse_r3()?;
+                Ok(self.node(start, ParseR3::NonTerminal(parser_ident, r3.into())))
+            }
+            TokenKind::LexerIdent(_) => {
+                let lexer_ident = self.text(TokenKind::LexerIdent(String::new()))?;
+                let r3 = self.parse_r3()?;
+                Ok(self.node(start, ParseR3::Terminal(lexer_ident, r3.into())))
+            }
+            TokenKind::Directive(_) => {
+                let directive = self.text(TokenKind::Directive(String::new()))?;
+                let r3 = self.parse_r3()?;
+                Ok(self.node(start, ParseR3::Directive(directive, r3.into())))
+            }
+            TokenKind::And => {
+                self.expect(TokenKind::And)?;
+                if !matches!(
+                    self.next.kind,
+                    TokenKind::ParserIdent(_) | TokenKind::LexerIdent(_)
+                ) {
+                    return unexpected_token!(
+                        self.next,
+                        [
+                            TokenKind::ParserIdent(String::new()),
+                            TokenKind::LexerIdent(String::new()),
+                        ]
+                    );
+                }
+                let r3 = self.parse_r3()?;
+                Ok(self.node(start, ParseR3::And(r3.into())))
+            }
+            TokenKind::Not => {
+                self.expect(TokenKind::Not)?;
+                if !matches!(
+                    self.next.kind,
+                    TokenKind::ParserIdent(_) | TokenKind::LexerIdent(_)
+                ) {
+                    return unexpected_token!(
+                        self.next,
+                        [
+                            TokenKind::ParserIdent(String::new()),
+                            TokenKind::LexerIdent(String::new()),
+                        ]
+                    );
+                }
+                let r3 = self.parse_r3()?;
+                Ok(self.node(start, ParseR3::Not(r3.into())))
+            }
+            TokenKind::Bar | TokenKind::Semicolon => Ok(self.node(start, ParseR3::Epsilon)),
+            _ => unexpected_token!(
+                self.next,
+                [
+                    TokenKind::ParserIdent(String::new()),
+                    TokenKind::LexerIdent(String::new()),
+                    TokenKind::Directive(String::new()),
+                    TokenKind::Bar,
+                    TokenKind::And,
+                    TokenKind::Not,
+                    TokenKind::Semicolon,
+                ]
+            ),
+        }
+    }
+
+    fn parse_r4(&mut self) -> Result<Node<ParseR4>> {
+        let start = self.next.span.start;
+        match self.next.kind {
+            TokenKind::Bar => {
+                self.expect(TokenKind::Bar)?;
+                let r2 = self.parse_r2()?;
+                Ok(self.node(start, ParseR4::Concat(r2.into())))
+            }
+            TokenKind::Semicolon => Ok(self.node(start, ParseR4::Epsilon)),
+            _ => unexpected_token!(self.next, [TokenKind::Bar, TokenKind::Semicolon]),
+        }
+    }
+}
+
+pub fn parse(input: Vec<Token>) -> Result<Node<ParseS>> {
+    let mut iter = input.into_iter();
+    let first = iter.next().ok_or(ParserError::UnexpectedEof)?;
+    GeneratedParser {
+        remaining_input: iter,
+        previous_end: first.span.start,
+        next: first,
+    }
+    .parse_s()
+}
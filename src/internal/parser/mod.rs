@@ -1,4 +1,262 @@
 pub mod functional;
-pub mod slr;
+pub mod generated;
 pub mod transform;
 pub mod tree;
+
+use std::{
+    collections::{HashSet, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    diagnostic::{of_error, Diagnostic},
+    error::{Error, NameErrors, SyntaxErrors},
+    internal::{
+        analysis::names,
+        ast::Grammar,
+        lexer::lexer::{doc_comments, tokenize, tokenize_recovering},
+    },
+    span::{FileId, SourceMap, Span},
+};
+
+/// What diagnostics call a grammar read from a string
+const UNNAMED: &str = "<grammar>";
+
+/// The diagnostic of an error of the meta-parser, which every one of its errors has
+fn diagnostic(err: &anyhow::Error) -> Diagnostic {
+    of_error(err.as_ref()).unwrap_or_else(|| Diagnostic::error(err.to_string()))
+}
+
+fn syntax_errors(source: &str, diagnostics: Vec<Diagnostic>) -> SyntaxErrors {
+    SyntaxErrors {
+        name: UNNAMED.into(),
+        source: source.into(),
+        diagnostics,
+    }
+}
+
+/// Read one grammar file, adding it to `files`
+fn read(path: &Path, files: &mut SourceMap) -> Result<Grammar, Error> {
+    let source = fs::read_to_string(path).map_err(|source| Error::Io {
+        path: path.into(),
+        source,
+    })?;
+    let file = files.add(path.into(), source);
+    Grammar::parse_in(files.source(file), file).map_err(|mut err| {
+        err.name = path.display().to_string();
+        err.into()
+    })
+}
+
+impl Grammar {
+    pub(crate) fn parse_partial_in(source: &str, file: FileId) -> (Grammar, Vec<Diagnostic>) {
+        let (tokens, lexer_errors) = tokenize_recovering(source, file);
+        let mut diagnostics: Vec<Diagnostic> = lexer_errors.iter().map(Diagnostic::from).collect();
+        let mut grammar = match functional::parse_recovering(tokens) {
+            Ok((tree, parser_errors)) => {
+                diagnostics.extend(parser_errors.iter().map(Diagnostic::from));
+                Grammar::from(tree)
+            }
+            Err(err) => {
+                diagnostics.push(diagnostic(&err));
+                Grammar::default()
+            }
+        };
+        grammar.document(source, file);
+        diagnostics
+            .sort_by_key(|diagnostic| diagnostic.primary.as_ref().map(|label| label.span.start));
+        (grammar, diagnostics)
+    }
+
+    /// Take the doc comment before each rule of `source`, the source of `file`
+    fn document(&mut self, source: &str, file: FileId) {
+        let comments = doc_comments(source, file);
+        for rule in &self.rules {
+            if let Some(span) = comments.get(&rule.span().start) {
+                let text = doc_text(&source[span.start..span.end]);
+                self.docs.entry(rule.name().into()).or_insert(text);
+            }
+        }
+    }
+
+    pub(crate) fn parse_in(source: &str, file: FileId) -> Result<Grammar, SyntaxErrors> {
+        match Grammar::parse_partial_in(source, file) {
+            (grammar, diagnostics) if diagnostics.is_empty() => Ok(grammar),
+            (_, diagnostics) => Err(syntax_errors(source, diagnostics)),
+        }
+    }
+
+    /// Read every rule of a grammar without syntax errors, and a diagnostic for each error in
+    /// the others, in source order
+    pub fn parse_partial(source: &str) -> (Grammar, Vec<Diagnostic>) {
+        Grammar::parse_partial_in(source, FileId::default())
+    }
+
+    /// Read a grammar, failing with every syntax error in it. Its imports are left alone.
+    pub fn parse(source: &str) -> Result<Grammar, SyntaxErrors> {
+        Grammar::parse_in(source, FileId::default())
+    }
+
+    /// Read a grammar file and its imports, see [`Grammar::load_with`]
+    pub fn load(path: impl AsRef<Path>) -> Result<Grammar, Error> {
+        Grammar::load_into(path, &mut SourceMap::default())
+    }
+
+    /// Read a grammar file and its imports, which are only looked for next to the files importing
+    /// them, see [`Grammar::load_with`]
+    pub fn load_into(path: impl AsRef<Path>, files: &mut SourceMap) -> Result<Grammar, Error> {
+        Grammar::load_with(path, &[], files)
+    }
+
+    /// Read a grammar file and every grammar it imports, directly or not. `%import name;` imports
+    /// `name.g4` from the directory of the importing file, or else from the first directory of
+    /// `include` which has it. Like in ANTLR, the rules of an import come after those of the
+    /// grammar, and are left out where the grammar has a rule of the same name; the directives of
+    /// an import are not taken. Every file read is added to `files`, which the spans of the
+    /// grammar point into. Loading fails when a rule is defined twice in a file, or referred to
+    /// without being defined in any.
+    pub fn load_with(
+        path: impl AsRef<Path>,
+        include: &[PathBuf],
+        files: &mut SourceMap,
+    ) -> Result<Grammar, Error> {
+        let path = path.as_ref();
+        let mut grammar = read(path, files)?;
+        let mut pending: VecDeque<PathBuf> = grammar
+            .imports(path, include)
+            .map(|(_, path)| path)
+            .collect();
+        while let Some(path) = pending.pop_front() {
+            if files.find(&path).is_some() {
+                continue;
+            }
+            let imported = read(&path, files)?;
+            pending.extend(imported.imports(&path, include).map(|(_, path)| path));
+            grammar.import(imported);
+        }
+        let diagnostics = names::check_names(&grammar);
+        if !diagnostics.is_empty() {
+            return Err(NameErrors { diagnostics }.into());
+        }
+        Ok(grammar)
+    }
+
+    /// The files imported by the grammar read from `path`, with the `%import` of each. An import
+    /// found nowhere is looked for next to `path`, to be reported there.
+    pub(crate) fn imports<'a>(
+        &'a self,
+        path: &'a Path,
+        include: &'a [PathBuf],
+    ) -> impl Iterator<Item = (Span, PathBuf)> + 'a {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        self.directives
+            .iter()
+            .filter(|directive| directive.name == "import")
+            .flat_map(|directive| directive.args.iter().map(|name| (directive.span, name)))
+            .map(move |(span, name)| {
+                let file = format!("{name}.g4");
+                let path = std::iter::once(dir)
+                    .chain(include.iter().map(PathBuf::as_path))
+                    .map(|dir| dir.join(&file))
+                    .find(|path| path.exists())
+                    .unwrap_or_else(|| dir.join(&file));
+                (span, path)
+            })
+    }
+
+    /// Add the rules of an imported grammar which this one does not have, with their docs
+    pub(crate) fn import(&mut self, mut imported: Grammar) {
+        let defined: HashSet<String> = self
+            .rules
+            .iter()
+            .map(|rule| rule.name().to_string())
+            .collect();
+        imported.docs.retain(|name, _| !defined.contains(name));
+        self.docs.extend(imported.docs);
+        self.rules.extend(
+            imported
+                .rules
+                .into_iter()
+                .filter(|rule| !defined.contains(rule.name())),
+        );
+    }
+}
+
+/// Read a grammar with the meta-parser generated from `examples/rantlr.g4`, failing on the first
+/// syntax error
+pub fn parse_generated(source: &str) -> Result<Grammar, SyntaxErrors> {
+    let fail = |err: anyhow::Error| syntax_errors(source, vec![diagnostic(&err)]);
+    let tree = generated::parse(tokenize(source).map_err(fail)?).map_err(fail)?;
+    let mut grammar = Grammar::from(tree);
+    grammar.document(source, FileId::default());
+    Ok(grammar)
+}
+
+/// The text of a doc comment without its delimiters, nor the `*` and the space after it which
+/// its lines may start with. Lines lose their indentation, and blank lines around the text are
+/// dropped.
+fn doc_text(comment: &str) -> String {
+    let text = comment.strip_prefix("/**").unwrap_or(comment);
+    let text = text.strip_suffix("*/").unwrap_or(text);
+    let lines: Vec<&str> = text
+        .lines()
+        .map(|line| {
+            let line = line.trim_start();
+            let line = match line.strip_prefix('*') {
+                Some(rest) => rest.strip_prefix(' ').unwrap_or(rest),
+                None => line,
+            };
+            line.trim_end()
+        })
+        .collect();
+    lines.join("\n").trim_matches('\n').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! test_docs {
+        ($name:ident, $source:expr => $docs:expr) => {
+            #[test]
+            fn $name() {
+                let grammar = Grammar::parse($source).unwrap();
+                let docs: &[(&str, &str)] = &$docs;
+                let expected = docs
+                    .iter()
+                    .map(|(name, doc)| (name.to_string(), doc.to_string()))
+                    .collect();
+                assert_eq!(grammar.docs, expected);
+                assert_eq!(parse_generated($source).unwrap(), grammar);
+            }
+        };
+    }
+
+    test_docs!(
+        docs_of_rules,
+        "/** A sum. */\ns: s PLUS A | A;\n/**\n * Digits,\n *   indented.\n *\n * More.\n */\nA: [0-9]+;\n"
+            => [("s", "A sum."), ("A", "Digits,\n  indented.\n\nMore.")]
+    );
+    test_docs!(
+        docs_skip_other_comments,
+        "/* plain */ s: A;\n/**/ t: A;\n/*** banner */ A: a;\n" => []
+    );
+    test_docs!(
+        last_doc_wins,
+        "/** first */\n// aside\n/** second */\ns: A;\nA: a;\n" => [("s", "second")]
+    );
+    test_docs!(
+        docs_of_directives_are_dropped,
+        "/** left */ %left A;\ns: A;\nA: a;\n" => []
+    );
+
+    #[test]
+    fn docs_of_imports() {
+        let mut grammar = Grammar::parse("s: A B;\nA: a;\n").unwrap();
+        let imported = Grammar::parse("/** imported */ A: b;\n/** b */ B: b;\n").unwrap();
+        grammar.import(imported);
+        assert_eq!(grammar.doc("A"), None);
+        assert_eq!(grammar.doc("B"), Some("b"));
+    }
+}
@@ -1,6 +0,0 @@
-enum SlrAction {
-    Shift(usize),
-    Reduce(usize),
-    Go(usize),
-    Accept,
-}
@@ -1,6 +1,7 @@
 use crate::{
     ast::Node,
-    internal::ast::{Grammar, Ident, Production},
+    internal::ast::{Alternative, Directive, Grammar, Ident, Predicate, Production},
+    span::Span,
 };
 
 use super::tree::{ParseR1, ParseR2, ParseR3, ParseR4, ParseS};
@@ -16,41 +17,141 @@ impl<T> PushReturn<T> for Vec<T> {
     }
 }
 
-fn visit_parse_s(node: &Node<ParseS>) -> Vec<Production> {
+/// The span the parser annotated a node with
+fn span<T>(node: &Node<T>) -> Span {
+    node.get_annotation::<Span>().copied().unwrap_or_default()
+}
+
+/// The span of a name starting a node, such as the ident of a rule or a `%directive`
+fn name_span<T>(node: &Node<T>, len: usize) -> Span {
+    let span = span(node);
+    Span::new(span.file, span.start..span.start + len)
+}
+
+enum Statement {
+    Production(Production),
+    Directive(Directive),
+}
+
+/// The statements of a grammar, last first
+fn visit_parse_s(node: &Node<ParseS>) -> Vec<Statement> {
     match &node.node {
-        ParseS::Concat(production, next) => {
-            visit_parse_s(next).push_return(visit_parse_r1(production))
+        ParseS::Concat(statement, next) => {
+            visit_parse_s(next).push_return(visit_parse_r1(statement))
         }
         ParseS::Epsilon => vec![],
     }
 }
 
-fn visit_parse_r1(node: &Node<ParseR1>) -> Production {
+fn visit_parse_r1(node: &Node<ParseR1>) -> Statement {
     match &node.node {
-        ParseR1::Terminal(ident, pattern) => Production::Lexer(ident.into(), pattern.into()),
-        ParseR1::NonTerminal(ident, rule) => Production::Parser(ident.into(), visit_parse_r2(rule)),
+        ParseR1::Terminal(ident, pattern) => {
+            Statement::Production(Production::Lexer(ident.into(), pattern.into(), span(node)))
+        }
+        ParseR1::NonTerminal(ident, rule) => Statement::Production(Production::Parser(
+            ident.into(),
+            visit_parse_r2(rule).into_iter().rev().collect(),
+            span(node),
+        )),
+        ParseR1::Directive(name, args) => Statement::Directive(Directive {
+            name: name.into(),
+            args: alternative(args)
+                .idents
+                .into_iter()
+                .map(|arg| arg.name().to_string())
+                .collect(),
+            span: span(node),
+        }),
     }
 }
 
-fn visit_parse_r2(node: &Node<ParseR2>) -> Vec<Vec<Ident>> {
+/// The alternatives of a rule, last first
+fn visit_parse_r2(node: &Node<ParseR2>) -> Vec<Alternative> {
     match &node.node {
-        ParseR2::Rule(rule, rules) => visit_parse_r4(rules).push_return(visit_parse_r3(rule)),
+        ParseR2::Rule(rule, rules) => visit_parse_r4(rules).push_return(alternative(rule)),
     }
 }
 
-fn visit_parse_r3(node: &Node<ParseR3>) -> Vec<Ident> {
-    match &node.node {
+/// An alternative in order, from the reversed one [`visit_parse_r3`] builds
+fn alternative(node: &Node<ParseR3>) -> Alternative {
+    let mut alternative = visit_parse_r3(node);
+    alternative.idents.reverse();
+    alternative.predicates.reverse();
+    alternative.directives.reverse();
+    for predicate in &mut alternative.predicates {
+        predicate.position = alternative.idents.len() - predicate.position;
+    }
+    alternative
+}
+
+/// Remove the first ident of a reversed alternative, to bind it to what precedes it
+fn take_first_ident(alternative: &mut Alternative) -> Ident {
+    let ident = alternative.idents.pop().unwrap();
+    for predicate in &mut alternative.predicates {
+        predicate.position = predicate.position.min(alternative.idents.len());
+    }
+    ident
+}
+
+/// Directives inside an alternative take no arguments, except `%prec` and `%label` which bind the ident after them.
+/// Lookaheads bind the ident after them as well.
+///
+/// The alternative is built reversed, with the position of each lookahead counted from the end,
+/// for [`alternative`] to put back in order.
+fn visit_parse_r3(node: &Node<ParseR3>) -> Alternative {
+    let mut alternative = match &node.node {
         ParseR3::NonTerminal(ident, rest) => {
-            visit_parse_r3(rest).push_return(Ident::NonTerminal(ident.into()))
+            let mut alternative = visit_parse_r3(rest);
+            let ident = Ident::NonTerminal(ident.into(), name_span(node, ident.len()));
+            alternative.idents.push(ident);
+            alternative
         }
         ParseR3::Terminal(ident, rest) => {
-            visit_parse_r3(rest).push_return(Ident::Terminal(ident.into()))
+            let mut alternative = visit_parse_r3(rest);
+            let ident = Ident::Terminal(ident.into(), name_span(node, ident.len()));
+            alternative.idents.push(ident);
+            alternative
         }
-        ParseR3::Epsilon => vec![],
-    }
+        ParseR3::And(rest) | ParseR3::Not(rest) => {
+            let mut alternative = visit_parse_r3(rest);
+            let ident = take_first_ident(&mut alternative);
+            alternative.predicates.push(Predicate {
+                position: alternative.idents.len(),
+                negated: matches!(node.node, ParseR3::Not(_)),
+                ident,
+            });
+            alternative
+        }
+        ParseR3::Directive(name, rest) => {
+            let mut alternative = visit_parse_r3(rest);
+            let args = if (name == "prec" || name == "label") && !alternative.idents.is_empty() {
+                vec![take_first_ident(&mut alternative).name().to_string()]
+            } else {
+                vec![]
+            };
+            // The span of an alternative directive covers its argument
+            let mut directive_span = name_span(node, name.len() + 1);
+            if let Some(arg) = args.first() {
+                let rest = span(rest);
+                directive_span =
+                    directive_span.to(Span::new(rest.file, rest.start..rest.start + arg.len()));
+            }
+            alternative.directives.push(Directive {
+                name: name.into(),
+                args,
+                span: directive_span,
+            });
+            alternative
+        }
+        ParseR3::Epsilon => Alternative::default(),
+    };
+    // Every level of the alternative covers the rest of it, so the outermost one covers all of it
+    alternative.span = span(node);
+    alternative
 }
 
-fn visit_parse_r4(node: &Node<ParseR4>) -> Vec<Vec<Ident>> {
+/// The alternatives after the first, last first
+fn visit_parse_r4(node: &Node<ParseR4>) -> Vec<Alternative> {
     match &node.node {
         ParseR4::Concat(rest) => visit_parse_r2(rest),
         ParseR4::Epsilon => vec![],
@@ -58,9 +159,14 @@ fn visit_parse_r4(node: &Node<ParseR4>) -> Vec<Vec<Ident>> {
 }
 
 pub fn transform(tree: &Node<ParseS>) -> Grammar {
-    Grammar {
-        rules: visit_parse_s(tree),
+    let mut grammar = Grammar::default();
+    for statement in visit_parse_s(tree).into_iter().rev() {
+        match statement {
+            Statement::Production(production) => grammar.rules.push(production),
+            Statement::Directive(directive) => grammar.directives.push(directive),
+        }
     }
+    grammar
 }
 
 impl From<Node<ParseS>> for Grammar {
@@ -68,3 +174,37 @@ impl From<Node<ParseS>> for Grammar {
         transform(&value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::testing::grammar;
+
+    fn text(src: &str, span: Span) -> &str {
+        &src[span.start..span.end]
+    }
+
+    #[test]
+    fn transform_keeps_spans() {
+        let src = "s: A  b %prec A | ;\n%deny unused_token;\nB: b;";
+        let grammar = grammar(src);
+        let Production::Parser(_, alternatives, span) = &grammar.rules[0] else {
+            panic!("{:?}", grammar.rules[0]);
+        };
+        assert_eq!(text(src, *span), "s: A  b %prec A |");
+        assert_eq!(text(src, grammar.rules[0].name_span()), "s");
+        assert_eq!(text(src, alternatives[0].span), "A  b %prec A");
+        let idents: Vec<&str> = alternatives[0]
+            .idents
+            .iter()
+            .map(|ident| match ident {
+                Ident::Terminal(_, span) | Ident::NonTerminal(_, span) => text(src, *span),
+            })
+            .collect();
+        assert_eq!(idents, ["A", "b"]);
+        assert_eq!(text(src, alternatives[0].directives[0].span), "%prec A");
+        assert_eq!(alternatives[1].span, Span::new(span.file, 18..18));
+        assert_eq!(text(src, grammar.directives[0].span), "%deny unused_token");
+        assert_eq!(text(src, grammar.rules[1].name_span()), "B");
+    }
+}
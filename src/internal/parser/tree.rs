@@ -1,3 +1,4 @@
+// @generated by `rantlr bootstrap` from `examples/rantlr.g4`. Do not edit by hand.
 use crate::ast::Node;
 
 #[derive(Debug)]
@@ -10,6 +11,7 @@ pub enum ParseS {
 pub enum ParseR1 {
     NonTerminal(String, Box<Node<ParseR2>>),
     Terminal(String, String),
+    Directive(String, Box<Node<ParseR3>>),
 }
 
 #[derive(Debug)]
@@ -21,6 +23,9 @@ pub enum ParseR2 {
 pub enum ParseR3 {
     NonTerminal(String, Box<Node<ParseR3>>),
     Terminal(String, Box<Node<ParseR3>>),
+    Directive(String, Box<Node<ParseR3>>),
+    And(Box<Node<ParseR3>>),
+    Not(Box<Node<ParseR3>>),
     Epsilon,
 }
 
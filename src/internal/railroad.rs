@@ -0,0 +1,532 @@
+use std::fmt::Write;
+
+use regex_syntax::ast::{self, Ast, RepetitionKind, RepetitionRange};
+
+use crate::internal::ast::{Grammar, Ident, Production};
+
+/// Width of a character in the monospace font of boxes
+const CHAR_WIDTH: f64 = 8.0;
+/// Half the height of a box, which the line runs through the middle of
+const HALF_HEIGHT: f64 = 11.0;
+/// Radius of the arcs branching off and back into the line
+const ARC: f64 = 10.0;
+/// Space between the items of a sequence, and around the line at each end of a diagram
+const GAP: f64 = 10.0;
+/// Least space between the boxes of two branches
+const SPACING: f64 = 8.0;
+
+/// Styles shared by every diagram
+const STYLE: &str = "\
+svg.railroad path { fill: none; stroke: #333; stroke-width: 1.5; }
+svg.railroad rect { stroke: #333; stroke-width: 1.5; }
+svg.railroad rect.terminal { fill: #fff7d6; }
+svg.railroad rect.nonterminal { fill: #e3efff; }
+svg.railroad rect.special { fill: #eee; }
+svg.railroad text { font: 13px monospace; text-anchor: middle; }
+svg.railroad text.comment { font: italic 11px monospace; }
+svg.railroad a text { fill: #0645ad; }";
+
+/// A railroad diagram, drawn along a line from its left to its right
+#[derive(Debug, PartialEq)]
+enum Node {
+    /// A lexer rule, or literal text in a lexer rule
+    Terminal(String),
+    /// A parser rule
+    NonTerminal(String),
+    /// A character class or assertion of a lexer rule, or a lookahead of a parser rule
+    Special(String),
+    /// Text alongside the line, such as how often a loop repeats
+    Comment(String),
+    /// Nothing, where the line goes straight through
+    Skip,
+    Sequence(Vec<Node>),
+    /// Branches below one another, the first on the line
+    Choice(Vec<Node>),
+    /// An item on the line, repeated along a loop back below it through a separator
+    Repeat(Box<Node>, Box<Node>),
+}
+
+/// The extent of a node: its width, and how far it reaches above and below the line
+#[derive(Debug, Clone, Copy)]
+struct Size {
+    width: f64,
+    up: f64,
+    down: f64,
+}
+
+impl Node {
+    fn optional(node: Node) -> Node {
+        Node::Choice(vec![Node::Skip, node])
+    }
+
+    fn sequence(mut nodes: Vec<Node>) -> Node {
+        nodes.retain(|node| *node != Node::Skip);
+        match nodes.len() {
+            0 => Node::Skip,
+            1 => nodes.pop().unwrap(),
+            _ => Node::Sequence(nodes),
+        }
+    }
+
+    fn size(&self) -> Size {
+        match self {
+            Node::Terminal(text) | Node::NonTerminal(text) | Node::Special(text) => Size {
+                width: text.chars().count() as f64 * CHAR_WIDTH + 2.0 * GAP,
+                up: HALF_HEIGHT,
+                down: HALF_HEIGHT,
+            },
+            Node::Comment(text) => Size {
+                width: text.chars().count() as f64 * CHAR_WIDTH,
+                up: HALF_HEIGHT / 2.0,
+                down: HALF_HEIGHT / 2.0,
+            },
+            Node::Skip => Size {
+                width: 0.0,
+                up: 0.0,
+                down: 0.0,
+            },
+            Node::Sequence(nodes) => {
+                let sizes: Vec<Size> = nodes.iter().map(Node::size).collect();
+                Size {
+                    width: sizes.iter().map(|size| size.width).sum::<f64>()
+                        + GAP * (sizes.len() - 1) as f64,
+                    up: sizes.iter().map(|size| size.up).fold(0.0, f64::max),
+                    down: sizes.iter().map(|size| size.down).fold(0.0, f64::max),
+                }
+            }
+            Node::Choice(branches) => {
+                let (lines, last) = branch_lines(branches);
+                let widest = branches.iter().map(|branch| branch.size().width);
+                Size {
+                    width: widest.fold(0.0, f64::max) + 4.0 * ARC,
+                    up: branches[0].size().up,
+                    down: lines[lines.len() - 1] + last.down,
+                }
+            }
+            Node::Repeat(item, separator) => {
+                let (item, separator) = (item.size(), separator.size());
+                Size {
+                    width: item.width.max(separator.width) + 2.0 * ARC,
+                    up: item.up,
+                    down: loop_line(item, separator) + separator.down,
+                }
+            }
+        }
+    }
+
+    /// Draw the node along the line at `y`, from `x` to `x` and its width
+    fn draw(&self, x: f64, y: f64, svg: &mut Svg) {
+        let size = self.size();
+        match self {
+            Node::Terminal(text) => svg.boxed(x, y, size.width, text, "terminal", HALF_HEIGHT),
+            Node::NonTerminal(text) => svg.boxed(x, y, size.width, text, "nonterminal", 0.0),
+            Node::Special(text) => svg.boxed(x, y, size.width, text, "special", 4.0),
+            Node::Comment(text) => svg.text(x + size.width / 2.0, y + 4.0, text, Some("comment")),
+            Node::Skip => {}
+            Node::Sequence(nodes) => {
+                let mut x = x;
+                for (index, node) in nodes.iter().enumerate() {
+                    if index > 0 {
+                        svg.path(format!("M{x} {y}h{GAP}"));
+                        x += GAP;
+                    }
+                    node.draw(x, y, svg);
+                    x += node.size().width;
+                }
+            }
+            Node::Choice(branches) => {
+                let (lines, _) = branch_lines(branches);
+                let right = x + size.width;
+                for (branch, line) in branches.iter().zip(lines) {
+                    let width = branch.size().width;
+                    let (start, end) = (x + 2.0 * ARC, x + 2.0 * ARC + width);
+                    if line == 0.0 {
+                        svg.path(format!("M{x} {y}H{start}M{end} {y}H{right}"));
+                    } else {
+                        let down = line - 2.0 * ARC;
+                        svg.path(format!(
+                            "M{x} {y}a{ARC} {ARC} 0 0 1 {ARC} {ARC}v{down}a{ARC} {ARC} 0 0 0 {ARC} {ARC}\
+                             M{end} {}H{}a{ARC} {ARC} 0 0 0 {ARC} -{ARC}v-{down}a{ARC} {ARC} 0 0 1 {ARC} -{ARC}",
+                            y + line,
+                            right - 2.0 * ARC,
+                        ));
+                    }
+                    branch.draw(start, y + line, svg);
+                }
+            }
+            Node::Repeat(item, separator) => {
+                let (item_size, separator_size) = (item.size(), separator.size());
+                let inner = size.width - 2.0 * ARC;
+                let line = loop_line(item_size, separator_size);
+                let down = line - 2.0 * ARC;
+                let (left, right) = (x + ARC, x + ARC + inner);
+                let separator_x = left + (inner - separator_size.width) / 2.0;
+                svg.path(format!(
+                    "M{x} {y}H{left}M{} {y}H{}",
+                    left + item_size.width,
+                    x + size.width,
+                ));
+                svg.path(format!(
+                    "M{right} {y}a{ARC} {ARC} 0 0 1 {ARC} {ARC}v{down}a{ARC} {ARC} 0 0 1 -{ARC} {ARC}\
+                     H{}M{separator_x} {}H{left}a{ARC} {ARC} 0 0 1 -{ARC} -{ARC}v-{down}a{ARC} {ARC} 0 0 1 {ARC} -{ARC}",
+                    separator_x + separator_size.width,
+                    y + line,
+                ));
+                item.draw(left, y, svg);
+                separator.draw(separator_x, y + line, svg);
+            }
+        }
+    }
+}
+
+/// How far below the line each branch of a choice runs, and the size of the last branch. Each
+/// is at least two arcs below the one before, to turn down to it and back.
+fn branch_lines(branches: &[Node]) -> (Vec<f64>, Size) {
+    let mut lines = vec![0.0];
+    let mut last = branches[0].size();
+    for branch in &branches[1..] {
+        let size = branch.size();
+        let line = lines[lines.len() - 1] + (last.down + SPACING + size.up).max(2.0 * ARC);
+        lines.push(line);
+        last = size;
+    }
+    (lines, last)
+}
+
+/// How far below the line a loop runs back
+fn loop_line(item: Size, separator: Size) -> f64 {
+    (item.down + SPACING + separator.up).max(2.0 * ARC)
+}
+
+/// The diagram of a lexer rule, from the structure of its pattern
+fn pattern(pattern: &str) -> Node {
+    match ast::parse::Parser::new().parse(pattern) {
+        Ok(ast) => regex(&ast, pattern),
+        Err(_) => Node::Special(pattern.into()),
+    }
+}
+
+fn regex(ast: &Ast, pattern: &str) -> Node {
+    let source = |span: &ast::Span| pattern[span.start.offset..span.end.offset].to_string();
+    match ast {
+        Ast::Empty(_) | Ast::Flags(_) => Node::Skip,
+        Ast::Literal(literal) => Node::Terminal(literal_text(literal.c)),
+        Ast::Dot(_) => Node::Special("any character".into()),
+        Ast::Assertion(assertion) => Node::Special(source(&assertion.span)),
+        Ast::ClassUnicode(class) => Node::Special(source(&class.span)),
+        Ast::ClassPerl(class) => Node::Special(source(&class.span)),
+        Ast::ClassBracketed(class) => Node::Special(source(&class.span)),
+        Ast::Group(group) => regex(&group.ast, pattern),
+        Ast::Alternation(alternation) => Node::Choice(
+            alternation
+                .asts
+                .iter()
+                .map(|ast| regex(ast, pattern))
+                .collect(),
+        ),
+        // Runs of literals read as one word
+        Ast::Concat(concat) => {
+            let mut nodes: Vec<Node> = vec![];
+            for ast in &concat.asts {
+                match (nodes.last_mut(), ast) {
+                    (Some(Node::Terminal(text)), Ast::Literal(literal)) => {
+                        text.push_str(&literal_text(literal.c));
+                    }
+                    _ => nodes.push(regex(ast, pattern)),
+                }
+            }
+            Node::sequence(nodes)
+        }
+        Ast::Repetition(repetition) => {
+            let item = Box::new(regex(&repetition.ast, pattern));
+            let skip = Box::new(Node::Skip);
+            match &repetition.op.kind {
+                RepetitionKind::ZeroOrOne => Node::optional(*item),
+                RepetitionKind::ZeroOrMore => Node::optional(Node::Repeat(item, skip)),
+                RepetitionKind::OneOrMore => Node::Repeat(item, skip),
+                RepetitionKind::Range(range) => {
+                    let min = match range {
+                        RepetitionRange::Exactly(min)
+                        | RepetitionRange::AtLeast(min)
+                        | RepetitionRange::Bounded(min, _) => *min,
+                    };
+                    let count = Node::Comment(source(&repetition.op.span));
+                    let node = Node::Repeat(item, Box::new(count));
+                    if min == 0 {
+                        Node::optional(node)
+                    } else {
+                        node
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A literal character as it shows in a box, with spaces and control characters made visible
+fn literal_text(c: char) -> String {
+    match c {
+        ' ' => "␣".into(),
+        c if c.is_control() => c.escape_default().to_string(),
+        c => c.into(),
+    }
+}
+
+/// The diagram of a rule: the alternatives of a parser rule, or the pattern of a lexer rule
+fn diagram(rule: &Production) -> Node {
+    let alternatives = match rule {
+        Production::Parser(_, alternatives, _) => alternatives,
+        Production::Lexer(_, source, _) => return pattern(source),
+    };
+    let mut branches: Vec<Node> = alternatives
+        .iter()
+        .map(|alternative| {
+            let mut nodes: Vec<Node> = alternative
+                .idents
+                .iter()
+                .map(|ident| match ident {
+                    Ident::Terminal(name, _) => Node::Terminal(name.clone()),
+                    Ident::NonTerminal(name, _) => Node::NonTerminal(name.clone()),
+                })
+                .collect();
+            // Lookaheads go before the ident they check, later ones first to keep positions
+            for predicate in alternative.predicates.iter().rev() {
+                let operator = if predicate.negated { '!' } else { '&' };
+                let node = Node::Special(format!("{operator}{}", predicate.ident.name()));
+                nodes.insert(predicate.position, node);
+            }
+            Node::sequence(nodes)
+        })
+        .collect();
+    match branches.len() {
+        1 => branches.pop().unwrap(),
+        _ => Node::Choice(branches),
+    }
+}
+
+/// SVG elements being written, with the rules boxes link to
+struct Svg<'a> {
+    out: String,
+    href: &'a dyn Fn(&str) -> Option<String>,
+}
+
+impl Svg<'_> {
+    fn path(&mut self, d: String) {
+        writeln!(self.out, r#"<path d="{d}"/>"#).unwrap();
+    }
+
+    fn text(&mut self, x: f64, y: f64, text: &str, class: Option<&str>) {
+        let class = class.map_or(String::new(), |class| format!(r#" class="{class}""#));
+        writeln!(
+            self.out,
+            r#"<text x="{x}" y="{y}"{class}>{}</text>"#,
+            escape(text)
+        )
+        .unwrap();
+    }
+
+    /// A box on the line, linked to the rule of its text if there is one
+    fn boxed(&mut self, x: f64, y: f64, width: f64, text: &str, class: &str, radius: f64) {
+        let href = match class {
+            "special" => None,
+            _ => (self.href)(text),
+        };
+        if let Some(href) = &href {
+            writeln!(self.out, r#"<a href="{}">"#, escape(href)).unwrap();
+        }
+        writeln!(
+            self.out,
+            r#"<rect x="{x}" y="{}" width="{width}" height="{}" rx="{radius}" class="{class}"/>"#,
+            y - HALF_HEIGHT,
+            2.0 * HALF_HEIGHT
+        )
+        .unwrap();
+        self.text(x + width / 2.0, y + 4.0, text, None);
+        if href.is_some() {
+            writeln!(self.out, "</a>").unwrap();
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Draw the diagram of a rule between the bars marking its start and end
+fn render(rule: &Production, href: &dyn Fn(&str) -> Option<String>, style: bool) -> String {
+    let node = diagram(rule);
+    let size = node.size();
+    let width = size.width + 4.0 * GAP;
+    let height = size.up + size.down + 2.0 * GAP;
+    let y = GAP + size.up;
+    let mut svg = Svg {
+        out: String::new(),
+        href,
+    };
+    writeln!(
+        svg.out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" class="railroad" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+    )
+    .unwrap();
+    writeln!(svg.out, "<title>{}</title>", escape(rule.name())).unwrap();
+    if style {
+        writeln!(svg.out, "<style>\n{STYLE}\n</style>").unwrap();
+    }
+    let (start, end) = (GAP, width - GAP);
+    svg.path(format!(
+        "M{start} {}v{}M{start} {y}h{GAP}M{} {y}H{end}M{end} {}v{}",
+        y - HALF_HEIGHT / 2.0,
+        HALF_HEIGHT,
+        start + GAP + size.width,
+        y - HALF_HEIGHT / 2.0,
+        HALF_HEIGHT,
+    ));
+    node.draw(start + GAP, y, &mut svg);
+    svg.out.push_str("</svg>\n");
+    svg.out
+}
+
+/// The railroad diagram of a rule as a standalone SVG image. The rules of the grammar it uses
+/// link to `href` of their name.
+pub fn svg(grammar: &Grammar, rule: &Production, href: impl Fn(&str) -> String) -> String {
+    let link = |name: &str| {
+        let defined = grammar.rules.iter().any(|rule| rule.name() == name);
+        defined.then(|| href(name))
+    };
+    render(rule, &link, true)
+}
+
+/// The railroad diagrams of every rule on a self-contained HTML page. Each rule is under a
+/// heading with its name as anchor, which the rules using it link to, and lists those rules.
+pub fn html(grammar: &Grammar, title: &str) -> String {
+    let used_by = grammar.used_by();
+    let link = |name: &str| {
+        let defined = grammar.rules.iter().any(|rule| rule.name() == name);
+        defined.then(|| format!("#{name}"))
+    };
+    let title = escape(title);
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>\nbody {{ font-family: sans-serif; margin: 2em; }}\nh2 {{ font: bold 15px monospace; }}\n\
+         p {{ font-size: 13px; }}\n{STYLE}\n</style>\n</head>\n<body>\n<h1>{title}</h1>\n"
+    );
+    for rule in &grammar.rules {
+        let name = rule.name();
+        writeln!(
+            out,
+            r##"<h2 id="{name}"><a href="#{name}">{name}</a></h2>"##
+        )
+        .unwrap();
+        out.push_str(&render(rule, &link, false));
+        if let Some(users) = used_by.get(name) {
+            let users: Vec<String> = users
+                .iter()
+                .map(|user| format!(r##"<a href="#{user}">{user}</a>"##))
+                .collect();
+            writeln!(out, "<p>Used by {}</p>", users.join(", ")).unwrap();
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_snapshot;
+
+    use super::*;
+
+    fn rule(source: &str) -> Production {
+        Grammar::parse(source).unwrap().rules.remove(0)
+    }
+
+    macro_rules! test_diagram {
+        ($name:ident, $source:expr, $expected:expr) => {
+            #[test]
+            fn $name() {
+                assert_eq!(diagram(&rule($source)), $expected);
+            }
+        };
+    }
+
+    fn terminal(text: &str) -> Node {
+        Node::Terminal(text.into())
+    }
+
+    fn special(text: &str) -> Node {
+        Node::Special(text.into())
+    }
+
+    test_diagram!(
+        parser_rule,
+        "s: s PLUS NUM | NUM | ;",
+        Node::Choice(vec![
+            Node::Sequence(vec![
+                Node::NonTerminal("s".into()),
+                terminal("PLUS"),
+                terminal("NUM")
+            ]),
+            terminal("NUM"),
+            Node::Skip,
+        ])
+    );
+    test_diagram!(
+        lookaheads,
+        "s: !A &B B C;",
+        Node::Sequence(vec![
+            special("!A"),
+            special("&B"),
+            terminal("B"),
+            terminal("C")
+        ])
+    );
+    test_diagram!(
+        literal_runs,
+        "KEYWORD: let|else\\x20if;",
+        Node::Choice(vec![terminal("let"), terminal("else␣if")])
+    );
+    test_diagram!(
+        repetitions,
+        "IDENT: [a-z][a-z0-9]*;",
+        Node::Sequence(vec![
+            special("[a-z]"),
+            Node::optional(Node::Repeat(
+                Box::new(special("[a-z0-9]")),
+                Box::new(Node::Skip)
+            )),
+        ])
+    );
+    test_diagram!(
+        counted_repetition,
+        "HEX: 0x[0-9a-f]{2,4};",
+        Node::Sequence(vec![
+            terminal("0x"),
+            Node::Repeat(
+                Box::new(special("[0-9a-f]")),
+                Box::new(Node::Comment("{2,4}".into()))
+            ),
+        ])
+    );
+
+    #[test]
+    fn svg_links_defined_rules() {
+        let grammar = Grammar::parse("sum: sum PLUS NUM | NUM; NUM: [0-9]+;").unwrap();
+        let svg = svg(&grammar, &grammar.rules[0], |name| format!("{name}.svg"));
+        assert_snapshot!(svg);
+    }
+
+    #[test]
+    fn html_page() {
+        let grammar = Grammar::parse("s: t A | !A t; t: A B; A: a;").unwrap();
+        let html = html(&grammar, "<g>");
+        assert!(html.contains("<title>&lt;g&gt;</title>"));
+        assert!(html.contains(r##"<h2 id="t"><a href="#t">t</a></h2>"##));
+        assert!(html.contains(r##"<p>Used by <a href="#s">s</a></p>"##));
+        assert!(html.contains(r##"<p>Used by <a href="#s">s</a>, <a href="#t">t</a></p>"##));
+        assert_eq!(html.matches("<svg ").count(), 3);
+        assert_eq!(html.matches(r##"<a href="#A">"##).count(), 3);
+    }
+}
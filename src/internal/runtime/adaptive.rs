@@ -0,0 +1,182 @@
+//! A recursive descent interpreter choosing alternatives with adaptive LL(*) prediction.
+//!
+//! The grammar is walked as an ATN with an explicit stack of rule invocations, so deep inputs
+//! do not overflow the native stack. Rules with a single alternative need no prediction.
+
+use std::sync::{Mutex, PoisonError};
+
+use crate::{
+    error::InputError,
+    internal::{
+        analysis::sets::reject_left_recursion,
+        ast::{Grammar, Symbol},
+    },
+};
+
+use super::{
+    allstar::{Atn, Predictor, Transition},
+    lexer::Lexer,
+    sppf::{Forest, Label},
+    Parse, Symbols,
+};
+
+/// The ATN of a grammar. The start state of each nonterminal has an epsilon transition to the
+/// first state of each of its rules, in the order of [`Symbols::rules`], and each alternative
+/// is a chain of states with one transition per symbol, ending with an epsilon transition to
+/// the stop state.
+pub fn atn(symbols: &Symbols) -> Atn {
+    let mut atn = Atn {
+        end: symbols.end(),
+        ..Atn::default()
+    };
+    let add_state = |atn: &mut Atn| {
+        atn.transitions.push(vec![]);
+        atn.transitions.len() - 1
+    };
+    for _ in &symbols.nonterminals {
+        let start = add_state(&mut atn);
+        let stop = add_state(&mut atn);
+        atn.rules.push((start, stop));
+    }
+    for rule in &symbols.rules {
+        let (start, stop) = atn.rules[symbols.nonterminal(rule.lhs)];
+        let mut current = add_state(&mut atn);
+        atn.transitions[start].push(Transition::Epsilon(current));
+        for symbol in &rule.rhs {
+            let next = add_state(&mut atn);
+            atn.transitions[current].push(match symbol {
+                Symbol::Terminal(name) => Transition::Terminal(symbols.terminal(name), next),
+                Symbol::NonTerminal(name) => Transition::Rule(symbols.nonterminal(name), next),
+            });
+            current = next;
+        }
+        atn.transitions[current].push(Transition::Epsilon(stop));
+    }
+    atn
+}
+
+/// A rule being parsed
+#[derive(Debug)]
+struct Frame {
+    nonterminal: usize,
+    rule: usize,
+    start: usize,
+    /// The state to return to, except for the start rule
+    follow: Option<usize>,
+    children: Vec<usize>,
+}
+
+#[derive(Debug)]
+pub struct Adaptive<'a> {
+    pub symbols: Symbols<'a>,
+    lexer: Lexer,
+    atn: Atn,
+    /// The rules of each nonterminal, by alternative
+    by_lhs: Vec<Vec<usize>>,
+    /// Shared by every parse, so that the DFA cache keeps growing
+    predictor: Mutex<Predictor>,
+}
+
+impl<'a> Adaptive<'a> {
+    pub fn new(grammar: &'a Grammar) -> Result<Self, InputError> {
+        reject_left_recursion(grammar)?;
+        let symbols = Symbols::new(grammar)?;
+        let lexer = Lexer::new(grammar, &symbols.terminals, symbols.end())?;
+        let atn = atn(&symbols);
+        let mut by_lhs = vec![vec![]; symbols.nonterminals.len()];
+        for (index, rule) in symbols.rules.iter().enumerate() {
+            by_lhs[symbols.nonterminal(rule.lhs)].push(index);
+        }
+        Ok(Adaptive {
+            predictor: Mutex::new(Predictor::new(atn.clone())),
+            symbols,
+            lexer,
+            atn,
+            by_lhs,
+        })
+    }
+
+    pub fn parse<'input>(&self, input: &'input str) -> Result<Parse<'input>, InputError> {
+        let tokens = self.lexer.tokenize(input)?;
+        let terminals: Vec<usize> = tokens.iter().map(|token| token.terminal).collect();
+        let mut predictor = self
+            .predictor
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut forest = Forest::default();
+        let mut frames: Vec<Frame> = vec![];
+        // The follow states of the rules being parsed, as the calling context of predictions
+        let mut context = vec![];
+        let mut call = Some((0, None));
+        let mut position = 0;
+        let mut state = 0;
+        loop {
+            if let Some((nonterminal, follow)) = call.take() {
+                let alternatives = &self.by_lhs[nonterminal];
+                let alternative = if alternatives.len() == 1 {
+                    0
+                } else {
+                    predictor
+                        .predict(nonterminal, &terminals, position, &context)
+                        .map_err(|err| {
+                            self.symbols.unexpected(
+                                &tokens[err.position.min(tokens.len() - 1)],
+                                &err.expected,
+                            )
+                        })?
+                };
+                let Transition::Epsilon(first) =
+                    self.atn.transitions[self.atn.rules[nonterminal].0][alternative]
+                else {
+                    unreachable!("start states only have epsilon transitions")
+                };
+                frames.push(Frame {
+                    nonterminal,
+                    rule: alternatives[alternative],
+                    start: position,
+                    follow,
+                    children: vec![],
+                });
+                state = first;
+            }
+            let frame = frames.last_mut().expect("a rule is being parsed");
+            match self.atn.transitions[state][0] {
+                Transition::Terminal(terminal, next) => {
+                    if terminals[position] != terminal {
+                        return Err(self.symbols.unexpected(&tokens[position], &[terminal]));
+                    }
+                    let leaf = forest.node(Label::Terminal(terminal), position, position + 1);
+                    frame.children.push(leaf);
+                    position += 1;
+                    state = next;
+                }
+                Transition::Rule(nonterminal, follow) => {
+                    context.push(follow);
+                    call = Some((nonterminal, Some(follow)));
+                }
+                Transition::Epsilon(_) => {
+                    let frame = frames.pop().unwrap();
+                    let label = Label::NonTerminal(frame.nonterminal);
+                    let node = forest.node(label, frame.start, position);
+                    forest.pack(node, frame.rule, frame.children);
+                    match (frames.last_mut(), frame.follow) {
+                        (Some(parent), Some(follow)) => {
+                            parent.children.push(node);
+                            context.pop();
+                            state = follow;
+                        }
+                        _ => {
+                            let end = self.symbols.end();
+                            if terminals[position] != end {
+                                return Err(self.symbols.unexpected(&tokens[position], &[end]));
+                            }
+                            forest.root = Some(node);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        self.symbols.disambiguate(tokens, forest)
+    }
+}
@@ -0,0 +1,366 @@
+//! Adaptive LL(*) prediction, as in ANTLR 4.
+//!
+//! A grammar is simulated as an augmented transition network, where every parser rule has a start
+//! state with an epsilon transition to the first state of each of its alternatives. To choose an
+//! alternative, prediction follows every alternative at once over the upcoming tokens until only
+//! one is left. It first runs without the rules that called the decision (SLL), caching what it
+//! learns per decision as a DFA over sets of configurations. Only when SLL finds a conflict does
+//! it retry with the full calling context (LL), which is exact but not cached.
+//!
+//! Left recursive rules would make prediction loop forever, and must be rewritten beforehand.
+//!
+//! This module only depends on `std`, as generated parsers embed it verbatim.
+
+use std::collections::{BTreeSet, HashMap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Epsilon(usize),
+    /// Match a terminal and go to a state
+    Terminal(usize, usize),
+    /// Call the start state of a nonterminal, returning to a follow state
+    Rule(usize, usize),
+}
+
+/// An augmented transition network, with terminals, nonterminals and states numbered from zero.
+/// Nonterminal 0 is the start rule, which is followed by the end of input.
+#[derive(Debug, Clone, Default)]
+pub struct Atn {
+    pub transitions: Vec<Vec<Transition>>,
+    /// The start and stop state of each nonterminal
+    pub rules: Vec<(usize, usize)>,
+    /// The terminal at the end of input
+    pub end: usize,
+}
+
+/// No alternative can continue with the token at `position`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoViableAlternative {
+    pub position: usize,
+    pub expected: Vec<usize>,
+}
+
+/// An ATN state reached by an alternative, with the follow states of the rules called on the
+/// way there, innermost last. In LL mode, `outer` counts the calling rules it returned into.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Config {
+    state: usize,
+    alt: usize,
+    stack: Vec<usize>,
+    outer: usize,
+}
+
+#[derive(Debug)]
+struct DfaState {
+    configs: BTreeSet<Config>,
+    prediction: Prediction,
+    /// Target states by terminal, where `None` means no configuration can continue
+    edges: HashMap<usize, Option<usize>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Prediction {
+    /// Only this alternative is left
+    Unique(usize),
+    /// Every configuration shares its state and stack with another alternative,
+    /// so no further token can tell them apart
+    Conflict(usize),
+    Undecided,
+}
+
+#[derive(Debug, Default)]
+struct Dfa {
+    states: Vec<DfaState>,
+    index: HashMap<BTreeSet<Config>, usize>,
+}
+
+/// Adaptive prediction over an ATN, with a DFA cache per decision which is kept across parses
+#[derive(Debug)]
+pub struct Predictor {
+    atn: Atn,
+    /// The nonterminal each stop state belongs to
+    stops: Vec<Option<usize>>,
+    /// The states following each call of a nonterminal
+    follows: Vec<Vec<usize>>,
+    dfas: Vec<Dfa>,
+}
+
+impl Predictor {
+    pub fn new(atn: Atn) -> Self {
+        let mut stops = vec![None; atn.transitions.len()];
+        for (nonterminal, &(_, stop)) in atn.rules.iter().enumerate() {
+            stops[stop] = Some(nonterminal);
+        }
+        let mut follows = vec![vec![]; atn.rules.len()];
+        for transition in atn.transitions.iter().flatten() {
+            if let Transition::Rule(nonterminal, follow) = *transition {
+                follows[nonterminal].push(follow);
+            }
+        }
+        let dfas = atn.rules.iter().map(|_| Dfa::default()).collect();
+        Predictor {
+            atn,
+            stops,
+            follows,
+            dfas,
+        }
+    }
+
+    /// The alternative of `nonterminal` to parse the tokens from `position` with, by index in the
+    /// epsilon transitions of its start state. `context` holds the follow states of the rules
+    /// being parsed, innermost last. When the input is ambiguous, the first alternative wins.
+    pub fn predict(
+        &mut self,
+        nonterminal: usize,
+        tokens: &[usize],
+        position: usize,
+        context: &[usize],
+    ) -> Result<usize, NoViableAlternative> {
+        match self.predict_sll(nonterminal, tokens, position)? {
+            Prediction::Unique(alt) => Ok(alt),
+            _ => self.predict_ll(nonterminal, tokens, position, context),
+        }
+    }
+
+    fn start(&self, nonterminal: usize, context: Option<&[usize]>) -> BTreeSet<Config> {
+        let mut configs = BTreeSet::new();
+        let start = self.atn.rules[nonterminal].0;
+        for (alt, transition) in self.atn.transitions[start].iter().enumerate() {
+            if let Transition::Epsilon(state) = *transition {
+                let config = Config {
+                    state,
+                    alt,
+                    stack: vec![],
+                    outer: 0,
+                };
+                self.closure(config, context, &mut configs);
+            }
+        }
+        configs
+    }
+
+    fn predict_sll(
+        &mut self,
+        nonterminal: usize,
+        tokens: &[usize],
+        position: usize,
+    ) -> Result<Prediction, NoViableAlternative> {
+        if self.dfas[nonterminal].states.is_empty() {
+            let configs = self.start(nonterminal, None);
+            self.add_state(nonterminal, configs);
+        }
+        let mut state = 0;
+        let mut position = position;
+        loop {
+            let dfa = &self.dfas[nonterminal];
+            match dfa.states[state].prediction {
+                Prediction::Undecided => {}
+                prediction => return Ok(prediction),
+            }
+            let terminal = tokens.get(position).copied().unwrap_or(self.atn.end);
+            let next = match dfa.states[state].edges.get(&terminal) {
+                Some(&next) => next,
+                None => {
+                    let reach = self.reach(&dfa.states[state].configs, terminal, None);
+                    let next = (!reach.is_empty()).then(|| self.add_state(nonterminal, reach));
+                    self.dfas[nonterminal].states[state]
+                        .edges
+                        .insert(terminal, next);
+                    next
+                }
+            };
+            let Some(next) = next else {
+                let configs = &self.dfas[nonterminal].states[state].configs;
+                return self.finished(configs, position).map(Prediction::Unique);
+            };
+            state = next;
+            position += 1;
+        }
+    }
+
+    fn predict_ll(
+        &self,
+        nonterminal: usize,
+        tokens: &[usize],
+        position: usize,
+        context: &[usize],
+    ) -> Result<usize, NoViableAlternative> {
+        let mut configs = self.start(nonterminal, Some(context));
+        let mut position = position;
+        loop {
+            match self.classify(&configs) {
+                Prediction::Unique(alt) | Prediction::Conflict(alt) => return Ok(alt),
+                Prediction::Undecided => {}
+            }
+            let terminal = tokens.get(position).copied().unwrap_or(self.atn.end);
+            let reach = self.reach(&configs, terminal, Some(context));
+            if reach.is_empty() {
+                return self.finished(&configs, position);
+            }
+            configs = reach;
+            position += 1;
+        }
+    }
+
+    fn add_state(&mut self, nonterminal: usize, configs: BTreeSet<Config>) -> usize {
+        let prediction = self.classify(&configs);
+        let dfa = &mut self.dfas[nonterminal];
+        if let Some(&state) = dfa.index.get(&configs) {
+            return state;
+        }
+        dfa.index.insert(configs.clone(), dfa.states.len());
+        dfa.states.push(DfaState {
+            configs,
+            prediction,
+            edges: HashMap::new(),
+        });
+        dfa.states.len() - 1
+    }
+
+    fn classify(&self, configs: &BTreeSet<Config>) -> Prediction {
+        let first = configs.iter().map(|config| config.alt).min();
+        let Some(first) = first else {
+            return Prediction::Undecided;
+        };
+        if configs.iter().all(|config| config.alt == first) {
+            return Prediction::Unique(first);
+        }
+        let mut alts: HashMap<(usize, &[usize], usize), BTreeSet<usize>> = HashMap::new();
+        for config in configs {
+            alts.entry((config.state, &config.stack, config.outer))
+                .or_default()
+                .insert(config.alt);
+        }
+        if alts.values().all(|alts| alts.len() > 1) {
+            Prediction::Conflict(first)
+        } else {
+            Prediction::Undecided
+        }
+    }
+
+    /// When no configuration can continue, the first alternative which already finished the
+    /// decision's rule is taken, leaving the error to be found by the parser
+    fn finished(
+        &self,
+        configs: &BTreeSet<Config>,
+        position: usize,
+    ) -> Result<usize, NoViableAlternative> {
+        configs
+            .iter()
+            .filter(|config| config.outer > 0 || self.is_final(config))
+            .map(|config| config.alt)
+            .min()
+            .ok_or_else(|| {
+                let mut expected: Vec<usize> = configs
+                    .iter()
+                    .flat_map(|config| &self.atn.transitions[config.state])
+                    .filter_map(|transition| match transition {
+                        Transition::Terminal(terminal, _) => Some(*terminal),
+                        _ => None,
+                    })
+                    .collect();
+                expected.sort_unstable();
+                expected.dedup();
+                NoViableAlternative { position, expected }
+            })
+    }
+
+    fn is_final(&self, config: &Config) -> bool {
+        self.stops[config.state].is_some() && config.stack.is_empty()
+    }
+
+    /// The configurations after matching a terminal. Final configurations only match the end of input.
+    fn reach(
+        &self,
+        configs: &BTreeSet<Config>,
+        terminal: usize,
+        context: Option<&[usize]>,
+    ) -> BTreeSet<Config> {
+        let mut reach = BTreeSet::new();
+        for config in configs {
+            if self.is_final(config) {
+                if terminal == self.atn.end {
+                    reach.insert(config.clone());
+                }
+                continue;
+            }
+            for transition in &self.atn.transitions[config.state] {
+                if let Transition::Terminal(matched, state) = *transition {
+                    if matched == terminal {
+                        let config = Config {
+                            state,
+                            ..config.clone()
+                        };
+                        self.closure(config, context, &mut reach);
+                    }
+                }
+            }
+        }
+        reach
+    }
+
+    /// Add the configurations reachable without matching a terminal: those about to match one,
+    /// and final ones at the end of the start rule. Returning from a rule with an empty stack
+    /// goes to the state the calling context gives (LL), or to every state following a call of
+    /// the rule (SLL).
+    fn closure(&self, config: Config, context: Option<&[usize]>, out: &mut BTreeSet<Config>) {
+        let mut visited = BTreeSet::new();
+        let mut work = vec![config];
+        while let Some(config) = work.pop() {
+            if !visited.insert(config.clone()) {
+                continue;
+            }
+            if let Some(nonterminal) = self.stops[config.state] {
+                let mut config = config;
+                if let Some(follow) = config.stack.pop() {
+                    config.state = follow;
+                    work.push(config);
+                    continue;
+                }
+                match context {
+                    Some(context) if config.outer < context.len() => {
+                        config.state = context[context.len() - 1 - config.outer];
+                        config.outer += 1;
+                        work.push(config);
+                    }
+                    Some(_) => {
+                        out.insert(config);
+                    }
+                    None => {
+                        for &state in &self.follows[nonterminal] {
+                            work.push(Config {
+                                state,
+                                outer: 1,
+                                ..config.clone()
+                            });
+                        }
+                        if nonterminal == 0 {
+                            out.insert(config);
+                        }
+                    }
+                }
+                continue;
+            }
+            for transition in &self.atn.transitions[config.state] {
+                match *transition {
+                    Transition::Epsilon(state) => work.push(Config {
+                        state,
+                        ..config.clone()
+                    }),
+                    Transition::Rule(nonterminal, follow) => {
+                        let mut stack = config.stack.clone();
+                        stack.push(follow);
+                        work.push(Config {
+                            state: self.atn.rules[nonterminal].0,
+                            stack,
+                            ..config.clone()
+                        });
+                    }
+                    Transition::Terminal(..) => {
+                        out.insert(config.clone());
+                    }
+                }
+            }
+        }
+    }
+}
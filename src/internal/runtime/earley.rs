@@ -0,0 +1,467 @@
+//! An Earley parser, which accepts any context-free grammar.
+//!
+//! Nullable nonterminals are handled as by Aycock and Horspool: predicting a nullable
+//! nonterminal also steps over it. Leo's deterministic reduction paths keep the chart of a
+//! right recursion linear, as only the topmost completion of a path is added to it.
+//!
+//! Each item records how it was reached, and the forest is built top down from the
+//! finished chart by following only those links. A Leo path is expanded once, when the
+//! builder reaches its top, so building stays linear wherever recognizing was.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    error::InputError,
+    internal::{analysis::sets::nullable, ast::Grammar},
+};
+
+use super::{
+    lexer::{Lexer, Token},
+    sppf::{Forest, Label},
+    Parse, Symbols,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Item {
+    rule: usize,
+    dot: usize,
+    origin: usize,
+}
+
+/// How an item was reached from the one with its dot a symbol back
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Link {
+    /// The symbol before the dot starts at this set
+    Symbol(usize),
+    /// The item tops the Leo path of a nonterminal, by the set it starts at and the nonterminal
+    Leo(usize, usize),
+}
+
+/// The completion a nonterminal finished at some set leads to, when that is the only way
+/// to continue. `below` is the next completion on the path, if any.
+#[derive(Debug, Clone, Copy)]
+struct Leo {
+    rule: usize,
+    origin: usize,
+    below: Option<(usize, usize)>,
+    top: (usize, usize),
+}
+
+#[derive(Debug, Default)]
+struct Chart {
+    sets: Vec<Vec<Item>>,
+    /// The index of each item in its set
+    members: Vec<HashMap<Item, usize>>,
+    /// How each item was reached, by set and index
+    links: Vec<Vec<Vec<Link>>>,
+    /// Memoized paths, by the set the nonterminal starts at and the nonterminal
+    leo: HashMap<(usize, usize), Option<Leo>>,
+}
+
+impl Chart {
+    fn add(&mut self, set: usize, item: Item, link: Option<Link>) {
+        let (sets, links) = (&mut self.sets[set], &mut self.links[set]);
+        let index = *self.members[set].entry(item).or_insert_with(|| {
+            sets.push(item);
+            links.push(vec![]);
+            sets.len() - 1
+        });
+        links[index].extend(link);
+    }
+
+    /// How an item was reached, with the symbols before the dot split earliest first
+    fn links(&self, set: usize, item: Item) -> Vec<Link> {
+        let mut links = self.links[set][self.members[set][&item]].clone();
+        links.sort_unstable();
+        // Nullable symbols are stepped over both when predicted and when completed
+        links.dedup();
+        links
+    }
+}
+
+#[derive(Debug)]
+pub struct Earley<'a> {
+    pub symbols: Symbols<'a>,
+    lexer: Lexer,
+    /// The nonterminal of each rule, and its symbols
+    rules: Vec<(usize, Vec<Label>)>,
+    by_lhs: Vec<Vec<usize>>,
+    nullable: Vec<bool>,
+}
+
+impl<'a> Earley<'a> {
+    pub fn new(grammar: &'a Grammar) -> Result<Self, InputError> {
+        let symbols = Symbols::new(grammar)?;
+        let lexer = Lexer::new(grammar, &symbols.terminals, symbols.end())?;
+        let rules: Vec<(usize, Vec<Label>)> = symbols
+            .rules
+            .iter()
+            .map(|rule| {
+                let rhs = rule
+                    .rhs
+                    .iter()
+                    .map(|&symbol| symbols.label(symbol))
+                    .collect();
+                (symbols.nonterminal(rule.lhs), rhs)
+            })
+            .collect();
+        let mut by_lhs = vec![vec![]; symbols.nonterminals.len()];
+        for (index, (lhs, _)) in rules.iter().enumerate() {
+            by_lhs[*lhs].push(index);
+        }
+        let nullable_names = nullable(grammar);
+        let nullable = symbols
+            .nonterminals
+            .iter()
+            .map(|name| nullable_names.contains(name))
+            .collect();
+        Ok(Earley {
+            symbols,
+            lexer,
+            rules,
+            by_lhs,
+            nullable,
+        })
+    }
+
+    pub fn parse<'input>(&self, input: &'input str) -> Result<Parse<'input>, InputError> {
+        let tokens = self.lexer.tokenize(input)?;
+        // The end of input token is not part of any rule
+        let terminals: Vec<usize> = tokens[..tokens.len() - 1]
+            .iter()
+            .map(|token| token.terminal)
+            .collect();
+        let chart = self.recognize(&terminals, &tokens)?;
+        let mut builder = Builder {
+            earley: self,
+            chart: &chart,
+            terminals: &terminals,
+            completed: vec![None; terminals.len() + 1],
+            skipped: vec![HashMap::new(); terminals.len() + 1],
+            expanded: HashSet::new(),
+            built: HashSet::new(),
+            pending: vec![],
+            forest: Forest::default(),
+        };
+        builder.forest.root = Some(builder.build(0));
+        self.symbols.disambiguate(tokens, builder.forest)
+    }
+
+    fn next_symbol(&self, item: Item) -> Option<Label> {
+        self.rules[item.rule].1.get(item.dot).copied()
+    }
+
+    fn recognize(&self, terminals: &[usize], tokens: &[Token]) -> Result<Chart, InputError> {
+        let length = terminals.len();
+        let mut chart = Chart {
+            sets: vec![vec![]; length + 1],
+            members: vec![HashMap::new(); length + 1],
+            links: vec![vec![]; length + 1],
+            ..Chart::default()
+        };
+        for &rule in self.by_lhs.first().into_iter().flatten() {
+            chart.add(
+                0,
+                Item {
+                    rule,
+                    dot: 0,
+                    origin: 0,
+                },
+                None,
+            );
+        }
+
+        for (set, token) in tokens.iter().enumerate() {
+            // Nonterminals completed in this set, with their origins
+            let mut completed = HashSet::new();
+            let mut index = 0;
+            while index < chart.sets[set].len() {
+                let item = chart.sets[set][index];
+                let advanced = Item {
+                    dot: item.dot + 1,
+                    ..item
+                };
+                match self.next_symbol(item) {
+                    None => self.complete(&mut chart, &mut completed, set, item),
+                    Some(Label::Terminal(terminal)) => {
+                        if terminals.get(set) == Some(&terminal) {
+                            chart.add(set + 1, advanced, Some(Link::Symbol(set)));
+                        }
+                    }
+                    Some(Label::NonTerminal(nonterminal)) => {
+                        for &rule in &self.by_lhs[nonterminal] {
+                            chart.add(
+                                set,
+                                Item {
+                                    rule,
+                                    dot: 0,
+                                    origin: set,
+                                },
+                                None,
+                            );
+                        }
+                        if self.nullable[nonterminal] {
+                            chart.add(set, advanced, Some(Link::Symbol(set)));
+                        }
+                    }
+                    Some(Label::Intermediate(..)) => {
+                        unreachable!("rules only hold terminals and nonterminals")
+                    }
+                }
+                index += 1;
+            }
+
+            let accepted = set == length
+                && chart.sets[set].iter().any(|item| {
+                    item.origin == 0
+                        && self.rules[item.rule].0 == 0
+                        && self.next_symbol(*item).is_none()
+                });
+            if accepted || (set < length && !chart.sets[set + 1].is_empty()) {
+                continue;
+            }
+            let mut expected: Vec<usize> = chart.sets[set]
+                .iter()
+                .filter_map(|&item| match self.next_symbol(item) {
+                    Some(Label::Terminal(terminal)) => Some(terminal),
+                    _ => None,
+                })
+                .collect();
+            expected.sort_unstable();
+            expected.dedup();
+            return Err(self.symbols.unexpected(token, &expected));
+        }
+        Ok(chart)
+    }
+
+    /// Advance the items waiting for the nonterminal of a complete item, once for each
+    /// nonterminal and origin. Completions within the set are nullable, and their waiting
+    /// items were already advanced when predicting them.
+    fn complete(
+        &self,
+        chart: &mut Chart,
+        completed: &mut HashSet<(usize, usize)>,
+        set: usize,
+        item: Item,
+    ) {
+        let lhs = self.rules[item.rule].0;
+        if !completed.insert((lhs, item.origin)) {
+            return;
+        }
+        if item.origin < set {
+            if let Some(leo) = self.leo(chart, item.origin, lhs) {
+                let (rule, origin) = leo.top;
+                let dot = self.rules[rule].1.len();
+                let link = Link::Leo(item.origin, lhs);
+                chart.add(set, Item { rule, dot, origin }, Some(link));
+                return;
+            }
+        }
+        for index in 0..chart.sets[item.origin].len() {
+            let waiting = chart.sets[item.origin][index];
+            if self.next_symbol(waiting) == Some(Label::NonTerminal(lhs)) {
+                chart.add(
+                    set,
+                    Item {
+                        dot: waiting.dot + 1,
+                        ..waiting
+                    },
+                    Some(Link::Symbol(item.origin)),
+                );
+            }
+        }
+    }
+
+    /// The deterministic reduction path of `nonterminal` started at `set`: when exactly one item
+    /// of the set waits for it, and that item is complete after it. A path ends at the start
+    /// rule from the first set, so that accepting sees its completion.
+    fn leo(&self, chart: &mut Chart, set: usize, nonterminal: usize) -> Option<Leo> {
+        if let Some(&leo) = chart.leo.get(&(set, nonterminal)) {
+            return leo;
+        }
+        // A cyclic path ends where it meets itself
+        chart.leo.insert((set, nonterminal), None);
+        let mut waiting = chart.sets[set]
+            .iter()
+            .filter(|&&item| self.next_symbol(item) == Some(Label::NonTerminal(nonterminal)));
+        let leo = match (waiting.next(), waiting.next()) {
+            (Some(&item), None) if item.dot + 1 == self.rules[item.rule].1.len() => {
+                let lhs = self.rules[item.rule].0;
+                let below = match (lhs, item.origin) {
+                    (0, 0) => None,
+                    _ => self.leo(chart, item.origin, lhs),
+                };
+                Some(Leo {
+                    rule: item.rule,
+                    origin: item.origin,
+                    below: below.map(|_| (item.origin, lhs)),
+                    top: below.map_or((item.rule, item.origin), |below| below.top),
+                })
+            }
+            _ => None,
+        };
+        chart.leo.insert((set, nonterminal), leo);
+        leo
+    }
+}
+
+/// Completions of a set, by nonterminal and origin
+type Completions<T> = HashMap<(usize, usize), Vec<T>>;
+
+/// Builds the forest top down from the chart, following the links of its items
+struct Builder<'e, 'a> {
+    earley: &'e Earley<'a>,
+    chart: &'e Chart,
+    terminals: &'e [usize],
+    /// Rules of the complete items of each set, by nonterminal and origin
+    completed: Vec<Option<Completions<usize>>>,
+    /// Completions of each set skipped by Leo paths, by nonterminal and origin, as rules and
+    /// the set their last symbol starts at
+    skipped: Vec<Completions<(usize, usize)>>,
+    /// Leo paths expanded so far, by the set they end at and their key
+    expanded: HashSet<(usize, (usize, usize))>,
+    built: HashSet<(Label, usize, usize)>,
+    pending: Vec<(Label, usize, usize)>,
+    forest: Forest,
+}
+
+impl Builder<'_, '_> {
+    fn completed(&mut self, nonterminal: usize, origin: usize, set: usize) -> Vec<usize> {
+        let (earley, chart) = (self.earley, self.chart);
+        let completed = self.completed[set].get_or_insert_with(|| {
+            let mut completed = Completions::new();
+            for &item in &chart.sets[set] {
+                if earley.next_symbol(item).is_none() {
+                    let lhs = earley.rules[item.rule].0;
+                    completed
+                        .entry((lhs, item.origin))
+                        .or_default()
+                        .push(item.rule);
+                }
+            }
+            for rules in completed.values_mut() {
+                rules.sort_unstable();
+            }
+            completed
+        });
+        completed
+            .get(&(nonterminal, origin))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Record the completions skipped by a Leo path ending at `set`, the first time it is reached
+    fn expand(&mut self, set: usize, key: (usize, usize)) {
+        if !self.expanded.insert((set, key)) {
+            return;
+        }
+        let mut next = Some(key);
+        while let Some((start, nonterminal)) = next {
+            let leo = self.chart.leo[&(start, nonterminal)]
+                .expect("items are linked along existing paths");
+            let lhs = self.earley.rules[leo.rule].0;
+            self.skipped[set]
+                .entry((lhs, leo.origin))
+                .or_default()
+                .push((leo.rule, start));
+            next = leo.below;
+        }
+    }
+
+    /// The forest node of a symbol over `start..end`, queued to be built if it is new
+    fn node(&mut self, label: Label, start: usize, end: usize) -> usize {
+        if !matches!(label, Label::Terminal(_)) && self.built.insert((label, start, end)) {
+            self.pending.push((label, start, end));
+        }
+        self.forest.node(label, start, end)
+    }
+
+    /// Add the derivation of the first `dot` symbols of a rule started at `origin` whose
+    /// last symbol spans `middle..end`
+    fn pack(
+        &mut self,
+        node: usize,
+        rule: usize,
+        dot: usize,
+        origin: usize,
+        middle: usize,
+        end: usize,
+    ) {
+        let symbols = &self.earley.rules[rule].1;
+        let (first, symbol) = (symbols[0], symbols[dot - 1]);
+        let last = self.node(symbol, middle, end);
+        let children = match dot {
+            1 => vec![last],
+            2 => vec![self.node(first, origin, middle), last],
+            _ => vec![
+                self.node(Label::Intermediate(rule, dot - 1), origin, middle),
+                last,
+            ],
+        };
+        self.forest.pack(node, rule, children);
+    }
+
+    /// Build every node reachable from the root, which must derive the whole input.
+    /// A queue rather than recursion keeps deep derivations off the call stack.
+    fn build(&mut self, root: usize) -> usize {
+        let root = self.node(Label::NonTerminal(root), 0, self.terminals.len());
+        while let Some((label, start, end)) = self.pending.pop() {
+            let node = self.forest.node(label, start, end);
+            match label {
+                Label::NonTerminal(nonterminal) => {
+                    // Rules with the start of their last symbol, if they have any
+                    let mut derivations = vec![];
+                    for rule in self.completed(nonterminal, start, end) {
+                        let dot = self.earley.rules[rule].1.len();
+                        if dot == 0 {
+                            derivations.push((rule, None));
+                        }
+                        let item = Item {
+                            rule,
+                            dot,
+                            origin: start,
+                        };
+                        for link in self.chart.links(end, item) {
+                            match link {
+                                Link::Symbol(middle) => derivations.push((rule, Some(middle))),
+                                Link::Leo(from, nonterminal) => {
+                                    self.expand(end, (from, nonterminal))
+                                }
+                            }
+                        }
+                    }
+                    if let Some(skipped) = self.skipped[end].get(&(nonterminal, start)) {
+                        derivations
+                            .extend(skipped.iter().map(|&(rule, middle)| (rule, Some(middle))));
+                    }
+                    derivations.sort_unstable();
+                    for (rule, middle) in derivations {
+                        match middle {
+                            Some(middle) => {
+                                let dot = self.earley.rules[rule].1.len();
+                                self.pack(node, rule, dot, start, middle, end);
+                            }
+                            None => {
+                                self.forest.pack(node, rule, vec![]);
+                            }
+                        }
+                    }
+                }
+                Label::Intermediate(rule, dot) => {
+                    let item = Item {
+                        rule,
+                        dot,
+                        origin: start,
+                    };
+                    for link in self.chart.links(end, item) {
+                        if let Link::Symbol(middle) = link {
+                            self.pack(node, rule, dot, start, middle, end);
+                        }
+                    }
+                }
+                Label::Terminal(_) => unreachable!("terminals are never queued"),
+            }
+        }
+        root
+    }
+}
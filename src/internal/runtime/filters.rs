@@ -0,0 +1,170 @@
+use std::cmp::Ordering;
+
+use crate::internal::{
+    ast::{Grammar, Symbol},
+    table::precedence::{Associativity, Precedence},
+};
+
+use super::{sppf::Filters, Symbols};
+
+/// The disambiguation filters declared by a grammar:
+///
+/// - priorities and associativity, from `%left`, `%right`, `%nonassoc` and `%prec`
+/// - `%reject rule TERMINALS;`, so the rule never derives exactly those terminals
+/// - `%prefer` and `%avoid` on alternatives
+/// - `%follow_restrict rule TERMINALS;`, so the rule is never followed by those terminals
+///
+/// Filters naming unknown symbols are ignored, as reported by the `invalid_filter` lint.
+pub fn filters(grammar: &Grammar, symbols: &Symbols) -> Filters {
+    let mut filters = Filters::default();
+    operator_filters(grammar, symbols, &mut filters);
+
+    for directive in &grammar.directives {
+        let list = match directive.name.as_str() {
+            "reject" => &mut filters.rejects,
+            "follow_restrict" => &mut filters.follow,
+            _ => continue,
+        };
+        let Some((rule, rest)) = directive.args.split_first() else {
+            continue;
+        };
+        let Some(nonterminal) = symbols.nonterminals.iter().position(|&other| other == rule) else {
+            continue;
+        };
+        let terminals = rest
+            .iter()
+            .filter_map(|name| symbols.terminals.iter().position(|&other| other == name))
+            .collect();
+        list.push((nonterminal, terminals));
+    }
+
+    let alternatives = grammar
+        .parser_rules()
+        .flat_map(|(_, alternatives)| alternatives);
+    for (rule, alternative) in alternatives.enumerate() {
+        if alternative.has_directive("prefer") {
+            filters.preferred.push(rule);
+        }
+        if alternative.has_directive("avoid") {
+            filters.avoided.push(rule);
+        }
+    }
+    filters
+}
+
+/// A rule with a precedence may not have a rule of lower precedence as its first or last child,
+/// nor one of the same precedence on the side its associativity forbids. A child is only
+/// restricted on a side where it is open, such as the right side of a prefix operator,
+/// because only there could it extend past the operator of its parent.
+fn operator_filters(grammar: &Grammar, symbols: &Symbols, filters: &mut Filters) {
+    let precedence = Precedence::new(grammar);
+    for (parent, parent_rule) in symbols.rules.iter().enumerate() {
+        let Some((level, associativity)) = precedence.of_rule(parent_rule) else {
+            continue;
+        };
+        for (child, child_rule) in symbols.rules.iter().enumerate() {
+            let Some((child_level, _)) = precedence.of_rule(child_rule) else {
+                continue;
+            };
+            let (first, last) = match child_level.cmp(&level) {
+                Ordering::Less => (true, true),
+                Ordering::Equal => match associativity {
+                    Associativity::Left => (false, true),
+                    Associativity::Right => (true, false),
+                    Associativity::NonAssoc => (true, true),
+                },
+                Ordering::Greater => (false, false),
+            };
+            let derives =
+                |symbol: Option<&Symbol>| symbol == Some(&Symbol::NonTerminal(child_rule.lhs));
+            let open = |symbol: Option<&Symbol>| matches!(symbol, Some(Symbol::NonTerminal(_)));
+            if first && derives(parent_rule.rhs.first()) && open(child_rule.rhs.last()) {
+                filters.not_first.push((parent, child));
+            }
+            if last && derives(parent_rule.rhs.last()) && open(child_rule.rhs.first()) {
+                filters.not_last.push((parent, child));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::InputError,
+        internal::{runtime::earley::Earley, testing::grammar},
+    };
+
+    const OPERATORS: &str = "
+        %left PLUS MINUS; %left STAR; %right POW; %nonassoc EQ;
+        e: e PLUS e | e MINUS e | e STAR e | e POW e | e EQ e | MINUS e %prec STAR | NUM;
+        PLUS: \\+; MINUS: -; STAR: \\*; POW: \\^; EQ: =; NUM: [0-9]+;
+    ";
+
+    fn filtered(src: &str, input: &str) -> Result<String, InputError> {
+        let grammar = grammar(src);
+        let earley = Earley::new(&grammar).unwrap();
+        let parse = earley.parse(input)?;
+        Ok(earley.symbols.render(&parse))
+    }
+
+    macro_rules! test_filters {
+        ($name:ident, $src:expr, $input:expr => $should_be:expr) => {
+            #[test]
+            fn $name() {
+                assert_eq!(filtered($src, $input).unwrap(), $should_be);
+            }
+        };
+    }
+
+    test_filters!(filter_priority, OPERATORS, "1 + 2 * 3" =>
+        "e[0..5] -> e[0..1] \"+\" e[2..5]\n\
+         e[0..1] -> \"1\"\n\
+         e[2..5] -> e[2..3] \"*\" e[4..5]\n\
+         e[2..3] -> \"2\"\n\
+         e[4..5] -> \"3\"\n");
+    test_filters!(filter_left_associative, OPERATORS, "1 - 2 - 3" =>
+        "e[0..5] -> e[0..3] \"-\" e[4..5]\n\
+         e[0..3] -> e[0..1] \"-\" e[2..3]\n\
+         e[0..1] -> \"1\"\n\
+         e[2..3] -> \"2\"\n\
+         e[4..5] -> \"3\"\n");
+    test_filters!(filter_right_associative, OPERATORS, "1 ^ 2 ^ 3" =>
+        "e[0..5] -> e[0..1] \"^\" e[2..5]\n\
+         e[0..1] -> \"1\"\n\
+         e[2..5] -> e[2..3] \"^\" e[4..5]\n\
+         e[2..3] -> \"2\"\n\
+         e[4..5] -> \"3\"\n");
+    // A prefix operator cannot extend past the operator to its left, so it is never restricted there
+    test_filters!(filter_prefix_operator, OPERATORS, "2 * - 3 + 4" =>
+        "e[0..6] -> e[0..4] \"+\" e[5..6]\n\
+         e[0..4] -> e[0..1] \"*\" e[2..4]\n\
+         e[0..1] -> \"2\"\n\
+         e[2..4] -> \"-\" e[3..4]\n\
+         e[3..4] -> \"3\"\n\
+         e[5..6] -> \"4\"\n");
+    test_filters!(filter_reject, "%reject name IF; s: name | IF; name: ID | IF; IF: if; ID: [a-z]+;", "if" =>
+        "s[0..1] -> \"if\"\n");
+    test_filters!(filter_prefer, "s: IF s %prefer | IF s ELSE s | X; IF: i; ELSE: e; X: x;", "i i x e x" =>
+        "s[0..5] -> \"i\" s[1..5]\n\
+         s[1..5] -> \"i\" s[2..3] \"e\" s[4..5]\n\
+         s[2..3] -> \"x\"\n\
+         s[4..5] -> \"x\"\n");
+    test_filters!(filter_avoid, "s: IF s %avoid | IF s ELSE s | X; IF: i; ELSE: e; X: x;", "i i x e x" =>
+        "s[0..5] -> \"i\" s[1..3] \"e\" s[4..5]\n\
+         s[1..3] -> \"i\" s[2..3]\n\
+         s[2..3] -> \"x\"\n\
+         s[4..5] -> \"x\"\n");
+    test_filters!(filter_follow_restrict, "%follow_restrict word X; s: word rest; word: X | X X; rest: X |; X: x;", "x x" =>
+        "s[0..2] -> word[0..2] rest[2..2]\n\
+         word[0..2] -> \"x\" \"x\"\n\
+         rest[2..2] -> ε\n");
+
+    #[test]
+    fn filter_non_associative() {
+        assert!(matches!(
+            filtered(OPERATORS, "1 = 2 = 3"),
+            Err(InputError::Filtered)
+        ));
+    }
+}
@@ -0,0 +1,300 @@
+//! A GLR driver in the style of Tomita, for LR tables whose cells may hold several actions.
+//!
+//! Every parse is followed at once on a graph-structured stack, where stacks which reach the
+//! same state at the same position are merged. Each reduction is recorded in a shared packed
+//! parse forest, so the result holds every derivation of the input. Reductions pop one symbol
+//! at a time and are merged where their paths meet, so rules of any length stay cubic.
+//!
+//! This module only depends on `std` and its sibling `sppf`, as generated parsers embed it verbatim.
+
+use std::collections::{HashMap, HashSet};
+
+use super::sppf::{Forest, Label};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Shift(usize),
+    Reduce(usize),
+    Accept,
+}
+
+/// An LR table with terminals, nonterminals and rules numbered from zero
+pub trait Table {
+    /// Every action on a terminal in a state, of which there may be more than one
+    fn actions(&self, state: usize, terminal: usize) -> &[Action];
+    fn goto(&self, state: usize, nonterminal: usize) -> Option<usize>;
+    /// The nonterminal a rule reduces to, and the number of symbols it pops
+    fn rule(&self, rule: usize) -> (usize, usize);
+    /// Terminals with any action in a state
+    fn expected(&self, state: usize) -> Vec<usize>;
+}
+
+/// No stack could shift the token at `position`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub position: usize,
+    pub expected: Vec<usize>,
+}
+
+#[derive(Debug)]
+struct Vertex {
+    state: usize,
+    /// The number of tokens shifted before reaching this vertex
+    level: usize,
+    /// Edges to vertices further down the stack, labelled with the forest node between them
+    edges: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Default)]
+struct Stack {
+    vertices: Vec<Vertex>,
+}
+
+impl Stack {
+    /// The vertex of `frontier` in `state`, created if there is none
+    fn vertex(&mut self, frontier: &mut Vec<usize>, state: usize, level: usize) -> usize {
+        if let Some(&vertex) = frontier
+            .iter()
+            .find(|&&vertex| self.vertices[vertex].state == state)
+        {
+            return vertex;
+        }
+        self.vertices.push(Vertex {
+            state,
+            level,
+            edges: vec![],
+        });
+        frontier.push(self.vertices.len() - 1);
+        self.vertices.len() - 1
+    }
+
+    /// Add an edge, returning whether it was new
+    fn link(&mut self, from: usize, to: usize, label: usize) -> bool {
+        let edges = &mut self.vertices[from].edges;
+        if edges.contains(&(to, label)) {
+            return false;
+        }
+        edges.push((to, label));
+        true
+    }
+}
+
+/// Parse a sequence of terminals, which must end with the end of input terminal.
+/// The root of the returned forest derives every token but the last.
+pub fn parse(table: &impl Table, tokens: &[usize]) -> Result<Forest, Error> {
+    let mut forest = Forest::default();
+    let mut stack = Stack::default();
+    let mut frontier = vec![];
+    stack.vertex(&mut frontier, 0, 0);
+
+    for (position, &terminal) in tokens.iter().enumerate() {
+        reduce(
+            table,
+            &mut stack,
+            &mut forest,
+            &mut frontier,
+            position,
+            terminal,
+        );
+
+        for &vertex in &frontier {
+            let vertex = &stack.vertices[vertex];
+            if table
+                .actions(vertex.state, terminal)
+                .contains(&Action::Accept)
+            {
+                forest.root = vertex
+                    .edges
+                    .iter()
+                    .find(|&&(to, _)| to == 0)
+                    .map(|&(_, label)| label);
+                return Ok(forest);
+            }
+        }
+
+        let mut next = vec![];
+        for &vertex in &frontier {
+            for &action in table.actions(stack.vertices[vertex].state, terminal) {
+                if let Action::Shift(state) = action {
+                    let leaf = forest.node(Label::Terminal(terminal), position, position + 1);
+                    let shifted = stack.vertex(&mut next, state, position + 1);
+                    stack.link(shifted, vertex, leaf);
+                }
+            }
+        }
+        if next.is_empty() {
+            let mut expected: Vec<usize> = frontier
+                .iter()
+                .flat_map(|&vertex| table.expected(stack.vertices[vertex].state))
+                .collect();
+            expected.sort_unstable();
+            expected.dedup();
+            return Err(Error { position, expected });
+        }
+        frontier = next;
+    }
+    Err(Error {
+        position: tokens.len(),
+        expected: vec![],
+    })
+}
+
+/// A reduction partway down the stack, with `remaining` symbols of the rule still to be popped
+/// from `vertex`, and the forest node of the symbols already popped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Reduction {
+    vertex: usize,
+    rule: usize,
+    remaining: usize,
+    popped: Option<usize>,
+}
+
+/// The reductions at one position. Reductions pop one edge at a time, keeping the symbols popped
+/// so far under an intermediate node, so that paths with the same end are followed once.
+struct Reducer<'a, T> {
+    table: &'a T,
+    stack: &'a mut Stack,
+    forest: &'a mut Forest,
+    frontier: &'a mut Vec<usize>,
+    position: usize,
+    terminal: usize,
+    /// Reductions to pop every edge of their vertex from, or only the given edge
+    queue: Vec<(Reduction, Option<(usize, usize)>)>,
+    seen: HashSet<Reduction>,
+    /// Reductions which popped the edges of a vertex of the current position. A reduction
+    /// of no width may add edges there later, which are popped in turn.
+    waiting: HashMap<usize, Vec<Reduction>>,
+}
+
+impl<T: Table> Reducer<'_, T> {
+    /// Queue every reduction of a vertex of the current position
+    fn start(&mut self, vertex: usize) {
+        for &action in self
+            .table
+            .actions(self.stack.vertices[vertex].state, self.terminal)
+        {
+            if let Action::Reduce(rule) = action {
+                let (_, length) = self.table.rule(rule);
+                self.push(Reduction {
+                    vertex,
+                    rule,
+                    remaining: length,
+                    popped: None,
+                });
+            }
+        }
+    }
+
+    fn push(&mut self, reduction: Reduction) {
+        if self.seen.insert(reduction) {
+            self.queue.push((reduction, None));
+        }
+    }
+
+    fn run(&mut self) {
+        while let Some((reduction, edge)) = self.queue.pop() {
+            if reduction.remaining == 0 {
+                let children = reduction.popped.into_iter().collect();
+                self.finish(reduction.rule, reduction.vertex, children);
+                continue;
+            }
+            let edges = match edge {
+                Some(edge) => vec![edge],
+                None => {
+                    if self.stack.vertices[reduction.vertex].level == self.position {
+                        self.waiting
+                            .entry(reduction.vertex)
+                            .or_default()
+                            .push(reduction);
+                    }
+                    self.stack.vertices[reduction.vertex].edges.clone()
+                }
+            };
+            for (to, label) in edges {
+                self.pop(reduction, to, label);
+            }
+        }
+    }
+
+    /// Pop the next symbol of a reduction, over an edge to `to` labelled `label`
+    fn pop(&mut self, reduction: Reduction, to: usize, label: usize) {
+        let Reduction {
+            rule,
+            remaining,
+            popped,
+            ..
+        } = reduction;
+        let remaining = remaining - 1;
+        if remaining == 0 {
+            let children = std::iter::once(label).chain(popped).collect();
+            self.finish(rule, to, children);
+            return;
+        }
+        let popped = match popped {
+            None => label,
+            Some(popped) => {
+                let start = self.stack.vertices[to].level;
+                let intermediate = Label::Intermediate(rule, remaining);
+                let node = self.forest.node(intermediate, start, self.position);
+                self.forest.pack(node, rule, vec![label, popped]);
+                node
+            }
+        };
+        self.push(Reduction {
+            vertex: to,
+            rule,
+            remaining,
+            popped: Some(popped),
+        });
+    }
+
+    /// Record a reduction to `target` in the forest, and push its nonterminal on the stack
+    fn finish(&mut self, rule: usize, target: usize, children: Vec<usize>) {
+        let (lhs, _) = self.table.rule(rule);
+        let start = self.stack.vertices[target].level;
+        let node = self
+            .forest
+            .node(Label::NonTerminal(lhs), start, self.position);
+        self.forest.pack(node, rule, children);
+        let Some(state) = self.table.goto(self.stack.vertices[target].state, lhs) else {
+            return;
+        };
+        let vertices = self.frontier.len();
+        let reduced = self.stack.vertex(self.frontier, state, self.position);
+        if self.frontier.len() > vertices {
+            self.start(reduced);
+        }
+        if self.stack.link(reduced, target, node) {
+            for &reduction in self.waiting.get(&reduced).into_iter().flatten() {
+                self.queue.push((reduction, Some((target, node))));
+            }
+        }
+    }
+}
+
+/// Perform every reduction on `terminal` at the current position
+fn reduce(
+    table: &impl Table,
+    stack: &mut Stack,
+    forest: &mut Forest,
+    frontier: &mut Vec<usize>,
+    position: usize,
+    terminal: usize,
+) {
+    let vertices = frontier.clone();
+    let mut reducer = Reducer {
+        table,
+        stack,
+        forest,
+        frontier,
+        position,
+        terminal,
+        queue: vec![],
+        seen: HashSet::new(),
+        waiting: HashMap::new(),
+    };
+    for vertex in vertices {
+        reducer.start(vertex);
+    }
+    reducer.run();
+}
@@ -0,0 +1,78 @@
+use regex::Regex;
+
+use crate::{error::InputError, internal::ast::Grammar};
+
+/// A token of the input, where `terminal` indexes the terminals of the lexer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'input> {
+    pub terminal: usize,
+    pub text: &'input str,
+    /// Byte offsets of the token in the input
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Lexer built from the lexer rules of a grammar, behaving like the one in generated parsers
+#[derive(Debug)]
+pub struct Lexer {
+    patterns: Vec<(usize, Regex)>,
+    end: usize,
+}
+
+impl Lexer {
+    /// `terminals` numbers the terminals, and the terminal at `end` is produced at the end of input
+    pub fn new(grammar: &Grammar, terminals: &[&str], end: usize) -> Result<Self, InputError> {
+        let patterns = grammar
+            .lexer_rules()
+            .map(|(name, pattern)| {
+                let terminal = terminals
+                    .iter()
+                    .position(|&terminal| terminal == name)
+                    .expect("every lexer rule is a terminal");
+                Regex::new(&format!("^(?:{pattern})"))
+                    .map(|regex| (terminal, regex))
+                    .map_err(|err| InputError::InvalidPattern(name.into(), err.to_string()))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Lexer { patterns, end })
+    }
+
+    /// Split the input into tokens, skipping whitespace. The longest match wins, and ties go to
+    /// the lexer rule declared first. The last token is always the end of input.
+    pub fn tokenize<'input>(&self, input: &'input str) -> Result<Vec<Token<'input>>, InputError> {
+        let mut tokens = vec![];
+        let mut offset = 0;
+        loop {
+            offset += input[offset..].len() - input[offset..].trim_start().len();
+            let rest = &input[offset..];
+            if rest.is_empty() {
+                tokens.push(Token {
+                    terminal: self.end,
+                    text: "",
+                    start: offset,
+                    end: offset,
+                });
+                return Ok(tokens);
+            }
+            let mut best: Option<(usize, usize)> = None;
+            for (terminal, pattern) in &self.patterns {
+                if let Some(found) = pattern.find(rest) {
+                    if found.end() > best.map_or(0, |(_, length)| length) {
+                        best = Some((*terminal, found.end()));
+                    }
+                }
+            }
+            let Some((terminal, length)) = best else {
+                let c = rest.chars().next().unwrap();
+                return Err(InputError::UnexpectedChar(c, offset));
+            };
+            tokens.push(Token {
+                terminal,
+                text: &rest[..length],
+                start: offset,
+                end: offset + length,
+            });
+            offset += length;
+        }
+    }
+}
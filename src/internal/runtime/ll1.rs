@@ -0,0 +1,136 @@
+//! A predictive parser driven by the LL(1) table.
+//!
+//! Symbols still to match are kept on an explicit stack, with a marker below the symbols of
+//! each expanded rule to finish its node, so arbitrarily nested input parses without recursion.
+
+use std::collections::HashMap;
+
+use crate::{
+    error::InputError,
+    internal::{ast::Grammar, table::ll1::Ll1Table},
+};
+
+use super::{
+    lexer::Lexer,
+    sppf::{Forest, Label},
+    Parse, Symbols,
+};
+
+#[derive(Debug, Clone, Copy)]
+enum Entry {
+    Expect(Label),
+    /// The symbols of the innermost expanded rule were all matched
+    Finish,
+}
+
+/// A rule being expanded
+#[derive(Debug)]
+struct Frame {
+    nonterminal: usize,
+    rule: usize,
+    start: usize,
+    children: Vec<usize>,
+}
+
+#[derive(Debug)]
+pub struct Ll1<'a> {
+    pub symbols: Symbols<'a>,
+    lexer: Lexer,
+    /// The rule to expand by nonterminal and lookahead terminal
+    table: Vec<HashMap<usize, usize>>,
+    /// The symbols of each rule
+    rules: Vec<Vec<Label>>,
+}
+
+impl<'a> Ll1<'a> {
+    /// Fails when a cell of the table holds more than one rule
+    pub fn new(grammar: &'a Grammar, table: &Ll1Table<'a>) -> Result<Self, InputError> {
+        table.reject_conflicts()?;
+        let symbols = Symbols::new(grammar)?;
+        let lexer = Lexer::new(grammar, &symbols.terminals, symbols.end())?;
+        let mut cells = vec![HashMap::new(); symbols.nonterminals.len()];
+        for (&(nonterminal, terminal), rules) in &table.entries {
+            // The table numbers rules with the augmented start rule first
+            cells[symbols.nonterminal(nonterminal)]
+                .insert(symbols.terminal(terminal), rules[0] - 1);
+        }
+        let rules = symbols
+            .rules
+            .iter()
+            .map(|rule| {
+                rule.rhs
+                    .iter()
+                    .map(|&symbol| symbols.label(symbol))
+                    .collect()
+            })
+            .collect();
+        Ok(Ll1 {
+            symbols,
+            lexer,
+            table: cells,
+            rules,
+        })
+    }
+
+    pub fn parse<'input>(&self, input: &'input str) -> Result<Parse<'input>, InputError> {
+        let tokens = self.lexer.tokenize(input)?;
+        let terminals: Vec<usize> = tokens.iter().map(|token| token.terminal).collect();
+        let mut forest = Forest::default();
+        let mut stack = vec![Entry::Expect(Label::NonTerminal(0))];
+        let mut frames: Vec<Frame> = vec![];
+        let mut position = 0;
+        while let Some(entry) = stack.pop() {
+            let lookahead = terminals[position];
+            match entry {
+                Entry::Expect(label @ Label::Terminal(terminal)) => {
+                    if lookahead != terminal {
+                        return Err(self.symbols.unexpected(&tokens[position], &[terminal]));
+                    }
+                    let leaf = forest.node(label, position, position + 1);
+                    let frame = frames.last_mut().expect("terminals belong to a rule");
+                    frame.children.push(leaf);
+                    position += 1;
+                }
+                Entry::Expect(Label::NonTerminal(nonterminal)) => {
+                    let cells = &self.table[nonterminal];
+                    let Some(&rule) = cells.get(&lookahead) else {
+                        let mut expected: Vec<usize> = cells.keys().copied().collect();
+                        expected.sort_unstable();
+                        return Err(self.symbols.unexpected(&tokens[position], &expected));
+                    };
+                    frames.push(Frame {
+                        nonterminal,
+                        rule,
+                        start: position,
+                        children: vec![],
+                    });
+                    stack.push(Entry::Finish);
+                    stack.extend(
+                        self.rules[rule]
+                            .iter()
+                            .rev()
+                            .map(|&label| Entry::Expect(label)),
+                    );
+                }
+                Entry::Expect(Label::Intermediate(..)) => {
+                    unreachable!("rules only hold terminals and nonterminals")
+                }
+                Entry::Finish => {
+                    let frame = frames.pop().expect("every finish marker has a rule");
+                    let label = Label::NonTerminal(frame.nonterminal);
+                    let node = forest.node(label, frame.start, position);
+                    forest.pack(node, frame.rule, frame.children);
+                    match frames.last_mut() {
+                        Some(parent) => parent.children.push(node),
+                        None => forest.root = Some(node),
+                    }
+                }
+            }
+        }
+        let end = self.symbols.end();
+        if terminals[position] != end {
+            return Err(self.symbols.unexpected(&tokens[position], &[end]));
+        }
+        self.symbols.disambiguate(tokens, forest)
+    }
+}
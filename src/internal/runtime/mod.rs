@@ -0,0 +1,727 @@
+//! Interpretation of grammars on input, without generating a parser first.
+//!
+//! Backends number terminals, nonterminals and rules as in [`Symbols`], and return every
+//! derivation of the input as a shared packed parse forest.
+
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{
+    error::{InputError, NameErrors},
+    internal::{
+        analysis::{names, sets::END},
+        ast::{Grammar, Symbol},
+        codegen::terminals,
+        table::{
+            ll1::Ll1Table,
+            lr::{LrAction, LrKind, LrTable},
+            Rule,
+        },
+    },
+    span::{FileId, Span},
+};
+
+use self::{
+    adaptive::Adaptive,
+    earley::Earley,
+    glr::Action,
+    lexer::{Lexer, Token},
+    ll1::Ll1,
+    peg::Peg,
+    sppf::{Ambiguity, Filters, Forest, Label, Tree},
+};
+
+pub mod adaptive;
+pub mod allstar;
+pub mod earley;
+pub mod filters;
+pub mod glr;
+pub mod lexer;
+pub mod ll1;
+pub mod peg;
+pub mod sppf;
+
+/// The numbering of a grammar shared by every backend. The end of input is the last terminal,
+/// and rules exclude the augmented start rule.
+#[derive(Debug)]
+pub struct Symbols<'a> {
+    pub terminals: Vec<&'a str>,
+    pub nonterminals: Vec<&'a str>,
+    pub rules: Vec<Rule<'a>>,
+    /// Disambiguation filters applied to every forest
+    pub filters: Filters,
+}
+
+impl<'a> Symbols<'a> {
+    /// Number the symbols of a grammar, which fails when it defines a rule twice or refers to an
+    /// undefined one
+    pub fn new(grammar: &'a Grammar) -> Result<Self, InputError> {
+        let diagnostics = names::check_names(grammar);
+        if !diagnostics.is_empty() {
+            return Err(NameErrors { diagnostics }.into());
+        }
+        let mut terminals = terminals(grammar);
+        terminals.push(END);
+        let mut symbols = Symbols {
+            terminals,
+            nonterminals: grammar.parser_rules().map(|(name, _)| name).collect(),
+            rules: crate::internal::table::rules(grammar)
+                .into_iter()
+                .skip(1)
+                .collect(),
+            filters: Filters::default(),
+        };
+        symbols.filters = filters::filters(grammar, &symbols);
+        Ok(symbols)
+    }
+
+    /// Apply the disambiguation filters of the grammar to a forest of the tokens
+    pub fn disambiguate<'input>(
+        &self,
+        tokens: Vec<Token<'input>>,
+        mut forest: Forest,
+    ) -> Result<Parse<'input>, InputError> {
+        let terminals: Vec<usize> = tokens.iter().map(|token| token.terminal).collect();
+        forest.filter(&self.filters, &terminals);
+        match forest.root {
+            Some(_) => Ok(Parse { tokens, forest }),
+            None => Err(InputError::Filtered),
+        }
+    }
+
+    pub fn end(&self) -> usize {
+        self.terminals.len() - 1
+    }
+
+    pub fn terminal(&self, name: &str) -> usize {
+        self.terminals
+            .iter()
+            .position(|&terminal| terminal == name)
+            .expect("the terminal is part of the grammar")
+    }
+
+    pub fn nonterminal(&self, name: &str) -> usize {
+        self.nonterminals
+            .iter()
+            .position(|&nonterminal| nonterminal == name)
+            .expect("the nonterminal is part of the grammar")
+    }
+
+    pub fn label(&self, symbol: Symbol) -> Label {
+        match symbol {
+            Symbol::Terminal(name) => Label::Terminal(self.terminal(name)),
+            Symbol::NonTerminal(name) => Label::NonTerminal(self.nonterminal(name)),
+        }
+    }
+
+    /// The error for an unexpected token, expecting some terminals
+    pub fn unexpected(&self, token: &Token, expected: &[usize]) -> InputError {
+        let name = self.terminals[token.terminal];
+        InputError::UnexpectedToken {
+            found: if token.text.is_empty() {
+                name.into()
+            } else {
+                format!("{name} `{}`", token.text)
+            },
+            span: Span::new(FileId::default(), token.start..token.end),
+            expected: expected
+                .iter()
+                .map(|&terminal| self.terminals[terminal])
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }
+
+    /// A derivation drawn as a tree, with the rule used at each node and the text of each leaf
+    pub fn render_tree(&self, parse: &Parse, tree: &Tree) -> String {
+        let mut out = String::new();
+        self.render_subtree(&mut out, parse, tree, "", None);
+        out
+    }
+
+    /// `last` is `None` for the root, otherwise whether this is the last child of its parent
+    fn render_subtree(
+        &self,
+        out: &mut String,
+        parse: &Parse,
+        tree: &Tree,
+        prefix: &str,
+        last: Option<bool>,
+    ) {
+        let (branch, child_prefix) = match last {
+            None => ("", prefix.to_string()),
+            Some(true) => ("└─ ", format!("{prefix}   ")),
+            Some(false) => ("├─ ", format!("{prefix}│  ")),
+        };
+        match tree {
+            Tree::Leaf { terminal, position } => writeln!(
+                out,
+                "{prefix}{branch}{} {:?}",
+                self.terminals[*terminal], parse.tokens[*position].text
+            )
+            .unwrap(),
+            Tree::Node { rule, children } => {
+                writeln!(out, "{prefix}{branch}{}", self.rules[*rule]).unwrap();
+                for (index, child) in children.iter().enumerate() {
+                    let last = Some(index + 1 == children.len());
+                    self.render_subtree(out, parse, child, &child_prefix, last);
+                }
+            }
+        }
+    }
+
+    /// Every derivation in a forest reachable from its root, one line per sequence of children
+    /// of a nonterminal, with spans counted in tokens
+    pub fn render(&self, parse: &Parse) -> String {
+        let mut out = String::new();
+        let forest = &parse.forest;
+        for node in forest.reachable() {
+            let node = &forest.nodes[node];
+            let Label::NonTerminal(nonterminal) = node.label else {
+                continue;
+            };
+            for children in node
+                .packed
+                .iter()
+                .flat_map(|packed| forest.children(packed))
+            {
+                write!(
+                    out,
+                    "{}[{}..{}] ->",
+                    self.nonterminals[nonterminal], node.start, node.end
+                )
+                .unwrap();
+                if children.is_empty() {
+                    out.push_str(" ε");
+                }
+                for child in children {
+                    let child = &forest.nodes[child];
+                    match child.label {
+                        Label::Terminal(_) => {
+                            write!(out, " {:?}", parse.tokens[child.start].text).unwrap()
+                        }
+                        Label::NonTerminal(nonterminal) => write!(
+                            out,
+                            " {}[{}..{}]",
+                            self.nonterminals[nonterminal], child.start, child.end
+                        )
+                        .unwrap(),
+                        Label::Intermediate(..) => unreachable!("intermediate nodes are expanded"),
+                    }
+                }
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
+
+/// The tokens of some input, and every derivation of them
+#[derive(Debug)]
+pub struct Parse<'input> {
+    pub tokens: Vec<Token<'input>>,
+    pub forest: Forest,
+}
+
+impl Parse<'_> {
+    /// The parse tree, when the filters left a single derivation
+    pub fn tree(&self) -> Result<Tree, Ambiguity> {
+        self.forest.tree()
+    }
+}
+
+/// An LR table numbered for the GLR driver
+#[derive(Debug)]
+struct GlrTable {
+    actions: Vec<HashMap<usize, Vec<Action>>>,
+    gotos: Vec<HashMap<usize, usize>>,
+    rules: Vec<(usize, usize)>,
+}
+
+impl glr::Table for GlrTable {
+    fn actions(&self, state: usize, terminal: usize) -> &[Action] {
+        self.actions[state]
+            .get(&terminal)
+            .map_or(&[], |actions| actions.as_slice())
+    }
+
+    fn goto(&self, state: usize, nonterminal: usize) -> Option<usize> {
+        self.gotos[state].get(&nonterminal).copied()
+    }
+
+    fn rule(&self, rule: usize) -> (usize, usize) {
+        self.rules[rule]
+    }
+
+    fn expected(&self, state: usize) -> Vec<usize> {
+        self.actions[state].keys().copied().collect()
+    }
+}
+
+/// Interpreter running the GLR driver on an LR table, keeping any conflicts in it
+#[derive(Debug)]
+pub struct Glr<'a> {
+    pub symbols: Symbols<'a>,
+    lexer: Lexer,
+    table: GlrTable,
+}
+
+impl<'a> Glr<'a> {
+    pub fn new(grammar: &'a Grammar, table: &LrTable<'a>) -> Result<Self, InputError> {
+        let symbols = Symbols::new(grammar)?;
+        let lexer = Lexer::new(grammar, &symbols.terminals, symbols.end())?;
+        let mut actions = vec![];
+        let mut gotos = vec![];
+        for row in &table.actions {
+            let mut action_row: HashMap<usize, Vec<Action>> = HashMap::new();
+            let mut goto_row = HashMap::new();
+            for (symbol, cell) in row {
+                match symbol {
+                    Symbol::Terminal(name) => {
+                        action_row.insert(
+                            symbols.terminal(name),
+                            cell.iter()
+                                .filter_map(|action| match action {
+                                    LrAction::Shift(state) => Some(Action::Shift(*state)),
+                                    // The GLR driver numbers rules without the augmented rule
+                                    LrAction::Reduce(rule) => Some(Action::Reduce(rule - 1)),
+                                    LrAction::Accept => Some(Action::Accept),
+                                    LrAction::Go(_) => None,
+                                })
+                                .collect(),
+                        );
+                    }
+                    Symbol::NonTerminal(name) => {
+                        if let Some(LrAction::Go(state)) = cell.first() {
+                            goto_row.insert(symbols.nonterminal(name), *state);
+                        }
+                    }
+                }
+            }
+            actions.push(action_row);
+            gotos.push(goto_row);
+        }
+        let rules = symbols
+            .rules
+            .iter()
+            .map(|rule| (symbols.nonterminal(rule.lhs), rule.rhs.len()))
+            .collect();
+        Ok(Glr {
+            symbols,
+            lexer,
+            table: GlrTable {
+                actions,
+                gotos,
+                rules,
+            },
+        })
+    }
+
+    pub fn parse<'input>(&self, input: &'input str) -> Result<Parse<'input>, InputError> {
+        let tokens = self.lexer.tokenize(input)?;
+        let terminals: Vec<usize> = tokens.iter().map(|token| token.terminal).collect();
+        match glr::parse(&self.table, &terminals) {
+            Ok(forest) => self.symbols.disambiguate(tokens, forest),
+            Err(err) => Err(self
+                .symbols
+                .unexpected(&tokens[err.position.min(tokens.len() - 1)], &err.expected)),
+        }
+    }
+}
+
+/// A backend of the interpreter
+#[derive(Debug)]
+pub enum Interpreter<'a> {
+    Ll1(Ll1<'a>),
+    Glr(Glr<'a>),
+    Earley(Earley<'a>),
+    Adaptive(Adaptive<'a>),
+    Peg(Peg<'a>),
+}
+
+impl<'a> Interpreter<'a> {
+    /// The LL(1) driver when its table has no conflicts, then the GLR driver when the LALR table
+    /// has no conflicts, as it then runs as a plain LR parser, or else the Earley parser, which
+    /// accepts any grammar
+    pub fn auto(grammar: &'a Grammar) -> Result<Self, InputError> {
+        let table = Ll1Table::new(grammar);
+        if table.conflicts().is_empty() {
+            return Ok(Interpreter::Ll1(Ll1::new(grammar, &table)?));
+        }
+        let table = LrTable::new(grammar, LrKind::Lalr);
+        if table.conflicts().is_empty() {
+            Ok(Interpreter::Glr(Glr::new(grammar, &table)?))
+        } else {
+            Ok(Interpreter::Earley(Earley::new(grammar)?))
+        }
+    }
+
+    pub fn symbols(&self) -> &Symbols<'a> {
+        match self {
+            Interpreter::Ll1(ll1) => &ll1.symbols,
+            Interpreter::Glr(glr) => &glr.symbols,
+            Interpreter::Earley(earley) => &earley.symbols,
+            Interpreter::Adaptive(adaptive) => &adaptive.symbols,
+            Interpreter::Peg(peg) => &peg.symbols,
+        }
+    }
+
+    pub fn parse<'input>(&self, input: &'input str) -> Result<Parse<'input>, InputError> {
+        match self {
+            Interpreter::Ll1(ll1) => ll1.parse(input),
+            Interpreter::Glr(glr) => glr.parse(input),
+            Interpreter::Earley(earley) => earley.parse(input),
+            Interpreter::Adaptive(adaptive) => adaptive.parse(input),
+            Interpreter::Peg(peg) => peg.parse(input),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::{runtime::sppf::Count, testing::grammar};
+
+    fn ll1(grammar: &Grammar) -> Interpreter<'_> {
+        Interpreter::Ll1(Ll1::new(grammar, &Ll1Table::new(grammar)).unwrap())
+    }
+
+    fn glr(grammar: &Grammar) -> Interpreter<'_> {
+        let table = LrTable::new(grammar, LrKind::Lalr);
+        Interpreter::Glr(Glr::new(grammar, &table).unwrap())
+    }
+
+    fn earley(grammar: &Grammar) -> Interpreter<'_> {
+        Interpreter::Earley(Earley::new(grammar).unwrap())
+    }
+
+    fn adaptive(grammar: &Grammar) -> Interpreter<'_> {
+        Interpreter::Adaptive(Adaptive::new(grammar).unwrap())
+    }
+
+    fn peg(grammar: &Grammar) -> Interpreter<'_> {
+        Interpreter::Peg(Peg::new(grammar).unwrap())
+    }
+
+    macro_rules! test_interpreter {
+        ($name:ident, $backend:ident, $src:expr, $input:expr => $should_be:expr) => {
+            #[test]
+            fn $name() {
+                let grammar = grammar($src);
+                let interpreter = $backend(&grammar);
+                let parse = interpreter.parse($input).unwrap();
+                assert_eq!(interpreter.symbols().render(&parse), $should_be);
+            }
+        };
+    }
+
+    const BRACKETS: &str =
+        "list: item list |; item: NUM | LP list RP; NUM: [0-9]+; LP: \\(; RP: \\);";
+
+    test_interpreter!(ll1_nested, ll1, BRACKETS, "1 (2)" =>
+        "list[0..4] -> item[0..1] list[1..4]\n\
+         item[0..1] -> \"1\"\n\
+         list[1..4] -> item[1..4] list[4..4]\n\
+         item[1..4] -> \"(\" list[2..3] \")\"\n\
+         list[2..3] -> item[2..3] list[3..3]\n\
+         item[2..3] -> \"2\"\n\
+         list[3..3] -> ε\n\
+         list[4..4] -> ε\n");
+
+    #[test]
+    fn ll1_deeply_nested() {
+        let grammar = grammar(BRACKETS);
+        let depth = 20_000;
+        let input = format!("{}{}", "(".repeat(depth), ")".repeat(depth));
+        let parse = ll1(&grammar).parse(&input).unwrap();
+        assert_eq!(parse.forest.count(), Count::Finite(1));
+        // Its tree is as deep as the input, and dropped without recursion as well
+        drop(parse.tree().unwrap());
+    }
+
+    #[test]
+    fn ll1_rejects_conflicts() {
+        let grammar = grammar("e: e PLUS t | t; t: ID; PLUS: \\+; ID: [a-z]+;");
+        let err = Ll1::new(&grammar, &Ll1Table::new(&grammar)).unwrap_err();
+        assert_eq!(err.to_string(), "The LL(1) table has 1 conflict");
+    }
+
+    test_interpreter!(glr_deterministic, glr, "e: e PLUS t | t; t: ID; PLUS: \\+; ID: [a-z]+;", "a + b" =>
+        "e[0..3] -> e[0..1] \"+\" t[2..3]\n\
+         e[0..1] -> t[0..1]\n\
+         t[0..1] -> \"a\"\n\
+         t[2..3] -> \"b\"\n");
+    test_interpreter!(glr_ambiguous, glr, "e: e PLUS e | ID; PLUS: \\+; ID: [a-z]+;", "a + b + c" =>
+        "e[0..5] -> e[0..3] \"+\" e[4..5]\n\
+         e[0..5] -> e[0..1] \"+\" e[2..5]\n\
+         e[0..3] -> e[0..1] \"+\" e[2..3]\n\
+         e[0..1] -> \"a\"\n\
+         e[2..3] -> \"b\"\n\
+         e[4..5] -> \"c\"\n\
+         e[2..5] -> e[2..3] \"+\" e[4..5]\n");
+    test_interpreter!(glr_nullable, glr, "s: a s B |; a: ; B: b;", "b b" =>
+        "s[0..2] -> a[0..0] s[0..1] \"b\"\n\
+         a[0..0] -> ε\n\
+         s[0..1] -> a[0..0] s[0..0] \"b\"\n\
+         s[0..0] -> ε\n");
+    test_interpreter!(glr_cyclic, glr, "s: s | A; A: a;", "a" =>
+        "s[0..1] -> \"a\"\n\
+         s[0..1] -> s[0..1]\n");
+
+    test_interpreter!(earley_ambiguous, earley, "e: e PLUS e | ID; PLUS: \\+; ID: [a-z]+;", "a + b + c" =>
+        "e[0..5] -> e[0..1] \"+\" e[2..5]\n\
+         e[0..5] -> e[0..3] \"+\" e[4..5]\n\
+         e[0..1] -> \"a\"\n\
+         e[2..5] -> e[2..3] \"+\" e[4..5]\n\
+         e[2..3] -> \"b\"\n\
+         e[4..5] -> \"c\"\n\
+         e[0..3] -> e[0..1] \"+\" e[2..3]\n");
+    // Not LR(k) for any k, as the middle of the input is only known at its end
+    test_interpreter!(earley_palindrome, earley, "s: A s A | B s B | C; A: a; B: b; C: c;", "a b c b a" =>
+        "s[0..5] -> \"a\" s[1..4] \"a\"\n\
+         s[1..4] -> \"b\" s[2..3] \"b\"\n\
+         s[2..3] -> \"c\"\n");
+    test_interpreter!(earley_nullable, earley, "s: a a s B |; a: ; B: b;", "b" =>
+        "s[0..1] -> a[0..0] a[0..0] s[0..0] \"b\"\n\
+         a[0..0] -> ε\n\
+         s[0..0] -> ε\n");
+    test_interpreter!(earley_cyclic, earley, "s: s | A; A: a;", "a" =>
+        "s[0..1] -> s[0..1]\n\
+         s[0..1] -> \"a\"\n");
+    // Leo paths skip the completions of the inner lists, which must still end up in the forest
+    test_interpreter!(earley_right_recursion, earley, "l: A l | A; A: a;", "a a a" =>
+        "l[0..3] -> \"a\" l[1..3]\n\
+         l[1..3] -> \"a\" l[2..3]\n\
+         l[2..3] -> \"a\"\n");
+
+    #[test]
+    fn earley_right_recursion_is_linear() {
+        let grammar = grammar("l: A l | A; A: a;");
+        let input = "a ".repeat(2000);
+        let parse = earley(&grammar).parse(&input).unwrap();
+        assert_eq!(parse.forest.nodes.len(), 4000);
+        assert_eq!(parse.forest.count(), Count::Finite(1));
+    }
+
+    #[test]
+    fn earley_left_recursion_is_linear() {
+        let grammar = grammar("s: s PLUS N | N; PLUS: \\+; N: [0-9];");
+        let input = "1+".repeat(1000) + "1";
+        let parse = earley(&grammar).parse(&input).unwrap();
+        // A node for each terminal and each prefix ending in `N`, and one for each ending in `+`
+        assert_eq!(parse.forest.nodes.len(), 4002);
+        assert_eq!(parse.forest.count(), Count::Finite(1));
+    }
+
+    // The only way to continue `y` is to complete the start rule, which accepting must still see
+    test_interpreter!(earley_leo_path_to_start, earley, "s: y B | A x; y: s; x: C; A: a; B: b; C: c;", "a c" =>
+        "s[0..2] -> \"a\" x[1..2]\n\
+         x[1..2] -> \"c\"\n");
+
+    // Both alternatives start with any number of `c`, so no fixed lookahead tells them apart
+    test_interpreter!(adaptive_unbounded_lookahead, adaptive, "s: x A | x B; x: C x | C; A: a; B: b; C: c;", "c c c b" =>
+        "s[0..4] -> x[0..3] \"b\"\n\
+         x[0..3] -> \"c\" x[1..3]\n\
+         x[1..3] -> \"c\" x[2..3]\n\
+         x[2..3] -> \"c\"\n");
+    // SLL conflicts on whether `t` takes the `a`, which only the calling alternative of `s` decides
+    test_interpreter!(adaptive_full_context, adaptive, "s: A t A | B t; t: A |; A: a; B: b;", "a a" =>
+        "s[0..2] -> \"a\" t[1..1] \"a\"\n\
+         t[1..1] -> ε\n");
+    test_interpreter!(adaptive_nullable, adaptive, "s: a B s |; a: ; B: b;", "b b" =>
+        "s[0..2] -> a[0..0] \"b\" s[1..2]\n\
+         a[0..0] -> ε\n\
+         s[1..2] -> a[1..1] \"b\" s[2..2]\n\
+         a[1..1] -> ε\n\
+         s[2..2] -> ε\n");
+    // The first alternative wins on ambiguous input
+    test_interpreter!(adaptive_ambiguous, adaptive, "s: A b | a B; a: A; b: B; A: a; B: b;", "a b" =>
+        "s[0..2] -> \"a\" b[1..2]\n\
+         b[1..2] -> \"b\"\n");
+
+    #[test]
+    fn adaptive_reuses_its_dfa_cache() {
+        let grammar = grammar("s: x A | x B; x: C x | C; A: a; B: b; C: c;");
+        let interpreter = adaptive(&grammar);
+        for input in ["c a", "c c b", "c c c c a"] {
+            interpreter.parse(input).unwrap();
+        }
+        let err = interpreter.parse("c c d").unwrap_err();
+        assert_eq!(err.to_string(), "Unexpected character 'd' at offset 4");
+    }
+
+    #[test]
+    fn adaptive_deep_input() {
+        let grammar = grammar("l: A l | A; A: a;");
+        let input = "a ".repeat(20000);
+        let parse = adaptive(&grammar).parse(&input).unwrap();
+        assert_eq!(parse.forest.count(), Count::Finite(1));
+    }
+
+    #[test]
+    fn adaptive_rejects_left_recursion() {
+        let grammar = grammar("e: e PLUS e | ID; PLUS: \\+; ID: [a-z]+;");
+        let err = Adaptive::new(&grammar).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Left recursive rules need a bottom-up backend: e"
+        );
+    }
+
+    test_interpreter!(peg_ordered_choice, peg, "s: A B | A; A: a; B: b;", "a" =>
+        "s[0..1] -> \"a\"\n");
+    test_interpreter!(peg_and_lookahead, peg, "s: &x A | A C; x: A B; A: a; B: b; C: c;", "a c" =>
+        "s[0..2] -> \"a\" \"c\"\n");
+    test_interpreter!(peg_not_lookahead, peg, "s: x s |; x: !A y; y: A | B; A: a; B: b;", "b b" =>
+        "s[0..2] -> x[0..1] s[1..2]\n\
+         x[0..1] -> y[0..1]\n\
+         y[0..1] -> \"b\"\n\
+         s[1..2] -> x[1..2] s[2..2]\n\
+         x[1..2] -> y[1..2]\n\
+         y[1..2] -> \"b\"\n\
+         s[2..2] -> ε\n");
+
+    #[test]
+    fn peg_deep_input() {
+        let grammar = grammar("l: A l | A; A: a;");
+        let input = "a ".repeat(20000);
+        let parse = peg(&grammar).parse(&input).unwrap();
+        assert_eq!(parse.forest.count(), Count::Finite(1));
+    }
+
+    #[test]
+    fn peg_rejects_left_recursion() {
+        let grammar = grammar("e: e PLUS e | ID; PLUS: \\+; ID: [a-z]+;");
+        let err = Peg::new(&grammar).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Left recursive rules need a bottom-up backend: e"
+        );
+    }
+
+    macro_rules! test_interpreter_error {
+        ($name:ident, $backend:ident, $src:expr, $input:expr => $should_be:expr) => {
+            #[test]
+            fn $name() {
+                let grammar = grammar($src);
+                let err = $backend(&grammar).parse($input).unwrap_err();
+                assert_eq!(err.to_string(), $should_be);
+            }
+        };
+    }
+
+    test_interpreter_error!(ll1_reports_unclosed_bracket, ll1, BRACKETS, "(1" =>
+        "Unexpected $end at offset 2, expected one of: RP");
+    test_interpreter_error!(ll1_reports_trailing_tokens, ll1, BRACKETS, "())" =>
+        "Unexpected RP `)` at offset 2, expected one of: $end");
+    test_interpreter_error!(glr_reports_expected_tokens, glr, "e: e PLUS e | ID; PLUS: \\+; ID: [a-z]+;", "a + + b" =>
+        "Unexpected PLUS `+` at offset 4, expected one of: ID");
+    test_interpreter_error!(earley_reports_expected_tokens, earley, "e: e PLUS e | ID; PLUS: \\+; ID: [a-z]+;", "a + + b" =>
+        "Unexpected PLUS `+` at offset 4, expected one of: ID");
+    test_interpreter_error!(earley_reports_early_end, earley, "e: e PLUS e | ID; PLUS: \\+; ID: [a-z]+;", "a +" =>
+        "Unexpected $end at offset 3, expected one of: ID");
+    test_interpreter_error!(adaptive_reports_expected_tokens, adaptive, "s: x A | x B; x: C x | C; A: a; B: b; C: c;", "c c c" =>
+        "Unexpected $end at offset 5, expected one of: A, B, C");
+    test_interpreter_error!(adaptive_reports_trailing_tokens, adaptive, "s: A; A: a;", "a a" =>
+        "Unexpected A `a` at offset 2, expected one of: $end");
+    // The first alternative of `a` matches, and is never revisited when `B` fails after it
+    test_interpreter_error!(peg_commits_to_first_match, peg, "s: a B; a: A | A A; A: a; B: b;", "a a b" =>
+        "Unexpected A `a` at offset 2, expected one of: B");
+    test_interpreter_error!(peg_reports_unfinished_input, peg, "s: x s |; x: !A y; y: A | B; A: a; B: b;", "b a" =>
+        "Unexpected A `a` at offset 2, expected one of: $end");
+
+    #[test]
+    fn forest_counts_and_iterates_derivations() {
+        let grammar = grammar("e: e PLUS e | ID; PLUS: \\+; ID: [a-z]+;");
+        let parse = earley(&grammar).parse("a + b + c + d").unwrap();
+        assert_eq!(parse.forest.count(), Count::Finite(5));
+        assert_eq!(parse.forest.derivations().count(), 5);
+        let first: Vec<Tree> = parse.forest.derivations().take(2).collect();
+        assert_eq!(first.len(), 2);
+        assert_ne!(first[0], first[1]);
+        assert_eq!(parse.tree().unwrap_err().derivations, Count::Finite(5));
+    }
+
+    #[test]
+    fn forest_tree_of_unambiguous_input() {
+        let grammar = grammar("e: e PLUS t | t; t: ID; PLUS: \\+; ID: [a-z]+;");
+        let interpreter = glr(&grammar);
+        let parse = interpreter.parse("a + b").unwrap();
+        let tree = parse.tree().unwrap();
+        assert_eq!(
+            interpreter.symbols().render_tree(&parse, &tree),
+            "e -> e PLUS t\n\
+             ├─ e -> t\n\
+             │  └─ t -> ID\n\
+             │     └─ ID \"a\"\n\
+             ├─ PLUS \"+\"\n\
+             └─ t -> ID\n   \
+                └─ ID \"b\"\n"
+        );
+    }
+
+    #[test]
+    fn forest_with_cycle_has_infinitely_many_derivations() {
+        let grammar = grammar("s: s | A; A: a;");
+        let parse = earley(&grammar).parse("a").unwrap();
+        assert_eq!(parse.forest.count(), Count::Infinite);
+        assert_eq!(parse.forest.derivations().count(), 1);
+    }
+
+    // Rules of more than two symbols are split with intermediate nodes, keeping the forest cubic
+    #[test]
+    fn forest_of_long_rules_is_binary() {
+        let grammar = grammar("s: s s s | A; A: a;");
+        let input = "a ".repeat(31);
+        for interpreter in [glr(&grammar), earley(&grammar)] {
+            let parse = interpreter.parse(&input).unwrap();
+            let packed = parse.forest.nodes.iter().flat_map(|node| &node.packed);
+            assert!(packed.clone().all(|packed| packed.children.len() <= 2));
+            // Without intermediate nodes, there would be 5471
+            assert!(packed.count() < 3000);
+            assert_eq!(parse.forest.count(), Count::Finite(11_124_755_664));
+        }
+    }
+
+    // The intermediate nodes of `s[1..3]` are shared with `s[0..3]`, where they are not part of a
+    // cycle, so cutting the cycle there must not lose the derivations of `s[0..3]`
+    #[test]
+    fn forest_cycle_through_shared_intermediate_nodes() {
+        let grammar = grammar("s: x y z s | A; x: B |; y: B |; z: B |; A: a; B: b;");
+        for interpreter in [glr(&grammar), earley(&grammar)] {
+            let parse = interpreter.parse("b b a").unwrap();
+            assert_eq!(parse.forest.count(), Count::Infinite);
+            assert_eq!(parse.forest.derivations().count(), 12);
+        }
+    }
+
+    #[test]
+    fn auto_falls_back_to_earley() {
+        let ll1 = grammar("e: t r; r: PLUS e |; t: ID; PLUS: \\+; ID: [a-z]+;");
+        let deterministic = grammar("e: e PLUS t | t; t: ID; PLUS: \\+; ID: [a-z]+;");
+        let ambiguous = grammar("e: e PLUS e | ID; PLUS: \\+; ID: [a-z]+;");
+        assert!(matches!(Interpreter::auto(&ll1), Ok(Interpreter::Ll1(_))));
+        assert!(matches!(
+            Interpreter::auto(&deterministic),
+            Ok(Interpreter::Glr(_))
+        ));
+        assert!(matches!(
+            Interpreter::auto(&ambiguous),
+            Ok(Interpreter::Earley(_))
+        ));
+    }
+
+    #[test]
+    fn undefined_rule_is_an_error() {
+        let grammar = grammar("e: a | N; N: n;");
+        let table = LrTable::new(&grammar, LrKind::Lalr);
+        let undefined = |err: InputError| match err {
+            InputError::Names(err) => err.to_string() == "undefined rule `a`",
+            _ => false,
+        };
+        assert!(undefined(Interpreter::auto(&grammar).unwrap_err()));
+        assert!(undefined(Glr::new(&grammar, &table).unwrap_err()));
+        assert!(undefined(Earley::new(&grammar).unwrap_err()));
+        assert!(undefined(Adaptive::new(&grammar).unwrap_err()));
+        assert!(undefined(Peg::new(&grammar).unwrap_err()));
+    }
+}
@@ -0,0 +1,215 @@
+//! A packrat parser, which reads `|` as ordered choice.
+//!
+//! Alternatives are tried in order and the first to match wins, so every input has at most one
+//! tree. `&ident` and `!ident` look ahead without consuming input. Every rule is memoized by the
+//! position it starts at, which keeps parsing linear, and rules run on an explicit stack rather
+//! than by recursion.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+    error::InputError,
+    internal::{
+        analysis::sets::reject_left_recursion,
+        ast::{Grammar, Ident},
+    },
+};
+
+use super::{
+    lexer::Lexer,
+    sppf::{Forest, Label},
+    Parse, Symbols,
+};
+
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Match(Label),
+    Check { label: Label, negated: bool },
+}
+
+/// An alternative of a rule being tried
+#[derive(Debug)]
+struct Frame {
+    nonterminal: usize,
+    start: usize,
+    alternative: usize,
+    step: usize,
+    position: usize,
+    children: Vec<usize>,
+    /// Whether the rule runs inside a lookahead, whose failures are not reported
+    lookahead: bool,
+}
+
+impl Frame {
+    fn new(nonterminal: usize, start: usize, lookahead: bool) -> Self {
+        Frame {
+            nonterminal,
+            start,
+            alternative: 0,
+            step: 0,
+            position: start,
+            children: vec![],
+            lookahead,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Peg<'a> {
+    pub symbols: Symbols<'a>,
+    lexer: Lexer,
+    /// The rule number and steps of each alternative, by nonterminal
+    alternatives: Vec<Vec<(usize, Vec<Step>)>>,
+}
+
+impl<'a> Peg<'a> {
+    pub fn new(grammar: &'a Grammar) -> Result<Self, InputError> {
+        reject_left_recursion(grammar)?;
+        let symbols = Symbols::new(grammar)?;
+        let lexer = Lexer::new(grammar, &symbols.terminals, symbols.end())?;
+        let label = |ident: &Ident| symbols.label(ident.symbol());
+        // Rules are numbered in the order of the parser rules and their alternatives
+        let mut rule = 0;
+        let mut alternatives = vec![];
+        for (_, rule_alternatives) in grammar.parser_rules() {
+            let mut steps_by_alternative = vec![];
+            for alternative in rule_alternatives {
+                let mut steps = vec![];
+                for position in 0..=alternative.idents.len() {
+                    for predicate in &alternative.predicates {
+                        if predicate.position == position {
+                            steps.push(Step::Check {
+                                label: label(&predicate.ident),
+                                negated: predicate.negated,
+                            });
+                        }
+                    }
+                    if let Some(ident) = alternative.idents.get(position) {
+                        steps.push(Step::Match(label(ident)));
+                    }
+                }
+                steps_by_alternative.push((rule, steps));
+                rule += 1;
+            }
+            alternatives.push(steps_by_alternative);
+        }
+        Ok(Peg {
+            symbols,
+            lexer,
+            alternatives,
+        })
+    }
+
+    pub fn parse<'input>(&self, input: &'input str) -> Result<Parse<'input>, InputError> {
+        let tokens = self.lexer.tokenize(input)?;
+        let terminals: Vec<usize> = tokens.iter().map(|token| token.terminal).collect();
+        let mut forest = Forest::default();
+        // The node each rule matched by start position, or `None` when it failed. A rule is
+        // marked as failed while it runs, so it fails instead of looping if a lookahead reaches
+        // it again at the same position.
+        let mut memo: HashMap<(usize, usize), Option<usize>> = HashMap::new();
+        // The farthest position a terminal failed to match at, and the terminals expected there
+        let mut farthest = (0, BTreeSet::new());
+        let mut frames = vec![Frame::new(0, 0, false)];
+        memo.insert((0, 0), None);
+        // The outcome of the rule which just returned to the frame below
+        let mut returned: Option<Option<usize>> = None;
+        let root = loop {
+            let frame = frames.last_mut().expect("the start rule is running");
+            let (rule, steps) = &self.alternatives[frame.nonterminal][frame.alternative];
+            if returned.is_none() && frame.step == steps.len() {
+                let frame = frames.pop().unwrap();
+                let label = Label::NonTerminal(frame.nonterminal);
+                let node = forest.node(label, frame.start, frame.position);
+                forest.pack(node, *rule, frame.children);
+                memo.insert((frame.nonterminal, frame.start), Some(node));
+                if frames.is_empty() {
+                    break Some(node);
+                }
+                returned = Some(Some(node));
+                continue;
+            }
+            let (label, negated) = match steps[frame.step] {
+                Step::Match(label) => (label, None),
+                Step::Check { label, negated } => (label, Some(negated)),
+            };
+            let outcome = match (returned.take(), label) {
+                (Some(outcome), _) => outcome,
+                (None, Label::Terminal(terminal)) => {
+                    let position = frame.position;
+                    if terminals[position] == terminal {
+                        Some(forest.node(label, position, position + 1))
+                    } else {
+                        if !frame.lookahead && negated.is_none() && position >= farthest.0 {
+                            if position > farthest.0 {
+                                farthest = (position, BTreeSet::new());
+                            }
+                            farthest.1.insert(terminal);
+                        }
+                        None
+                    }
+                }
+                (None, Label::NonTerminal(nonterminal)) => {
+                    match memo.get(&(nonterminal, frame.position)) {
+                        Some(&outcome) => outcome,
+                        None => {
+                            memo.insert((nonterminal, frame.position), None);
+                            let lookahead = frame.lookahead || negated.is_some();
+                            let frame = Frame::new(nonterminal, frame.position, lookahead);
+                            frames.push(frame);
+                            continue;
+                        }
+                    }
+                }
+                (None, Label::Intermediate(..)) => {
+                    unreachable!("rules only hold terminals and nonterminals")
+                }
+            };
+            let frame = frames.last_mut().unwrap();
+            let matched = match negated {
+                None => outcome.is_some(),
+                Some(negated) => outcome.is_some() != negated,
+            };
+            if matched {
+                if let (None, Some(node)) = (negated, outcome) {
+                    frame.children.push(node);
+                    frame.position = forest.nodes[node].end;
+                }
+                frame.step += 1;
+                continue;
+            }
+            frame.alternative += 1;
+            frame.step = 0;
+            frame.position = frame.start;
+            frame.children.clear();
+            if frame.alternative == self.alternatives[frame.nonterminal].len() {
+                frames.pop();
+                if frames.is_empty() {
+                    break None;
+                }
+                returned = Some(None);
+            }
+        };
+        let end = terminals.len() - 1;
+        match root {
+            Some(root) if forest.nodes[root].end == end => {
+                forest.root = Some(root);
+                self.symbols.disambiguate(tokens, forest)
+            }
+            _ => {
+                // A start rule matching a prefix of the input expects the end after it
+                if let Some(root) = root {
+                    let position = forest.nodes[root].end;
+                    if position > farthest.0 {
+                        farthest = (position, BTreeSet::new());
+                    }
+                    if position == farthest.0 {
+                        farthest.1.insert(self.symbols.end());
+                    }
+                }
+                let expected: Vec<usize> = farthest.1.into_iter().collect();
+                Err(self.symbols.unexpected(&tokens[farthest.0], &expected))
+            }
+        }
+    }
+}
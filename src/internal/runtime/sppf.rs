@@ -0,0 +1,643 @@
+//! Shared packed parse forests, and SDF style filters to disambiguate them.
+//!
+//! This module only depends on `std`, as generated parsers embed it verbatim.
+
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Label {
+    Terminal(usize),
+    NonTerminal(usize),
+    /// Several symbols of a rule, by rule and dot: those before the dot when the node is the
+    /// first child of its packed nodes, and those after it when it is the last. Rules with more
+    /// than two symbols are split into these, so every packed node has at most two children.
+    Intermediate(usize, usize),
+}
+
+/// One way of deriving a forest node, by a rule and the forest nodes of its symbols, where an
+/// intermediate child stands for the symbols under it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Packed {
+    pub rule: usize,
+    pub children: Vec<usize>,
+}
+
+/// A symbol spanning the tokens `start..end`, with every way of deriving it.
+/// Terminals have no packed nodes.
+#[derive(Debug, Clone)]
+pub struct ForestNode {
+    pub label: Label,
+    pub start: usize,
+    pub end: usize,
+    pub packed: Vec<Packed>,
+}
+
+/// A single derivation, as an ordinary parse tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Tree {
+    /// The terminal at a position of the input
+    Leaf {
+        terminal: usize,
+        position: usize,
+    },
+    Node {
+        rule: usize,
+        children: Vec<Tree>,
+    },
+}
+
+/// Trees are dropped with a stack of their children rather than recursively, as deeply nested
+/// input would otherwise overflow the stack when its tree is freed
+impl Drop for Tree {
+    fn drop(&mut self) {
+        let Tree::Node { children, .. } = self else {
+            return;
+        };
+        let mut stack = std::mem::take(children);
+        while let Some(mut tree) = stack.pop() {
+            if let Tree::Node { children, .. } = &mut tree {
+                stack.append(children);
+            }
+        }
+    }
+}
+
+/// The number of derivations in a forest. Cycles allow infinitely many, and
+/// finite counts saturate at `u128::MAX`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Count {
+    Finite(u128),
+    Infinite,
+}
+
+impl fmt::Display for Count {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Count::Finite(count) => write!(f, "{count}"),
+            Count::Infinite => write!(f, "infinitely many"),
+        }
+    }
+}
+
+/// A forest which does not hold exactly one derivation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ambiguity {
+    /// The first node with more than one derivation, if any
+    pub node: Option<usize>,
+    pub derivations: Count,
+}
+
+impl fmt::Display for Ambiguity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected a single derivation, found {}",
+            self.derivations
+        )
+    }
+}
+
+impl std::error::Error for Ambiguity {}
+
+/// Disambiguation filters in the style of SDF, over rules, nonterminals and terminals by number
+#[derive(Debug, Clone, Default)]
+pub struct Filters {
+    /// `(parent, child)` rules where the child may not derive the first symbol of the parent.
+    /// This expresses both priorities and right associativity.
+    pub not_first: Vec<(usize, usize)>,
+    /// `(parent, child)` rules where the child may not derive the last symbol of the parent.
+    /// This expresses both priorities and left associativity.
+    pub not_last: Vec<(usize, usize)>,
+    /// Nonterminals which may not derive exactly this sequence of terminals
+    pub rejects: Vec<(usize, Vec<usize>)>,
+    /// Rules which win over the other derivations of the same node
+    pub preferred: Vec<usize>,
+    /// Rules which lose to the other derivations of the same node
+    pub avoided: Vec<usize>,
+    /// Nonterminals which may not be followed directly by any of these terminals
+    pub follow: Vec<(usize, Vec<usize>)>,
+}
+
+/// A depth first walk of the forest from its root
+#[derive(Debug, Default)]
+struct Walk {
+    /// Reachable nodes, children before their parents
+    postorder: Vec<usize>,
+    /// Paths closing a cycle, each from a symbol to the packed node leading back to it through
+    /// nodes further up the walk, as nodes and the index of the packed node walked from them
+    cycles: Vec<Vec<(usize, usize)>>,
+}
+
+/// Nodes are shared by every derivation which uses the same symbol over the same span, and
+/// packed nodes have at most two children, so a forest stays cubic in the length of the input
+/// even when the number of derivations is exponential.
+#[derive(Debug, Clone, Default)]
+pub struct Forest {
+    pub nodes: Vec<ForestNode>,
+    pub root: Option<usize>,
+    index: HashMap<(Label, usize, usize), usize>,
+    /// Packed nodes added so far, by node
+    packings: HashSet<(usize, Packed)>,
+}
+
+impl Forest {
+    /// The node for `label` over `start..end`, created if it does not exist yet
+    pub fn node(&mut self, label: Label, start: usize, end: usize) -> usize {
+        *self.index.entry((label, start, end)).or_insert_with(|| {
+            self.nodes.push(ForestNode {
+                label,
+                start,
+                end,
+                packed: vec![],
+            });
+            self.nodes.len() - 1
+        })
+    }
+
+    /// Add a derivation to a node, returning whether it was new. Derivations with more than two
+    /// children are split, with the symbols before the last one under an intermediate node.
+    pub fn pack(&mut self, node: usize, rule: usize, mut children: Vec<usize>) -> bool {
+        if children.len() > 2 {
+            let last = children.pop().expect("there are children");
+            let start = self.nodes[node].start;
+            let end = self.nodes[last].start;
+            let before = self.node(Label::Intermediate(rule, children.len()), start, end);
+            self.pack(before, rule, children);
+            children = vec![before, last];
+        }
+        let packed = Packed { rule, children };
+        if !self.packings.insert((node, packed.clone())) {
+            return false;
+        }
+        self.nodes[node].packed.push(packed);
+        true
+    }
+
+    /// Every sequence of children a packed node stands for, with intermediate nodes expanded
+    pub fn children(&self, packed: &Packed) -> Vec<Vec<usize>> {
+        let mut sequences = vec![vec![]];
+        for &child in &packed.children {
+            let expansions = match self.nodes[child].label {
+                Label::Intermediate(..) => self.nodes[child]
+                    .packed
+                    .iter()
+                    .flat_map(|packed| self.children(packed))
+                    .collect(),
+                _ => vec![vec![child]],
+            };
+            sequences = sequences
+                .iter()
+                .flat_map(|sequence| {
+                    expansions.iter().map(move |expansion| {
+                        let mut sequence = sequence.clone();
+                        sequence.extend(expansion);
+                        sequence
+                    })
+                })
+                .collect();
+        }
+        sequences
+    }
+
+    /// The number of symbols a packed node derives, counting those under intermediate nodes
+    fn length(&self, packed: &Packed) -> usize {
+        packed
+            .children
+            .iter()
+            .map(|&child| match self.nodes[child].label {
+                Label::Intermediate(..) => self.length(&self.nodes[child].packed[0]),
+                _ => 1,
+            })
+            .sum()
+    }
+
+    /// Apply disambiguation filters. The terminals of the input are needed to check rejects and
+    /// follow restrictions, including the end of input terminal. When the filters remove every
+    /// derivation, the forest is left without a root.
+    ///
+    /// Rejects and follow restrictions remove nodes, and `not_first` and `not_last` remove
+    /// derivations of a node only where it is used as the first or last child of a rule. A node
+    /// used both there and elsewhere is copied, so other uses are unaffected. A node which lost
+    /// every derivation is removed along with every derivation using it. Last, preferred rules
+    /// and avoided rules choose among the derivations left at each node.
+    pub fn filter(&mut self, filters: &Filters, terminals: &[usize]) {
+        for node in &mut self.nodes {
+            let Label::NonTerminal(nonterminal) = node.label else {
+                continue;
+            };
+            let rejected = filters.rejects.iter().any(|(rejected, sequence)| {
+                *rejected == nonterminal && terminals[node.start..node.end] == sequence[..]
+            });
+            let restricted = filters.follow.iter().any(|(restricted, followers)| {
+                *restricted == nonterminal
+                    && terminals
+                        .get(node.end)
+                        .is_some_and(|terminal| followers.contains(terminal))
+            });
+            if rejected || restricted {
+                node.packed.clear();
+            }
+        }
+
+        // Intermediate nodes hold either the first symbols of a rule or the last ones, and only
+        // that end is restricted
+        let mut ends = vec![(true, true); self.nodes.len()];
+        for node in &self.nodes {
+            for packed in &node.packed {
+                for (position, &child) in packed.children.iter().enumerate() {
+                    if let Label::Intermediate(..) = self.nodes[child].label {
+                        ends[child] = (position == 0, position + 1 == packed.children.len());
+                    }
+                }
+            }
+        }
+        let mut copies = HashMap::new();
+        // Copies are appended while this runs, and are filtered in turn
+        let mut node = 0;
+        while node < self.nodes.len() {
+            let (first, last) = ends.get(node).copied().unwrap_or((true, true));
+            let mut kept = vec![];
+            // Cloned rather than taken, as a node may be its own child
+            for mut packed in self.nodes[node].packed.clone() {
+                let mut restrictions = vec![];
+                if first {
+                    restrictions.push((0, &filters.not_first));
+                }
+                if last {
+                    let position = packed.children.len().wrapping_sub(1);
+                    restrictions.push((position, &filters.not_last));
+                }
+                let mut allowed = true;
+                for (position, forbidden) in restrictions {
+                    let Some(&child) = packed.children.get(position) else {
+                        continue;
+                    };
+                    match self.restrict(child, packed.rule, forbidden, &mut copies) {
+                        Some(child) => packed.children[position] = child,
+                        None => allowed = false,
+                    }
+                }
+                if allowed {
+                    kept.push(packed);
+                }
+            }
+            self.nodes[node].packed = kept;
+            node += 1;
+        }
+        self.prune();
+
+        for node in &mut self.nodes {
+            if node
+                .packed
+                .iter()
+                .any(|packed| filters.preferred.contains(&packed.rule))
+            {
+                node.packed
+                    .retain(|packed| filters.preferred.contains(&packed.rule));
+            }
+            if node
+                .packed
+                .iter()
+                .any(|packed| !filters.avoided.contains(&packed.rule))
+            {
+                node.packed
+                    .retain(|packed| !filters.avoided.contains(&packed.rule));
+            }
+        }
+    }
+
+    /// The node to use as a child of `parent`, without derivations by the `forbidden` rules
+    /// under it. Returns `None` when no derivation is left.
+    fn restrict(
+        &mut self,
+        child: usize,
+        parent: usize,
+        forbidden: &[(usize, usize)],
+        copies: &mut HashMap<(usize, Vec<usize>), usize>,
+    ) -> Option<usize> {
+        // The children of an intermediate node are restricted when that node is filtered
+        if let Label::Terminal(_) | Label::Intermediate(..) = self.nodes[child].label {
+            return Some(child);
+        }
+        let removed: Vec<usize> = self.nodes[child]
+            .packed
+            .iter()
+            .map(|packed| packed.rule)
+            .filter(|&rule| forbidden.contains(&(parent, rule)))
+            .collect();
+        let node = &self.nodes[child];
+        if removed.is_empty() && !node.packed.is_empty() {
+            return Some(child);
+        }
+        if removed.len() == node.packed.len() {
+            return None;
+        }
+        if let Some(&copy) = copies.get(&(child, removed.clone())) {
+            return Some(copy);
+        }
+        let mut copy = node.clone();
+        copy.packed.retain(|packed| !removed.contains(&packed.rule));
+        self.nodes.push(copy);
+        copies.insert((child, removed), self.nodes.len() - 1);
+        Some(self.nodes.len() - 1)
+    }
+
+    /// Remove every derivation using a node without derivations, other than a terminal, until
+    /// there are none
+    fn prune(&mut self) {
+        loop {
+            let dead: Vec<bool> = self
+                .nodes
+                .iter()
+                .map(|node| !matches!(node.label, Label::Terminal(_)) && node.packed.is_empty())
+                .collect();
+            let mut changed = false;
+            for node in &mut self.nodes {
+                let before = node.packed.len();
+                node.packed
+                    .retain(|packed| !packed.children.iter().any(|&child| dead[child]));
+                changed |= node.packed.len() != before;
+            }
+            if !changed {
+                break;
+            }
+        }
+        if self
+            .root
+            .is_some_and(|root| self.nodes[root].packed.is_empty())
+        {
+            self.root = None;
+        }
+    }
+
+    fn walk(&self) -> Walk {
+        let mut walk = Walk::default();
+        let Some(root) = self.root else {
+            return walk;
+        };
+        // Nodes are unvisited, on the stack, or done
+        let mut visited = vec![false; self.nodes.len()];
+        let mut on_stack = vec![false; self.nodes.len()];
+        let edges = |node: usize| -> Vec<(usize, usize)> {
+            self.nodes[node]
+                .packed
+                .iter()
+                .enumerate()
+                .flat_map(|(index, packed)| {
+                    packed.children.iter().map(move |&child| (index, child))
+                })
+                .collect()
+        };
+        let mut stack = vec![(root, edges(root), 0)];
+        visited[root] = true;
+        on_stack[root] = true;
+        while let Some((node, edges_of_node, cursor)) = stack.last_mut() {
+            let node = *node;
+            let Some(&(_, child)) = edges_of_node.get(*cursor) else {
+                on_stack[node] = false;
+                walk.postorder.push(node);
+                stack.pop();
+                continue;
+            };
+            *cursor += 1;
+            if on_stack[child] {
+                let symbol = stack
+                    .iter()
+                    .rposition(|&(node, _, _)| {
+                        !matches!(self.nodes[node].label, Label::Intermediate(..))
+                    })
+                    .expect("the root is a symbol");
+                let step = |(node, edges, cursor): &(usize, Vec<(usize, usize)>, usize)| {
+                    (*node, edges[cursor - 1].0)
+                };
+                let mut path: Vec<_> = stack[symbol..].iter().map(step).collect();
+                // Going back up to an intermediate node only closes a cycle through the symbol
+                // below it, so the path goes on down to there
+                if let Label::Intermediate(..) = self.nodes[child].label {
+                    let start = stack
+                        .iter()
+                        .position(|&(node, _, _)| node == child)
+                        .expect("the child is on the stack");
+                    path.extend(
+                        stack[start..]
+                            .iter()
+                            .take_while(|&&(node, _, _)| {
+                                matches!(self.nodes[node].label, Label::Intermediate(..))
+                            })
+                            .map(step),
+                    );
+                }
+                walk.cycles.push(path);
+            } else if !visited[child] {
+                visited[child] = true;
+                on_stack[child] = true;
+                stack.push((child, edges(child), 0));
+            }
+        }
+        walk
+    }
+
+    /// The forest without cycles, and a walk of it. A cycle is cut at the packed node leading
+    /// back up the walk, in a copy of the intermediate nodes between it and the closest symbol,
+    /// as they may also be used outside the cycle. The forest is copied only when it has cycles.
+    fn acyclic(&self) -> (Cow<'_, Forest>, Walk) {
+        let mut forest = Cow::Borrowed(self);
+        loop {
+            let walk = forest.walk();
+            if walk.cycles.is_empty() {
+                return (forest, walk);
+            }
+            // Cycles through nodes already cut are left to the next walk
+            let forest = forest.to_mut();
+            let mut cut = HashSet::new();
+            for path in walk.cycles {
+                if path.iter().all(|&(node, _)| !cut.contains(&node)) {
+                    cut.extend(path.iter().map(|&(node, _)| node));
+                    forest.cut(&path);
+                }
+            }
+        }
+    }
+
+    /// Remove the last packed node of a path from copies of the intermediate nodes along it, so
+    /// that only the symbol the path starts from loses the derivations through it
+    fn cut(&mut self, path: &[(usize, usize)]) {
+        let mut replaced: Option<(usize, usize)> = None;
+        for (depth, &(node, packed)) in path.iter().enumerate().rev() {
+            let mut copy = self.nodes[node].clone();
+            match replaced {
+                None => {
+                    copy.packed.remove(packed);
+                }
+                Some((old, new)) => {
+                    for child in &mut copy.packed[packed].children {
+                        if *child == old {
+                            *child = new;
+                        }
+                    }
+                }
+            }
+            if depth == 0 {
+                self.nodes[node] = copy;
+            } else {
+                self.nodes.push(copy);
+                replaced = Some((node, self.nodes.len() - 1));
+            }
+        }
+    }
+
+    /// The number of derivations of each node
+    fn counts(&self, walk: &Walk) -> Vec<u128> {
+        let mut counts = vec![0; self.nodes.len()];
+        for &node in &walk.postorder {
+            counts[node] = match self.nodes[node].label {
+                Label::Terminal(_) => 1,
+                Label::NonTerminal(_) | Label::Intermediate(..) => self.nodes[node]
+                    .packed
+                    .iter()
+                    .map(|packed| {
+                        packed.children.iter().fold(1u128, |product, &child| {
+                            product.saturating_mul(counts[child])
+                        })
+                    })
+                    .fold(0u128, u128::saturating_add),
+            };
+        }
+        counts
+    }
+
+    /// The number of derivations from the root
+    pub fn count(&self) -> Count {
+        let (forest, walk) = self.acyclic();
+        if let Cow::Owned(_) = forest {
+            return Count::Infinite;
+        }
+        Count::Finite(forest.root.map_or(0, |root| forest.counts(&walk)[root]))
+    }
+
+    /// Every derivation from the root, built as it is needed. Derivations going through a cycle
+    /// of the forest are skipped, so there are finitely many.
+    pub fn derivations(&self) -> Derivations<'_> {
+        let (forest, walk) = self.acyclic();
+        let counts = forest.counts(&walk);
+        let total = forest.root.map_or(0, |root| counts[root]);
+        Derivations {
+            forest,
+            counts,
+            next: 0,
+            total,
+        }
+    }
+
+    /// The only derivation from the root, once filters have removed the others
+    pub fn tree(&self) -> Result<Tree, Ambiguity> {
+        let count = self.count();
+        if count != Count::Finite(1) {
+            return Err(Ambiguity {
+                node: self
+                    .reachable()
+                    .into_iter()
+                    .find(|&node| self.nodes[node].packed.len() > 1),
+                derivations: count,
+            });
+        }
+        Ok(self.derivations().next().expect("there is one derivation"))
+    }
+
+    /// Nodes reachable from the root, in depth first preorder
+    pub fn reachable(&self) -> Vec<usize> {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut order = vec![];
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(node) = stack.pop() {
+            if std::mem::replace(&mut seen[node], true) {
+                continue;
+            }
+            order.push(node);
+            for packed in self.nodes[node].packed.iter().rev() {
+                stack.extend(packed.children.iter().rev());
+            }
+        }
+        order
+    }
+}
+
+/// Iterator over the derivations of a forest, in order of the packed nodes chosen
+#[derive(Debug)]
+pub struct Derivations<'f> {
+    forest: Cow<'f, Forest>,
+    counts: Vec<u128>,
+    next: u128,
+    total: u128,
+}
+
+impl Derivations<'_> {
+    /// The derivation numbered `index` of a node, by splitting the index among the
+    /// packed nodes and then among their children. The children of intermediate nodes become
+    /// children of the symbol above them. The tree is built without recursion.
+    fn derivation(&self, node: usize, index: u128) -> Tree {
+        enum Entry {
+            Leaf(usize, usize),
+            Node(usize, usize),
+        }
+        let forest = &*self.forest;
+        let mut preorder = vec![];
+        let mut stack = vec![(node, index)];
+        while let Some((node, mut index)) = stack.pop() {
+            let label = forest.nodes[node].label;
+            if let Label::Terminal(terminal) = label {
+                preorder.push(Entry::Leaf(terminal, forest.nodes[node].start));
+                continue;
+            }
+            for packed in &forest.nodes[node].packed {
+                let count = packed.children.iter().fold(1u128, |product, &child| {
+                    product.saturating_mul(self.counts[child])
+                });
+                if index >= count {
+                    index -= count;
+                    continue;
+                }
+                if let Label::NonTerminal(_) = label {
+                    preorder.push(Entry::Node(packed.rule, forest.length(packed)));
+                }
+                // The first child takes the lowest digits of the index, and is pushed last
+                let mut children = vec![];
+                for &child in &packed.children {
+                    children.push((child, index % self.counts[child]));
+                    index /= self.counts[child];
+                }
+                stack.extend(children.into_iter().rev());
+                break;
+            }
+        }
+
+        let mut trees = vec![];
+        for entry in preorder.into_iter().rev() {
+            match entry {
+                Entry::Leaf(terminal, position) => trees.push(Tree::Leaf { terminal, position }),
+                Entry::Node(rule, length) => {
+                    let children = trees.drain(trees.len() - length..).rev().collect();
+                    trees.push(Tree::Node { rule, children });
+                }
+            }
+        }
+        trees.pop().expect("a derivation has a root")
+    }
+}
+
+impl Iterator for Derivations<'_> {
+    type Item = Tree;
+
+    fn next(&mut self) -> Option<Tree> {
+        let root = self.forest.root?;
+        if self.next >= self.total {
+            return None;
+        }
+        self.next += 1;
+        Some(self.derivation(root, self.next - 1))
+    }
+}
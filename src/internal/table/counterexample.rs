@@ -0,0 +1,850 @@
+//! Explanations of table conflicts, in the spirit of Bison's `-Wcounterexamples`.
+//!
+//! Every conflict is explained by the shortest way of reaching it, both as a sentential
+//! form and as a terminal example input. When a small enough ambiguous sentence exists,
+//! it is shown with two distinct derivation trees.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+};
+
+use crate::{
+    diagnostic::Diagnostic,
+    internal::{
+        analysis::sets::{FirstFollow, END},
+        ast::{Grammar, Symbol},
+    },
+    span::Span,
+};
+
+use super::{
+    ll1::{Ll1Conflict, Ll1Table},
+    lr::{Item, LrAction, LrConflict, LrTable},
+    Rule,
+};
+
+/// Longest sentence, in terminals, considered when searching for an ambiguity
+const MAX_SENTENCE_LENGTH: usize = 12;
+/// Number of sentential forms expanded when searching for an ambiguity
+const SEARCH_LIMIT: usize = 20_000;
+
+/// A derivation tree. Terminals are leaves without a rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Derivation<'a> {
+    pub symbol: Symbol<'a>,
+    pub rule: Option<usize>,
+    pub children: Vec<Derivation<'a>>,
+}
+
+impl<'a> Derivation<'a> {
+    fn leaf(terminal: &'a str) -> Self {
+        Derivation {
+            symbol: Symbol::Terminal(terminal),
+            rule: None,
+            children: vec![],
+        }
+    }
+
+    pub fn terminals(&self) -> Vec<&'a str> {
+        match self.symbol {
+            Symbol::Terminal(name) => vec![name],
+            Symbol::NonTerminal(_) => self.children.iter().flat_map(Self::terminals).collect(),
+        }
+    }
+
+    fn uses_any(&self, rules: &[usize]) -> bool {
+        self.rule.is_some_and(|rule| rules.contains(&rule))
+            || self.children.iter().any(|child| child.uses_any(rules))
+    }
+
+    /// `last` is `None` for the root, otherwise whether this is the last child of its parent
+    fn render(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        indent: &str,
+        prefix: &str,
+        last: Option<bool>,
+    ) -> fmt::Result {
+        let (branch, child_prefix) = match last {
+            None => ("", prefix.to_string()),
+            Some(true) => ("└─ ", format!("{prefix}   ")),
+            Some(false) => ("├─ ", format!("{prefix}│  ")),
+        };
+        let empty = if self.rule.is_some() && self.children.is_empty() {
+            " (ε)"
+        } else {
+            ""
+        };
+        writeln!(f, "{indent}{prefix}{branch}{}{empty}", self.symbol)?;
+        for (i, child) in self.children.iter().enumerate() {
+            child.render(f, indent, &child_prefix, Some(i + 1 == self.children.len()))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Derivation<'_> {
+    /// Renders the tree, indenting every line by the requested width
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let indent = " ".repeat(f.width().unwrap_or(0));
+        self.render(f, &indent, "", None)
+    }
+}
+
+/// The rule at the root of the smallest derivation tree of each nonterminal, from which parsers
+/// make up a rule missing in the input. Unproductive nonterminals have none.
+pub(crate) fn smallest<'a>(rules: &[Rule<'a>]) -> HashMap<&'a str, usize> {
+    Shortest::new(rules)
+        .smallest
+        .into_iter()
+        .map(|(name, (_, rule))| (name, rule))
+        .collect()
+}
+
+/// Smallest derivation trees of every rule, overall and restricted to the empty string
+struct Shortest<'r, 'a> {
+    rules: &'r [Rule<'a>],
+    /// Smallest tree size, and the rule used at the root
+    smallest: HashMap<&'a str, (usize, usize)>,
+    /// Smallest tree size deriving the empty string, and the rule used at the root
+    empty: HashMap<&'a str, (usize, usize)>,
+    /// Length of the shortest string derivable
+    min_length: HashMap<&'a str, usize>,
+}
+
+impl<'r, 'a> Shortest<'r, 'a> {
+    fn new(rules: &'r [Rule<'a>]) -> Self {
+        let mut shortest = Shortest {
+            rules,
+            smallest: HashMap::new(),
+            empty: HashMap::new(),
+            min_length: HashMap::new(),
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (index, rule) in rules.iter().enumerate() {
+                let size = rule.rhs.iter().try_fold(1, |acc, symbol| match symbol {
+                    Symbol::Terminal(_) => Some(acc + 1),
+                    Symbol::NonTerminal(name) => {
+                        shortest.smallest.get(name).map(|(size, _)| acc + size)
+                    }
+                });
+                if let Some(size) = size {
+                    if shortest
+                        .smallest
+                        .get(rule.lhs)
+                        .is_none_or(|&(best, _)| size < best)
+                    {
+                        shortest.smallest.insert(rule.lhs, (size, index));
+                        changed = true;
+                    }
+                }
+
+                let size = rule.rhs.iter().try_fold(1, |acc, symbol| match symbol {
+                    Symbol::Terminal(_) => None,
+                    Symbol::NonTerminal(name) => {
+                        shortest.empty.get(name).map(|(size, _)| acc + size)
+                    }
+                });
+                if let Some(size) = size {
+                    if shortest
+                        .empty
+                        .get(rule.lhs)
+                        .is_none_or(|&(best, _)| size < best)
+                    {
+                        shortest.empty.insert(rule.lhs, (size, index));
+                        changed = true;
+                    }
+                }
+
+                if let Some(length) = shortest.length_of(&rule.rhs) {
+                    if shortest
+                        .min_length
+                        .get(rule.lhs)
+                        .is_none_or(|&best| length < best)
+                    {
+                        shortest.min_length.insert(rule.lhs, length);
+                        changed = true;
+                    }
+                }
+            }
+        }
+        shortest
+    }
+
+    /// Length of the shortest string derivable from a sequence of symbols
+    fn length_of(&self, symbols: &[Symbol<'a>]) -> Option<usize> {
+        symbols.iter().try_fold(0, |acc, symbol| match symbol {
+            Symbol::Terminal(_) => Some(acc + 1),
+            Symbol::NonTerminal(name) => self.min_length.get(name).map(|length| acc + length),
+        })
+    }
+
+    fn tree(&self, symbol: Symbol<'a>) -> Option<Derivation<'a>> {
+        self.tree_from(symbol, &self.smallest)
+    }
+
+    fn empty_tree(&self, symbol: Symbol<'a>) -> Option<Derivation<'a>> {
+        self.tree_from(symbol, &self.empty)
+    }
+
+    /// The size of the root is strictly larger than the size of each child, so this terminates
+    fn tree_from(
+        &self,
+        symbol: Symbol<'a>,
+        table: &HashMap<&'a str, (usize, usize)>,
+    ) -> Option<Derivation<'a>> {
+        match symbol {
+            Symbol::Terminal(name) => Some(Derivation::leaf(name)),
+            Symbol::NonTerminal(name) => {
+                let &(_, rule) = table.get(name)?;
+                Some(Derivation {
+                    symbol,
+                    rule: Some(rule),
+                    children: self.rules[rule]
+                        .rhs
+                        .iter()
+                        .map(|&child| self.tree_from(child, table))
+                        .collect::<Option<_>>()?,
+                })
+            }
+        }
+    }
+
+    fn trees(&self, symbols: &[Symbol<'a>]) -> Option<Vec<Derivation<'a>>> {
+        symbols.iter().map(|&symbol| self.tree(symbol)).collect()
+    }
+
+    /// A derivation of the symbols whose yield starts with `terminal`
+    fn starting_with(
+        &self,
+        symbols: &[Symbol<'a>],
+        terminal: &'a str,
+        visiting: &mut HashSet<&'a str>,
+    ) -> Option<Vec<Derivation<'a>>> {
+        let (&first, rest) = symbols.split_first()?;
+        let head = match first {
+            Symbol::Terminal(name) if name == terminal => Some(Derivation::leaf(name)),
+            Symbol::Terminal(_) => return None,
+            Symbol::NonTerminal(name) if visiting.insert(name) => {
+                let head = self
+                    .rules
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, rule)| rule.lhs == name)
+                    .find_map(|(index, rule)| {
+                        Some(Derivation {
+                            symbol: first,
+                            rule: Some(index),
+                            children: self.starting_with(&rule.rhs, terminal, visiting)?,
+                        })
+                    });
+                visiting.remove(name);
+                head
+            }
+            Symbol::NonTerminal(_) => None,
+        };
+        match head {
+            Some(head) => {
+                let mut trees = vec![head];
+                trees.extend(self.trees(rest)?);
+                Some(trees)
+            }
+            None => {
+                let mut trees = vec![self.empty_tree(first)?];
+                trees.extend(self.starting_with(rest, terminal, visiting)?);
+                Some(trees)
+            }
+        }
+    }
+}
+
+/// Finds up to two distinct derivations of a sentence, by memoized top-down search
+struct AmbiguityParser<'r, 'a> {
+    rules: &'r [Rule<'a>],
+    shortest: &'r Shortest<'r, 'a>,
+    input: &'r [&'a str],
+    memo: HashMap<(&'a str, usize, usize), Vec<Derivation<'a>>>,
+    active: HashSet<(&'a str, usize, usize)>,
+}
+
+impl<'r, 'a> AmbiguityParser<'r, 'a> {
+    fn nonterminal(&mut self, name: &'a str, start: usize, end: usize) -> Vec<Derivation<'a>> {
+        let key = (name, start, end);
+        if let Some(found) = self.memo.get(&key) {
+            return found.clone();
+        }
+        if !self.active.insert(key) {
+            return vec![];
+        }
+        let mut found = vec![];
+        for (index, rule) in self
+            .rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| rule.lhs == name)
+        {
+            for children in self.sequence(&rule.rhs, start, end) {
+                found.push(Derivation {
+                    symbol: Symbol::NonTerminal(name),
+                    rule: Some(index),
+                    children,
+                });
+            }
+            if found.len() >= 2 {
+                break;
+            }
+        }
+        found.truncate(2);
+        self.active.remove(&key);
+        self.memo.insert(key, found.clone());
+        found
+    }
+
+    fn sequence(
+        &mut self,
+        symbols: &[Symbol<'a>],
+        start: usize,
+        end: usize,
+    ) -> Vec<Vec<Derivation<'a>>> {
+        let Some((&first, rest)) = symbols.split_first() else {
+            return if start == end { vec![vec![]] } else { vec![] };
+        };
+        let Some(rest_length) = self.shortest.length_of(rest) else {
+            return vec![];
+        };
+        let mut found = vec![];
+        let heads: Vec<(Derivation, usize)> = match first {
+            Symbol::Terminal(name) => {
+                if start < end && self.input[start] == name {
+                    vec![(Derivation::leaf(name), start + 1)]
+                } else {
+                    vec![]
+                }
+            }
+            Symbol::NonTerminal(name) => {
+                let Some(&min_length) = self.shortest.min_length.get(name) else {
+                    return vec![];
+                };
+                let mut heads = vec![];
+                for split in start + min_length..=end.saturating_sub(rest_length) {
+                    for head in self.nonterminal(name, start, split) {
+                        heads.push((head, split));
+                    }
+                }
+                heads
+            }
+        };
+        for (head, split) in heads {
+            for tail in self.sequence(rest, split, end) {
+                let mut children = vec![head.clone()];
+                children.extend(tail);
+                found.push(children);
+                if found.len() >= 2 {
+                    return found;
+                }
+            }
+        }
+        found
+    }
+}
+
+/// Search the sentences of the grammar, shortest derivations first, for one with two derivations.
+/// The sentence must contain `terminal`, and one of the derivations must use one of `involved`.
+fn find_ambiguity<'a>(
+    rules: &[Rule<'a>],
+    shortest: &Shortest<'_, 'a>,
+    terminal: &str,
+    involved: &[usize],
+) -> Option<(Vec<&'a str>, Derivation<'a>, Derivation<'a>)> {
+    let start = rules[0].rhs.first().copied()?;
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([vec![start]]);
+    let mut expanded = 0;
+    while let Some(form) = queue.pop_front() {
+        expanded += 1;
+        if expanded > SEARCH_LIMIT {
+            return None;
+        }
+        let Some(position) = form
+            .iter()
+            .position(|symbol| matches!(symbol, Symbol::NonTerminal(_)))
+        else {
+            let sentence: Vec<&str> = form.iter().map(|symbol| symbol.name()).collect();
+            if !sentence.contains(&terminal) && terminal != END {
+                continue;
+            }
+            let mut parser = AmbiguityParser {
+                rules,
+                shortest,
+                input: &sentence,
+                memo: HashMap::new(),
+                active: HashSet::new(),
+            };
+            let mut derivations = parser.nonterminal(start.name(), 0, sentence.len());
+            if derivations.len() >= 2 && derivations.iter().any(|d| d.uses_any(involved)) {
+                let second = derivations.pop().unwrap();
+                let first = derivations.pop().unwrap();
+                return Some((sentence, first, second));
+            }
+            continue;
+        };
+
+        let name = form[position].name();
+        for rule in rules.iter().skip(1).filter(|rule| rule.lhs == name) {
+            let mut next = form[..position].to_vec();
+            next.extend(rule.rhs.iter().copied());
+            next.extend(form[position + 1..].iter().copied());
+            if shortest
+                .length_of(&next)
+                .is_some_and(|length| length <= MAX_SENTENCE_LENGTH)
+                && seen.insert(next.clone())
+            {
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+/// Two derivations of the same sentence
+#[derive(Debug)]
+pub struct Unifying<'a> {
+    pub sentence: Vec<&'a str>,
+    pub first: Derivation<'a>,
+    pub second: Derivation<'a>,
+}
+
+/// A competing action or alternative of a conflict: its kind, the item or rule it is, and the
+/// alternative of the grammar it is from
+pub type Candidate = (String, String, Option<Span>);
+
+/// Human readable explanation of a single conflict
+#[derive(Debug)]
+pub struct Explanation<'a> {
+    pub title: String,
+    /// The competing actions or alternatives, each with the alternative of the grammar it is from
+    pub candidates: Vec<Candidate>,
+    /// Shortest sentential form reaching the conflict, before the lookahead
+    pub prefix: Vec<Symbol<'a>>,
+    /// The prefix with every nonterminal replaced by its shortest yield
+    pub example: Vec<&'a str>,
+    pub lookahead: &'a str,
+    /// How each candidate continues from the lookahead, where one could be found
+    pub derivations: Vec<(String, Derivation<'a>)>,
+    pub unifying: Option<Unifying<'a>>,
+}
+
+impl fmt::Display for Explanation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn join<T: fmt::Display>(items: &[T]) -> String {
+            items
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+        fn dotted<T: fmt::Display>(items: &[T], lookahead: &str) -> String {
+            let mut out = join(items);
+            if !out.is_empty() {
+                out.push(' ');
+            }
+            out.push_str("• ");
+            out.push_str(lookahead);
+            out
+        }
+
+        writeln!(f, "{}", self.title)?;
+        for (label, candidate, _) in &self.candidates {
+            writeln!(f, "  {label}: {candidate}")?;
+        }
+        writeln!(
+            f,
+            "  shortest prefix: {}",
+            dotted(&self.prefix, self.lookahead)
+        )?;
+        writeln!(
+            f,
+            "  example input:   {}",
+            dotted(&self.example, self.lookahead)
+        )?;
+        for (label, derivation) in &self.derivations {
+            writeln!(f, "  {label}:")?;
+            write!(f, "{derivation:4}")?;
+        }
+        match &self.unifying {
+            Some(unifying) => {
+                writeln!(f, "  ambiguous input: {}", join(&unifying.sentence))?;
+                writeln!(f, "  first derivation:")?;
+                write!(f, "{:4}", unifying.first)?;
+                writeln!(f, "  second derivation:")?;
+                write!(f, "{:4}", unifying.second)
+            }
+            None => writeln!(
+                f,
+                "  no ambiguous input found, the grammar may need more lookahead"
+            ),
+        }
+    }
+}
+
+fn terminalize<'a>(shortest: &Shortest<'_, 'a>, prefix: &[Symbol<'a>]) -> Vec<&'a str> {
+    prefix
+        .iter()
+        .filter_map(|&symbol| shortest.tree(symbol))
+        .flat_map(|tree| tree.terminals())
+        .collect()
+}
+
+/// Breadth first search through the automaton for the shortest path to `target`
+fn lr_prefix<'a>(table: &LrTable<'a>, target: usize) -> Vec<Symbol<'a>> {
+    let mut previous: HashMap<usize, (usize, Symbol)> = HashMap::new();
+    let mut queue = VecDeque::from([0]);
+    while let Some(state) = queue.pop_front() {
+        if state == target {
+            break;
+        }
+        for (&symbol, &next) in &table.automaton.states[state].transitions {
+            if next != 0 && !previous.contains_key(&next) {
+                previous.insert(next, (state, symbol));
+                queue.push_back(next);
+            }
+        }
+    }
+    let mut prefix = vec![];
+    let mut state = target;
+    while let Some(&(before, symbol)) = previous.get(&state) {
+        prefix.push(symbol);
+        state = before;
+    }
+    prefix.reverse();
+    prefix
+}
+
+/// The shortest viable prefix leading to `state`, and that prefix with every nonterminal replaced
+/// by its shortest yield
+pub fn example_lr<'a>(table: &LrTable<'a>, state: usize) -> (Vec<Symbol<'a>>, Vec<&'a str>) {
+    let shortest = Shortest::new(&table.automaton.rules);
+    let prefix = lr_prefix(table, state);
+    let example = terminalize(&shortest, &prefix);
+    (prefix, example)
+}
+
+/// A conflict as an error with the given title, labelling the alternative of each candidate
+fn conflict_diagnostic(title: String, candidates: &[Candidate]) -> Diagnostic {
+    let mut diagnostic = Diagnostic::error(title).with_code("E007");
+    for (label, candidate, span) in candidates {
+        let Some(span) = *span else {
+            continue;
+        };
+        let message = format!("{label}: {candidate}");
+        diagnostic = if diagnostic.primary.is_none() {
+            diagnostic.with_primary(span, message)
+        } else {
+            diagnostic.with_secondary(span, message)
+        };
+    }
+    diagnostic
+}
+
+impl Explanation<'_> {
+    /// The explanation as an error labelling the alternatives of its candidates, with the way to
+    /// the conflict and its derivations as notes
+    pub fn diagnostic(&self) -> Diagnostic {
+        let mut diagnostic = conflict_diagnostic(self.title.clone(), &self.candidates);
+        // Each line indented once starts a note, and lines indented further belong to it
+        let rendered = self.to_string();
+        for line in rendered.lines().skip(1 + self.candidates.len()) {
+            let line = line.strip_prefix("  ").unwrap_or(line);
+            match diagnostic.notes.last_mut() {
+                Some(note) if line.starts_with(' ') => {
+                    note.push('\n');
+                    note.push_str(line);
+                }
+                _ => diagnostic.notes.push(line.to_string()),
+            }
+        }
+        diagnostic
+    }
+}
+
+fn lr_title(conflict: &LrConflict) -> String {
+    format!(
+        "{} conflict in state {} on `{}`",
+        conflict.kind(),
+        conflict.state,
+        conflict.terminal
+    )
+}
+
+/// The candidates of an LR conflict, and the rules it reduces
+fn lr_candidates(table: &LrTable, conflict: &LrConflict) -> (Vec<Candidate>, Vec<usize>) {
+    let automaton = &table.automaton;
+    let state = &automaton.states[conflict.state];
+    let mut candidates = vec![];
+    let mut reduced = vec![];
+    for action in &conflict.actions {
+        match action {
+            LrAction::Shift(_) => {
+                for &item in &state.items {
+                    if automaton.next_symbol(item) == Some(Symbol::Terminal(conflict.terminal)) {
+                        let span = automaton.rules[item.rule].span;
+                        candidates.push(("shift".into(), automaton.display_item(item), span));
+                    }
+                }
+            }
+            LrAction::Reduce(rule) => {
+                reduced.push(*rule);
+                let item = Item {
+                    rule: *rule,
+                    dot: automaton.rules[*rule].rhs.len(),
+                };
+                let span = automaton.rules[*rule].span;
+                candidates.push(("reduce".into(), automaton.display_item(item), span));
+            }
+            LrAction::Accept => candidates.push((
+                "accept".into(),
+                automaton.display_item(Item { rule: 0, dot: 1 }),
+                None,
+            )),
+            LrAction::Go(_) => {}
+        }
+    }
+    (candidates, reduced)
+}
+
+/// An LR conflict as an error labelling the alternatives of its candidates, without explaining it
+pub fn diagnose_lr(table: &LrTable, conflict: &LrConflict) -> Diagnostic {
+    conflict_diagnostic(lr_title(conflict), &lr_candidates(table, conflict).0)
+}
+
+pub fn explain_lr<'a>(table: &LrTable<'a>, conflict: &LrConflict<'a>) -> Explanation<'a> {
+    let automaton = &table.automaton;
+    let shortest = Shortest::new(&automaton.rules);
+    let (candidates, reduced) = lr_candidates(table, conflict);
+
+    let prefix = lr_prefix(table, conflict.state);
+    let example = terminalize(&shortest, &prefix);
+    let unifying = find_ambiguity(&automaton.rules, &shortest, conflict.terminal, &reduced).map(
+        |(sentence, first, second)| Unifying {
+            sentence,
+            first,
+            second,
+        },
+    );
+
+    Explanation {
+        title: lr_title(conflict),
+        candidates,
+        prefix,
+        example,
+        lookahead: conflict.terminal,
+        derivations: vec![],
+        unifying,
+    }
+}
+
+/// Shortest leftmost sentential form whose leftmost nonterminal is `target`, and which can
+/// continue with `lookahead`. Returns the symbols before the nonterminal.
+fn ll_prefix<'a>(
+    rules: &[Rule<'a>],
+    sets: &FirstFollow<'a>,
+    target: &'a str,
+    lookahead: &'a str,
+) -> Vec<Symbol<'a>> {
+    let Some(&start) = rules[0].rhs.first() else {
+        return vec![];
+    };
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([(vec![], vec![start])]);
+    while let Some((prefix, form)) = queue.pop_front() {
+        if seen.len() > SEARCH_LIMIT {
+            break;
+        }
+        let Some(position) = form
+            .iter()
+            .position(|symbol| matches!(symbol, Symbol::NonTerminal(_)))
+        else {
+            continue;
+        };
+        let mut prefix: Vec<Symbol> = prefix;
+        prefix.extend_from_slice(&form[..position]);
+        if form[position] == Symbol::NonTerminal(target) {
+            let (first, nullable) = sets.first_of(&form[position..]);
+            if first.contains(lookahead) || (nullable && lookahead == END) {
+                return prefix;
+            }
+        }
+        let name = form[position].name();
+        for rule in rules.iter().skip(1).filter(|rule| rule.lhs == name) {
+            let mut next = rule.rhs.clone();
+            next.extend(form[position + 1..].iter().copied());
+            if seen.insert((prefix.clone(), next.clone())) {
+                queue.push_back((prefix.clone(), next));
+            }
+        }
+    }
+    vec![]
+}
+
+fn ll1_title(conflict: &Ll1Conflict) -> String {
+    format!(
+        "LL(1) conflict in rule `{}` on `{}`",
+        conflict.nonterminal, conflict.terminal
+    )
+}
+
+fn ll1_candidate(rule: &Rule) -> Candidate {
+    ("alternative".into(), rule.to_string(), rule.span)
+}
+
+/// An LL(1) conflict as an error labelling its alternatives, without explaining it
+pub fn diagnose_ll1(table: &Ll1Table, conflict: &Ll1Conflict) -> Diagnostic {
+    let candidates: Vec<_> = conflict
+        .rules
+        .iter()
+        .map(|&rule| ll1_candidate(&table.rules[rule]))
+        .collect();
+    conflict_diagnostic(ll1_title(conflict), &candidates)
+}
+
+pub fn explain_ll1<'a>(
+    grammar: &'a Grammar,
+    table: &Ll1Table<'a>,
+    conflict: &Ll1Conflict<'a>,
+) -> Explanation<'a> {
+    let sets = FirstFollow::new(grammar);
+    let shortest = Shortest::new(&table.rules);
+
+    let mut candidates = vec![];
+    let mut derivations = vec![];
+    for (i, &rule) in conflict.rules.iter().enumerate() {
+        candidates.push(ll1_candidate(&table.rules[rule]));
+        let children = shortest
+            .starting_with(
+                &table.rules[rule].rhs,
+                conflict.terminal,
+                &mut HashSet::new(),
+            )
+            .or_else(|| {
+                table.rules[rule]
+                    .rhs
+                    .iter()
+                    .map(|&symbol| shortest.empty_tree(symbol))
+                    .collect()
+            });
+        if let Some(children) = children {
+            let label = if children.iter().all(|child| child.terminals().is_empty()) {
+                format!(
+                    "alternative {} derives ε, followed by `{}`",
+                    i + 1,
+                    conflict.terminal
+                )
+            } else {
+                format!("alternative {} derives `{}`", i + 1, conflict.terminal)
+            };
+            derivations.push((
+                label,
+                Derivation {
+                    symbol: Symbol::NonTerminal(conflict.nonterminal),
+                    rule: Some(rule),
+                    children,
+                },
+            ));
+        }
+    }
+
+    let prefix = ll_prefix(&table.rules, &sets, conflict.nonterminal, conflict.terminal);
+    let example = terminalize(&shortest, &prefix);
+    let unifying = find_ambiguity(&table.rules, &shortest, conflict.terminal, &conflict.rules).map(
+        |(sentence, first, second)| Unifying {
+            sentence,
+            first,
+            second,
+        },
+    );
+
+    Explanation {
+        title: ll1_title(conflict),
+        candidates,
+        prefix,
+        example,
+        lookahead: conflict.terminal,
+        derivations,
+        unifying,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::{table::lr::LrKind, testing::grammar};
+
+    #[test]
+    fn explain_ambiguous_expression() {
+        let grammar = grammar("e: e PLUS e | ID;");
+        let table = LrTable::new(&grammar, LrKind::Lalr);
+        let conflicts = table.conflicts();
+        let explanation = explain_lr(&table, &conflicts[0]);
+
+        assert_eq!(
+            explanation.to_string(),
+            "\
+shift/reduce conflict in state 4 on `PLUS`
+  shift: e -> e • PLUS e
+  reduce: e -> e PLUS e •
+  shortest prefix: e PLUS e • PLUS
+  example input:   ID PLUS ID • PLUS
+  ambiguous input: ID PLUS ID PLUS ID
+  first derivation:
+    e
+    ├─ e
+    │  └─ ID
+    ├─ PLUS
+    └─ e
+       ├─ e
+       │  └─ ID
+       ├─ PLUS
+       └─ e
+          └─ ID
+  second derivation:
+    e
+    ├─ e
+    │  ├─ e
+    │  │  └─ ID
+    │  ├─ PLUS
+    │  └─ e
+    │     └─ ID
+    ├─ PLUS
+    └─ e
+       └─ ID
+"
+        );
+    }
+
+    #[test]
+    fn explain_lookahead_conflict_without_ambiguity() {
+        let grammar = grammar("s: a X | b Y; a: ID; b: ID;");
+        let table = Ll1Table::new(&grammar);
+        let explanation = explain_ll1(&grammar, &table, &table.conflicts()[0]);
+
+        assert!(explanation.unifying.is_none());
+        assert_eq!(explanation.derivations.len(), 2);
+        assert_eq!(explanation.example, Vec::<&str>::new());
+    }
+
+    #[test]
+    fn explain_ll1_conflict_after_prefix() {
+        let grammar = grammar("s: OPEN t; t: ID | ID COMMA t;");
+        let table = Ll1Table::new(&grammar);
+        let explanation = explain_ll1(&grammar, &table, &table.conflicts()[0]);
+
+        assert_eq!(explanation.example, vec!["OPEN"]);
+        assert_eq!(
+            explanation.derivations[1].1.terminals(),
+            vec!["ID", "COMMA", "ID"]
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    error::GrammarError,
+    internal::{analysis::sets::FirstFollow, ast::Grammar},
+};
+
+use super::{counterexample::diagnose_ll1, rules, Rule};
+
+/// The predictive parsing table, mapping a rule and a lookahead terminal to the alternatives to expand.
+/// Rule indices refer to [`Ll1Table::rules`], where rule 0 is the augmented start and never predicted.
+#[derive(Debug)]
+pub struct Ll1Table<'a> {
+    pub rules: Vec<Rule<'a>>,
+    pub entries: BTreeMap<(&'a str, &'a str), Vec<usize>>,
+}
+
+/// A table cell predicting more than one alternative
+#[derive(Debug, Clone)]
+pub struct Ll1Conflict<'a> {
+    pub nonterminal: &'a str,
+    pub terminal: &'a str,
+    pub rules: Vec<usize>,
+}
+
+impl<'a> Ll1Table<'a> {
+    pub fn new(grammar: &'a Grammar) -> Self {
+        let sets = FirstFollow::new(grammar);
+        let rules = rules(grammar);
+        let mut entries: BTreeMap<(&str, &str), Vec<usize>> = BTreeMap::new();
+        for (index, rule) in rules.iter().enumerate().skip(1) {
+            let (mut lookaheads, nullable) = sets.first_of(&rule.rhs);
+            if nullable {
+                lookaheads.extend(sets.follow[rule.lhs].iter().copied());
+            }
+            for terminal in lookaheads {
+                entries.entry((rule.lhs, terminal)).or_default().push(index);
+            }
+        }
+        Ll1Table { rules, entries }
+    }
+
+    pub fn conflicts(&self) -> Vec<Ll1Conflict<'a>> {
+        self.entries
+            .iter()
+            .filter(|(_, rules)| rules.len() > 1)
+            .map(|(&(nonterminal, terminal), rules)| Ll1Conflict {
+                nonterminal,
+                terminal,
+                rules: rules.clone(),
+            })
+            .collect()
+    }
+
+    /// Fail with an error for each conflict, for the backends which need a table without any
+    pub fn reject_conflicts(&self) -> Result<(), GrammarError> {
+        let conflicts = self.conflicts();
+        if conflicts.is_empty() {
+            return Ok(());
+        }
+        let diagnostics = conflicts
+            .iter()
+            .map(|conflict| diagnose_ll1(self, conflict))
+            .collect();
+        Err(GrammarError::Ll1Conflicts(diagnostics))
+    }
+}
@@ -0,0 +1,431 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+};
+
+use crate::internal::{
+    analysis::sets::{FirstFollow, END},
+    ast::{Grammar, Symbol},
+};
+
+use super::{
+    precedence::{Decision, Precedence},
+    rules, Rule,
+};
+
+/// An LR(0) item, a rule with a dot marking how much of it has been seen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Item {
+    pub rule: usize,
+    pub dot: usize,
+}
+
+#[derive(Debug)]
+pub struct State<'a> {
+    /// The closure of the kernel items, which come first
+    pub items: Vec<Item>,
+    pub transitions: BTreeMap<Symbol<'a>, usize>,
+}
+
+/// The LR(0) automaton of a grammar, from which both SLR and LALR tables are built
+#[derive(Debug)]
+pub struct Automaton<'a> {
+    pub rules: Vec<Rule<'a>>,
+    pub states: Vec<State<'a>>,
+}
+
+impl<'a> Automaton<'a> {
+    pub fn new(grammar: &'a Grammar) -> Self {
+        let mut automaton = Automaton {
+            rules: rules(grammar),
+            states: vec![],
+        };
+        let mut kernels: HashMap<Vec<Item>, usize> = HashMap::new();
+        let initial = vec![Item { rule: 0, dot: 0 }];
+        kernels.insert(initial.clone(), 0);
+        automaton.states.push(automaton.state(initial));
+
+        let mut next = 0;
+        while next < automaton.states.len() {
+            let mut successors: BTreeMap<Symbol, Vec<Item>> = BTreeMap::new();
+            for &item in &automaton.states[next].items {
+                if let Some(symbol) = automaton.next_symbol(item) {
+                    successors.entry(symbol).or_default().push(Item {
+                        rule: item.rule,
+                        dot: item.dot + 1,
+                    });
+                }
+            }
+            for (symbol, kernel) in successors {
+                let target = match kernels.get(&kernel) {
+                    Some(&target) => target,
+                    None => {
+                        let target = automaton.states.len();
+                        kernels.insert(kernel.clone(), target);
+                        automaton.states.push(automaton.state(kernel));
+                        target
+                    }
+                };
+                automaton.states[next].transitions.insert(symbol, target);
+            }
+            next += 1;
+        }
+        automaton
+    }
+
+    fn state(&self, kernel: Vec<Item>) -> State<'a> {
+        let mut items = kernel;
+        let mut i = 0;
+        while i < items.len() {
+            if let Some(Symbol::NonTerminal(name)) = self.next_symbol(items[i]) {
+                for (rule, _) in self.rules.iter().enumerate().filter(|(_, r)| r.lhs == name) {
+                    let item = Item { rule, dot: 0 };
+                    if !items.contains(&item) {
+                        items.push(item);
+                    }
+                }
+            }
+            i += 1;
+        }
+        State {
+            items,
+            transitions: BTreeMap::new(),
+        }
+    }
+
+    pub fn next_symbol(&self, item: Item) -> Option<Symbol<'a>> {
+        self.rules[item.rule].rhs.get(item.dot).copied()
+    }
+
+    pub fn display_item(&self, item: Item) -> String {
+        let rule = &self.rules[item.rule];
+        let mut out = format!("{} ->", rule.lhs);
+        for (i, symbol) in rule.rhs.iter().enumerate() {
+            if i == item.dot {
+                out.push_str(" •");
+            }
+            out.push_str(&format!(" {symbol}"));
+        }
+        if item.dot == rule.rhs.len() {
+            out.push_str(" •");
+        }
+        out
+    }
+
+    /// LALR(1) lookaheads of every item, found by propagating lookaheads
+    /// through closures and transitions until nothing changes
+    fn lalr_lookaheads(&self, sets: &FirstFollow<'a>) -> Vec<HashMap<Item, BTreeSet<&'a str>>> {
+        let mut lookaheads: Vec<HashMap<Item, BTreeSet<&str>>> = self
+            .states
+            .iter()
+            .map(|state| {
+                state
+                    .items
+                    .iter()
+                    .map(|&item| (item, BTreeSet::new()))
+                    .collect()
+            })
+            .collect();
+        lookaheads[0]
+            .get_mut(&Item { rule: 0, dot: 0 })
+            .unwrap()
+            .insert(END);
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (index, state) in self.states.iter().enumerate() {
+                for &item in &state.items {
+                    let Some(symbol) = self.next_symbol(item) else {
+                        continue;
+                    };
+                    let current = lookaheads[index][&item].clone();
+
+                    if let Symbol::NonTerminal(name) = symbol {
+                        let rest = &self.rules[item.rule].rhs[item.dot + 1..];
+                        let (mut spontaneous, nullable) = sets.first_of(rest);
+                        if nullable {
+                            spontaneous.extend(current.iter().copied());
+                        }
+                        for (rule, _) in
+                            self.rules.iter().enumerate().filter(|(_, r)| r.lhs == name)
+                        {
+                            let entry = lookaheads[index].get_mut(&Item { rule, dot: 0 }).unwrap();
+                            let before = entry.len();
+                            entry.extend(spontaneous.iter().copied());
+                            changed |= entry.len() != before;
+                        }
+                    }
+
+                    let target = state.transitions[&symbol];
+                    let advanced = Item {
+                        rule: item.rule,
+                        dot: item.dot + 1,
+                    };
+                    let entry = lookaheads[target].get_mut(&advanced).unwrap();
+                    let before = entry.len();
+                    entry.extend(current);
+                    changed |= entry.len() != before;
+                }
+            }
+        }
+        lookaheads
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LrKind {
+    Slr,
+    Lalr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LrAction {
+    Shift(usize),
+    Reduce(usize),
+    Go(usize),
+    Accept,
+}
+
+impl fmt::Display for LrAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LrAction::Shift(state) => write!(f, "shift and go to state {state}"),
+            LrAction::Reduce(rule) => write!(f, "reduce by rule {rule}"),
+            LrAction::Go(state) => write!(f, "go to state {state}"),
+            LrAction::Accept => write!(f, "accept"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    ShiftReduce,
+    ReduceReduce,
+}
+
+impl fmt::Display for ConflictKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConflictKind::ShiftReduce => write!(f, "shift/reduce"),
+            ConflictKind::ReduceReduce => write!(f, "reduce/reduce"),
+        }
+    }
+}
+
+/// A table cell with more than one action
+#[derive(Debug, Clone)]
+pub struct LrConflict<'a> {
+    pub state: usize,
+    pub terminal: &'a str,
+    pub actions: Vec<LrAction>,
+}
+
+impl LrConflict<'_> {
+    pub fn kind(&self) -> ConflictKind {
+        if self
+            .actions
+            .iter()
+            .any(|action| matches!(action, LrAction::Shift(_)))
+        {
+            ConflictKind::ShiftReduce
+        } else {
+            ConflictKind::ReduceReduce
+        }
+    }
+}
+
+/// A shift/reduce conflict settled by precedence and associativity
+#[derive(Debug, Clone)]
+pub struct Resolution<'a> {
+    pub state: usize,
+    pub terminal: &'a str,
+    pub rule: usize,
+    pub decision: Decision,
+}
+
+/// Combined ACTION and GOTO table. Cells keep every action, so conflicts remain visible.
+/// Conflicts settled by precedence are not kept, but recorded in `resolutions`.
+#[derive(Debug)]
+pub struct LrTable<'a> {
+    pub automaton: Automaton<'a>,
+    pub actions: Vec<BTreeMap<Symbol<'a>, Vec<LrAction>>>,
+    pub resolutions: Vec<Resolution<'a>>,
+}
+
+impl<'a> LrTable<'a> {
+    pub fn new(grammar: &'a Grammar, kind: LrKind) -> Self {
+        let automaton = Automaton::new(grammar);
+        let sets = FirstFollow::new(grammar);
+        let precedence = Precedence::new(grammar);
+        let lalr = match kind {
+            LrKind::Lalr => Some(automaton.lalr_lookaheads(&sets)),
+            LrKind::Slr => None,
+        };
+
+        let mut actions = vec![];
+        let mut resolutions = vec![];
+        for (index, state) in automaton.states.iter().enumerate() {
+            let mut row: BTreeMap<Symbol, Vec<LrAction>> = BTreeMap::new();
+            for (&symbol, &target) in &state.transitions {
+                let action = match symbol {
+                    Symbol::Terminal(_) => LrAction::Shift(target),
+                    Symbol::NonTerminal(_) => LrAction::Go(target),
+                };
+                row.entry(symbol).or_default().push(action);
+            }
+            for &item in &state.items {
+                if automaton.next_symbol(item).is_some() {
+                    continue;
+                }
+                if item.rule == 0 {
+                    row.entry(Symbol::Terminal(END))
+                        .or_default()
+                        .push(LrAction::Accept);
+                    continue;
+                }
+                let lookaheads = match &lalr {
+                    Some(lookaheads) => lookaheads[index][&item].clone(),
+                    None => sets.follow[automaton.rules[item.rule].lhs].clone(),
+                };
+                for terminal in lookaheads {
+                    row.entry(Symbol::Terminal(terminal))
+                        .or_default()
+                        .push(LrAction::Reduce(item.rule));
+                }
+            }
+            for (symbol, cell) in &mut row {
+                resolutions.extend(
+                    resolve(&precedence, &automaton.rules, symbol.name(), cell)
+                        .into_iter()
+                        .map(|(rule, decision)| Resolution {
+                            state: index,
+                            terminal: symbol.name(),
+                            rule,
+                            decision,
+                        }),
+                );
+            }
+            row.retain(|_, cell| !cell.is_empty());
+            actions.push(row);
+        }
+
+        LrTable {
+            automaton,
+            actions,
+            resolutions,
+        }
+    }
+
+    pub fn conflicts(&self) -> Vec<LrConflict<'a>> {
+        self.actions
+            .iter()
+            .enumerate()
+            .flat_map(|(state, row)| {
+                row.iter()
+                    .filter(|(_, actions)| actions.len() > 1)
+                    .map(move |(symbol, actions)| LrConflict {
+                        state,
+                        terminal: symbol.name(),
+                        actions: actions.clone(),
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Settle the shift/reduce conflicts of a cell by precedence, the way yacc does.
+/// Returns the reduced rules that were decided, and how.
+fn resolve<'a>(
+    precedence: &Precedence<'a>,
+    rules: &[Rule<'a>],
+    terminal: &str,
+    cell: &mut Vec<LrAction>,
+) -> Vec<(usize, Decision)> {
+    let mut decided = vec![];
+    for action in cell.clone() {
+        if !cell
+            .iter()
+            .any(|action| matches!(action, LrAction::Shift(_)))
+        {
+            break;
+        }
+        let LrAction::Reduce(rule) = action else {
+            continue;
+        };
+        let Some(decision) = precedence.decide(&rules[rule], terminal) else {
+            continue;
+        };
+        decided.push((rule, decision));
+        cell.retain(|&other| match decision {
+            Decision::Shift => other != action,
+            Decision::Reduce => !matches!(other, LrAction::Shift(_)),
+            Decision::Error => other != action && !matches!(other, LrAction::Shift(_)),
+        });
+    }
+    decided
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::testing::grammar;
+
+    macro_rules! test_conflicts {
+        ($name:ident, $kind:expr, $src:expr => [$($conflict:ident),*]) => {
+            #[test]
+            fn $name() {
+                let grammar = grammar($src);
+                let table = LrTable::new(&grammar, $kind);
+                let kinds: Vec<ConflictKind> = table.conflicts().iter().map(LrConflict::kind).collect();
+                assert_eq!(kinds, vec![$(ConflictKind::$conflict),*]);
+            }
+        };
+    }
+
+    test_conflicts!(slr_no_conflicts, LrKind::Slr, "e: e PLUS t | t; t: ID;" => []);
+    test_conflicts!(slr_ambiguous_expression, LrKind::Slr, "e: e PLUS e | ID;" => [ShiftReduce]);
+    // The classic grammar which is LALR(1) but not SLR(1)
+    test_conflicts!(slr_not_lalr, LrKind::Slr, "s: l EQ r | r; l: STAR r | ID; r: l;" => [ShiftReduce]);
+    test_conflicts!(lalr_resolves_slr_conflict, LrKind::Lalr, "s: l EQ r | r; l: STAR r | ID; r: l;" => []);
+    test_conflicts!(lalr_reduce_reduce, LrKind::Lalr, "s: a | b; a: ID; b: ID;" => [ReduceReduce]);
+    test_conflicts!(lalr_nullable, LrKind::Lalr, "s: A s |;" => []);
+
+    test_conflicts!(precedence_resolves_expression, LrKind::Lalr,
+        "%left PLUS MINUS; %left STAR; e: e PLUS e | e MINUS e | e STAR e | MINUS e %prec STAR | ID;" => []);
+    test_conflicts!(precedence_needs_both_sides, LrKind::Lalr, "%left PLUS; e: e PLUS e | e STAR e | ID;" => [ShiftReduce, ShiftReduce, ShiftReduce]);
+
+    #[test]
+    fn precedence_decisions() {
+        let grammar =
+            grammar("%left PLUS; %right POW; %nonassoc EQ; e: e PLUS e | e POW e | e EQ e | ID;");
+        let table = LrTable::new(&grammar, LrKind::Lalr);
+        let decision = |lhs: &str, terminal: &str| {
+            table
+                .resolutions
+                .iter()
+                .find(|resolution| {
+                    resolution.terminal == terminal
+                        && table.automaton.rules[resolution.rule].rhs[1].name() == lhs
+                })
+                .map(|resolution| resolution.decision)
+        };
+        assert_eq!(decision("PLUS", "PLUS"), Some(Decision::Reduce));
+        assert_eq!(decision("PLUS", "POW"), Some(Decision::Shift));
+        assert_eq!(decision("POW", "POW"), Some(Decision::Shift));
+        assert_eq!(decision("POW", "PLUS"), Some(Decision::Reduce));
+        assert_eq!(decision("EQ", "EQ"), Some(Decision::Error));
+        assert!(table.conflicts().is_empty());
+    }
+
+    #[test]
+    fn lr0_automaton_states() {
+        let grammar = grammar("e: e PLUS t | t; t: ID;");
+        let automaton = Automaton::new(&grammar);
+        assert_eq!(automaton.states.len(), 6);
+        assert_eq!(
+            automaton.display_item(automaton.states[0].items[1]),
+            "e -> • e PLUS t"
+        );
+    }
+}
@@ -0,0 +1,455 @@
+//! Hand-written syntax error messages for LR parsers, in the style of Menhir's `.messages` files.
+//!
+//! A message belongs to the state an LR parser is in after shifting the last token of a
+//! sentence: it is shown when the parser fails on whatever comes next. A messages file is a list
+//! of entries, each made of sentences such as `expr: NUM PLUS`, a blank line, and the message,
+//! which runs up to the next blank line. Lines starting with `#` are comments.
+//!
+//! Sentences rather than state numbers identify the states, so a messages file survives changes
+//! to the grammar that renumber the automaton, and tells which entries went stale otherwise.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    fs,
+    path::Path,
+};
+
+use crate::{
+    diagnostic::Diagnostic,
+    error::{Error, MessagesError},
+    internal::{
+        analysis::sets::END,
+        ast::Symbol,
+        table::lr::{Item, LrAction, LrTable},
+    },
+    span::{FileId, Span},
+};
+
+use super::counterexample::example_lr;
+
+/// The message of an entry written by `rantlr errors --list`, to be replaced
+pub const PLACEHOLDER: &str = "<YOUR SYNTAX ERROR MESSAGE HERE>";
+
+/// A state in which the parser can fail on the next token
+#[derive(Debug)]
+pub struct ErrorState<'a> {
+    pub state: usize,
+    /// Shortest viable prefix leading to the state
+    pub prefix: Vec<Symbol<'a>>,
+    /// The prefix with every nonterminal replaced by its shortest yield
+    pub sentence: Vec<&'a str>,
+}
+
+/// An entry of a messages file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Every sentence of the entry, with the line it is on
+    pub sentences: Vec<(usize, Vec<String>)>,
+    pub message: String,
+}
+
+/// The states after reducing as the table says and then shifting `terminal`, or `None` if the
+/// parser in `states` fails on it
+fn step(table: &LrTable, states: &[usize], terminal: &str) -> Option<Vec<usize>> {
+    let mut states = states.to_vec();
+    loop {
+        let row = &table.actions[*states.last()?];
+        match row.get(&Symbol::Terminal(terminal))?.first()? {
+            LrAction::Shift(next) => {
+                states.push(*next);
+                return Some(states);
+            }
+            LrAction::Reduce(rule) => {
+                let rule = &table.automaton.rules[*rule];
+                states.truncate(states.len() - rule.rhs.len());
+                let row = &table.actions[*states.last()?];
+                let Some(LrAction::Go(next)) = row.get(&Symbol::NonTerminal(rule.lhs))?.first()
+                else {
+                    return None;
+                };
+                states.push(*next);
+            }
+            LrAction::Accept => return Some(states),
+            LrAction::Go(_) => return None,
+        }
+    }
+}
+
+/// Run the table on a sentence. Returns the states after shifting its last token, or the index
+/// of the token the parser fails on.
+pub fn run(table: &LrTable, sentence: &[&str]) -> Result<Vec<usize>, usize> {
+    sentence
+        .iter()
+        .enumerate()
+        .try_fold(vec![0], |states, (index, terminal)| {
+            step(table, &states, terminal).ok_or(index)
+        })
+}
+
+/// Every terminal of the table, the end of input included
+fn terminals<'a>(table: &LrTable<'a>) -> BTreeSet<&'a str> {
+    table
+        .actions
+        .iter()
+        .flat_map(|row| row.keys())
+        .filter_map(|symbol| match symbol {
+            Symbol::Terminal(terminal) => Some(*terminal),
+            Symbol::NonTerminal(_) => None,
+        })
+        .collect()
+}
+
+/// The states entered by shifting a token, and the initial one, in which the parser can fail on
+/// the next token. States the shortest sentence does not lead to, as may happen when conflicts
+/// were resolved, are left out.
+pub fn error_states<'a>(table: &LrTable<'a>) -> Vec<ErrorState<'a>> {
+    let terminals = terminals(table);
+    (0..table.automaton.states.len())
+        .filter_map(|state| {
+            let (prefix, sentence) = example_lr(table, state);
+            if matches!(prefix.last(), Some(Symbol::NonTerminal(_))) {
+                return None;
+            }
+            let states = run(table, &sentence).ok()?;
+            let fails = terminals
+                .iter()
+                .any(|terminal| step(table, &states, terminal).is_none());
+            (states.last() == Some(&state) && fails).then_some(ErrorState {
+                state,
+                prefix,
+                sentence,
+            })
+        })
+        .collect()
+}
+
+/// The name of the start rule, which every sentence begins with
+pub fn start<'a>(table: &LrTable<'a>) -> &'a str {
+    table.automaton.rules[0]
+        .rhs
+        .first()
+        .map_or("", |symbol| symbol.name())
+}
+
+/// Entries for the states, each with the placeholder message and comments on where it is
+pub fn render(table: &LrTable, states: &[ErrorState]) -> String {
+    let automaton = &table.automaton;
+    let mut out = String::new();
+    for error in states {
+        let mut sentence = format!("{}:", start(table));
+        for terminal in &error.sentence {
+            write!(sentence, " {terminal}").unwrap();
+        }
+        let prefix: Vec<String> = error.prefix.iter().map(ToString::to_string).collect();
+        let after = if prefix.is_empty() {
+            "at the start of the input".into()
+        } else {
+            format!("after: {}", prefix.join(" "))
+        };
+        writeln!(out, "{sentence}\n##").unwrap();
+        writeln!(
+            out,
+            "## Ends in an error in state {}, {after}\n##",
+            error.state
+        )
+        .unwrap();
+        for &item in &automaton.states[error.state].items {
+            // The kernel, which is what the state has seen so far
+            if item.dot > 0 || item == (Item { rule: 0, dot: 0 }) {
+                writeln!(out, "## {}", automaton.display_item(item)).unwrap();
+            }
+        }
+        writeln!(out, "##\n\n{PLACEHOLDER}\n").unwrap();
+    }
+    out
+}
+
+/// Read the entries of a messages file, whose sentences must start with the rule `start`
+pub fn parse(src: &str, start: &str) -> Result<Vec<Entry>, MessagesError> {
+    let mut entries = vec![];
+    let mut sentences = vec![];
+    let mut message: Vec<&str> = vec![];
+    // Whether the sentences of the current entry were ended by a blank line
+    let mut in_message = false;
+    for (index, line) in src.lines().enumerate() {
+        let line = line.trim_end();
+        if line.starts_with('#') {
+            continue;
+        }
+        if in_message {
+            if !line.is_empty() {
+                message.push(line);
+            } else if !message.is_empty() {
+                entries.push(Entry {
+                    sentences: std::mem::take(&mut sentences),
+                    message: message.join("\n"),
+                });
+                message.clear();
+                in_message = false;
+            }
+        } else if line.is_empty() {
+            in_message = !sentences.is_empty();
+        } else {
+            let terminals = line
+                .strip_prefix(start)
+                .and_then(|rest| rest.trim_start().strip_prefix(':'))
+                .ok_or_else(|| MessagesError::Sentence {
+                    line: index + 1,
+                    start: start.into(),
+                    found: line.into(),
+                })?;
+            sentences.push((
+                index + 1,
+                terminals.split_whitespace().map(String::from).collect(),
+            ));
+        }
+    }
+    if let Some(&(line, _)) = sentences.first() {
+        if message.is_empty() {
+            return Err(MessagesError::NoMessage(line));
+        }
+        entries.push(Entry {
+            sentences,
+            message: message.join("\n"),
+        });
+    }
+    Ok(entries)
+}
+
+/// The message of every state an entry leads to. Errors tell of sentences which are wrong or
+/// stale, of entries still holding the placeholder, and of error states without an entry.
+pub fn resolve(
+    table: &LrTable,
+    entries: &[Entry],
+) -> (BTreeMap<usize, String>, Vec<MessagesError>) {
+    let known = terminals(table);
+    let error_states: BTreeSet<usize> = error_states(table)
+        .iter()
+        .map(|error| error.state)
+        .collect();
+    let mut messages = BTreeMap::new();
+    // The line of the sentence each state was first reached by
+    let mut lines: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut errors = vec![];
+    for entry in entries {
+        if entry.message == PLACEHOLDER {
+            errors.push(MessagesError::Placeholder(entry.sentences[0].0));
+        }
+        for (line, sentence) in &entry.sentences {
+            let line = *line;
+            if let Some(unknown) = sentence
+                .iter()
+                .find(|terminal| *terminal == END || !known.contains(terminal.as_str()))
+            {
+                errors.push(MessagesError::UnknownTerminal {
+                    line,
+                    terminal: unknown.clone(),
+                });
+                continue;
+            }
+            let terminals: Vec<&str> = sentence
+                .iter()
+                .filter_map(|terminal| known.get(terminal.as_str()).copied())
+                .collect();
+            let state = match run(table, &terminals) {
+                Ok(states) => *states.last().unwrap(),
+                Err(index) => {
+                    errors.push(MessagesError::Fails {
+                        line,
+                        position: index + 1,
+                        terminal: sentence[index].clone(),
+                    });
+                    continue;
+                }
+            };
+            if !error_states.contains(&state) {
+                errors.push(MessagesError::NoError { line, state });
+            } else if let Some(&other) = lines.get(&state) {
+                errors.push(MessagesError::Duplicate { line, state, other });
+            } else {
+                lines.insert(state, line);
+                messages.insert(state, entry.message.clone());
+            }
+        }
+    }
+    let missing: Vec<usize> = error_states
+        .into_iter()
+        .filter(|state| !messages.contains_key(state))
+        .collect();
+    if !missing.is_empty() {
+        errors.push(MessagesError::Missing(missing));
+    }
+    (messages, errors)
+}
+
+/// Read a messages file, and the message of every state it leads to. Fails with every problem
+/// of the file, see [`resolve`].
+pub fn load(path: &Path, table: &LrTable) -> Result<BTreeMap<usize, String>, Error> {
+    let source = fs::read_to_string(path).map_err(|source| Error::Io {
+        path: path.into(),
+        source,
+    })?;
+    let (messages, errors) = match parse(&source, start(table)) {
+        Ok(entries) => resolve(table, &entries),
+        Err(err) => (BTreeMap::new(), vec![err]),
+    };
+    if errors.is_empty() {
+        Ok(messages)
+    } else {
+        Err(Error::Messages {
+            path: path.into(),
+            errors,
+        })
+    }
+}
+
+/// The span of a line of `source`, counted from 1, without its line break
+fn line_span(source: &str, file: FileId, line: usize) -> Span {
+    let start: usize = source
+        .split_inclusive('\n')
+        .take(line - 1)
+        .map(str::len)
+        .sum();
+    let text = source[start..].lines().next().unwrap_or("");
+    Span::new(file, start..start + text.len())
+}
+
+/// A problem with a messages file as an error on the line it is about, if it has one. `source`
+/// is the file, read as `file`.
+pub fn diagnostic(err: &MessagesError, file: FileId, source: &str) -> Diagnostic {
+    let line = match err {
+        MessagesError::Sentence { line, .. }
+        | MessagesError::NoMessage(line)
+        | MessagesError::Placeholder(line)
+        | MessagesError::UnknownTerminal { line, .. }
+        | MessagesError::Fails { line, .. }
+        | MessagesError::NoError { line, .. }
+        | MessagesError::Duplicate { line, .. } => *line,
+        MessagesError::Missing(_) => {
+            return Diagnostic::error(err.to_string())
+                .with_code("E010")
+                .with_help("run `rantlr errors --list --messages` to list the missing entries")
+        }
+    };
+    let message = err.to_string();
+    let message = message
+        .strip_prefix(&format!("line {line}: "))
+        .unwrap_or(&message);
+    let diagnostic = Diagnostic::error(message)
+        .with_code("E010")
+        .with_primary(line_span(source, file, line), "");
+    match err {
+        MessagesError::Duplicate { other, .. } => diagnostic.with_secondary(
+            line_span(source, file, *other),
+            "the state is already reached here",
+        ),
+        _ => diagnostic,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::{table::lr::LrKind, testing::grammar};
+
+    const GRAMMAR: &str = "s: LP s RP | ID;";
+
+    fn sentence(terminals: &[&str]) -> String {
+        let mut out = "s:".to_string();
+        for terminal in terminals {
+            out.push(' ');
+            out.push_str(terminal);
+        }
+        out
+    }
+
+    #[test]
+    fn error_states_by_sentence() {
+        let grammar = grammar(GRAMMAR);
+        let table = LrTable::new(&grammar, LrKind::Lalr);
+        let sentences: Vec<String> = error_states(&table)
+            .iter()
+            .map(|error| sentence(&error.sentence))
+            .collect();
+        assert_eq!(sentences, vec!["s:", "s: ID", "s: LP", "s: LP ID RP"]);
+    }
+
+    #[test]
+    fn render_then_parse() {
+        let grammar = grammar(GRAMMAR);
+        let table = LrTable::new(&grammar, LrKind::Lalr);
+        let rendered = render(&table, &error_states(&table));
+        let entries = parse(&rendered, "s").unwrap();
+        assert_eq!(entries.len(), 4);
+        assert!(entries.iter().all(|entry| entry.message == PLACEHOLDER));
+        assert_eq!(entries[1].sentences, vec![(10, vec!["ID".to_string()])]);
+    }
+
+    macro_rules! test_parse {
+        ($name:ident, $src:expr => $should_be:expr) => {
+            #[test]
+            fn $name() {
+                let entries = parse($src, "s").map(|entries| {
+                    entries
+                        .into_iter()
+                        .map(|entry| (entry.sentences.len(), entry.message))
+                        .collect::<Vec<_>>()
+                });
+                assert_eq!(entries.map_err(|err| err.to_string()), $should_be);
+            }
+        };
+    }
+
+    test_parse!(parse_entries, "s: ID\n\nafter an identifier\n\n# comment\ns:\ns: LP\n## note\n\nfirst line\nsecond line\n"
+        => Ok(vec![(1, "after an identifier".to_string()), (2, "first line\nsecond line".to_string())]));
+    test_parse!(parse_empty, "# nothing yet\n" => Ok(vec![]));
+    test_parse!(parse_wrong_start, "e: ID\n\nmessage\n"
+        => Err("line 1: expected a sentence such as `s: A B`, found `e: ID`".to_string()));
+    test_parse!(parse_no_message, "s: ID\n\n" => Err("line 1: the entry has no message".to_string()));
+
+    macro_rules! test_resolve {
+        ($name:ident, $src:expr => $should_be:expr) => {
+            #[test]
+            fn $name() {
+                let grammar = grammar(GRAMMAR);
+                let table = LrTable::new(&grammar, LrKind::Lalr);
+                let (_, errors) = resolve(&table, &parse($src, "s").unwrap());
+                let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+                assert_eq!(errors, $should_be as Vec<&str>);
+            }
+        };
+    }
+
+    test_resolve!(resolve_complete, "s:\ns: LP\n\nexpected `(` or a name\n\ns: ID\ns: LP ID RP\n\nexpected `)` or the end\n"
+        => vec![]);
+    test_resolve!(resolve_missing, "s: ID\n\nafter an identifier\n"
+        => vec!["error states without a message: 0, 2, 5"]);
+    test_resolve!(resolve_stale, "s:\ns: LP\ns: LP LP\n\nm\n\ns: ID RP\ns: LP ID RP\ns: ID\ns: LP ID\ns: COMMA\n\n<YOUR SYNTAX ERROR MESSAGE HERE>\n" => vec![
+        "line 3: the sentence leads to state 2, like the one on line 2",
+        "line 7: the entry still has the placeholder message",
+        "line 7: the parser fails on token 2 of the sentence, `RP`",
+        "line 10: the sentence leads to state 1, like the one on line 9",
+        "line 11: unknown terminal `COMMA`",
+    ]);
+
+    #[test]
+    fn diagnostics_on_lines() {
+        let grammar = grammar(GRAMMAR);
+        let table = LrTable::new(&grammar, LrKind::Lalr);
+        let src = "s: LP\ns: LP LP\n\nm\n";
+        let (_, errors) = resolve(&table, &parse(src, "s").unwrap());
+        let duplicate = errors
+            .iter()
+            .find(|err| matches!(err, MessagesError::Duplicate { .. }))
+            .unwrap();
+        let diagnostic = diagnostic(duplicate, FileId::default(), src);
+        assert_eq!(
+            diagnostic.render("g.messages", FileId::default(), src),
+            "error[E010]: the sentence leads to state 2, like the one on line 1\n \
+             --> g.messages:2:1\n  |\n\
+             1 | s: LP\n  | ----- the state is already reached here\n\
+             2 | s: LP LP\n  | ^^^^^^^^\n"
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt;
+
+use crate::{
+    internal::ast::{Grammar, Ident, Symbol},
+    span::Span,
+};
+
+pub mod counterexample;
+pub mod ll1;
+pub mod lr;
+pub mod messages;
+pub mod policy;
+pub mod precedence;
+
+/// The left hand side of the augmented rule `$accept -> start`
+pub const ACCEPT: &str = "$accept";
+
+/// A single alternative of a parser rule, flattened so table entries can refer to it by index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule<'a> {
+    pub lhs: &'a str,
+    /// Index of the alternative within its parser rule
+    pub alternative: usize,
+    pub rhs: Vec<Symbol<'a>>,
+    /// Precedence terminal set with `%prec`
+    pub prec: Option<&'a str>,
+    /// The alternative in the grammar, which the augmented rule has none of
+    pub span: Option<Span>,
+}
+
+impl fmt::Display for Rule<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ->", self.lhs)?;
+        if self.rhs.is_empty() {
+            write!(f, " ε")?;
+        }
+        for symbol in &self.rhs {
+            write!(f, " {symbol}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Flatten every alternative of every parser rule into a list of rules.
+/// Rule 0 is the augmented `$accept -> start`.
+pub fn rules(grammar: &Grammar) -> Vec<Rule<'_>> {
+    let augmented = Rule {
+        lhs: ACCEPT,
+        alternative: 0,
+        rhs: grammar
+            .start_rule()
+            .map(Symbol::NonTerminal)
+            .into_iter()
+            .collect(),
+        prec: None,
+        span: None,
+    };
+    std::iter::once(augmented)
+        .chain(grammar.parser_rules().flat_map(|(name, alternatives)| {
+            alternatives
+                .iter()
+                .enumerate()
+                .map(move |(index, alternative)| Rule {
+                    lhs: name,
+                    alternative: index,
+                    rhs: alternative.idents.iter().map(Ident::symbol).collect(),
+                    prec: alternative.prec(),
+                    span: Some(alternative.span),
+                })
+        }))
+        .collect()
+}
@@ -0,0 +1,217 @@
+use crate::{
+    diagnostic::{Diagnostic, Severity},
+    error::ConflictError,
+    internal::ast::Grammar,
+    span::Span,
+};
+
+use super::{
+    counterexample::diagnose_lr,
+    lr::{ConflictKind, LrAction, LrKind, LrTable},
+};
+
+/// What to do with conflicts which are not covered by `%expect` and `%expect_rr`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    #[default]
+    Error,
+    /// Report every conflict, then resolve it by default
+    Warn,
+    /// Resolve silently by default, preferring shift over reduce and earlier rules over later ones
+    PreferShift,
+}
+
+/// The number of conflicts a grammar declares it knowingly carries, with the span of the
+/// directive declaring it
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Expect {
+    pub shift_reduce: Option<(usize, Span)>,
+    pub reduce_reduce: Option<(usize, Span)>,
+}
+
+impl Expect {
+    pub fn new(grammar: &Grammar) -> Result<Self, ConflictError> {
+        let mut expect = Expect::default();
+        for directive in &grammar.directives {
+            let slot = match directive.name.as_str() {
+                "expect" => &mut expect.shift_reduce,
+                "expect_rr" => &mut expect.reduce_reduce,
+                _ => continue,
+            };
+            let invalid = |count: String| {
+                ConflictError::InvalidExpect(directive.name.clone(), count, directive.span)
+            };
+            let [count] = directive.args.as_slice() else {
+                return Err(invalid(directive.args.join(" ")));
+            };
+            let count = count.parse().map_err(|_| invalid(count.clone()))?;
+            *slot = Some((count, directive.span));
+        }
+        Ok(expect)
+    }
+}
+
+/// Check the conflicts of the table against the expectations of its grammar, and the remaining
+/// ones against the policy. Afterwards, every cell of the table holds at most one action.
+/// Returns a warning for every conflict which was reported rather than expected.
+pub fn apply(
+    table: &mut LrTable,
+    expect: &Expect,
+    policy: ConflictPolicy,
+) -> Result<Vec<Diagnostic>, ConflictError> {
+    let conflicts = table.conflicts();
+    let count = |kind| conflicts.iter().filter(|c| c.kind() == kind).count();
+    let (shift_reduce, reduce_reduce) = (
+        count(ConflictKind::ShiftReduce),
+        count(ConflictKind::ReduceReduce),
+    );
+
+    if let Some((expected, span)) = expect.shift_reduce {
+        if expected != shift_reduce {
+            return Err(ConflictError::ShiftReduceCount {
+                expected,
+                found: shift_reduce,
+                span,
+            });
+        }
+    }
+    if let Some((expected, span)) = expect.reduce_reduce {
+        if expected != reduce_reduce {
+            return Err(ConflictError::ReduceReduceCount {
+                expected,
+                found: reduce_reduce,
+                span,
+            });
+        }
+    }
+
+    let leftover: Vec<_> = conflicts
+        .iter()
+        .filter(|conflict| match conflict.kind() {
+            ConflictKind::ShiftReduce => expect.shift_reduce.is_none(),
+            ConflictKind::ReduceReduce => expect.reduce_reduce.is_none(),
+        })
+        .collect();
+    let diagnostics = || leftover.iter().map(|conflict| diagnose_lr(table, conflict));
+    let warnings = match policy {
+        ConflictPolicy::Error if !leftover.is_empty() => {
+            return Err(ConflictError::Unresolved(diagnostics().collect()))
+        }
+        ConflictPolicy::Warn => diagnostics()
+            .map(|mut diagnostic| {
+                diagnostic.severity = Severity::Warning;
+                diagnostic.message.push_str(", resolved by default");
+                diagnostic
+            })
+            .collect(),
+        ConflictPolicy::Error | ConflictPolicy::PreferShift => vec![],
+    };
+
+    for row in &mut table.actions {
+        for cell in row.values_mut() {
+            if let Some(&preferred) = cell.iter().min_by_key(|action| match action {
+                LrAction::Shift(_) | LrAction::Go(_) => (0, 0),
+                LrAction::Accept => (1, 0),
+                LrAction::Reduce(rule) => (2, *rule),
+            }) {
+                *cell = vec![preferred];
+            }
+        }
+    }
+    Ok(warnings)
+}
+
+/// The table of a grammar with at most one action per cell, see [`apply`], and its warnings
+pub fn resolved_table(
+    grammar: &Grammar,
+    kind: LrKind,
+    policy: ConflictPolicy,
+) -> Result<(LrTable<'_>, Vec<Diagnostic>), ConflictError> {
+    let mut table = LrTable::new(grammar, kind);
+    let warnings = apply(&mut table, &Expect::new(grammar)?, policy)?;
+    Ok((table, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::testing::grammar;
+
+    const DANGLING_ELSE: &str = "s: IF e THEN s | IF e THEN s ELSE s | ID; e: ID;";
+
+    fn check(src: &str, policy: ConflictPolicy) -> Result<Vec<String>, ConflictError> {
+        let grammar = grammar(src);
+        let mut table = LrTable::new(&grammar, LrKind::Lalr);
+        let result = apply(&mut table, &Expect::new(&grammar)?, policy);
+        if result.is_ok() {
+            assert!(table.conflicts().is_empty());
+        }
+        Ok(result?
+            .iter()
+            .map(|warning| warning.message.clone())
+            .collect())
+    }
+
+    #[test]
+    fn expected_conflict_is_silent() {
+        let src = format!("%expect 1; {DANGLING_ELSE}");
+        assert_eq!(
+            check(&src, ConflictPolicy::Error).unwrap(),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn expected_count_mismatch_fails() {
+        let src = format!("%expect 2; {DANGLING_ELSE}");
+        assert!(matches!(
+            check(&src, ConflictPolicy::PreferShift),
+            Err(ConflictError::ShiftReduceCount {
+                expected: 2,
+                found: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn unexpected_reduce_reduce_fails() {
+        let src = "%expect_rr 0; s: a | b; a: ID; b: ID;";
+        assert!(matches!(
+            check(src, ConflictPolicy::PreferShift),
+            Err(ConflictError::ReduceReduceCount {
+                expected: 0,
+                found: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn leftover_conflicts_follow_policy() {
+        let Err(ConflictError::Unresolved(errors)) = check(DANGLING_ELSE, ConflictPolicy::Error)
+        else {
+            panic!("the conflict is unresolved");
+        };
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code.as_deref(), Some("E007"));
+        // The shift of `ELSE` and the reduction of the shorter `if`
+        assert!(errors[0].primary.is_some());
+        assert_eq!(errors[0].secondary.len(), 1);
+        assert_eq!(
+            check(DANGLING_ELSE, ConflictPolicy::Warn).unwrap(),
+            ["shift/reduce conflict in state 7 on `ELSE`, resolved by default"]
+        );
+        assert!(check(DANGLING_ELSE, ConflictPolicy::PreferShift)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn invalid_expect() {
+        assert!(matches!(
+            check("%expect many; s: ID;", ConflictPolicy::Error),
+            Err(ConflictError::InvalidExpect(..))
+        ));
+    }
+}
@@ -0,0 +1,88 @@
+use std::{collections::HashMap, fmt};
+
+use crate::internal::ast::{Grammar, Symbol};
+
+use super::Rule;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    Left,
+    Right,
+    NonAssoc,
+}
+
+/// Terminal precedences declared with `%left`, `%right` and `%nonassoc`.
+/// As in yacc, each declaration binds tighter than the ones before it.
+#[derive(Debug, Default)]
+pub struct Precedence<'a> {
+    levels: HashMap<&'a str, (usize, Associativity)>,
+}
+
+/// How a shift/reduce conflict was settled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Shift,
+    Reduce,
+    /// Both actions are removed, so the input is a syntax error
+    Error,
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Decision::Shift => write!(f, "shift"),
+            Decision::Reduce => write!(f, "reduce"),
+            Decision::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl<'a> Precedence<'a> {
+    pub fn new(grammar: &'a Grammar) -> Self {
+        let mut precedence = Precedence::default();
+        let mut level = 0;
+        for directive in &grammar.directives {
+            let assoc = match directive.name.as_str() {
+                "left" => Associativity::Left,
+                "right" => Associativity::Right,
+                "nonassoc" => Associativity::NonAssoc,
+                _ => continue,
+            };
+            level += 1;
+            for terminal in &directive.args {
+                precedence.levels.insert(terminal, (level, assoc));
+            }
+        }
+        precedence
+    }
+
+    pub fn of_terminal(&self, terminal: &str) -> Option<(usize, Associativity)> {
+        self.levels.get(terminal).copied()
+    }
+
+    /// A rule takes the precedence of its `%prec` terminal, or else of its last terminal
+    pub fn of_rule(&self, rule: &Rule<'a>) -> Option<(usize, Associativity)> {
+        let terminal = rule.prec.or_else(|| {
+            rule.rhs.iter().rev().find_map(|symbol| match symbol {
+                Symbol::Terminal(name) => Some(*name),
+                Symbol::NonTerminal(_) => None,
+            })
+        })?;
+        self.of_terminal(terminal)
+    }
+
+    /// Decide between shifting `terminal` and reducing by `rule`, if both have a precedence
+    pub fn decide(&self, rule: &Rule<'a>, terminal: &str) -> Option<Decision> {
+        let (rule_level, _) = self.of_rule(rule)?;
+        let (terminal_level, assoc) = self.of_terminal(terminal)?;
+        Some(match rule_level.cmp(&terminal_level) {
+            std::cmp::Ordering::Greater => Decision::Reduce,
+            std::cmp::Ordering::Less => Decision::Shift,
+            std::cmp::Ordering::Equal => match assoc {
+                Associativity::Left => Decision::Reduce,
+                Associativity::Right => Decision::Shift,
+                Associativity::NonAssoc => Decision::Error,
+            },
+        })
+    }
+}
@@ -0,0 +1,8 @@
+//! Helpers shared by the unit tests
+
+use crate::internal::{ast::Grammar, lexer::lexer::tokenize, parser::functional::parse};
+
+/// A grammar from its source, which must be valid
+pub fn grammar(src: &str) -> Grammar {
+    Grammar::from(parse(tokenize(src).unwrap()).unwrap())
+}
@@ -0,0 +1,121 @@
+//! rantlr reads ANTLR style grammars, checks them, runs them on input and generates Rust parsers
+//! from them.
+//!
+//! A grammar is read with [`Grammar::parse`], or with [`Grammar::load`] along with its imports,
+//! which report every syntax error
+//! as a [`diagnostic::Diagnostic`]. From there:
+//!
+//! - [`analysis`] checks the names of the rules, lints the grammar and computes the sets parsers
+//!   are built from,
+//! - [`table`] builds LL(1) and LR tables, and explains their conflicts,
+//! - [`runtime`] parses input with the grammar directly, without generating anything,
+//! - [`codegen`] generates a standalone Rust parser module, which [`build`] does from build
+//!   scripts, and the `rantlr-macros` crate at compile time as `rantlr_macros::grammar!`. That
+//!   crate depends on this one, which cannot re-export its macros as `rantlr::grammar!`,
+//! - [`format`] lays grammar files out canonically, keeping their comments,
+//! - [`docs`] generates reference documentation from the `/** ... */` comments of the rules,
+//! - [`railroad`] draws the rules as railroad diagrams, cross-linked to each other,
+//! - [`graph`] draws the rules, or the LR automaton, as Graphviz graphs,
+//! - [`lsp`] serves editors as a language server.
+//!
+//! ```
+//! use rantlr::{codegen, runtime::Interpreter, Grammar};
+//!
+//! let grammar = Grammar::parse("sum: sum PLUS NUM | NUM; PLUS: \\+; NUM: [0-9]+;")?;
+//! assert!(rantlr::analysis::lint(&grammar).is_empty());
+//!
+//! let interpreter = Interpreter::auto(&grammar)?;
+//! let parse = interpreter.parse("1+2")?;
+//! assert!(parse.tree().is_ok());
+//!
+//! let generated = codegen::generate(&grammar, "sum.g4", &codegen::Options::default())?;
+//! assert!(generated.code.contains("pub fn parse("));
+//! # Ok::<(), rantlr::Error>(())
+//! ```
+//!
+//! Errors are typed by stage, and each converts into [`Error`].
+
+mod ast;
+pub mod build;
+pub mod diagnostic;
+pub mod error;
+mod internal;
+pub mod lsp;
+pub mod output;
+pub mod span;
+
+pub use crate::{
+    error::{Error, SyntaxErrors},
+    internal::ast::{Alternative, Directive, Grammar, Ident, Predicate, Production, Symbol},
+};
+
+/// Name checks, lints, and the sets of symbols they and the parsers are built from
+pub mod analysis {
+    pub use crate::internal::analysis::{
+        lint::{lint, Lint, LintId, Severity},
+        names::check_names,
+        sets::{left_recursive, nullable, productive, reachable, FirstFollow, END},
+    };
+}
+
+/// Parsing tables, their conflicts, and how conflicts are explained and settled
+pub mod table {
+    pub use crate::internal::table::{
+        counterexample::{example_lr, explain_ll1, explain_lr, Derivation, Explanation, Unifying},
+        ll1::{Ll1Conflict, Ll1Table},
+        lr::{
+            Automaton, ConflictKind, Item, LrAction, LrConflict, LrKind, LrTable, Resolution, State,
+        },
+        messages,
+        policy::{apply, resolved_table, ConflictPolicy, Expect},
+        precedence::{Associativity, Decision},
+        rules, Rule, ACCEPT,
+    };
+}
+
+/// Parsing input with a grammar directly, into a shared packed parse forest
+pub mod runtime {
+    pub use crate::internal::runtime::{
+        adaptive::Adaptive,
+        earley::Earley,
+        lexer::{Lexer, Token},
+        ll1::Ll1,
+        peg::Peg,
+        sppf::{Ambiguity, Forest, Tree},
+        Glr, Interpreter, Parse, Symbols,
+    };
+}
+
+/// Generation of standalone Rust parser modules
+pub mod codegen {
+    pub use crate::internal::codegen::{generate, Backend, Generated, Options};
+}
+
+/// Reference documentation of grammars, from the doc comments of their rules
+pub mod docs {
+    pub use crate::internal::docs::{html, markdown};
+}
+
+/// Formatting grammar files canonically, keeping their comments
+pub mod format {
+    pub use crate::internal::format::{format, MAX_WIDTH};
+}
+
+/// Graphs of grammars in the DOT language of Graphviz
+pub mod graph {
+    pub use crate::internal::graph::{automaton, rules};
+}
+
+/// Railroad diagrams of the rules of a grammar, as SVG images or an HTML page of them all
+pub mod railroad {
+    pub use crate::internal::railroad::{html, svg};
+}
+
+/// rantlr's own meta-parser, which it generates from `examples/rantlr.g4`
+#[doc(hidden)]
+pub mod meta {
+    pub use crate::internal::{
+        codegen::bootstrap::{generate, Bootstrap},
+        parser::parse_generated,
+    };
+}
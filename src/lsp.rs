@@ -0,0 +1,1000 @@
+//! A language server for grammar files, speaking the Language Server Protocol over standard input
+//! and output, as `rantlr lsp` does.
+//!
+//! It reports syntax errors and lints while a grammar is edited, finds where rules are defined and
+//! used, renames them, completes their names, shows their nullability and FIRST and FOLLOW sets on
+//! hover, and offers the fixes of diagnostics as code actions. Imports are read from the editor
+//! when they are open in it, and from disk otherwise.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    diagnostic::{Diagnostic, Severity},
+    internal::{
+        analysis::{lint::lint, names::check_names, sets::FirstFollow},
+        ast::{Grammar, Production},
+        lexer::{lexer::tokenize_recovering, token::TokenKind},
+    },
+    span::{FileId, LineCol, LineIndex, SourceMap, Span, Unit},
+};
+
+/// The JSON-RPC error codes the server answers with
+const PARSE_ERROR: i64 = -32700;
+const INVALID_PARAMS: i64 = -32602;
+const METHOD_NOT_FOUND: i64 = -32601;
+const REQUEST_FAILED: i64 = -32803;
+
+/// Grammar directives whose arguments are rules or terminals
+const SYMBOL_DIRECTIVES: [&str; 5] = ["left", "right", "nonassoc", "reject", "follow_restrict"];
+
+type Response = Result<Value, (i64, String)>;
+
+/// Serve one client until it exits or closes the input, returning whether it shut the server
+/// down first, as a client expects the exit status to tell
+pub fn serve(
+    include: Vec<PathBuf>,
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> io::Result<bool> {
+    let mut server = Server {
+        include,
+        documents: HashMap::new(),
+        shut_down: false,
+    };
+    while let Some(message) = read_message(&mut input)? {
+        if message["method"] == "exit" {
+            break;
+        }
+        for reply in server.handle(&message) {
+            write_message(&mut output, &reply)?;
+        }
+    }
+    Ok(server.shut_down)
+}
+
+/// A message framed by its `Content-Length` header, `Null` when it is not JSON, or `None` at the
+/// end of input
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok();
+        }
+    }
+    let Some(length) = length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message without a `Content-Length` header",
+        ));
+    };
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body).unwrap_or(Value::Null)))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()
+}
+
+/// The path of a `file:` URI
+fn path_of(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut path = Vec::with_capacity(encoded.len());
+    let mut index = 0;
+    while index < encoded.len() {
+        let escaped = encoded
+            .get(index + 1..index + 3)
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) if encoded[index] == b'%' => {
+                path.push(byte);
+                index += 3;
+            }
+            _ => {
+                path.push(encoded[index]);
+                index += 1;
+            }
+        }
+    }
+    let path = String::from_utf8(path).ok()?;
+    // `file:///C:/grammars` is the Windows path `C:/grammars`
+    match path.strip_prefix('/') {
+        Some(windows) if windows.get(1..2) == Some(":") => Some(windows.into()),
+        _ => Some(path.into()),
+    }
+}
+
+/// The `file:` URI of a path
+fn uri_of(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    let path = path.to_string_lossy().replace('\\', "/");
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/:".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    uri
+}
+
+/// Where a rule name is written in a grammar file
+#[derive(Debug)]
+struct Occurrence {
+    name: String,
+    span: Span,
+    /// Whether the rule is defined here
+    definition: bool,
+}
+
+/// Every rule name written in `source`, found from its tokens to work on grammars with syntax
+/// errors. The arguments of directives count where they name rules or terminals: after `%prec`,
+/// and in the directives of [`SYMBOL_DIRECTIVES`].
+fn occurrences(source: &str, file: FileId) -> Vec<Occurrence> {
+    let (tokens, _) = tokenize_recovering(source, file);
+    let mut occurrences = vec![];
+    let mut directive: Option<&str> = None;
+    let mut statement_start = true;
+    for (index, token) in tokens.iter().enumerate() {
+        let name = match &token.kind {
+            TokenKind::ParserIdent(name) | TokenKind::LexerIdent(name) => name,
+            TokenKind::Semicolon => {
+                directive = None;
+                statement_start = true;
+                continue;
+            }
+            TokenKind::Directive(name) if statement_start => {
+                directive = Some(name);
+                statement_start = false;
+                continue;
+            }
+            _ => {
+                statement_start = false;
+                continue;
+            }
+        };
+        let symbol = match directive {
+            Some(directive) => SYMBOL_DIRECTIVES.contains(&directive),
+            None => index == 0 || tokens[index - 1].kind != TokenKind::Directive("label".into()),
+        };
+        if symbol {
+            occurrences.push(Occurrence {
+                name: name.clone(),
+                span: token.span,
+                definition: statement_start
+                    && tokens
+                        .get(index + 1)
+                        .is_some_and(|next| next.kind == TokenKind::Colon),
+            });
+        }
+        statement_start = false;
+    }
+    occurrences
+}
+
+/// An open document read with its imports, as far as they could be read
+struct Analysis {
+    /// The document first, then its imports
+    files: SourceMap,
+    /// The URI of each file
+    uris: Vec<String>,
+    grammar: Grammar,
+    /// Syntax errors and lints, in the document or about its imports
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Analysis {
+    fn files(&self) -> impl Iterator<Item = FileId> {
+        (0..self.uris.len()).map(|index| FileId(u32::try_from(index).unwrap()))
+    }
+
+    fn range(&self, span: Span) -> Value {
+        let index = LineIndex::new(self.files.source(span.file));
+        let position = |offset| {
+            let LineCol { line, col } = index.line_col(offset, Unit::Utf16);
+            json!({ "line": line, "character": col })
+        };
+        json!({ "start": position(span.start), "end": position(span.end) })
+    }
+
+    fn location(&self, span: Span) -> Value {
+        json!({ "uri": self.uris[span.file.0 as usize], "range": self.range(span) })
+    }
+
+    /// The byte offset of a position in the document
+    fn offset(&self, position: &Value) -> Option<usize> {
+        let line_col = LineCol {
+            line: usize::try_from(position["line"].as_u64()?).ok()?,
+            col: usize::try_from(position["character"].as_u64()?).ok()?,
+        };
+        let source = self.files.source(FileId::default());
+        Some(LineIndex::new(source).offset(line_col, Unit::Utf16))
+    }
+
+    /// The rule name at a position in the document
+    fn occurrence_at(&self, position: &Value) -> Option<Occurrence> {
+        let offset = self.offset(position)?;
+        let source = self.files.source(FileId::default());
+        occurrences(source, FileId::default())
+            .into_iter()
+            .find(|occurrence| occurrence.span.start <= offset && offset <= occurrence.span.end)
+    }
+
+    /// Where a rule is defined, in the document or an import
+    fn definition(&self, name: &str) -> Option<(&Production, Span)> {
+        let rule = self.grammar.rules.iter().find(|rule| rule.name() == name)?;
+        let span = rule.span();
+        Some((
+            rule,
+            Span::new(span.file, span.start..span.start + name.len()),
+        ))
+    }
+
+    /// Every place a rule name is written, in the document and its imports
+    fn references(&self, name: &str) -> Vec<Occurrence> {
+        self.files()
+            .flat_map(|file| occurrences(self.files.source(file), file))
+            .filter(|occurrence| occurrence.name == name)
+            .collect()
+    }
+
+    /// A diagnostic of the document, located at its start when it has no location
+    fn diagnostic(&self, diagnostic: &Diagnostic) -> Value {
+        let mut message = diagnostic.message.clone();
+        for note in &diagnostic.notes {
+            message.push_str(&format!("\nnote: {note}"));
+        }
+        if let Some(help) = &diagnostic.help {
+            message.push_str(&format!("\nhelp: {help}"));
+        }
+        let range = match &diagnostic.primary {
+            Some(label) => self.range(label.span),
+            None => self.range(Span::new(FileId::default(), 0..0)),
+        };
+        let related: Vec<Value> = diagnostic
+            .secondary
+            .iter()
+            .map(|label| json!({ "location": self.location(label.span), "message": label.message }))
+            .collect();
+        let severity = match diagnostic.severity {
+            Severity::Error => 1,
+            Severity::Warning => 2,
+        };
+        json!({
+            "range": range,
+            "severity": severity,
+            "code": diagnostic.code,
+            "source": "rantlr",
+            "message": message,
+            "relatedInformation": related,
+        })
+    }
+
+    /// The diagnostics shown in the document: those located in it, and those without a location
+    fn document_diagnostics(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter().filter(|diagnostic| {
+            diagnostic
+                .primary
+                .as_ref()
+                .is_none_or(|label| label.span.file == FileId::default())
+        })
+    }
+
+    /// Text edits by the URI of the file they apply to
+    fn workspace_edit(&self, edits: impl IntoIterator<Item = (Span, String)>) -> Value {
+        let mut changes = serde_json::Map::new();
+        for (span, text) in edits {
+            let uri = &self.uris[span.file.0 as usize];
+            let edits = changes.entry(uri.clone()).or_insert_with(|| json!([]));
+            edits
+                .as_array_mut()
+                .unwrap()
+                .push(json!({ "range": self.range(span), "newText": text }));
+        }
+        json!({ "changes": changes })
+    }
+}
+
+struct Server {
+    include: Vec<PathBuf>,
+    /// The text of every open document by its URI
+    documents: HashMap<String, String>,
+    shut_down: bool,
+}
+
+impl Server {
+    /// The replies to a message: a response to a request, and diagnostics after a change
+    fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let Some(method) = message["method"].as_str() else {
+            if message.is_null() {
+                let error = Err((PARSE_ERROR, "the message is not JSON".into()));
+                return vec![response(&Value::Null, error)];
+            }
+            // A response, though the server sends no requests
+            return vec![];
+        };
+        let params = &message["params"];
+        match message.get("id") {
+            Some(id) => vec![response(id, self.request(method, params))],
+            None => self.notification(method, params),
+        }
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Response {
+        match method {
+            "initialize" => Ok(capabilities()),
+            "shutdown" => {
+                self.shut_down = true;
+                Ok(Value::Null)
+            }
+            "textDocument/definition" => self.with_analysis(params, definition),
+            "textDocument/references" => self.with_analysis(params, references),
+            "textDocument/prepareRename" => self.with_analysis(params, prepare_rename),
+            "textDocument/rename" => self.with_analysis(params, rename),
+            "textDocument/completion" => self.with_analysis(params, completion),
+            "textDocument/hover" => self.with_analysis(params, hover),
+            "textDocument/codeAction" => self.with_analysis(params, code_actions),
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method `{method}`"))),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.into(), text.into());
+            }
+            // The server asks for whole documents on change
+            "textDocument/didChange" => {
+                let changes = params["contentChanges"].as_array();
+                let Some(text) = changes.and_then(|changes| changes.last()?["text"].as_str())
+                else {
+                    return vec![];
+                };
+                self.documents.insert(uri.into(), text.into());
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                let cleared = json!({ "uri": uri, "diagnostics": [] });
+                let mut replies = vec![notification("textDocument/publishDiagnostics", cleared)];
+                replies.extend(self.publish_diagnostics());
+                return replies;
+            }
+            _ => return vec![],
+        }
+        // Any open document may import the one which changed
+        self.publish_diagnostics()
+    }
+
+    fn publish_diagnostics(&self) -> Vec<Value> {
+        let mut uris: Vec<&String> = self.documents.keys().collect();
+        uris.sort();
+        uris.into_iter()
+            .map(|uri| {
+                let analysis = self.analyze(uri);
+                let diagnostics: Vec<Value> = analysis
+                    .document_diagnostics()
+                    .map(|diagnostic| analysis.diagnostic(diagnostic))
+                    .collect();
+                let params = json!({ "uri": uri, "diagnostics": diagnostics });
+                notification("textDocument/publishDiagnostics", params)
+            })
+            .collect()
+    }
+
+    fn with_analysis(
+        &self,
+        params: &Value,
+        run: impl FnOnce(&Analysis, &Value) -> Response,
+    ) -> Response {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        if !self.documents.contains_key(uri) {
+            return Err((INVALID_PARAMS, format!("`{uri}` is not open")));
+        }
+        run(&self.analyze(uri), params)
+    }
+
+    /// The text of an imported file, from the editor if it is open there
+    fn read(&self, path: &Path) -> io::Result<(String, String)> {
+        let open = self
+            .documents
+            .iter()
+            .find(|(uri, _)| path_of(uri).as_deref() == Some(path));
+        match open {
+            Some((uri, text)) => Ok((uri.clone(), text.clone())),
+            None => Ok((uri_of(path), fs::read_to_string(path)?)),
+        }
+    }
+
+    /// Read an open document and its imports. Syntax errors are reported in the document, and
+    /// imports which cannot be read at the `%import` they come from; rules defined twice or
+    /// never, and lints, are only reported when there are neither.
+    fn analyze(&self, uri: &str) -> Analysis {
+        let path = path_of(uri).unwrap_or_else(|| PathBuf::from(uri));
+        let mut files = SourceMap::default();
+        let file = files.add(path.clone(), self.documents[uri].clone());
+        let mut uris = vec![uri.to_string()];
+        let (mut grammar, mut diagnostics) = Grammar::parse_partial_in(files.source(file), file);
+
+        let mut pending: VecDeque<(Span, PathBuf)> =
+            grammar.imports(&path, &self.include).collect();
+        while let Some((span, path)) = pending.pop_front() {
+            if files.find(&path).is_some() {
+                continue;
+            }
+            let (uri, text) = match self.read(&path) {
+                Ok(read) => read,
+                Err(err) => {
+                    let message = format!("cannot read `{}`: {err}", path.display());
+                    diagnostics.push(Diagnostic::error(message).with_primary(span, ""));
+                    continue;
+                }
+            };
+            let file = files.add(path.clone(), text);
+            uris.push(uri);
+            match Grammar::parse_in(files.source(file), file) {
+                Ok(imported) => {
+                    // Imports of imports are reported at the import they came through
+                    pending.extend(
+                        imported
+                            .imports(&path, &self.include)
+                            .map(|(_, path)| (span, path)),
+                    );
+                    grammar.import(imported);
+                }
+                Err(err) => {
+                    let message = format!(
+                        "`{}` has {} syntax errors",
+                        path.display(),
+                        err.diagnostics.len()
+                    );
+                    let diagnostic = Diagnostic::error(message).with_primary(span, "");
+                    diagnostics.push(diagnostic.with_help(format!("open `{}`", path.display())));
+                }
+            }
+        }
+        if diagnostics.is_empty() {
+            diagnostics = check_names(&grammar);
+            diagnostics.extend(lint(&grammar).iter().map(Diagnostic::from));
+        }
+        Analysis {
+            files,
+            uris,
+            grammar,
+            diagnostics,
+        }
+    }
+}
+
+fn response(id: &Value, result: Response) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        }),
+    }
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+fn capabilities() -> Value {
+    json!({
+        "capabilities": {
+            "textDocumentSync": { "openClose": true, "change": 1 },
+            "definitionProvider": true,
+            "referencesProvider": true,
+            "renameProvider": { "prepareProvider": true },
+            "completionProvider": {},
+            "hoverProvider": true,
+            "codeActionProvider": { "codeActionKinds": ["quickfix"] },
+        },
+        "serverInfo": { "name": "rantlr", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+fn definition(analysis: &Analysis, params: &Value) -> Response {
+    let Some(occurrence) = analysis.occurrence_at(&params["position"]) else {
+        return Ok(Value::Null);
+    };
+    Ok(analysis
+        .definition(&occurrence.name)
+        .map_or(Value::Null, |(_, span)| analysis.location(span)))
+}
+
+fn references(analysis: &Analysis, params: &Value) -> Response {
+    let Some(occurrence) = analysis.occurrence_at(&params["position"]) else {
+        return Ok(Value::Null);
+    };
+    let declaration = params["context"]["includeDeclaration"].as_bool() == Some(true);
+    let locations: Vec<Value> = analysis
+        .references(&occurrence.name)
+        .into_iter()
+        .filter(|reference| declaration || !reference.definition)
+        .map(|reference| analysis.location(reference.span))
+        .collect();
+    Ok(locations.into())
+}
+
+fn prepare_rename(analysis: &Analysis, params: &Value) -> Response {
+    Ok(analysis
+        .occurrence_at(&params["position"])
+        .map_or(Value::Null, |occurrence| {
+            json!({ "range": analysis.range(occurrence.span), "placeholder": occurrence.name })
+        }))
+}
+
+/// Rename a rule everywhere it is written, to a name of the same kind which no rule has
+fn rename(analysis: &Analysis, params: &Value) -> Response {
+    let Some(occurrence) = analysis.occurrence_at(&params["position"]) else {
+        return Err((REQUEST_FAILED, "there is no rule name here".into()));
+    };
+    let name = params["newName"].as_str().unwrap_or_default();
+    let kinds = |name: &str| -> Vec<TokenKind> {
+        let (tokens, errors) = tokenize_recovering(name, FileId::default());
+        if !errors.is_empty() {
+            return vec![];
+        }
+        tokens.into_iter().map(|token| token.kind).collect()
+    };
+    let valid = match (&kinds(&occurrence.name)[..], &kinds(name)[..]) {
+        ([TokenKind::ParserIdent(_), _], [TokenKind::ParserIdent(new), _])
+        | ([TokenKind::LexerIdent(_), _], [TokenKind::LexerIdent(new), _]) => new == name,
+        _ => false,
+    };
+    if !valid {
+        let kind = if occurrence.name.starts_with(char::is_uppercase) {
+            "lexer rule"
+        } else {
+            "parser rule"
+        };
+        return Err((REQUEST_FAILED, format!("`{name}` is not a {kind} name")));
+    }
+    if analysis.definition(name).is_some() {
+        return Err((
+            REQUEST_FAILED,
+            format!("there is a rule named `{name}` already"),
+        ));
+    }
+    let edits = analysis
+        .references(&occurrence.name)
+        .into_iter()
+        .map(|reference| (reference.span, name.to_string()));
+    Ok(analysis.workspace_edit(edits))
+}
+
+fn completion(analysis: &Analysis, _: &Value) -> Response {
+    let items: Vec<Value> = analysis
+        .grammar
+        .rules
+        .iter()
+        .map(|rule| match rule {
+            // Function and constant, as editors show them
+            Production::Parser(name, ..) => json!({ "label": name, "kind": 3 }),
+            Production::Lexer(name, pattern, _) => {
+                json!({ "label": name, "kind": 21, "detail": pattern })
+            }
+        })
+        .collect();
+    Ok(items.into())
+}
+
+/// Names of symbols as a Markdown list, or `none`
+fn symbols<'a>(names: impl IntoIterator<Item = &'a &'a str>) -> String {
+    let names: Vec<String> = names.into_iter().map(|name| format!("`{name}`")).collect();
+    if names.is_empty() {
+        "none".into()
+    } else {
+        names.join(", ")
+    }
+}
+
+/// The doc comment and definition of a rule, and for a parser rule whether it is nullable and its FIRST and
+/// FOLLOW sets
+fn hover(analysis: &Analysis, params: &Value) -> Response {
+    let Some(occurrence) = analysis.occurrence_at(&params["position"]) else {
+        return Ok(Value::Null);
+    };
+    let Some((rule, _)) = analysis.definition(&occurrence.name) else {
+        return Ok(Value::Null);
+    };
+    let span = rule.span();
+    // The span of a rule ends at its last element, before its `;`
+    let source = analysis.files.source(span.file);
+    let end = source[span.end..]
+        .find(';')
+        .map_or(source.len(), |semicolon| span.end + semicolon + 1);
+    let mut text = format!("```antlr\n{}\n```\n", &source[span.start..end]);
+    if let Some(doc) = analysis.grammar.doc(rule.name()) {
+        text = format!("{doc}\n\n{text}");
+    }
+    if let Production::Parser(name, ..) = rule {
+        let sets = FirstFollow::new(&analysis.grammar);
+        let nullable = if sets.nullable.contains(name.as_str()) {
+            "yes"
+        } else {
+            "no"
+        };
+        text.push_str(&format!(
+            "\n- nullable: {nullable}\n- FIRST: {}\n- FOLLOW: {}\n",
+            symbols(&sets.first[name.as_str()]),
+            symbols(&sets.follow[name.as_str()])
+        ));
+    }
+    Ok(json!({
+        "contents": { "kind": "markdown", "value": text },
+        "range": analysis.range(occurrence.span),
+    }))
+}
+
+/// The fixes suggested by the diagnostics in a range, and allowing the lints there
+fn code_actions(analysis: &Analysis, params: &Value) -> Response {
+    let (Some(start), Some(end)) = (
+        analysis.offset(&params["range"]["start"]),
+        analysis.offset(&params["range"]["end"]),
+    ) else {
+        return Err((INVALID_PARAMS, "the range is not a range".into()));
+    };
+    let mut actions = vec![];
+    for diagnostic in analysis.document_diagnostics() {
+        let span = diagnostic
+            .primary
+            .as_ref()
+            .map_or(Span::new(FileId::default(), 0..0), |label| label.span);
+        if span.start > end || start > span.end {
+            continue;
+        }
+        let quickfix = |title: String, edits: Vec<(Span, String)>| {
+            json!({
+                "title": title,
+                "kind": "quickfix",
+                "diagnostics": [analysis.diagnostic(diagnostic)],
+                "edit": analysis.workspace_edit(edits),
+            })
+        };
+        for suggestion in &diagnostic.suggestions {
+            let edit = (suggestion.span, suggestion.replacement.clone());
+            actions.push(quickfix(suggestion.message.clone(), vec![edit]));
+        }
+        let lint = diagnostic
+            .code
+            .as_deref()
+            .and_then(|code| code.split_once('/'));
+        if let Some((_, name)) = lint {
+            let start = Span::new(FileId::default(), 0..0);
+            let edit = (start, format!("%allow {name};\n"));
+            actions.push(quickfix(
+                format!("allow `{name}` in this grammar"),
+                vec![edit],
+            ));
+        }
+    }
+    Ok(actions.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use insta::assert_snapshot;
+
+    use super::*;
+
+    const URI: &str = "file:///g.g4";
+
+    /// A server with `text` open as `g.g4`, and the diagnostics it published on opening it
+    fn open(text: &str) -> (Server, Vec<Value>) {
+        let mut server = Server {
+            include: vec![],
+            documents: HashMap::new(),
+            shut_down: false,
+        };
+        let replies = server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": URI, "languageId": "antlr", "version": 1, "text": text } },
+        }));
+        (server, replies)
+    }
+
+    /// The result of a request about `g.g4`, at a line and character where `params` has none
+    fn request(
+        server: &mut Server,
+        method: &str,
+        line: u64,
+        character: u64,
+        params: Value,
+    ) -> Value {
+        let mut message = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": {
+                "textDocument": { "uri": URI },
+                "position": { "line": line, "character": character },
+            },
+        });
+        for (key, value) in params.as_object().unwrap() {
+            message["params"][key] = value.clone();
+        }
+        let replies = server.handle(&message);
+        assert_eq!(replies.len(), 1);
+        replies[0]["result"].clone()
+    }
+
+    fn pretty(value: &Value) -> String {
+        serde_json::to_string_pretty(value).unwrap()
+    }
+
+    #[test]
+    fn diagnostics_on_open() {
+        let (_, replies) = open("s: A;\nA a;\nB: b;\n");
+        assert_snapshot!(pretty(&replies.into()));
+    }
+
+    #[test]
+    fn lints_when_valid() {
+        let (_, replies) = open("s: A;\nA: a;\nB: b;\n");
+        let diagnostics = &replies[0]["params"]["diagnostics"];
+        assert_eq!(diagnostics[0]["code"], "L003/unused_token");
+        assert_eq!(
+            diagnostics[0]["range"]["start"],
+            json!({ "line": 2, "character": 0 })
+        );
+    }
+
+    #[test]
+    fn undefined_and_duplicate_rules() {
+        let (_, replies) = open("e: a | N;\nN: n;\n");
+        let diagnostics = &replies[0]["params"]["diagnostics"];
+        assert_eq!(diagnostics[0]["code"], "E004");
+        assert_eq!(diagnostics[0]["message"], "undefined rule `a`");
+        assert_eq!(
+            diagnostics[0]["range"],
+            json!({ "start": { "line": 0, "character": 3 }, "end": { "line": 0, "character": 4 } })
+        );
+        let (_, replies) = open("s: A;\ns: A A;\nA: a;\n");
+        let diagnostics = &replies[0]["params"]["diagnostics"];
+        assert_eq!(diagnostics[0]["code"], "E005");
+        assert_eq!(
+            diagnostics[0]["relatedInformation"][0]["location"]["range"]["start"],
+            json!({ "line": 0, "character": 0 })
+        );
+    }
+
+    #[test]
+    fn change_and_close() {
+        let (mut server, _) = open("s: A;\nA: a;\nB: b;\n");
+        let replies = server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didChange",
+            "params": {
+                "textDocument": { "uri": URI, "version": 2 },
+                "contentChanges": [{ "text": "s: A;\nA: a;\n" }],
+            },
+        }));
+        assert_eq!(replies[0]["params"]["diagnostics"], json!([]));
+        let replies = server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didClose",
+            "params": { "textDocument": { "uri": URI } },
+        }));
+        assert_eq!(replies.len(), 1);
+        let result = server.handle(&json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "textDocument/hover",
+            "params": { "textDocument": { "uri": URI }, "position": { "line": 0, "character": 0 } },
+        }));
+        assert_eq!(result[0]["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[test]
+    fn definition_and_references() {
+        let (mut server, _) = open("s: t A | %prec A t;\nt: A;\n%left A;\nA: a;\n");
+        let definition = request(&mut server, "textDocument/definition", 0, 4, json!({}));
+        assert_eq!(
+            definition["range"]["start"],
+            json!({ "line": 1, "character": 0 })
+        );
+        let context = json!({ "context": { "includeDeclaration": false } });
+        let references = request(&mut server, "textDocument/references", 3, 0, context);
+        let lines: Vec<&Value> = references
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|reference| &reference["range"]["start"]["line"])
+            .collect();
+        assert_eq!(lines, [0, 0, 1, 2]);
+    }
+
+    #[test]
+    fn labels_are_not_references() {
+        let (mut server, _) = open("s: A %label a | a;\na: A;\nA: a;\n");
+        let context = json!({ "context": { "includeDeclaration": true } });
+        let references = request(&mut server, "textDocument/references", 1, 0, context);
+        assert_eq!(references.as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn definition_in_import() {
+        let dir = env::temp_dir().join(format!("rantlr-lsp-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("tokens.g4"), "PLUS: \\+;\nNUM: [0-9]+;\n").unwrap();
+        let uri = uri_of(&dir.join("sum.g4"));
+        let mut server = Server {
+            include: vec![],
+            documents: HashMap::new(),
+            shut_down: false,
+        };
+        server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": uri, "text": "%import tokens;\nsum: sum PLUS NUM | NUM;\n" } },
+        }));
+        let replies = server.handle(&json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "textDocument/definition",
+            "params": { "textDocument": { "uri": uri }, "position": { "line": 1, "character": 15 } },
+        }));
+        let location = &replies[0]["result"];
+        assert_eq!(location["uri"], uri_of(&dir.join("tokens.g4")));
+        assert_eq!(
+            location["range"]["start"],
+            json!({ "line": 1, "character": 0 })
+        );
+    }
+
+    #[test]
+    fn missing_import() {
+        let (_, replies) = open("%import missing;\ns: A;\nA: a;\n");
+        let diagnostic = &replies[0]["params"]["diagnostics"][0];
+        assert_eq!(diagnostic["range"]["start"]["line"], 0);
+        assert!(diagnostic["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("cannot read"));
+    }
+
+    #[test]
+    fn rename() {
+        let (mut server, _) = open("s: t | t A;\nt: A;\nA: a;\n");
+        let prepared = request(&mut server, "textDocument/prepareRename", 1, 0, json!({}));
+        assert_eq!(prepared["placeholder"], "t");
+        let edit = request(
+            &mut server,
+            "textDocument/rename",
+            1,
+            0,
+            json!({ "newName": "term" }),
+        );
+        assert_snapshot!(pretty(&edit));
+    }
+
+    #[test]
+    fn rename_rejected() {
+        let (mut server, _) = open("s: t | t A;\nt: A;\nA: a;\n");
+        for name in ["T", "s", "t u", ""] {
+            let replies = server.handle(&json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "textDocument/rename",
+                "params": {
+                    "textDocument": { "uri": URI },
+                    "position": { "line": 1, "character": 0 },
+                    "newName": name,
+                },
+            }));
+            assert_eq!(replies[0]["error"]["code"], REQUEST_FAILED, "{name}");
+        }
+    }
+
+    #[test]
+    fn completion() {
+        let (mut server, _) = open("s: A;\nA: a;\n");
+        let items = request(&mut server, "textDocument/completion", 0, 3, json!({}));
+        assert_eq!(
+            items,
+            json!([
+                { "label": "s", "kind": 3 },
+                { "label": "A", "kind": 21, "detail": "a" },
+            ])
+        );
+    }
+
+    #[test]
+    fn hover() {
+        let (mut server, _) = open("s: t A;\n/** Maybe `A`. */\nt: A | ;\nA: a;\n");
+        let hover = request(&mut server, "textDocument/hover", 0, 3, json!({}));
+        assert_snapshot!(hover["contents"]["value"].as_str().unwrap());
+    }
+
+    #[test]
+    fn code_actions() {
+        let (mut server, _) = open("s: A;\nA: a;\nB: b;\n");
+        let range = json!({
+            "range": {
+                "start": { "line": 2, "character": 0 },
+                "end": { "line": 2, "character": 1 },
+            },
+            "context": { "diagnostics": [] },
+        });
+        let actions = request(&mut server, "textDocument/codeAction", 0, 0, range);
+        let titles: Vec<&Value> = actions
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|action| &action["title"])
+            .collect();
+        assert_eq!(
+            titles,
+            ["remove `B`", "allow `unused_token` in this grammar"]
+        );
+    }
+
+    #[test]
+    fn uris() {
+        let path = Path::new("/grammars/my sum.g4");
+        assert_eq!(uri_of(path), "file:///grammars/my%20sum.g4");
+        assert_eq!(path_of(&uri_of(path)).unwrap(), path);
+        assert_eq!(path_of("file:///C:/g.g4").unwrap(), Path::new("C:/g.g4"));
+        assert_eq!(path_of("untitled:Untitled-1"), None);
+    }
+
+    #[test]
+    fn serve_until_exit() {
+        let mut input = vec![];
+        for message in [
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "workspace/symbol", "params": {} }),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "shutdown" }),
+            json!({ "jsonrpc": "2.0", "method": "exit" }),
+        ] {
+            write_message(&mut input, &message).unwrap();
+        }
+        input.extend_from_slice(b"Content-Length: 3\r\n\r\n{{{");
+        let mut output = vec![];
+        assert!(serve(vec![], &input[..], &mut output).unwrap());
+        let output = String::from_utf8(output).unwrap();
+        let replies: Vec<Value> = output
+            .split("Content-Length: ")
+            .skip(1)
+            .map(|message| serde_json::from_str(message.split_once("\r\n\r\n").unwrap().1).unwrap())
+            .collect();
+        assert_eq!(replies.len(), 3);
+        assert_eq!(replies[1]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(replies[2]["result"], Value::Null);
+    }
+
+    #[test]
+    fn not_json() {
+        let (mut server, _) = open("");
+        let replies = server.handle(&Value::Null);
+        assert_eq!(replies[0]["error"]["code"], PARSE_ERROR);
+    }
+}
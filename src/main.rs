@@ -1,37 +1,987 @@
-//! # This is a header
-//! Some more text
-mod ast;
-mod error;
-mod internal;
-mod lexer;
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Mutex,
+};
 
-use std::{fs, path::PathBuf};
-
-use clap::Parser;
-
-use crate::internal::{ast::Grammar, lexer::lexer::tokenize, parser::functional::parse};
+use anyhow::Result;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rantlr::{
+    analysis::{check_names, lint, Severity},
+    codegen,
+    diagnostic::{of_error, of_grammar_error, Diagnostic},
+    docs,
+    error::MessagesError,
+    format::format,
+    graph, lsp, meta, output, railroad,
+    runtime::{Adaptive, Earley, Glr, Interpreter, Lexer, Ll1, Peg, Symbols},
+    span::{SourceMap, Span},
+    table::{
+        explain_ll1, explain_lr, messages, resolved_table, ConflictPolicy, Ll1Table, LrKind,
+        LrTable,
+    },
+    Error, Grammar, Production, SyntaxErrors,
+};
 
 #[derive(Parser)]
-#[command(version, about, long_about)]
+#[command(version, about, after_help = EXIT_STATUS)]
 struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// How errors, warnings and lints are printed on standard error
+    #[arg(long, value_enum, global = true, default_value_t = MessageFormat::Human)]
+    message_format: MessageFormat,
+}
+
+const EXIT_STATUS: &str = "\
+Exit status: 0 on success, 1 when a grammar or input has errors or the command fails, 2 on
+invalid usage
+
+Diagnostics go to standard error, and what a command produces to standard output. With
+`--message-format json` each diagnostic is a JSON object on a line of its own, described in the
+documentation of `rantlr::output`; with `--message-format sarif` they make up a single SARIF 2.1.0
+log, printed once the command is done.";
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum MessageFormat {
+    /// Rendered with the source lines they point at
+    Human,
+    /// A JSON object per diagnostic
+    Json,
+    /// A SARIF log of every diagnostic
+    Sarif,
+}
+
+/// Grammar files, and where their imports are looked for
+#[derive(Args)]
+struct Grammars {
+    #[arg(required = true, value_name = "GRAMMAR")]
+    files: Vec<PathBuf>,
+    #[command(flatten)]
+    include: Include,
+}
+
+/// A grammar file, and where its imports are looked for
+#[derive(Args)]
+struct OneGrammar {
+    #[arg(value_name = "GRAMMAR")]
     file: PathBuf,
+    #[command(flatten)]
+    include: Include,
+}
+
+#[derive(Args)]
+struct Include {
+    /// Also look for imported grammars in this directory, after the directory of the importing
+    /// grammar
+    #[arg(short = 'I', long = "include", value_name = "DIR")]
+    dirs: Vec<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Report every syntax error in grammars, rules defined twice or never, and the rules read
+    /// without errors. The references of a grammar with imports are checked once it has no
+    /// syntax errors, against the rules of its imports.
+    Check {
+        #[command(flatten)]
+        grammars: Grammars,
+    },
+    /// Format grammars in place, keeping their comments
+    Fmt {
+        #[arg(required = true, value_name = "GRAMMAR")]
+        files: Vec<PathBuf>,
+        /// Fail on grammars which are not formatted, instead of formatting them
+        #[arg(long)]
+        check: bool,
+    },
+    /// Lint grammars, failing if any lint is denied
+    Lint {
+        #[command(flatten)]
+        grammars: Grammars,
+    },
+    /// Build parsing tables and explain their conflicts, failing if any is left unresolved
+    Analyze {
+        #[command(flatten)]
+        grammars: Grammars,
+        #[arg(short, long, value_enum, default_value_t = TableKind::Lalr)]
+        table: TableKind,
+    },
+    /// Generate Rust parser modules
+    Generate {
+        #[command(flatten)]
+        grammars: Grammars,
+        /// Write the module of each grammar here, named after it as `<name>.rs`, instead of to
+        /// standard output
+        #[arg(short, long, value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+        /// The LL(1), LR and recursive descent backends also emit `parse_recovering`, which
+        /// returns a tree for broken input; alternatives starting with the `ERROR` terminal are
+        /// error productions
+        #[arg(short, long, value_enum, default_value_t = Backend::Lalr)]
+        backend: Backend,
+        /// The language of the generated modules
+        #[arg(long, value_enum, default_value_t = Target::Rust)]
+        target: Target,
+        /// What to do with conflicts not declared by `%expect` and `%expect_rr`.
+        /// The GLR backend keeps every conflict.
+        #[arg(long, value_enum, default_value_t = Conflicts::Error)]
+        conflicts: Conflicts,
+        /// Syntax error messages for the states of the LR backends, as listed by `rantlr errors`.
+        /// Every error state must have an entry, and every entry must still lead to one.
+        /// Only for a single grammar.
+        #[arg(long)]
+        messages: Option<PathBuf>,
+    },
+    /// List the states in which an LR parser can find a syntax error, as entries of a messages
+    /// file in the style of Menhir, or check such a file against the grammar
+    Errors {
+        #[command(flatten)]
+        grammar: OneGrammar,
+        /// Print an entry for every error state, or with `--messages` for every one it lacks
+        #[arg(long, required_unless_present = "messages")]
+        list: bool,
+        /// The messages file to check
+        #[arg(long)]
+        messages: Option<PathBuf>,
+        #[arg(short, long, value_enum, default_value_t = LrTableKind::Lalr)]
+        table: LrTableKind,
+        /// What to do with conflicts not declared by `%expect` and `%expect_rr`
+        #[arg(long, value_enum, default_value_t = Conflicts::Error)]
+        conflicts: Conflicts,
+    },
+    /// Split an input file into the tokens of a grammar, one per line with its byte range
+    Lex {
+        #[command(flatten)]
+        grammar: OneGrammar,
+        input: PathBuf,
+    },
+    /// Parse an input file with a grammar, printing every derivation of it
+    Parse {
+        #[command(flatten)]
+        grammar: OneGrammar,
+        input: PathBuf,
+        #[arg(short, long, value_enum, default_value_t = ParseBackend::Auto)]
+        backend: ParseBackend,
+        /// Print up to this many derivations as trees, instead of the forest of an ambiguous input
+        #[arg(long)]
+        first: Option<usize>,
+    },
+    /// Print the reference documentation of a grammar, from the `/** ... */` comments of its rules
+    Doc {
+        #[command(flatten)]
+        grammar: OneGrammar,
+        #[arg(long, value_enum, default_value_t = DocFormat::Markdown)]
+        format: DocFormat,
+    },
+    /// Draw the rules of a grammar as railroad diagrams, on an HTML page printed to standard output
+    Railroad {
+        #[command(flatten)]
+        grammar: OneGrammar,
+        /// Write an SVG image of each rule here instead, named after it as `<name>.svg`
+        #[arg(long, value_name = "DIR")]
+        svg_dir: Option<PathBuf>,
+    },
+    /// Print a Graphviz graph of the rules of a grammar and the rules and tokens they use, in the
+    /// DOT language
+    Graph {
+        #[command(flatten)]
+        grammar: OneGrammar,
+        /// Print the LR(0) automaton of the grammar instead, its states listing their items
+        #[arg(long)]
+        automaton: bool,
+    },
+    /// Serve editors as a language server over standard input and output
+    Lsp {
+        #[command(flatten)]
+        include: Include,
+    },
+    /// Generate rantlr's own meta-parser and tree types from its grammar, `examples/rantlr.g4`
+    Bootstrap {
+        file: PathBuf,
+        /// Where the meta-parser lives, as `tree.rs` and `generated.rs`
+        #[arg(long, default_value = "src/internal/parser")]
+        out_dir: PathBuf,
+        /// Fail if the meta-parser in `out_dir` is out of date, instead of writing it
+        #[arg(long)]
+        check: bool,
+        /// Grammars on which the meta-parser built into this binary must agree with the hand-written one
+        corpus: Vec<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Backend {
+    /// LL(1) table driven on an explicit stack, for grammars without LL(1) conflicts
+    Ll1,
+    Slr,
+    Lalr,
+    /// LALR table whose conflicts are kept, driven by a GLR parser producing a parse forest
+    Glr,
+    /// Recursive descent, with adaptive LL(*) prediction where one token of lookahead is not enough
+    RecursiveDescent,
+    /// Packrat parser reading `|` as ordered choice, with `&` and `!` lookaheads
+    Peg,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Target {
+    Rust,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ParseBackend {
+    /// LL(1) when its table has no conflicts, then LR when the LALR table has none, or else Earley
+    Auto,
+    /// LL(1) table driven on an explicit stack, failing on conflicts
+    Ll1,
+    /// LALR table, failing on conflicts not declared by `%expect` and `%expect_rr`
+    Lr,
+    /// LALR table whose conflicts are kept, driven by a GLR parser
+    Glr,
+    /// Earley parser, which accepts any grammar
+    Earley,
+    /// Recursive descent with adaptive LL(*) prediction, for grammars without left recursion
+    Allstar,
+    /// Packrat parser reading `|` as ordered choice, with `&` and `!` lookaheads
+    Peg,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Conflicts {
+    /// Fail on any conflict
+    Error,
+    /// Report conflicts, then resolve them by default
+    Warn,
+    /// Resolve conflicts silently by default, preferring shift
+    PreferShift,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DocFormat {
+    Markdown,
+    /// A self-contained HTML page
+    Html,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TableKind {
+    Ll1,
+    Slr,
+    Lalr,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LrTableKind {
+    Slr,
+    Lalr,
+}
+
+/// An error whose diagnostics were already emitted, which only fails the command
+#[derive(Debug)]
+struct Emitted;
+
+impl fmt::Display for Emitted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("already emitted")
+    }
+}
+
+impl std::error::Error for Emitted {}
 
-    /// Turn debugging information on
-    #[arg(short, long, action = clap::ArgAction::Count)]
-    debug: u8,
+/// Prints diagnostics in the chosen format
+struct Emitter {
+    format: MessageFormat,
+    /// The SARIF results, printed by [`Emitter::finish`]
+    results: Vec<serde_json::Value>,
 }
 
-fn main() {
-    let args = Cli::parse();
-    let content = fs::read_to_string(args.file).expect("Failed to read file");
-    println!("{content:?}");
+impl Emitter {
+    fn new(format: MessageFormat) -> Self {
+        Emitter {
+            format,
+            results: vec![],
+        }
+    }
 
-    let tokens = tokenize(&content).expect("Failed to tokenize");
-    println!("{tokens:?}");
+    fn emit(&mut self, diagnostic: &Diagnostic, files: &SourceMap) {
+        match self.format {
+            MessageFormat::Human => eprint!("{}", diagnostic.render_in(files)),
+            MessageFormat::Json => eprintln!("{}", output::json(diagnostic, files)),
+            MessageFormat::Sarif => self.results.push(output::sarif_result(diagnostic, files)),
+        }
+    }
 
-    let parse_tree = parse(tokens).expect("Failed to parse tree");
-    println!("{parse_tree:?}");
+    fn error(&mut self, message: impl Into<String>) {
+        self.emit(&Diagnostic::error(message), &SourceMap::default());
+    }
+
+    /// Syntax errors of a grammar in `files`, followed by a summary for people when there are
+    /// several
+    fn syntax(&mut self, err: &SyntaxErrors, files: &SourceMap) {
+        if self.format == MessageFormat::Human {
+            eprint!("{err}");
+            return;
+        }
+        for diagnostic in &err.diagnostics {
+            self.emit(diagnostic, files);
+        }
+    }
+
+    /// An error in a grammar read from `files`, with its diagnostics when it has some
+    fn grammar_error(&mut self, err: impl Into<Error>, files: &SourceMap) -> anyhow::Error {
+        let err = err.into();
+        let diagnostics = of_grammar_error(&err);
+        if diagnostics.is_empty() {
+            return err.into();
+        }
+        for diagnostic in &diagnostics {
+            self.emit(diagnostic, files);
+        }
+        Emitted.into()
+    }
+
+    /// An error located in `source`, read from `path`, with its diagnostic when it has one
+    fn located(&mut self, err: anyhow::Error, path: &Path, source: &str) -> anyhow::Error {
+        let Some(diagnostic) = of_error(err.as_ref()) else {
+            return err;
+        };
+        let mut files = SourceMap::default();
+        files.add(path.into(), source.into());
+        self.emit(&diagnostic, &files);
+        Emitted.into()
+    }
+
+    /// Syntax errors of the grammar at `path`, which is read on its own
+    fn syntax_errors(&mut self, mut err: SyntaxErrors, path: &Path) -> Emitted {
+        err.name = path.display().to_string();
+        let mut files = SourceMap::default();
+        files.add(path.into(), err.source.clone());
+        self.syntax(&err, &files);
+        Emitted
+    }
+
+    /// Print an error which ends a command
+    fn report(&mut self, err: &anyhow::Error) {
+        if !err.is::<Emitted>() {
+            self.error(err.to_string());
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.format == MessageFormat::Sarif {
+            let log = output::sarif(std::mem::take(&mut self.results));
+            eprintln!("{}", serde_json::to_string_pretty(&log).unwrap());
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<String> {
+    fs::read_to_string(path).map_err(|source| {
+        Error::Io {
+            path: path.into(),
+            source,
+        }
+        .into()
+    })
+}
+
+/// A grammar and its imports, with the files they were read from
+fn read_grammar(
+    file: &Path,
+    include: &Include,
+    emitter: &mut Emitter,
+) -> Result<(SourceMap, Grammar)> {
+    let mut files = SourceMap::default();
+    match Grammar::load_with(file, &include.dirs, &mut files) {
+        Ok(grammar) => Ok((files, grammar)),
+        Err(Error::Syntax(err)) => {
+            emitter.syntax(&err, &files);
+            Err(Emitted.into())
+        }
+        Err(err) => Err(emitter.grammar_error(err, &files)),
+    }
+}
+
+fn load_grammar(file: &Path, include: &Include, emitter: &mut Emitter) -> Result<Grammar> {
+    Ok(read_grammar(file, include, emitter)?.1)
+}
+
+/// Whether an error is standard output closed by its reader, as by `rantlr lex … | head`,
+/// which ends a command quietly
+fn broken_pipe(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<io::Error>()
+        .is_some_and(|err| err.kind() == io::ErrorKind::BrokenPipe)
+}
+
+/// Run a command on every file, going on after those it fails on
+fn each(
+    files: &[PathBuf],
+    emitter: &mut Emitter,
+    mut run: impl FnMut(&Path, &mut Emitter) -> Result<ExitCode>,
+) -> ExitCode {
+    let mut code = ExitCode::SUCCESS;
+    for file in files {
+        match run(file, emitter) {
+            Ok(ExitCode::SUCCESS) => {}
+            Ok(failure) => code = failure,
+            Err(err) if broken_pipe(&err) => break,
+            Err(err) => {
+                emitter.report(&err);
+                code = ExitCode::FAILURE;
+            }
+        }
+    }
+    code
+}
+
+fn run_check(file: &Path, include: &Include, emitter: &mut Emitter) -> Result<ExitCode> {
+    let mut out = io::stdout().lock();
+    let content = read(file)?;
+    let (grammar, mut diagnostics) = Grammar::parse_partial(&content);
+    let parsed = diagnostics.is_empty();
+    let imports = grammar
+        .directives
+        .iter()
+        .any(|directive| directive.name == "import");
+    if !parsed || !imports {
+        // A grammar recovered from syntax errors may have lost rules it refers to, and one with
+        // imports may refer to rules defined in them
+        let mut names = check_names(&grammar);
+        names.retain(|diagnostic| {
+            (parsed && !imports) || diagnostic.code.as_deref() != Some("E004")
+        });
+        diagnostics.extend(names);
+    }
+    let mut success = diagnostics.is_empty();
+    let err = SyntaxErrors {
+        name: file.display().to_string(),
+        source: content.clone(),
+        diagnostics,
+    };
+    let mut files = SourceMap::default();
+    files.add(file.into(), content);
+    emitter.syntax(&err, &files);
+    // Loading the imports checks the names of the grammar with their rules
+    if parsed && imports {
+        if let Err(err) = read_grammar(file, include, emitter) {
+            emitter.report(&err);
+            success = false;
+        }
+    }
+    let rules: Vec<&str> = grammar.rules.iter().map(Production::name).collect();
+    if rules.is_empty() {
+        writeln!(out, "read 0 rules")?;
+    } else {
+        writeln!(
+            out,
+            "read {}: {}",
+            plural(rules.len(), "rule"),
+            rules.join(", ")
+        )?;
+    }
+    Ok(exit_code(success))
+}
+
+fn run_fmt(file: &Path, check: bool, emitter: &mut Emitter) -> Result<ExitCode> {
+    let content = read(file)?;
+    let formatted = format(&content).map_err(|err| emitter.syntax_errors(err, file))?;
+    if formatted == content {
+        return Ok(ExitCode::SUCCESS);
+    }
+    if !check {
+        fs::write(file, formatted).map_err(|source| Error::Io {
+            path: file.into(),
+            source,
+        })?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    // Point at the first line which changes
+    let start = content
+        .split_inclusive('\n')
+        .zip(formatted.split_inclusive('\n'))
+        .take_while(|(line, formatted)| line == formatted)
+        .map(|(line, _)| line.len())
+        .sum::<usize>();
+    let end = content[start..]
+        .find('\n')
+        .map_or(content.len(), |end| start + end);
+    let mut files = SourceMap::default();
+    let id = files.add(file.into(), content);
+    let diagnostic = Diagnostic::error(format!("`{}` is not formatted", file.display()))
+        .with_primary(Span::new(id, start..end), "formatted differently from here")
+        .with_help(format!("run `rantlr fmt {}`", file.display()));
+    emitter.emit(&diagnostic, &files);
+    Ok(ExitCode::FAILURE)
+}
+
+fn run_lint(file: &Path, include: &Include, emitter: &mut Emitter) -> Result<ExitCode> {
+    let (files, grammar) = read_grammar(file, include, emitter)?;
+    let lints = lint(&grammar);
+    for lint in &lints {
+        emitter.emit(&Diagnostic::from(lint), &files);
+    }
+    if lints.iter().any(|lint| lint.severity == Severity::Deny) {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// `1 conflict`, `2 conflicts`
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("{count} {noun}")
+    } else {
+        format!("{count} {noun}s")
+    }
+}
+
+fn run_analyze(
+    file: &Path,
+    include: &Include,
+    table: TableKind,
+    emitter: &mut Emitter,
+) -> Result<ExitCode> {
+    let mut out = io::stdout().lock();
+    let (files, grammar) = read_grammar(file, include, emitter)?;
+    let kind = match table {
+        TableKind::Ll1 => {
+            let table = Ll1Table::new(&grammar);
+            let conflicts = table.conflicts();
+            for conflict in &conflicts {
+                let explanation = explain_ll1(&grammar, &table, conflict);
+                emitter.emit(&explanation.diagnostic(), &files);
+            }
+            writeln!(out, "{}", plural(conflicts.len(), "LL(1) conflict"))?;
+            return Ok(exit_code(conflicts.is_empty()));
+        }
+        TableKind::Slr => LrKind::Slr,
+        TableKind::Lalr => LrKind::Lalr,
+    };
+    let table = LrTable::new(&grammar, kind);
+    let conflicts = table.conflicts();
+    for resolution in &table.resolutions {
+        writeln!(
+            out,
+            "state {}: resolved shift/reduce conflict on `{}` against `{}` as {}",
+            resolution.state,
+            resolution.terminal,
+            table.automaton.rules[resolution.rule],
+            resolution.decision
+        )?;
+    }
+    for conflict in &conflicts {
+        emitter.emit(&explain_lr(&table, conflict).diagnostic(), &files);
+    }
+    writeln!(
+        out,
+        "{}, {}",
+        plural(table.automaton.states.len(), "state"),
+        plural(conflicts.len(), "conflict")
+    )?;
+    Ok(exit_code(conflicts.is_empty()))
+}
+
+impl From<Conflicts> for ConflictPolicy {
+    fn from(conflicts: Conflicts) -> Self {
+        match conflicts {
+            Conflicts::Error => ConflictPolicy::Error,
+            Conflicts::Warn => ConflictPolicy::Warn,
+            Conflicts::PreferShift => ConflictPolicy::PreferShift,
+        }
+    }
+}
+
+impl From<Backend> for codegen::Backend {
+    fn from(backend: Backend) -> Self {
+        match backend {
+            Backend::Ll1 => codegen::Backend::Ll1,
+            Backend::Slr => codegen::Backend::Slr,
+            Backend::Lalr => codegen::Backend::Lalr,
+            Backend::Glr => codegen::Backend::Glr,
+            Backend::RecursiveDescent => codegen::Backend::RecursiveDescent,
+            Backend::Peg => codegen::Backend::Peg,
+        }
+    }
+}
+
+/// Every problem with a messages file, as an error of its own on the line it is about
+fn emit_messages_errors(emitter: &mut Emitter, path: &Path, errors: &[MessagesError]) {
+    let mut files = SourceMap::default();
+    let file = files.add(path.into(), fs::read_to_string(path).unwrap_or_default());
+    for err in errors {
+        let diagnostic = messages::diagnostic(err, file, files.source(file));
+        emitter.emit(&diagnostic, &files);
+    }
+}
+
+fn run_generate(
+    file: &Path,
+    include: &Include,
+    out_dir: Option<&Path>,
+    options: &codegen::Options,
+    emitter: &mut Emitter,
+) -> Result<ExitCode> {
+    let mut out = io::stdout().lock();
+    let (files, grammar) = read_grammar(file, include, emitter)?;
+    let generated = match codegen::generate(&grammar, &file.display().to_string(), options) {
+        Ok(generated) => generated,
+        Err(Error::Messages { path, errors }) => {
+            emit_messages_errors(emitter, &path, &errors);
+            return Err(Emitted.into());
+        }
+        Err(err) => return Err(emitter.grammar_error(err, &files)),
+    };
+    for warning in &generated.warnings {
+        emitter.emit(warning, &files);
+    }
+    match out_dir {
+        Some(out_dir) => {
+            let stem = file.file_stem().unwrap_or(file.as_os_str());
+            let path = out_dir.join(Path::new(stem).with_extension("rs"));
+            fs::write(&path, generated.code).map_err(|source| Error::Io { path, source })?;
+        }
+        None => write!(out, "{}", generated.code)?,
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_errors(
+    grammar: &OneGrammar,
+    list: bool,
+    messages: Option<&Path>,
+    table: LrTableKind,
+    conflicts: Conflicts,
+    emitter: &mut Emitter,
+) -> Result<ExitCode> {
+    let mut out = io::stdout().lock();
+    let (files, grammar) = read_grammar(&grammar.file, &grammar.include, emitter)?;
+    let kind = match table {
+        LrTableKind::Slr => LrKind::Slr,
+        LrTableKind::Lalr => LrKind::Lalr,
+    };
+    let (table, warnings) = resolved_table(&grammar, kind, conflicts.into())
+        .map_err(|err| emitter.grammar_error(err, &files))?;
+    for warning in &warnings {
+        emitter.emit(warning, &files);
+    }
+    let mut states = messages::error_states(&table);
+    let Some(path) = messages else {
+        write!(out, "{}", messages::render(&table, &states))?;
+        return Ok(ExitCode::SUCCESS);
+    };
+    let (messages, errors) = match messages::parse(&read(path)?, messages::start(&table)) {
+        Ok(entries) => messages::resolve(&table, &entries),
+        Err(err) => (Default::default(), vec![err]),
+    };
+    if list {
+        states.retain(|error| !messages.contains_key(&error.state));
+        write!(out, "{}", messages::render(&table, &states))?;
+    }
+    emit_messages_errors(emitter, path, &errors);
+    Ok(exit_code(errors.is_empty()))
+}
+
+fn run_bootstrap(
+    file: &Path,
+    out_dir: &Path,
+    check: bool,
+    corpus: &[PathBuf],
+    emitter: &mut Emitter,
+) -> Result<ExitCode> {
+    let grammar = load_grammar(file, &Include { dirs: vec![] }, emitter)?;
+    let bootstrap = meta::generate(&grammar, &file.display().to_string())?;
+    let mut code = ExitCode::SUCCESS;
+    for (name, content) in [
+        ("tree.rs", &bootstrap.tree),
+        ("generated.rs", &bootstrap.parser),
+    ] {
+        let path = out_dir.join(name);
+        if !check {
+            fs::write(&path, content)?;
+        } else if fs::read_to_string(&path).ok().as_ref() != Some(content) {
+            emitter.error(format!(
+                "`{}` is out of date, run `rantlr bootstrap {}`",
+                path.display(),
+                file.display()
+            ));
+            code = ExitCode::FAILURE;
+        }
+    }
+    for grammar_file in corpus {
+        let content = read(grammar_file)?;
+        let hand_written =
+            Grammar::parse(&content).map_err(|err| emitter.syntax_errors(err, grammar_file))?;
+        let generated = meta::parse_generated(&content)
+            .map_err(|err| emitter.syntax_errors(err, grammar_file))?;
+        if hand_written != generated {
+            emitter.error(format!(
+                "the meta-parsers disagree on `{}`",
+                grammar_file.display()
+            ));
+            code = ExitCode::FAILURE;
+        }
+    }
+    Ok(code)
+}
+
+fn run_lex(grammar: &OneGrammar, input: &Path, emitter: &mut Emitter) -> Result<ExitCode> {
+    let mut out = io::stdout().lock();
+    let grammar = load_grammar(&grammar.file, &grammar.include, emitter)?;
+    let symbols = Symbols::new(&grammar)?;
+    let lexer = Lexer::new(&grammar, &symbols.terminals, symbols.end())?;
+    let content = read(input)?;
+    let tokens = lexer
+        .tokenize(&content)
+        .map_err(|err| emitter.located(err.into(), input, &content))?;
+    for token in tokens {
+        writeln!(
+            out,
+            "{}..{} {} {:?}",
+            token.start, token.end, symbols.terminals[token.terminal], token.text
+        )?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_parse(
+    grammar: &OneGrammar,
+    input: &Path,
+    backend: ParseBackend,
+    first: Option<usize>,
+    emitter: &mut Emitter,
+) -> Result<ExitCode> {
+    let mut out = io::stdout().lock();
+    let (files, grammar) = read_grammar(&grammar.file, &grammar.include, emitter)?;
+    let interpreter = || -> Result<Interpreter, Error> {
+        Ok(match backend {
+            ParseBackend::Auto => Interpreter::auto(&grammar)?,
+            ParseBackend::Ll1 => Interpreter::Ll1(Ll1::new(&grammar, &Ll1Table::new(&grammar))?),
+            ParseBackend::Lr => {
+                let (table, _) = resolved_table(&grammar, LrKind::Lalr, ConflictPolicy::Error)?;
+                Interpreter::Glr(Glr::new(&grammar, &table)?)
+            }
+            ParseBackend::Glr => {
+                Interpreter::Glr(Glr::new(&grammar, &LrTable::new(&grammar, LrKind::Lalr))?)
+            }
+            ParseBackend::Earley => Interpreter::Earley(Earley::new(&grammar)?),
+            ParseBackend::Allstar => Interpreter::Adaptive(Adaptive::new(&grammar)?),
+            ParseBackend::Peg => Interpreter::Peg(Peg::new(&grammar)?),
+        })
+    };
+    let interpreter = interpreter().map_err(|err| emitter.grammar_error(err, &files))?;
+    let content = read(input)?;
+    let parse = interpreter
+        .parse(&content)
+        .map_err(|err| emitter.located(err.into(), input, &content))?;
+    let symbols = interpreter.symbols();
+    if let Some(first) = first {
+        for (index, tree) in parse.forest.derivations().take(first).enumerate() {
+            if index > 0 {
+                writeln!(out)?;
+            }
+            write!(out, "{}", symbols.render_tree(&parse, &tree))?;
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    match parse.tree() {
+        Ok(tree) => write!(out, "{}", symbols.render_tree(&parse, &tree))?,
+        Err(ambiguity) => {
+            write!(out, "{}", symbols.render(&parse))?;
+            if emitter.format == MessageFormat::Human {
+                eprintln!(
+                    "note: the input is ambiguous, with {} derivations",
+                    ambiguity.derivations
+                );
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_doc(grammar: &OneGrammar, format: DocFormat, emitter: &mut Emitter) -> Result<ExitCode> {
+    let mut out = io::stdout().lock();
+    let file = &grammar.file;
+    let (files, grammar) = read_grammar(file, &grammar.include, emitter)?;
+    let title = file
+        .file_stem()
+        .unwrap_or(file.as_os_str())
+        .to_string_lossy();
+    match format {
+        DocFormat::Markdown => write!(out, "{}", docs::markdown(&grammar, &files, &title)?)?,
+        DocFormat::Html => write!(out, "{}", docs::html(&grammar, &files, &title)?)?,
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_railroad(
+    grammar: &OneGrammar,
+    svg_dir: Option<&Path>,
+    emitter: &mut Emitter,
+) -> Result<ExitCode> {
+    let mut out = io::stdout().lock();
+    let file = &grammar.file;
+    let grammar = load_grammar(file, &grammar.include, emitter)?;
+    let Some(svg_dir) = svg_dir else {
+        let title = file.file_stem().unwrap_or(file.as_os_str());
+        write!(
+            out,
+            "{}",
+            railroad::html(&grammar, &title.to_string_lossy())
+        )?;
+        return Ok(ExitCode::SUCCESS);
+    };
+    fs::create_dir_all(svg_dir).map_err(|source| Error::Io {
+        path: svg_dir.into(),
+        source,
+    })?;
+    for rule in &grammar.rules {
+        let svg = railroad::svg(&grammar, rule, |name| format!("{name}.svg"));
+        let path = svg_dir.join(format!("{}.svg", rule.name()));
+        fs::write(&path, svg).map_err(|source| Error::Io { path, source })?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_graph(grammar: &OneGrammar, automaton: bool, emitter: &mut Emitter) -> Result<ExitCode> {
+    let mut out = io::stdout().lock();
+    let grammar = load_grammar(&grammar.file, &grammar.include, emitter)?;
+    if automaton {
+        write!(out, "{}", graph::automaton(&grammar))?;
+    } else {
+        write!(out, "{}", graph::rules(&grammar))?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn exit_code(success: bool) -> ExitCode {
+    if success {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Where and why the last panic happened, taken by [`main`] to report it as an error
+static PANIC: Mutex<Option<String>> = Mutex::new(None);
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let mut emitter = Emitter::new(cli.message_format);
+    panic::set_hook(Box::new(|info| {
+        *PANIC.lock().unwrap_or_else(|err| err.into_inner()) = Some(info.to_string());
+    }));
+    // A panic is a bug, but it still fails the command with an error in the chosen format
+    let result = panic::catch_unwind(AssertUnwindSafe(|| run(cli.command, &mut emitter)))
+        .unwrap_or_else(|_| {
+            let panic = PANIC.lock().unwrap_or_else(|err| err.into_inner()).take();
+            let mut diagnostic = Diagnostic::error("rantlr panicked, which is a bug");
+            if let Some(panic) = panic {
+                diagnostic = diagnostic.with_note(panic);
+            }
+            emitter.emit(&diagnostic, &SourceMap::default());
+            Err(Emitted.into())
+        });
+    let code = match result {
+        Ok(code) => code,
+        Err(err) if broken_pipe(&err) => ExitCode::SUCCESS,
+        Err(err) => {
+            emitter.report(&err);
+            ExitCode::FAILURE
+        }
+    };
+    emitter.finish();
+    code
+}
 
-    let grammar = Grammar::from(parse_tree);
-    println!("{grammar:?}");
+fn run(command: Command, emitter: &mut Emitter) -> Result<ExitCode> {
+    match command {
+        Command::Check { grammars } => Ok(each(&grammars.files, emitter, |file, emitter| {
+            run_check(file, &grammars.include, emitter)
+        })),
+        Command::Fmt { files, check } => Ok(each(&files, emitter, |file, emitter| {
+            run_fmt(file, check, emitter)
+        })),
+        Command::Lint { grammars } => Ok(each(&grammars.files, emitter, |file, emitter| {
+            run_lint(file, &grammars.include, emitter)
+        })),
+        Command::Analyze { grammars, table } => {
+            Ok(each(&grammars.files, emitter, |file, emitter| {
+                run_analyze(file, &grammars.include, table, emitter)
+            }))
+        }
+        Command::Generate {
+            grammars,
+            out_dir,
+            backend,
+            target: Target::Rust,
+            conflicts,
+            messages,
+        } => {
+            if grammars.files.len() > 1 && out_dir.is_none() {
+                Err(anyhow::anyhow!(
+                    "several grammars are generated with `--out-dir`"
+                ))
+            } else if grammars.files.len() > 1 && messages.is_some() {
+                Err(anyhow::anyhow!("`--messages` is for a single grammar"))
+            } else {
+                let options = codegen::Options {
+                    backend: backend.into(),
+                    conflicts: conflicts.into(),
+                    messages,
+                };
+                Ok(each(&grammars.files, emitter, |file, emitter| {
+                    run_generate(
+                        file,
+                        &grammars.include,
+                        out_dir.as_deref(),
+                        &options,
+                        emitter,
+                    )
+                }))
+            }
+        }
+        Command::Errors {
+            grammar,
+            list,
+            messages,
+            table,
+            conflicts,
+        } => run_errors(
+            &grammar,
+            list,
+            messages.as_deref(),
+            table,
+            conflicts,
+            emitter,
+        ),
+        Command::Lex { grammar, input } => run_lex(&grammar, &input, emitter),
+        Command::Parse {
+            grammar,
+            input,
+            backend,
+            first,
+        } => run_parse(&grammar, &input, backend, first, emitter),
+        Command::Doc { grammar, format } => run_doc(&grammar, format, emitter),
+        Command::Railroad { grammar, svg_dir } => {
+            run_railroad(&grammar, svg_dir.as_deref(), emitter)
+        }
+        Command::Graph { grammar, automaton } => run_graph(&grammar, automaton, emitter),
+        Command::Lsp { include } => {
+            let stdin = io::stdin().lock();
+            lsp::serve(include.dirs, stdin, io::stdout().lock())
+                .map(exit_code)
+                .map_err(Into::into)
+        }
+        Command::Bootstrap {
+            file,
+            out_dir,
+            check,
+            corpus,
+        } => run_bootstrap(&file, &out_dir, check, &corpus, emitter),
+    }
 }
@@ -0,0 +1,228 @@
+//! Diagnostics as machine-readable output: JSON objects of a versioned schema for editors and
+//! scripts, and SARIF logs for code scanning.
+//!
+//! A JSON diagnostic is an object of these fields, of which later versions only add new ones:
+//!
+//! - `version`: [`VERSION`],
+//! - `severity`: `"error"` or `"warning"`,
+//! - `code`: such as `"E002"` or `"L003/unused_token"`, or `null`,
+//! - `message`,
+//! - `spans`: the primary span first, if there is one, then the secondary ones, each with its
+//!   `label` and whether it is `primary`,
+//! - `notes`, and `help` or `null`,
+//! - `suggestions`: fixes, each a `message` and the `replacement` of the source at `span`,
+//! - `rendered`: the diagnostic as it is printed for people.
+//!
+//! A span is the `file`, its `byte_start` and `byte_end`, and its `line_start`, `column_start`,
+//! `line_end` and `column_end`, counted from 1 with columns in characters.
+
+use std::path::Path;
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    diagnostic::{Diagnostic, Label},
+    span::{LineIndex, SourceMap, Span, Unit},
+};
+
+/// The version of the JSON schema
+pub const VERSION: u64 = 1;
+
+fn span(span: Span, files: &SourceMap) -> Value {
+    let index = LineIndex::new(files.source(span.file));
+    let start = index.line_col(span.start, Unit::Char);
+    let end = index.line_col(span.end, Unit::Char);
+    json!({
+        "file": files.path(span.file).display().to_string(),
+        "byte_start": span.start,
+        "byte_end": span.end,
+        "line_start": start.line + 1,
+        "column_start": start.col + 1,
+        "line_end": end.line + 1,
+        "column_end": end.col + 1,
+    })
+}
+
+/// Every label of a diagnostic, the primary one first
+fn labels(diagnostic: &Diagnostic) -> impl Iterator<Item = (&Label, bool)> {
+    diagnostic
+        .primary
+        .iter()
+        .map(|label| (label, true))
+        .chain(diagnostic.secondary.iter().map(|label| (label, false)))
+}
+
+/// A diagnostic as a JSON object, whose spans point into `files`
+pub fn json(diagnostic: &Diagnostic, files: &SourceMap) -> Value {
+    let spans: Vec<Value> = labels(diagnostic)
+        .map(|(label, primary)| {
+            let mut span = span(label.span, files);
+            span["primary"] = primary.into();
+            span["label"] = label.message.clone().into();
+            span
+        })
+        .collect();
+    let suggestions: Vec<Value> = diagnostic
+        .suggestions
+        .iter()
+        .map(|suggestion| {
+            json!({
+                "message": suggestion.message,
+                "span": span(suggestion.span, files),
+                "replacement": suggestion.replacement,
+            })
+        })
+        .collect();
+    json!({
+        "version": VERSION,
+        "severity": diagnostic.severity.to_string(),
+        "code": diagnostic.code,
+        "message": diagnostic.message,
+        "spans": spans,
+        "notes": diagnostic.notes,
+        "help": diagnostic.help,
+        "suggestions": suggestions,
+        "rendered": diagnostic.render_in(files),
+    })
+}
+
+/// A path as a SARIF URI, relative ones left relative
+fn uri(path: &Path) -> String {
+    let path = path.display().to_string().replace('\\', "/");
+    if path.starts_with('/') {
+        format!("file://{path}")
+    } else {
+        path
+    }
+}
+
+fn location(span: Span, files: &SourceMap) -> Value {
+    json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": uri(files.path(span.file)) },
+            "region": region(span, files),
+        }
+    })
+}
+
+fn region(span: Span, files: &SourceMap) -> Value {
+    let index = LineIndex::new(files.source(span.file));
+    let start = index.line_col(span.start, Unit::Char);
+    let end = index.line_col(span.end, Unit::Char);
+    json!({
+        "startLine": start.line + 1,
+        "startColumn": start.col + 1,
+        "endLine": end.line + 1,
+        "endColumn": end.col + 1,
+    })
+}
+
+/// A diagnostic as a SARIF result, whose spans point into `files`. Notes and help follow the
+/// message on lines of their own.
+pub fn sarif_result(diagnostic: &Diagnostic, files: &SourceMap) -> Value {
+    let mut text = diagnostic.message.clone();
+    for note in &diagnostic.notes {
+        text.push_str(&format!("\nnote: {note}"));
+    }
+    if let Some(help) = &diagnostic.help {
+        text.push_str(&format!("\nhelp: {help}"));
+    }
+    let mut result = Map::new();
+    if let Some(code) = &diagnostic.code {
+        result.insert("ruleId".into(), code.clone().into());
+    }
+    result.insert("level".into(), diagnostic.severity.to_string().into());
+    result.insert("message".into(), json!({ "text": text }));
+    let mut related = vec![];
+    for (index, (label, primary)) in labels(diagnostic).enumerate() {
+        let mut location = location(label.span, files);
+        if !label.message.is_empty() {
+            location["message"] = json!({ "text": label.message });
+        }
+        if primary {
+            result.insert("locations".into(), json!([location]));
+        } else {
+            location["id"] = index.into();
+            related.push(location);
+        }
+    }
+    if !related.is_empty() {
+        result.insert("relatedLocations".into(), related.into());
+    }
+    let fixes: Vec<Value> = diagnostic
+        .suggestions
+        .iter()
+        .map(|suggestion| {
+            json!({
+                "description": { "text": suggestion.message },
+                "artifactChanges": [{
+                    "artifactLocation": { "uri": uri(files.path(suggestion.span.file)) },
+                    "replacements": [{
+                        "deletedRegion": region(suggestion.span, files),
+                        "insertedContent": { "text": suggestion.replacement },
+                    }],
+                }],
+            })
+        })
+        .collect();
+    if !fixes.is_empty() {
+        result.insert("fixes".into(), fixes.into());
+    }
+    result.into()
+}
+
+/// A SARIF log of one run of rantlr, with its results
+pub fn sarif(results: Vec<Value>) -> Value {
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "rantlr",
+                    "version": env!("CARGO_PKG_VERSION"),
+                }
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_snapshot;
+
+    use super::*;
+    use crate::{analysis::lint, Grammar};
+
+    /// The diagnostics of a grammar read from `g.g4`, and the files they point into
+    fn diagnostics(source: &str) -> (Vec<Diagnostic>, SourceMap) {
+        let mut files = SourceMap::default();
+        files.add("g.g4".into(), source.into());
+        let diagnostics = match Grammar::parse(source) {
+            Ok(grammar) => lint(&grammar).iter().map(Diagnostic::from).collect(),
+            Err(err) => err.diagnostics,
+        };
+        (diagnostics, files)
+    }
+
+    macro_rules! test_output {
+        ($name:ident, $format:expr, $source:expr) => {
+            #[test]
+            fn $name() {
+                let (diagnostics, files) = diagnostics($source);
+                let output: Vec<Value> = diagnostics
+                    .iter()
+                    .map(|diagnostic| $format(diagnostic, &files))
+                    .collect();
+                assert_snapshot!(serde_json::to_string_pretty(&output).unwrap());
+            }
+        };
+    }
+
+    test_output!(json_lint, json, "s: A;\nA: a;\nB: b;\n");
+    test_output!(json_syntax_error, json, "s: A;\nA a;\n");
+    test_output!(sarif_lint, sarif_result, "s: A;\nA: a;\nB: b;\n");
+    test_output!(sarif_cycle, sarif_result, "s: t | A;\nt: s;\nA: a;\n");
+}
@@ -0,0 +1,224 @@
+//! Source locations, as byte ranges of a file, and their conversion to lines and columns.
+
+use std::{
+    fmt,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crate::ast::Annotation;
+
+/// A source file, numbered by whoever loaded it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FileId(pub u32);
+
+/// A byte range of a source file, debug printed as `Span(file, start..end)`
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub file: FileId,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(file: FileId, range: Range<usize>) -> Self {
+        Span {
+            file,
+            start: range.start,
+            end: range.end,
+        }
+    }
+
+    /// The smallest span covering both spans, which must be in the same file
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            file: self.file,
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+}
+
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Span({}, {}..{})", self.file.0, self.start, self.end)
+    }
+}
+
+impl Annotation for Span {}
+
+/// The files a grammar was read from, its own first and then its imports, indexed by the
+/// [`FileId`] of the spans into them
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<(PathBuf, String)>,
+}
+
+impl SourceMap {
+    pub fn add(&mut self, path: PathBuf, source: String) -> FileId {
+        self.files.push((path, source));
+        FileId(u32::try_from(self.files.len() - 1).expect("fewer than 2^32 files"))
+    }
+
+    pub fn find(&self, path: &Path) -> Option<FileId> {
+        let index = self.files.iter().position(|(other, _)| other == path)?;
+        Some(FileId(u32::try_from(index).unwrap()))
+    }
+
+    pub fn path(&self, file: FileId) -> &Path {
+        &self.files[file.0 as usize].0
+    }
+
+    pub fn source(&self, file: FileId) -> &str {
+        &self.files[file.0 as usize].1
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|(path, _)| path.as_path())
+    }
+}
+
+/// What a column counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    /// Bytes of UTF-8
+    Utf8,
+    /// UTF-16 code units, which editors speaking LSP count by default
+    Utf16,
+    /// Unicode scalar values, as a reader counts characters
+    Char,
+}
+
+/// A zero-based line and column, displayed one-based as `line:col`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineCol {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for LineCol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line + 1, self.col + 1)
+    }
+}
+
+/// The start of every line of a text, to find the line and column of a byte offset
+#[derive(Debug)]
+pub struct LineIndex<'a> {
+    text: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(offset, _)| offset + 1))
+            .collect();
+        LineIndex { text, line_starts }
+    }
+
+    /// The line and column of a byte offset. Offsets past the end or inside a character
+    /// are moved back to the closest character boundary.
+    pub fn line_col(&self, offset: usize, unit: Unit) -> LineCol {
+        let mut offset = offset.min(self.text.len());
+        while !self.text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let prefix = &self.text[self.line_starts[line]..offset];
+        let col = match unit {
+            Unit::Utf8 => prefix.len(),
+            Unit::Utf16 => prefix.chars().map(char::len_utf16).sum(),
+            Unit::Char => prefix.chars().count(),
+        };
+        LineCol { line, col }
+    }
+
+    /// The byte offset of a line and column, the inverse of [`LineIndex::line_col`]. Columns
+    /// past the end of their line, or inside a character, are moved back to the closest
+    /// character boundary; lines past the end give the end of the text.
+    pub fn offset(&self, position: LineCol, unit: Unit) -> usize {
+        let Some(&start) = self.line_starts.get(position.line) else {
+            return self.text.len();
+        };
+        let end = self
+            .line_starts
+            .get(position.line + 1)
+            .map_or(self.text.len(), |next| next - 1);
+        let mut col = 0;
+        for (offset, c) in self.text[start..end].char_indices() {
+            col += match unit {
+                Unit::Utf8 => c.len_utf8(),
+                Unit::Utf16 => c.len_utf16(),
+                Unit::Char => 1,
+            };
+            if col > position.col {
+                return start + offset;
+            }
+        }
+        end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! test_line_col {
+        ($name:ident, $text:expr, $offset:expr => $($unit:ident: ($line:expr, $col:expr)),*) => {
+            #[test]
+            fn $name() {
+                let index = LineIndex::new($text);
+                $(
+                    assert_eq!(
+                        index.line_col($offset, Unit::$unit),
+                        LineCol { line: $line, col: $col },
+                        "{:?}",
+                        Unit::$unit
+                    );
+                )*
+            }
+        };
+    }
+
+    test_line_col!(line_col_start, "a: B;", 0 => Utf8: (0, 0), Utf16: (0, 0), Char: (0, 0));
+    test_line_col!(line_col_second_line, "a: B;\nB: b;", 9 => Utf8: (1, 3), Utf16: (1, 3), Char: (1, 3));
+    test_line_col!(line_col_after_newline, "a\n", 2 => Utf8: (1, 0), Char: (1, 0));
+    test_line_col!(line_col_tab, "\ta", 1 => Utf8: (0, 1), Char: (0, 1));
+    test_line_col!(line_col_multibyte, "é𝄞x", 6 => Utf8: (0, 6), Utf16: (0, 3), Char: (0, 2));
+    test_line_col!(line_col_inside_char, "é", 1 => Utf8: (0, 0), Char: (0, 0));
+    test_line_col!(line_col_past_end, "ab", 10 => Utf8: (0, 2), Char: (0, 2));
+
+    macro_rules! test_offset {
+        ($name:ident, $text:expr, ($line:expr, $col:expr) => $($unit:ident: $offset:expr),*) => {
+            #[test]
+            fn $name() {
+                let index = LineIndex::new($text);
+                $(
+                    assert_eq!(
+                        index.offset(LineCol { line: $line, col: $col }, Unit::$unit),
+                        $offset,
+                        "{:?}",
+                        Unit::$unit
+                    );
+                )*
+            }
+        };
+    }
+
+    test_offset!(offset_second_line, "a: B;\nB: b;", (1, 3) => Utf8: 9, Utf16: 9, Char: 9);
+    test_offset!(offset_multibyte, "é𝄞x", (0, 3) => Utf8: 2, Utf16: 6, Char: 7);
+    test_offset!(offset_past_line_end, "ab\ncd", (0, 5) => Utf8: 2, Char: 2);
+    test_offset!(offset_past_end, "ab\ncd", (4, 0) => Utf8: 5, Char: 5);
+
+    #[test]
+    fn span_to_covers_both() {
+        let span = Span::new(FileId(0), 4..6).to(Span::new(FileId(0), 1..2));
+        assert_eq!((span.start, span.end), (1, 6));
+    }
+
+    #[test]
+    fn line_col_displays_one_based() {
+        assert_eq!(LineCol { line: 0, col: 4 }.to_string(), "1:5");
+    }
+}
@@ -0,0 +1,146 @@
+//! The `rantlr` binary: the exit status and output of its subcommands
+
+use std::{
+    fs,
+    io::Read,
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::OnceLock,
+};
+
+/// Grammars and inputs the commands are run on, by file name
+const FILES: &[(&str, &str)] = &[
+    (
+        "sum.g4",
+        "sum: sum PLUS NUM | NUM;\nPLUS: \\+;\nNUM: [0-9]+;\n",
+    ),
+    (
+        "ambiguous.g4",
+        "e: e PLUS e | NUM;\nPLUS: \\+;\nNUM: [0-9]+;\n",
+    ),
+    ("undefined.g4", "s: t NUM;\nNUM: [0-9]+;\n"),
+    ("broken.g4", "s: NUM\nNUM: [0-9]+;\n"),
+    (
+        "dropped.g4",
+        "s: a NUM;\na: : NUM;\nb: : NUM;\nNUM: [0-9]+;\n",
+    ),
+    ("one.g4", "NUM: [0-9]+;\n"),
+    ("empty.g4", ""),
+    ("unformatted.g4", "s:NUM;NUM:[0-9]+;\n"),
+    ("main.g4", "%import base;\ns: t NUM;\n"),
+    ("lib/base.g4", "t: PLUS;\nPLUS: \\+;\nNUM: [0-9]+;\n"),
+    ("sum.txt", "1+2+3"),
+    ("broken.txt", "1+"),
+];
+
+/// A directory holding [`FILES`], written once for every test
+fn dir() -> &'static PathBuf {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+    DIR.get_or_init(|| {
+        let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("cli");
+        for (name, content) in FILES {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    })
+}
+
+/// Run `rantlr` in [`dir`], for its exit status, standard output and standard error
+fn rantlr(args: &[&str]) -> (i32, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_rantlr"))
+        .args(args)
+        .current_dir(dir())
+        .output()
+        .unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+macro_rules! test_cli {
+    ($name:ident, [$($arg:expr),*] => $code:expr, stdout: $stdout:expr, stderr: $stderr:expr) => {
+        #[test]
+        fn $name() {
+            let (code, stdout, stderr) = rantlr(&[$($arg),*]);
+            assert_eq!(code, $code, "stdout:\n{stdout}\nstderr:\n{stderr}");
+            assert!(stdout.contains($stdout), "stdout:\n{stdout}");
+            assert!(stderr.contains($stderr), "stderr:\n{stderr}");
+        }
+    };
+}
+
+test_cli!(check, ["check", "sum.g4"] => 0, stdout: "read 3 rules: sum, PLUS, NUM", stderr: "");
+test_cli!(check_one_rule, ["check", "one.g4"] => 0, stdout: "read 1 rule: NUM\n", stderr: "");
+test_cli!(check_no_rules, ["check", "empty.g4"] => 1,
+    stdout: "read 0 rules\n", stderr: "error[E003]: the grammar is empty");
+test_cli!(check_syntax_error, ["check", "broken.g4"] => 1, stdout: "", stderr: "error[E002]");
+test_cli!(check_undefined_rule, ["check", "undefined.g4"] => 1,
+    stdout: "", stderr: "error[E004]: undefined rule `t`");
+test_cli!(check_missing_import, ["check", "main.g4"] => 1,
+    stdout: "", stderr: "cannot read `base.g4`");
+test_cli!(check_include, ["check", "-I", "lib", "main.g4"] => 0, stdout: "", stderr: "");
+test_cli!(check_missing_file, ["check", "missing.g4"] => 1,
+    stdout: "", stderr: "cannot read `missing.g4`");
+test_cli!(fmt_check, ["fmt", "--check", "unformatted.g4"] => 1,
+    stdout: "", stderr: "`unformatted.g4` is not formatted");
+test_cli!(lint, ["lint", "sum.g4"] => 0, stdout: "", stderr: "");
+test_cli!(analyze, ["analyze", "sum.g4"] => 0, stdout: "0 conflicts", stderr: "");
+test_cli!(analyze_conflict, ["analyze", "ambiguous.g4"] => 1,
+    stdout: "5 states, 1 conflict\n", stderr: "error[E007]: shift/reduce conflict");
+test_cli!(generate, ["generate", "sum.g4"] => 0, stdout: "pub fn parse", stderr: "");
+test_cli!(generate_conflict, ["generate", "ambiguous.g4"] => 1,
+    stdout: "", stderr: "error[E007]: shift/reduce conflict");
+test_cli!(generate_warn, ["generate", "--conflicts", "warn", "ambiguous.g4"] => 0,
+    stdout: "pub fn parse", stderr: "warning[E007]");
+test_cli!(generate_left_recursive, ["generate", "--backend", "peg", "sum.g4"] => 1,
+    stdout: "", stderr: "error[E009]");
+test_cli!(generate_include, ["generate", "-I", "lib", "main.g4"] => 0,
+    stdout: "pub fn parse", stderr: "");
+test_cli!(lex, ["lex", "sum.g4", "sum.txt"] => 0, stdout: "1..2 PLUS \"+\"", stderr: "");
+test_cli!(parse, ["parse", "sum.g4", "sum.txt"] => 0, stdout: "sum", stderr: "");
+test_cli!(parse_error, ["parse", "sum.g4", "broken.txt"] => 1, stdout: "", stderr: "error");
+test_cli!(graph, ["graph", "sum.g4"] => 0, stdout: "\"sum\" -> \"PLUS\";", stderr: "");
+test_cli!(graph_automaton, ["graph", "--automaton", "sum.g4"] => 0,
+    stdout: "digraph automaton {", stderr: "");
+test_cli!(json, ["--message-format", "json", "check", "undefined.g4"] => 1,
+    stdout: "", stderr: "\"code\":\"E004\"");
+test_cli!(unknown_subcommand, ["compile", "sum.g4"] => 2, stdout: "", stderr: "unrecognized subcommand");
+test_cli!(missing_argument, ["generate"] => 2, stdout: "", stderr: "required");
+test_cli!(invalid_value, ["generate", "--backend", "cyk", "sum.g4"] => 2,
+    stdout: "", stderr: "invalid value 'cyk'");
+
+/// A rule dropped by the recovery from a syntax error is not reported as undefined
+#[test]
+fn check_dropped_rule() {
+    let (code, _, stderr) = rantlr(&["check", "dropped.g4"]);
+    assert_eq!(code, 1);
+    assert!(!stderr.contains("E004"), "stderr:\n{stderr}");
+    assert!(
+        stderr.ends_with("error: aborting due to 2 previous errors\n"),
+        "stderr:\n{stderr}"
+    );
+}
+
+/// A reader closing standard output early, as `head` does, ends the command quietly
+#[test]
+fn closed_stdout() {
+    let input = vec!["1"; 100_000].join("+");
+    fs::write(dir().join("long.txt"), input).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rantlr"))
+        .args(["lex", "sum.g4", "long.txt"])
+        .current_dir(dir())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = [0; 8];
+    child.stdout.take().unwrap().read_exact(&mut line).unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(0), "stderr:\n{stderr}");
+    assert_eq!(stderr, "");
+}